@@ -0,0 +1,1382 @@
+const BLOCK_SIZE: usize = 16;
+const ERROR_COLOR: [u8; 4] = [0xff, 0, 0xff, 0xff];
+const ERROR_COLOR_HDR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Range {
+    bits: u32,
+    trits: bool,
+    quints: bool,
+}
+
+macro_rules! ranges {
+    ($(($bits:expr, $trits:expr, $quints:expr),)*) => {
+        [$(Range { bits: $bits, trits: $trits != 0, quints: $quints != 0 },)*]
+    };
+}
+
+/// Integer sequence encodings of ranges 2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64,
+/// 80, 96, 128, 160, 192 and 256. Weights use the first 12 of them.
+const RANGES: [Range; 21] = ranges![
+    (1, 0, 0),
+    (0, 1, 0),
+    (2, 0, 0),
+    (0, 0, 1),
+    (1, 1, 0),
+    (3, 0, 0),
+    (1, 0, 1),
+    (2, 1, 0),
+    (4, 0, 0),
+    (2, 0, 1),
+    (3, 1, 0),
+    (5, 0, 0),
+    (3, 0, 1),
+    (4, 1, 0),
+    (6, 0, 0),
+    (4, 0, 1),
+    (5, 1, 0),
+    (7, 0, 0),
+    (5, 0, 1),
+    (6, 1, 0),
+    (8, 0, 0),
+];
+
+/// Color endpoints must use at least the range of 6.
+const MIN_COLOR_RANGE: usize = 4;
+
+impl Range {
+    fn encoded_bits(self, count: u32) -> u32 {
+        let mut bits = self.bits * count;
+        if self.trits {
+            bits += (8 * count).div_ceil(5);
+        }
+        if self.quints {
+            bits += (7 * count).div_ceil(3);
+        }
+        bits
+    }
+}
+
+/// Reads bits from the LSB of a block, returning zeros past `end`.
+struct BitReader {
+    bits: u128,
+    pos: u32,
+    end: u32,
+}
+
+impl BitReader {
+    fn new(bits: u128, start: u32, end: u32) -> Self {
+        Self {
+            bits,
+            pos: start,
+            end,
+        }
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        let pos = self.pos;
+        self.pos += bits;
+        if bits == 0 || pos >= self.end {
+            return 0;
+        }
+        let bits = std::cmp::min(bits, self.end - pos);
+        ((self.bits >> pos) & ((1 << bits) - 1)) as u32
+    }
+}
+
+fn bits(block: u128, start: u32, count: u32) -> u32 {
+    ((block >> start) & ((1 << count) - 1)) as u32
+}
+
+fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |n: u32| (t >> n) & 1;
+    let (c, t3, t4);
+    if (t >> 2) & 7 == 7 {
+        c = ((t >> 5) << 2) | (t & 3);
+        t4 = 2;
+        t3 = 2;
+    } else {
+        c = t & 0x1f;
+        if (t >> 5) & 3 == 3 {
+            t4 = 2;
+            t3 = bit(7);
+        } else {
+            t4 = bit(7);
+            t3 = (t >> 5) & 3;
+        }
+    }
+    let cbit = |n: u32| (c >> n) & 1;
+    let (t0, t1, t2);
+    if c & 3 == 3 {
+        t2 = 2;
+        t1 = cbit(4);
+        t0 = (cbit(3) << 1) | (cbit(2) & !cbit(3) & 1);
+    } else if (c >> 2) & 3 == 3 {
+        t2 = 2;
+        t1 = 2;
+        t0 = c & 3;
+    } else {
+        t2 = cbit(4);
+        t1 = (c >> 2) & 3;
+        t0 = (cbit(1) << 1) | (cbit(0) & !cbit(1) & 1);
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |n: u32| (q >> n) & 1;
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let q2 = (bit(0) << 2) | ((bit(4) & !bit(0) & 1) << 1) | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (c, q2);
+    if (q >> 1) & 3 == 3 {
+        q2 = 4;
+        c = (((q >> 3) & 3) << 3) | ((!(q >> 5) & 3) << 1) | bit(0);
+    } else {
+        q2 = (q >> 5) & 3;
+        c = q & 0x1f;
+    }
+    let (q0, q1) = if c & 7 == 5 {
+        ((c >> 3) & 3, 4)
+    } else {
+        (c & 7, (c >> 3) & 3)
+    };
+    [q0, q1, q2]
+}
+
+/// Decodes `count` values of `range` as `(bits, trit or quint)` pairs.
+fn decode_ise(reader: &mut BitReader, range: Range, count: usize) -> Vec<(u32, u32)> {
+    let mut ret = Vec::with_capacity(count);
+    if range.trits {
+        while ret.len() < count {
+            let mut m = [0u32; 5];
+            let mut t = 0;
+            for (idx, &(shift, len)) in [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)].iter().enumerate()
+            {
+                m[idx] = reader.read(range.bits);
+                t |= reader.read(len) << shift;
+            }
+            let digits = decode_trits(t);
+            ret.extend(m.iter().copied().zip(digits.iter().copied()));
+        }
+    } else if range.quints {
+        while ret.len() < count {
+            let mut m = [0u32; 3];
+            let mut q = 0;
+            for (idx, &(shift, len)) in [(0, 3), (3, 2), (5, 2)].iter().enumerate() {
+                m[idx] = reader.read(range.bits);
+                q |= reader.read(len) << shift;
+            }
+            let digits = decode_quints(q);
+            ret.extend(m.iter().copied().zip(digits.iter().copied()));
+        }
+    } else {
+        for _ in 0..count {
+            ret.push((reader.read(range.bits), 0));
+        }
+    }
+    ret.truncate(count);
+    ret
+}
+
+fn replicate(v: u32, from: u32, to: u32) -> u32 {
+    if from == 0 {
+        return 0;
+    }
+    let mut ret = 0;
+    let mut filled = 0;
+    while filled < to {
+        let shift = to as i32 - filled as i32 - from as i32;
+        ret |= if shift >= 0 { v << shift } else { v >> -shift };
+        filled += from;
+    }
+    ret & ((1 << to) - 1)
+}
+
+fn unquantize_color(range: Range, (m, d): (u32, u32)) -> u32 {
+    if !range.trits && !range.quints {
+        return replicate(m, range.bits, 8);
+    }
+    let a = if m & 1 != 0 { 0x1ff } else { 0 };
+    let b = (m >> 1) & 1;
+    let cb = (m >> 1) & 3;
+    let dcb = (m >> 1) & 7;
+    let edcb = (m >> 1) & 0xf;
+    let fedcb = (m >> 1) & 0x1f;
+    let (bb, c) = match (range.trits, range.bits) {
+        (true, 1) => (0, 204),
+        (true, 2) => (b * 0x116, 93),
+        (true, 3) => ((cb >> 1) * 0x10a + (cb & 1) * 0x85, 44),
+        (true, 4) => ((dcb << 6) | dcb, 22),
+        (true, 5) => ((edcb << 5) | (edcb >> 2), 11),
+        (true, _) => ((fedcb << 4) | (fedcb >> 4), 5),
+        (false, 1) => (0, 113),
+        (false, 2) => (b * 0x10c, 54),
+        (false, 3) => ((cb >> 1) * 0x105 + (cb & 1) * 0x82, 26),
+        (false, 4) => ((dcb << 6) | (dcb >> 1), 13),
+        (false, _) => ((edcb << 5) | (edcb >> 3), 6),
+    };
+    let t = (d * c + bb) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+fn unquantize_weight(range: Range, (m, d): (u32, u32)) -> u32 {
+    let v = if !range.trits && !range.quints {
+        replicate(m, range.bits, 6)
+    } else if range.bits == 0 {
+        if range.trits {
+            [0, 32, 63][d as usize]
+        } else {
+            [0, 16, 32, 47, 63][d as usize]
+        }
+    } else {
+        let a = if m & 1 != 0 { 0x7f } else { 0 };
+        let b = (m >> 1) & 1;
+        let cb = (m >> 1) & 3;
+        let (bb, c) = match (range.trits, range.bits) {
+            (true, 1) => (0, 50),
+            (true, 2) => (b * 0x45, 23),
+            (true, _) => ((cb << 5) | cb, 11),
+            (false, 1) => (0, 28),
+            (false, _) => (b * 0x42, 13),
+        };
+        let t = (d * c + bb) ^ a;
+        (a & 0x20) | (t >> 2)
+    };
+    if v > 32 {
+        v + 1
+    } else {
+        v
+    }
+}
+
+/// Endpoint pair of a partition. Each channel is 16 bits, either UNORM16 or HDR
+/// logarithmic values.
+#[derive(Copy, Clone, Debug, Default)]
+struct Endpoints {
+    colors: [[u32; 4]; 2],
+    hdr: [bool; 4],
+}
+
+impl Endpoints {
+    fn ldr(e0: [i32; 4], e1: [i32; 4]) -> Self {
+        let expand = |e: [i32; 4]| {
+            let mut ret = [0u32; 4];
+            for (ret, v) in ret.iter_mut().zip(e.iter()) {
+                *ret = (*v).clamp(0, 0xff) as u32 * 0x101;
+            }
+            ret
+        };
+        Self {
+            colors: [expand(e0), expand(e1)],
+            hdr: [false; 4],
+        }
+    }
+
+    /// HDR endpoints from 12-bit values, with HDR alpha of 1.0.
+    fn hdr(e0: [i32; 3], e1: [i32; 3]) -> Self {
+        let expand = |e: [i32; 3]| {
+            [
+                (e[0] as u32) << 4,
+                (e[1] as u32) << 4,
+                (e[2] as u32) << 4,
+                0x7800,
+            ]
+        };
+        Self {
+            colors: [expand(e0), expand(e1)],
+            hdr: [true; 4],
+        }
+    }
+}
+
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3f;
+    let a = if a & 0x20 != 0 { a - 0x40 } else { a };
+    (a, b)
+}
+
+fn blue_contract(v: [i32; 4]) -> [i32; 4] {
+    [(v[0] + v[2]) >> 1, (v[1] + v[2]) >> 1, v[2], v[3]]
+}
+
+fn decode_hdr_luminance_large(v: &[i32]) -> Endpoints {
+    let (y0, y1) = if v[1] >= v[0] {
+        (v[0] << 4, v[1] << 4)
+    } else {
+        ((v[1] << 4) + 8, (v[0] << 4) - 8)
+    };
+    Endpoints::hdr([y0; 3], [y1; 3])
+}
+
+fn decode_hdr_luminance_small(v: &[i32]) -> Endpoints {
+    let (y0, d) = if v[0] & 0x80 != 0 {
+        (
+            ((v[1] & 0xe0) << 4) | ((v[0] & 0x7f) << 2),
+            (v[1] & 0x1f) << 2,
+        )
+    } else {
+        (
+            ((v[1] & 0xf0) << 4) | ((v[0] & 0x7f) << 1),
+            (v[1] & 0x0f) << 1,
+        )
+    };
+    let y1 = std::cmp::min(y0 + d, 0xfff);
+    Endpoints::hdr([y0; 3], [y1; 3])
+}
+
+fn decode_hdr_rgb_scale(v: &[i32]) -> Endpoints {
+    let modeval = ((v[0] & 0xc0) >> 6) | ((v[1] & 0x80) >> 5) | ((v[2] & 0x80) >> 4);
+    let (major, mode) = if modeval & 0xc != 0xc {
+        (modeval >> 2, modeval & 3)
+    } else if modeval != 0xf {
+        (modeval & 3, 4)
+    } else {
+        (0, 5)
+    };
+
+    let mut red = v[0] & 0x3f;
+    let mut green = v[1] & 0x1f;
+    let mut blue = v[2] & 0x1f;
+    let mut scale = v[3] & 0x1f;
+    let bit0 = (v[1] >> 6) & 1;
+    let bit1 = (v[1] >> 5) & 1;
+    let bit2 = (v[2] >> 6) & 1;
+    let bit3 = (v[2] >> 5) & 1;
+    let bit4 = (v[3] >> 7) & 1;
+    let bit5 = (v[3] >> 6) & 1;
+    let bit6 = (v[3] >> 5) & 1;
+
+    let one_hot = 1 << mode;
+    if one_hot & 0x30 != 0 {
+        green |= bit0 << 6;
+    }
+    if one_hot & 0x3a != 0 {
+        green |= bit1 << 5;
+    }
+    if one_hot & 0x30 != 0 {
+        blue |= bit2 << 6;
+    }
+    if one_hot & 0x3a != 0 {
+        blue |= bit3 << 5;
+    }
+    if one_hot & 0x3d != 0 {
+        scale |= bit6 << 5;
+    }
+    if one_hot & 0x2d != 0 {
+        scale |= bit5 << 6;
+    }
+    if one_hot & 0x04 != 0 {
+        scale |= bit4 << 7;
+    }
+    if one_hot & 0x3b != 0 {
+        red |= bit4 << 6;
+    }
+    if one_hot & 0x04 != 0 {
+        red |= bit3 << 6;
+    }
+    if one_hot & 0x10 != 0 {
+        red |= bit5 << 7;
+    }
+    if one_hot & 0x0f != 0 {
+        red |= bit2 << 7;
+    }
+    if one_hot & 0x05 != 0 {
+        red |= bit1 << 8;
+    }
+    if one_hot & 0x0a != 0 {
+        red |= bit0 << 8;
+    }
+    if one_hot & 0x05 != 0 {
+        red |= bit0 << 9;
+    }
+    if one_hot & 0x02 != 0 {
+        red |= bit6 << 9;
+    }
+    if one_hot & 0x01 != 0 {
+        red |= bit3 << 10;
+    }
+    if one_hot & 0x02 != 0 {
+        red |= bit5 << 10;
+    }
+
+    let shift = [1, 1, 2, 3, 4, 5][mode as usize];
+    red <<= shift;
+    green <<= shift;
+    blue <<= shift;
+    scale <<= shift;
+    if mode != 5 {
+        green = red - green;
+        blue = red - blue;
+    }
+    match major {
+        1 => std::mem::swap(&mut red, &mut green),
+        2 => std::mem::swap(&mut red, &mut blue),
+        _ => {}
+    }
+    let clamp = |v: i32| v.clamp(0, 0xfff);
+    let e1 = [clamp(red), clamp(green), clamp(blue)];
+    let e0 = [
+        clamp(red - scale),
+        clamp(green - scale),
+        clamp(blue - scale),
+    ];
+    Endpoints::hdr(e0, e1)
+}
+
+fn decode_hdr_rgb(v: &[i32]) -> Endpoints {
+    let modeval = ((v[1] & 0x80) >> 7) | ((v[2] & 0x80) >> 6) | ((v[3] & 0x80) >> 5);
+    let major = ((v[4] & 0x80) >> 7) | ((v[5] & 0x80) >> 6);
+    if major == 3 {
+        return Endpoints::hdr(
+            [v[0] << 4, v[2] << 4, (v[4] & 0x7f) << 5],
+            [v[1] << 4, v[3] << 4, (v[5] & 0x7f) << 5],
+        );
+    }
+
+    let mut a = v[0] | ((v[1] & 0x40) << 2);
+    let mut b0 = v[2] & 0x3f;
+    let mut b1 = v[3] & 0x3f;
+    let mut c = v[1] & 0x3f;
+    let mut d0 = v[4] & 0x7f;
+    let mut d1 = v[5] & 0x7f;
+    let d_bits = [7, 6, 7, 6, 5, 6, 5, 6][modeval as usize];
+    let bit0 = (v[2] >> 6) & 1;
+    let bit1 = (v[3] >> 6) & 1;
+    let bit2 = (v[4] >> 6) & 1;
+    let bit3 = (v[5] >> 6) & 1;
+    let bit4 = (v[4] >> 5) & 1;
+    let bit5 = (v[5] >> 5) & 1;
+
+    let one_hot = 1 << modeval;
+    if one_hot & 0xa4 != 0 {
+        a |= bit0 << 9;
+    }
+    if one_hot & 0x08 != 0 {
+        a |= bit2 << 9;
+    }
+    if one_hot & 0x50 != 0 {
+        a |= bit4 << 9;
+        a |= bit5 << 10;
+    }
+    if one_hot & 0xa0 != 0 {
+        a |= bit1 << 10;
+    }
+    if one_hot & 0xc0 != 0 {
+        a |= bit2 << 11;
+    }
+    if one_hot & 0x04 != 0 {
+        c |= bit1 << 6;
+    }
+    if one_hot & 0xe8 != 0 {
+        c |= bit3 << 6;
+    }
+    if one_hot & 0x20 != 0 {
+        c |= bit2 << 7;
+    }
+    if one_hot & 0x5b != 0 {
+        b0 |= bit0 << 6;
+        b1 |= bit1 << 6;
+    }
+    if one_hot & 0x12 != 0 {
+        b0 |= bit2 << 7;
+        b1 |= bit3 << 7;
+    }
+    if one_hot & 0xaf != 0 {
+        d0 |= bit4 << 5;
+        d1 |= bit5 << 5;
+    }
+    if one_hot & 0x05 != 0 {
+        d0 |= bit2 << 6;
+        d1 |= bit3 << 6;
+    }
+    let shift = 32 - d_bits;
+    let d0 = (d0 << shift) >> shift;
+    let d1 = (d1 << shift) >> shift;
+
+    let shift = (modeval >> 1) ^ 3;
+    let (a, b0, b1, c, d0, d1) = (
+        a << shift,
+        b0 << shift,
+        b1 << shift,
+        c << shift,
+        d0 << shift,
+        d1 << shift,
+    );
+    let clamp = |v: i32| v.clamp(0, 0xfff);
+    let mut e1 = [clamp(a), clamp(a - b0), clamp(a - b1)];
+    let mut e0 = [clamp(a - c), clamp(a - b0 - c - d0), clamp(a - b1 - c - d1)];
+    match major {
+        1 => {
+            e0.swap(0, 1);
+            e1.swap(0, 1);
+        }
+        2 => {
+            e0.swap(0, 2);
+            e1.swap(0, 2);
+        }
+        _ => {}
+    }
+    Endpoints::hdr(e0, e1)
+}
+
+fn decode_hdr_alpha(v6: i32, v7: i32) -> (u32, u32) {
+    let selector = ((v6 >> 7) & 1) | ((v7 >> 6) & 2);
+    let mut v6 = v6 & 0x7f;
+    let mut v7 = v7 & 0x7f;
+    if selector == 3 {
+        v6 <<= 5;
+        v7 <<= 5;
+    } else {
+        v6 |= (v7 << (selector + 1)) & 0x780;
+        v7 &= 0x3f >> selector;
+        v7 ^= 32 >> selector;
+        v7 -= 32 >> selector;
+        v6 <<= 4 - selector;
+        v7 <<= 4 - selector;
+        v7 = (v7 + v6).clamp(0, 0xfff);
+    }
+    ((v6 as u32) << 4, (v7 as u32) << 4)
+}
+
+fn decode_endpoints(cem: u32, v: &[i32]) -> Endpoints {
+    match cem {
+        0 => Endpoints::ldr([v[0], v[0], v[0], 0xff], [v[1], v[1], v[1], 0xff]),
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xc0);
+            let l1 = std::cmp::min(l0 + (v[1] & 0x3f), 0xff);
+            Endpoints::ldr([l0, l0, l0, 0xff], [l1, l1, l1, 0xff])
+        }
+        2 => decode_hdr_luminance_large(v),
+        3 => decode_hdr_luminance_small(v),
+        4 => Endpoints::ldr([v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]),
+        5 => {
+            let (d0, l0) = bit_transfer_signed(v[1], v[0]);
+            let (d1, a0) = bit_transfer_signed(v[3], v[2]);
+            let l1 = l0 + d0;
+            Endpoints::ldr([l0, l0, l0, a0], [l1, l1, l1, a0 + d1])
+        }
+        6 => Endpoints::ldr(
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                0xff,
+            ],
+            [v[0], v[1], v[2], 0xff],
+        ),
+        7 => decode_hdr_rgb_scale(v),
+        8 | 12 => {
+            let (a0, a1) = if cem == 12 {
+                (v[6], v[7])
+            } else {
+                (0xff, 0xff)
+            };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                Endpoints::ldr([v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1])
+            } else {
+                Endpoints::ldr(
+                    blue_contract([v[1], v[3], v[5], a1]),
+                    blue_contract([v[0], v[2], v[4], a0]),
+                )
+            }
+        }
+        9 | 13 => {
+            let (d0, r) = bit_transfer_signed(v[1], v[0]);
+            let (d1, g) = bit_transfer_signed(v[3], v[2]);
+            let (d2, b) = bit_transfer_signed(v[5], v[4]);
+            let (d3, a) = if cem == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 0xff)
+            };
+            let base = [r, g, b, a];
+            let offset = [r + d0, g + d1, b + d2, a + d3];
+            if d0 + d1 + d2 >= 0 {
+                Endpoints::ldr(base, offset)
+            } else {
+                Endpoints::ldr(blue_contract(offset), blue_contract(base))
+            }
+        }
+        10 => Endpoints::ldr(
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ),
+        11 => decode_hdr_rgb(v),
+        14 => {
+            let mut ret = decode_hdr_rgb(v);
+            ret.colors[0][3] = v[6] as u32 * 0x101;
+            ret.colors[1][3] = v[7] as u32 * 0x101;
+            ret.hdr[3] = false;
+            ret
+        }
+        _ => {
+            let mut ret = decode_hdr_rgb(v);
+            let (a0, a1) = decode_hdr_alpha(v[6], v[7]);
+            ret.colors[0][3] = a0;
+            ret.colors[1][3] = a1;
+            ret
+        }
+    }
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [0u32; 8];
+    for (idx, s) in seeds.iter_mut().enumerate() {
+        *s = (rnum >> (4 * idx)) & 0xf;
+        *s *= *s;
+    }
+    let (sh1, sh2) = if seed & 1 != 0 {
+        (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        )
+    };
+    for (idx, s) in seeds.iter_mut().enumerate() {
+        *s >>= if idx % 2 == 0 { sh1 } else { sh2 };
+    }
+
+    // z is always zero for 2D blocks, so seeds 9 to 12 don't matter
+    let a = (seeds[0] * x + seeds[1] * y + (rnum >> 14)) & 0x3f;
+    let b = (seeds[2] * x + seeds[3] * y + (rnum >> 10)) & 0x3f;
+    let c = if partitions >= 3 {
+        (seeds[4] * x + seeds[5] * y + (rnum >> 6)) & 0x3f
+    } else {
+        0
+    };
+    let d = if partitions >= 4 {
+        (seeds[6] * x + seeds[7] * y + (rnum >> 2)) & 0x3f
+    } else {
+        0
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+struct BlockMode {
+    grid_width: usize,
+    grid_height: usize,
+    dual_plane: bool,
+    weight_range: Range,
+}
+
+fn decode_block_mode(mode: u32) -> Option<BlockMode> {
+    let a = (mode >> 5) & 3;
+    let mut dual_plane = (mode >> 10) & 1 != 0;
+    let mut high_precision = (mode >> 9) & 1 != 0;
+    let mut range = (mode >> 4) & 1;
+    let (width, height);
+    if mode & 3 != 0 {
+        range |= (mode & 3) << 1;
+        let b = (mode >> 7) & 3;
+        match (mode >> 2) & 3 {
+            0 => {
+                width = b + 4;
+                height = a + 2;
+            }
+            1 => {
+                width = b + 8;
+                height = a + 2;
+            }
+            2 => {
+                width = a + 2;
+                height = b + 8;
+            }
+            _ => {
+                if mode & 0x100 != 0 {
+                    width = (b & 1) + 2;
+                    height = a + 2;
+                } else {
+                    width = a + 2;
+                    height = (b & 1) + 6;
+                }
+            }
+        }
+    } else {
+        range |= ((mode >> 2) & 3) << 1;
+        if (mode >> 2) & 3 == 0 {
+            return None;
+        }
+        let b = (mode >> 9) & 3;
+        match (mode >> 7) & 3 {
+            0 => {
+                width = 12;
+                height = a + 2;
+            }
+            1 => {
+                width = a + 2;
+                height = 12;
+            }
+            2 => {
+                width = a + 6;
+                height = b + 6;
+                dual_plane = false;
+                high_precision = false;
+            }
+            _ => match a {
+                0 => {
+                    width = 6;
+                    height = 10;
+                }
+                1 => {
+                    width = 10;
+                    height = 6;
+                }
+                _ => return None,
+            },
+        }
+    }
+    let range_idx = (range - 2) as usize + if high_precision { 6 } else { 0 };
+    Some(BlockMode {
+        grid_width: width as usize,
+        grid_height: height as usize,
+        dual_plane,
+        weight_range: RANGES[range_idx],
+    })
+}
+
+/// Interpolates the weight grid to the texels of the block.
+fn infill_weights(
+    weights: &[u32],
+    grid_width: usize,
+    grid_height: usize,
+    block_width: usize,
+    block_height: usize,
+) -> Vec<u32> {
+    let ds = (1024 + block_width / 2) / std::cmp::max(block_width - 1, 1);
+    let dt = (1024 + block_height / 2) / std::cmp::max(block_height - 1, 1);
+    let weight_at = |x: usize, y: usize| {
+        if x < grid_width && y < grid_height {
+            weights[y * grid_width + x]
+        } else {
+            0
+        }
+    };
+    let mut ret = Vec::with_capacity(block_width * block_height);
+    for t in 0..block_height {
+        for s in 0..block_width {
+            let gs = (ds * s * (grid_width - 1) + 32) >> 6;
+            let gt = (dt * t * (grid_height - 1) + 32) >> 6;
+            let (js, fs) = (gs >> 4, (gs & 0xf) as u32);
+            let (jt, ft) = (gt >> 4, (gt & 0xf) as u32);
+            let w11 = (fs * ft + 8) >> 4;
+            let w10 = ft - w11;
+            let w01 = fs - w11;
+            let w00 = 16 + w11 - fs - ft;
+            let weight = weight_at(js, jt) * w00
+                + weight_at(js + 1, jt) * w01
+                + weight_at(js, jt + 1) * w10
+                + weight_at(js + 1, jt + 1) * w11;
+            ret.push((weight + 8) >> 4);
+        }
+    }
+    ret
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    (e0 * (64 - weight) + e1 * weight + 32) >> 6
+}
+
+/// Texels of a decoded block before conversion to the output format.
+enum Texels {
+    Error,
+    /// UNORM16 color, or FP16 if `hdr` is set.
+    Constant {
+        color: [u16; 4],
+        hdr: bool,
+    },
+    Decoded(Vec<([u32; 4], [bool; 4])>),
+}
+
+fn decode_texels(block: u128, block_width: usize, block_height: usize) -> Texels {
+    let mode = bits(block, 0, 11);
+    if mode & 0x1ff == 0x1fc {
+        // void-extent block
+        if bits(block, 10, 2) != 3 {
+            return Texels::Error;
+        }
+        let mut color = [0u16; 4];
+        for (idx, c) in color.iter_mut().enumerate() {
+            *c = bits(block, 64 + 16 * idx as u32, 16) as u16;
+        }
+        return Texels::Constant {
+            color,
+            hdr: mode & 0x200 != 0,
+        };
+    }
+
+    let block_mode = match decode_block_mode(mode) {
+        Some(mode) => mode,
+        None => return Texels::Error,
+    };
+    let BlockMode {
+        grid_width,
+        grid_height,
+        dual_plane,
+        weight_range,
+    } = block_mode;
+    let planes = if dual_plane { 2 } else { 1 };
+    let weight_count = grid_width * grid_height * planes;
+    let weight_bits = weight_range.encoded_bits(weight_count as u32);
+    if grid_width > block_width
+        || grid_height > block_height
+        || weight_count > 64
+        || !(24..=96).contains(&weight_bits)
+    {
+        return Texels::Error;
+    }
+
+    let partitions = bits(block, 11, 2) + 1;
+    if partitions == 4 && dual_plane {
+        return Texels::Error;
+    }
+    let mut below_weights = 128 - weight_bits;
+    let mut cems = [0u32; 4];
+    let partition_index;
+    let color_start;
+    if partitions == 1 {
+        cems[0] = bits(block, 13, 4);
+        partition_index = 0;
+        color_start = 17;
+    } else {
+        partition_index = bits(block, 13, 10);
+        color_start = 29;
+        let selector = bits(block, 23, 2);
+        if selector == 0 {
+            let cem = bits(block, 25, 4);
+            for cem_out in cems.iter_mut() {
+                *cem_out = cem;
+            }
+        } else {
+            let extra_bits = 3 * partitions - 4;
+            below_weights -= extra_bits;
+            let encoded = bits(block, 23, 6) | (bits(block, below_weights, extra_bits) << 6);
+            let base_class = selector - 1;
+            for (idx, cem) in cems.iter_mut().take(partitions as usize).enumerate() {
+                let class = ((encoded >> (2 + idx)) & 1) + base_class;
+                let mode = (encoded >> (2 + partitions as usize + 2 * idx)) & 3;
+                *cem = (class << 2) | mode;
+            }
+        }
+    }
+    let ccs = if dual_plane {
+        below_weights -= 2;
+        Some(bits(block, below_weights, 2) as usize)
+    } else {
+        None
+    };
+    if below_weights < color_start {
+        return Texels::Error;
+    }
+
+    let cems = &cems[..partitions as usize];
+    let color_count: u32 = cems.iter().map(|cem| ((cem >> 2) + 1) * 2).sum();
+    if color_count > 18 {
+        return Texels::Error;
+    }
+    let color_bits = below_weights - color_start;
+    let color_range = match RANGES
+        .iter()
+        .rposition(|range| range.encoded_bits(color_count) <= color_bits)
+    {
+        Some(idx) if idx >= MIN_COLOR_RANGE => RANGES[idx],
+        _ => return Texels::Error,
+    };
+    let mut reader = BitReader::new(block, color_start, below_weights);
+    let colors: Vec<i32> = decode_ise(&mut reader, color_range, color_count as usize)
+        .into_iter()
+        .map(|v| unquantize_color(color_range, v) as i32)
+        .collect();
+    let mut endpoints = [Endpoints::default(); 4];
+    let mut offset = 0;
+    for (endpoint, &cem) in endpoints.iter_mut().zip(cems) {
+        let count = (((cem >> 2) + 1) * 2) as usize;
+        *endpoint = decode_endpoints(cem, &colors[offset..offset + count]);
+        offset += count;
+    }
+
+    let mut reader = BitReader::new(block.reverse_bits(), 0, weight_bits);
+    let weights: Vec<u32> = decode_ise(&mut reader, weight_range, weight_count)
+        .into_iter()
+        .map(|v| unquantize_weight(weight_range, v))
+        .collect();
+    let plane_weights = (0..planes)
+        .map(|plane| {
+            let grid: Vec<u32> = weights
+                .iter()
+                .skip(plane)
+                .step_by(planes)
+                .copied()
+                .collect();
+            infill_weights(&grid, grid_width, grid_height, block_width, block_height)
+        })
+        .collect::<Vec<_>>();
+
+    let small_block = block_width * block_height < 31;
+    let mut texels = Vec::with_capacity(block_width * block_height);
+    for y in 0..block_height {
+        for x in 0..block_width {
+            let idx = y * block_width + x;
+            let partition = if partitions == 1 {
+                0
+            } else {
+                select_partition(partition_index, x as u32, y as u32, partitions, small_block)
+            };
+            let endpoint = &endpoints[partition];
+            let mut color = [0u32; 4];
+            for (c, v) in color.iter_mut().enumerate() {
+                let plane = if ccs == Some(c) { 1 } else { 0 };
+                let weight = plane_weights[plane][idx];
+                *v = interpolate(endpoint.colors[0][c], endpoint.colors[1][c], weight);
+            }
+            texels.push((color, endpoint.hdr));
+        }
+    }
+    Texels::Decoded(texels)
+}
+
+fn lns_to_f16(v: u32) -> u16 {
+    let mantissa = v & 0x7ff;
+    let exp = v >> 11;
+    let mantissa = if mantissa < 512 {
+        3 * mantissa
+    } else if mantissa >= 1536 {
+        5 * mantissa - 2048
+    } else {
+        4 * mantissa - 512
+    };
+    std::cmp::min((exp << 10) + (mantissa >> 3), 0x7bff) as u16
+}
+
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = u32::from(h & 0x8000) << 16;
+    let exp = u32::from((h >> 10) & 0x1f);
+    let mantissa = u32::from(h & 0x3ff);
+    let bits = match exp {
+        0 if mantissa == 0 => sign,
+        0 => {
+            let mut exp = 127 - 15 + 1;
+            let mut mantissa = mantissa;
+            while mantissa & 0x400 == 0 {
+                mantissa <<= 1;
+                exp -= 1;
+            }
+            sign | (exp << 23) | ((mantissa & 0x3ff) << 13)
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+fn unorm16_to_u8(v: u32) -> u8 {
+    ((v * 255 + 32767) / 65536) as u8
+}
+
+fn unorm16_to_f32(v: u32) -> f32 {
+    v as f32 / 65535.0
+}
+
+fn read_block<R: std::io::Read>(input: &mut R) -> std::io::Result<u128> {
+    let mut buf = [0u8; BLOCK_SIZE];
+    input.read_exact(&mut buf)?;
+    Ok(u128::from_le_bytes(buf))
+}
+
+/// Decodes a block of the LDR profile as RGBA8 texels in row-major order.
+///
+/// Blocks using HDR endpoints and invalid blocks decode to magenta.
+pub fn decode_single_block<R: std::io::Read>(
+    input: &mut R,
+    block_width: usize,
+    block_height: usize,
+) -> std::io::Result<Vec<[u8; 4]>> {
+    let block = read_block(input)?;
+    let count = block_width * block_height;
+    let ret = match decode_texels(block, block_width, block_height) {
+        Texels::Error | Texels::Constant { hdr: true, .. } => vec![ERROR_COLOR; count],
+        Texels::Constant { color, hdr: false } => {
+            let color = [
+                unorm16_to_u8(color[0].into()),
+                unorm16_to_u8(color[1].into()),
+                unorm16_to_u8(color[2].into()),
+                unorm16_to_u8(color[3].into()),
+            ];
+            vec![color; count]
+        }
+        Texels::Decoded(texels) => texels
+            .into_iter()
+            .map(|(color, hdr)| {
+                if hdr.iter().any(|&hdr| hdr) {
+                    ERROR_COLOR
+                } else {
+                    [
+                        unorm16_to_u8(color[0]),
+                        unorm16_to_u8(color[1]),
+                        unorm16_to_u8(color[2]),
+                        unorm16_to_u8(color[3]),
+                    ]
+                }
+            })
+            .collect(),
+    };
+    Ok(ret)
+}
+
+/// Decodes a block of the HDR profile as RGBA float texels in row-major order.
+///
+/// Invalid blocks decode to magenta.
+pub fn decode_single_block_hdr<R: std::io::Read>(
+    input: &mut R,
+    block_width: usize,
+    block_height: usize,
+) -> std::io::Result<Vec<[f32; 4]>> {
+    let block = read_block(input)?;
+    let count = block_width * block_height;
+    let ret = match decode_texels(block, block_width, block_height) {
+        Texels::Error => vec![ERROR_COLOR_HDR; count],
+        Texels::Constant { color, hdr } => {
+            let mut ret = [0f32; 4];
+            for (ret, &c) in ret.iter_mut().zip(color.iter()) {
+                *ret = if hdr {
+                    f16_to_f32(c)
+                } else {
+                    unorm16_to_f32(c.into())
+                };
+            }
+            vec![ret; count]
+        }
+        Texels::Decoded(texels) => texels
+            .into_iter()
+            .map(|(color, hdr)| {
+                let mut ret = [0f32; 4];
+                for ((ret, &c), &hdr) in ret.iter_mut().zip(color.iter()).zip(hdr.iter()) {
+                    *ret = if hdr {
+                        f16_to_f32(lns_to_f16(c))
+                    } else {
+                        unorm16_to_f32(c)
+                    };
+                }
+                ret
+            })
+            .collect(),
+    };
+    Ok(ret)
+}
+
+fn decode_blocks<T: Copy + Default>(
+    mut data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+    decode_block: impl Fn(&mut &[u8]) -> std::io::Result<Vec<[T; 4]>>,
+) -> std::io::Result<Vec<T>> {
+    let blocks_x = width.div_ceil(block_width);
+    let blocks_y = height.div_ceil(block_height);
+    let scanline = width * 4;
+    let mut ret = vec![T::default(); scanline * height];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let block = decode_block(&mut data)?;
+            let x = block_x * block_width;
+            let y = block_y * block_height;
+            let copy_width = std::cmp::min(block_width, width - x);
+            for (row, texels) in block.chunks(block_width).take(height - y).enumerate() {
+                let start = (y + row) * scanline + x * 4;
+                let target = &mut ret[start..start + copy_width * 4];
+                for (target, texel) in target.chunks_mut(4).zip(texels) {
+                    target.copy_from_slice(texel);
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Decodes an LDR image as RGBA8, keeping the row order of `data`.
+pub fn decode_image(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+) -> std::io::Result<Vec<u8>> {
+    decode_blocks(data, width, height, block_width, block_height, |input| {
+        decode_single_block(input, block_width, block_height)
+    })
+}
+
+/// Decodes an HDR image as RGBA float, keeping the row order of `data`.
+pub fn decode_image_hdr(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+) -> std::io::Result<Vec<f32>> {
+    decode_blocks(data, width, height, block_width, block_height, |input| {
+        decode_single_block_hdr(input, block_width, block_height)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference blocks were encoded by hand following the ASTC specification, with the
+    // expected texels computed from the unquantization, infill and partition formulas there.
+
+    fn ldr(block: [u8; 16], block_width: usize, block_height: usize) -> Vec<[u8; 4]> {
+        decode_single_block(&mut &block[..], block_width, block_height).unwrap()
+    }
+
+    fn luminance(texels: &[[u8; 4]]) -> Vec<u8> {
+        for texel in texels {
+            assert_eq!(texel[..3], [texel[0]; 3]);
+            assert_eq!(texel[3], 0xff);
+        }
+        texels.iter().map(|texel| texel[0]).collect()
+    }
+
+    #[test]
+    fn integer_sequences() {
+        // every combination of trits and quints has an encoding
+        let mut trits = (0..256).map(decode_trits).collect::<Vec<_>>();
+        trits.sort_unstable();
+        trits.dedup();
+        assert_eq!(trits.len(), 243);
+        assert!(trits.iter().flatten().all(|&t| t < 3));
+        let mut quints = (0..128).map(decode_quints).collect::<Vec<_>>();
+        quints.sort_unstable();
+        quints.dedup();
+        assert_eq!(quints.len(), 125);
+        assert!(quints.iter().flatten().all(|&q| q < 5));
+        assert_eq!(decode_trits(0xff), [2, 1, 2, 2, 2]);
+        assert_eq!(decode_trits(0x1c), [0, 0, 0, 2, 2]);
+        assert_eq!(decode_quints(0x1e), [4, 4, 3]);
+        assert_eq!(decode_quints(0x66), [0, 0, 4]);
+    }
+
+    #[test]
+    fn unquantized_ranges() {
+        // unquantized values are evenly spread over 0..=255 for colors and 0..=64 for weights
+        for (idx, &range) in RANGES.iter().enumerate() {
+            let levels = (1u32 << range.bits)
+                * if range.trits { 3 } else { 1 }
+                * if range.quints { 5 } else { 1 };
+            let values = (0..levels)
+                .map(|v| (v & ((1 << range.bits) - 1), v >> range.bits))
+                .collect::<Vec<_>>();
+            if idx >= MIN_COLOR_RANGE {
+                let mut colors = values
+                    .iter()
+                    .map(|&v| unquantize_color(range, v))
+                    .collect::<Vec<_>>();
+                colors.sort_unstable();
+                for (level, &color) in colors.iter().enumerate() {
+                    let expected = (level as u32 * 255 * 2 + levels - 1) / (2 * (levels - 1));
+                    assert!(color.abs_diff(expected) <= 1, "range {} {:?}", idx, colors);
+                }
+            }
+            // weights use the first 12 ranges
+            if idx >= 12 {
+                continue;
+            }
+            let mut weights = values
+                .iter()
+                .map(|&v| unquantize_weight(range, v))
+                .collect::<Vec<_>>();
+            weights.sort_unstable();
+            for (level, &weight) in weights.iter().enumerate() {
+                let expected = (level as u32 * 64 * 2 + levels - 1) / (2 * (levels - 1));
+                assert!(
+                    weight.abs_diff(expected) <= 1,
+                    "range {} {:?}",
+                    idx,
+                    weights
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn direct_rgb() {
+        // 4x4 grid of 2-bit weights, (x + 2y) % 4
+        let block = 0x277227720000000140005191f4150042u128.to_le_bytes();
+        let colors = [
+            [10, 200, 0, 255],
+            [89, 147, 53, 255],
+            [171, 93, 108, 255],
+            [250, 40, 160, 255],
+        ];
+        let texels = ldr(block, 4, 4);
+        for (idx, texel) in texels.iter().enumerate() {
+            let (x, y) = (idx % 4, idx / 4);
+            assert_eq!(*texel, colors[(x + 2 * y) % 4]);
+        }
+
+        // the second endpoint is darker, so both are blue contracted and swapped
+        let block = 0x277227720000000078c8296851910042u128.to_le_bytes();
+        let colors = [
+            [50, 40, 60, 255],
+            [83, 73, 73, 255],
+            [117, 107, 87, 255],
+            [150, 140, 100, 255],
+        ];
+        let texels = ldr(block, 4, 4);
+        for (idx, texel) in texels.iter().enumerate() {
+            let (x, y) = (idx % 4, idx / 4);
+            assert_eq!(*texel, colors[(x + 2 * y) % 4]);
+        }
+    }
+
+    #[test]
+    fn weight_grid_infill() {
+        // 3x3 grid of 3-bit weights on a 6x6 block
+        let block = 0x1f58d1e00000000000000001fe0001bfu128.to_le_bytes();
+        assert_eq!(
+            luminance(&ldr(block, 6, 6)),
+            [
+                0, 96, 207, 227, 163, 108, 68, 116, 151, 167, 163, 151, 147, 120, 80, 92, 151, 199,
+                163, 120, 72, 88, 163, 227, 116, 116, 104, 131, 191, 243, 72, 100, 131, 167, 215,
+                255,
+            ]
+        );
+    }
+
+    #[test]
+    fn trit_and_quint_weights() {
+        let block = 0xc86007000000000000000001fe000051u128.to_le_bytes();
+        assert_eq!(
+            luminance(&ldr(block, 4, 4)),
+            [0, 127, 255, 0, 0, 255, 127, 0, 0, 0, 0, 0, 0, 127, 255, 0]
+        );
+        let block = 0x13f453d24800000000000001fe000052u128.to_le_bytes();
+        assert_eq!(
+            luminance(&ldr(block, 4, 4)),
+            [0, 64, 127, 191, 191, 255, 0, 64, 64, 127, 191, 255, 255, 0, 64, 127]
+        );
+    }
+
+    #[test]
+    fn dual_plane() {
+        // RGB follows x on the first plane, alpha is opaque in the top half on the second
+        let block = 0x77772222c001fe0001ff9051fe018441u128.to_le_bytes();
+        let texels = ldr(block, 4, 4);
+        for (idx, texel) in texels.iter().enumerate() {
+            let (x, y) = (idx % 4, idx / 4);
+            let rgb = if x % 2 == 0 {
+                [0, 40, 255]
+            } else {
+                [255, 200, 0]
+            };
+            assert_eq!(texel[..3], rgb);
+            assert_eq!(texel[3], if y < 2 { 255 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn two_partitions() {
+        // partition seed 1 of a small block, luminance-alpha endpoints
+        let block = 0x279c72c91fe00b4b5fffffe008002842u128.to_le_bytes();
+        let expected = [
+            [0, 255],
+            [90, 84],
+            [90, 171],
+            [255, 255],
+            [90, 84],
+            [90, 171],
+            [90, 255],
+            [0, 255],
+            [90, 171],
+            [90, 255],
+            [0, 255],
+            [84, 255],
+            [90, 255],
+            [90, 0],
+            [84, 255],
+            [171, 255],
+        ];
+        let texels = ldr(block, 4, 4);
+        for (texel, &[l, a]) in texels.iter().zip(expected.iter()) {
+            assert_eq!(*texel, [l, l, l, a]);
+        }
+    }
+
+    #[test]
+    fn hdr_endpoints() {
+        // direct HDR RGB, red from 1.0 to 2.0 along x and the rest at 1.0
+        let block = 0x27272727000000017978f0f100f16042u128.to_le_bytes();
+        let texels = decode_single_block_hdr(&mut &block[..], 4, 4).unwrap();
+        let red = [1.0, 1.265625, 1.609375, 2.0];
+        for (idx, texel) in texels.iter().enumerate() {
+            assert_eq!(*texel, [red[idx % 4], 1.0, 1.0, 1.0]);
+        }
+        // the LDR profile can't decode HDR endpoints
+        assert_eq!(ldr(block, 4, 4), vec![ERROR_COLOR; 16]);
+    }
+
+    #[test]
+    fn reserved_block_mode() {
+        assert_eq!(ldr([0; 16], 4, 4), vec![ERROR_COLOR; 16]);
+    }
+
+    #[test]
+    fn image_edges() {
+        // void-extent blocks with a different gray each, cropped to 6x5
+        let mut data = Vec::new();
+        for gray in [0x1111u128, 0x5555, 0x9999, 0xdddd].iter() {
+            let color = gray | gray << 16 | gray << 32 | 0xffff << 48;
+            data.extend_from_slice(&(0xffff_ffff_ffff_fdfc | color << 64).to_le_bytes());
+        }
+        let pixels = decode_image(&data, 6, 5, 4, 4).unwrap();
+        assert_eq!(pixels.len(), 6 * 5 * 4);
+        for (idx, pixel) in pixels.chunks(4).enumerate() {
+            let (x, y) = (idx % 6, idx / 6);
+            let gray = [0x11, 0x55, 0x99, 0xdd][(y / 4) * 2 + x / 4];
+            assert_eq!(pixel, [gray, gray, gray, 0xff]);
+        }
+        assert!(decode_image(&data, 6, 5 + 4, 4, 4).is_err());
+    }
+}
@@ -0,0 +1,1186 @@
+const SIGNATURE: &[u8] = b"Hx";
+/// Size of a header with a single level.
+const HEADER_MIN_SIZE: usize = 74;
+const MAX_LEVELS: usize = 16;
+const MAX_CODE_SIZE: usize = 16;
+const MAX_SUPPORTED_SYMS: usize = 8192;
+
+/// Order of the code length codes sent with a Huffman model. Codes 17 to 20 are runs.
+const MOST_PROBABLE_CODE_LENGTHS: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+const DXT1_FROM_LINEAR: [u8; 4] = [0, 2, 3, 1];
+const DXT5_FROM_LINEAR: [u8; 8] = [0, 2, 3, 4, 5, 6, 7, 1];
+
+/// Tiles of the 2x2 blocks in a chunk of the original format, and the number of tiles.
+const CHUNK_ENCODINGS: [([usize; 4], usize); 8] = [
+    ([0, 0, 0, 0], 1),
+    ([0, 0, 1, 1], 2),
+    ([0, 1, 0, 1], 2),
+    ([0, 0, 1, 2], 3),
+    ([1, 2, 0, 0], 3),
+    ([0, 1, 0, 2], 3),
+    ([1, 0, 2, 0], 3),
+    ([0, 1, 2, 3], 4),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    InvalidHeader,
+    UnsupportedFormat(u8),
+    InvalidLevel(usize),
+    InvalidData,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidHeader => write!(f, "invalid crunch header"),
+            Error::UnsupportedFormat(id) => write!(f, "crunch format {} is not supported", id),
+            Error::InvalidLevel(level) => write!(f, "level {} does not exist", level),
+            Error::InvalidData => write!(f, "invalid crunch data"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Revision of the crunch format.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// Format of the original crunch library, used by Unity before 2017.3.
+    Original,
+    /// Format revised by Unity in 2017.3, which also supports ETC textures.
+    Unity,
+}
+
+/// Block format of the unpacked texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    Dxt1,
+    Dxt5,
+    /// DXT5 with luma and chroma channels.
+    Dxt5CcxY,
+    /// DXT5 with two channels swizzled to green and alpha.
+    Dxt5XGxR,
+    /// DXT5 with three channels swizzled to green, blue and alpha.
+    Dxt5XGbr,
+    /// DXT5 with all channels swizzled.
+    Dxt5Agbr,
+    /// BC5 blocks.
+    DxnXy,
+    /// BC5 blocks with swapped channels.
+    DxnYx,
+    /// BC4 blocks.
+    Dxt5A,
+    Etc1,
+    Etc2,
+    Etc2A,
+    /// ETC1 blocks with a single color per block.
+    Etc1S,
+    /// ETC2 RGBA blocks with a single color per block.
+    Etc2AS,
+}
+
+impl Format {
+    fn from_id(id: u8) -> Option<Self> {
+        Some(match id {
+            0 => Format::Dxt1,
+            2 => Format::Dxt5,
+            3 => Format::Dxt5CcxY,
+            4 => Format::Dxt5XGxR,
+            5 => Format::Dxt5XGbr,
+            6 => Format::Dxt5Agbr,
+            7 => Format::DxnXy,
+            8 => Format::DxnYx,
+            9 => Format::Dxt5A,
+            10 => Format::Etc1,
+            11 => Format::Etc2,
+            12 => Format::Etc2A,
+            13 => Format::Etc1S,
+            14 => Format::Etc2AS,
+            _ => return None,
+        })
+    }
+
+    /// Size of a 4x4 block in bytes.
+    pub fn block_size(self) -> usize {
+        match self {
+            Format::Dxt1 | Format::Dxt5A | Format::Etc1 | Format::Etc2 | Format::Etc1S => 8,
+            _ => 16,
+        }
+    }
+
+    fn is_etc(self) -> bool {
+        matches!(
+            self,
+            Format::Etc1 | Format::Etc2 | Format::Etc2A | Format::Etc1S | Format::Etc2AS
+        )
+    }
+
+    /// Whether the two subblocks of an ETC block have separate colors.
+    fn has_subblocks(self) -> bool {
+        matches!(self, Format::Etc1 | Format::Etc2 | Format::Etc2A)
+    }
+
+    /// 8-byte halves of a block, in storage order.
+    fn fields(self) -> &'static [Field] {
+        match self {
+            Format::Dxt1 | Format::Etc1 | Format::Etc2 | Format::Etc1S => &[Field::Color],
+            Format::Dxt5A => &[Field::Alpha0],
+            Format::DxnXy | Format::DxnYx => &[Field::Alpha0, Field::Alpha1],
+            _ => &[Field::Alpha0, Field::Color],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Field {
+    Color,
+    Alpha0,
+    Alpha1,
+}
+
+impl Field {
+    fn palette(self) -> usize {
+        match self {
+            Field::Color => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// Reads bits from the MSB of each byte, returning zeros past the end.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buf: 0,
+            count: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            let byte = self.data.get(self.pos).copied().unwrap_or(0);
+            self.pos += 1;
+            self.buf |= u64::from(byte) << (56 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn read_bits(&mut self, bits: u32) -> u32 {
+        if bits == 0 {
+            return 0;
+        }
+        self.refill();
+        let v = (self.buf >> (64 - bits)) as u32;
+        self.buf <<= bits;
+        self.count -= bits;
+        v
+    }
+
+    fn decode(&mut self, model: &Huffman) -> Result<u32, Error> {
+        self.refill();
+        let (sym, len) = model
+            .lookup((self.buf >> 48) as u32)
+            .ok_or(Error::InvalidData)?;
+        self.buf <<= len;
+        self.count -= len;
+        Ok(sym)
+    }
+
+    /// Reads a Huffman model, whose code lengths are themselves Huffman coded.
+    fn read_model(&mut self) -> Result<Huffman, Error> {
+        let total_syms = self.read_bits(14) as usize;
+        if total_syms == 0 || total_syms > MAX_SUPPORTED_SYMS {
+            return Err(Error::InvalidData);
+        }
+        let num_code_lengths = self.read_bits(5) as usize;
+        if num_code_lengths == 0 || num_code_lengths > MOST_PROBABLE_CODE_LENGTHS.len() {
+            return Err(Error::InvalidData);
+        }
+        let mut code_length_sizes = [0u8; 21];
+        for &code in MOST_PROBABLE_CODE_LENGTHS.iter().take(num_code_lengths) {
+            code_length_sizes[code] = self.read_bits(3) as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_sizes)?;
+
+        let mut code_sizes = vec![0u8; total_syms];
+        let mut offset = 0;
+        while offset < total_syms {
+            let remaining = total_syms - offset;
+            let (len, repeat) = match self.decode(&code_lengths)? {
+                code @ 0..=16 => {
+                    code_sizes[offset] = code as u8;
+                    offset += 1;
+                    continue;
+                }
+                17 => (self.read_bits(3) as usize + 3, false),
+                18 => (self.read_bits(7) as usize + 11, false),
+                19 => (self.read_bits(2) as usize + 3, true),
+                _ => (self.read_bits(6) as usize + 7, true),
+            };
+            if len > remaining {
+                return Err(Error::InvalidData);
+            }
+            if repeat {
+                let prev = match offset.checked_sub(1) {
+                    Some(prev) if code_sizes[prev] != 0 => code_sizes[prev],
+                    _ => return Err(Error::InvalidData),
+                };
+                for size in &mut code_sizes[offset..offset + len] {
+                    *size = prev;
+                }
+            }
+            offset += len;
+        }
+        Huffman::new(&code_sizes)
+    }
+}
+
+/// Canonical Huffman code, where shorter codes come first and symbols of the same code size
+/// are ordered by their value.
+#[derive(Clone, Debug, Default)]
+struct Huffman {
+    /// First code of each size.
+    first_codes: [u32; MAX_CODE_SIZE + 1],
+    /// Index of the first symbol of each size in `symbols`.
+    first_indices: [u32; MAX_CODE_SIZE + 1],
+    /// Codes of each size are below this, left-aligned to 16 bits.
+    limits: [u32; MAX_CODE_SIZE + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(code_sizes: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0u32; MAX_CODE_SIZE + 1];
+        for &size in code_sizes {
+            let size = size as usize;
+            if size > MAX_CODE_SIZE {
+                return Err(Error::InvalidData);
+            }
+            counts[size] += 1;
+        }
+        counts[0] = 0;
+
+        let mut model = Huffman::default();
+        let mut code = 0;
+        let mut index = 0;
+        for (size, &count) in counts.iter().enumerate().skip(1) {
+            model.first_codes[size] = code;
+            model.first_indices[size] = index;
+            code += count;
+            index += count;
+            if code > 1 << size {
+                return Err(Error::InvalidData);
+            }
+            model.limits[size] = code << (MAX_CODE_SIZE - size);
+            code <<= 1;
+        }
+
+        model.symbols = Vec::with_capacity(index as usize);
+        for size in 1..=MAX_CODE_SIZE as u8 {
+            for (sym, _) in code_sizes.iter().enumerate().filter(|&(_, &s)| s == size) {
+                model.symbols.push(sym as u16);
+            }
+        }
+        Ok(model)
+    }
+
+    /// Finds the symbol of the code at the top of `bits`, returning the symbol and code size.
+    fn lookup(&self, bits: u32) -> Option<(u32, u32)> {
+        for size in 1..=MAX_CODE_SIZE {
+            if bits < self.limits[size] {
+                let code = bits >> (MAX_CODE_SIZE - size);
+                let index = self.first_indices[size] + code - self.first_codes[size];
+                let sym = *self.symbols.get(index as usize)?;
+                return Some((u32::from(sym), size as u32));
+            }
+        }
+        None
+    }
+}
+
+/// Adds the decoded delta to `index`, wrapping around `count`.
+fn next_index(
+    reader: &mut BitReader<'_>,
+    model: &Huffman,
+    index: usize,
+    count: usize,
+) -> Result<usize, Error> {
+    let index = index + reader.decode(model)? as usize;
+    let index = if index >= count { index - count } else { index };
+    if index < count {
+        Ok(index)
+    } else {
+        Err(Error::InvalidData)
+    }
+}
+
+fn read_be(data: &[u8], offset: usize, len: usize) -> usize {
+    data[offset..offset + len]
+        .iter()
+        .fold(0, |acc, &b| (acc << 8) | b as usize)
+}
+
+/// Per-column state of the Unity variant, referenced by the blocks of the next row.
+#[derive(Copy, Clone, Default)]
+struct Column {
+    reference: u32,
+    endpoints: [usize; 3],
+    /// Endpoints of the second subblock of ETC blocks.
+    second_endpoints: [usize; 3],
+}
+
+/// Crunched texture with its palettes decoded.
+pub struct Texture<'a> {
+    data: &'a [u8],
+    variant: Variant,
+    format: Format,
+    width: u32,
+    height: u32,
+    faces: usize,
+    level_offsets: Vec<usize>,
+    /// Chunk encodings of the original format, or endpoint references of the Unity variant.
+    reference_model: Huffman,
+    /// Models of color and alpha.
+    endpoint_models: [Huffman; 2],
+    selector_models: [Huffman; 2],
+    color_endpoints: Vec<u32>,
+    /// Two selectors per entry for ETC blocks with subblocks, unflipped first.
+    color_selectors: Vec<u32>,
+    alpha_endpoints: Vec<u16>,
+    /// Two selectors per entry for ETC2A blocks, unflipped first.
+    alpha_selectors: Vec<[u8; 6]>,
+}
+
+impl<'a> Texture<'a> {
+    pub fn parse(data: &'a [u8], variant: Variant) -> Result<Self, Error> {
+        if data.len() < HEADER_MIN_SIZE || &data[..2] != SIGNATURE {
+            return Err(Error::InvalidHeader);
+        }
+        let header_size = read_be(data, 2, 2);
+        let levels = data[16] as usize;
+        let faces = data[17] as usize;
+        if levels == 0
+            || levels > MAX_LEVELS
+            || faces == 0
+            || header_size < 70 + levels * 4
+            || data.len() < header_size
+        {
+            return Err(Error::InvalidHeader);
+        }
+        let format = match Format::from_id(data[18]) {
+            Some(format) if variant == Variant::Unity || !format.is_etc() => format,
+            _ => return Err(Error::UnsupportedFormat(data[18])),
+        };
+
+        let mut texture = Texture {
+            data,
+            variant,
+            format,
+            width: read_be(data, 12, 2) as u32,
+            height: read_be(data, 14, 2) as u32,
+            faces,
+            level_offsets: (0..levels).map(|i| read_be(data, 70 + i * 4, 4)).collect(),
+            reference_model: Huffman::default(),
+            endpoint_models: Default::default(),
+            selector_models: Default::default(),
+            color_endpoints: Vec::new(),
+            color_selectors: Vec::new(),
+            alpha_endpoints: Vec::new(),
+            alpha_selectors: Vec::new(),
+        };
+
+        // palettes of color endpoints, color selectors, alpha endpoints and alpha selectors
+        let palette = |idx: usize| {
+            let offset = 33 + idx * 8;
+            (
+                read_be(data, offset, 3),
+                read_be(data, offset + 3, 3),
+                read_be(data, offset + 6, 2),
+            )
+        };
+        let (has_color, has_alpha) = (palette(0).2 != 0, palette(2).2 != 0);
+        if !has_color && !has_alpha {
+            return Err(Error::InvalidData);
+        }
+
+        let mut reader = texture.section(read_be(data, 67, 3), read_be(data, 65, 2))?;
+        texture.reference_model = reader.read_model()?;
+        if has_color {
+            texture.endpoint_models[0] = reader.read_model()?;
+            texture.selector_models[0] = reader.read_model()?;
+        }
+        if has_alpha {
+            texture.endpoint_models[1] = reader.read_model()?;
+            texture.selector_models[1] = reader.read_model()?;
+        }
+
+        if has_color {
+            let (offset, size, count) = palette(0);
+            texture.read_color_endpoints(offset, size, count)?;
+            let (offset, size, count) = palette(1);
+            texture.read_color_selectors(offset, size, count)?;
+        }
+        if has_alpha {
+            let (offset, size, count) = palette(2);
+            texture.read_alpha_endpoints(offset, size, count)?;
+            let (offset, size, count) = palette(3);
+            texture.read_alpha_selectors(offset, size, count)?;
+        }
+        Ok(texture)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn levels(&self) -> usize {
+        self.level_offsets.len()
+    }
+
+    pub fn faces(&self) -> usize {
+        self.faces
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    fn section(&self, offset: usize, size: usize) -> Result<BitReader<'a>, Error> {
+        let data = self.data;
+        match data.get(offset..offset + size) {
+            Some(section) if size != 0 => Ok(BitReader::new(section)),
+            _ => Err(Error::InvalidData),
+        }
+    }
+
+    fn read_color_endpoints(
+        &mut self,
+        offset: usize,
+        size: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        let mut reader = self.section(offset, size)?;
+        self.color_endpoints = Vec::with_capacity(count);
+        if self.format.is_etc() {
+            // RGB555 and the intensity table, stored as deltas in each byte
+            let model = reader.read_model()?;
+            let mut v = 0u32;
+            for _ in 0..count {
+                for shift in &[0, 8, 16, 24] {
+                    v = v.wrapping_add(reader.decode(&model)? << shift);
+                }
+                v &= 0x1f1f_1f1f;
+                let endpoint = if self.format.has_subblocks() {
+                    v
+                } else {
+                    // differential ETC1 block with both subblocks sharing the color
+                    let table = v & 0x0700_0000;
+                    (table << 5) | (table << 2) | 0x0200_0000 | ((v & 0x001f_1f1f) << 3)
+                };
+                self.color_endpoints.push(endpoint);
+            }
+        } else {
+            // two RGB565 colors, stored as deltas of each channel
+            let models = [reader.read_model()?, reader.read_model()?];
+            let mut channels = [0u32; 6];
+            for _ in 0..count {
+                for (idx, c) in channels.iter_mut().enumerate() {
+                    let (model, mask) = if idx % 3 == 1 {
+                        (&models[1], 63)
+                    } else {
+                        (&models[0], 31)
+                    };
+                    *c = (*c + reader.decode(model)?) & mask;
+                }
+                let [r0, g0, b0, r1, g1, b1] = channels;
+                self.color_endpoints
+                    .push(b0 | (g0 << 5) | (r0 << 11) | (b1 << 16) | (g1 << 21) | (r1 << 27));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_color_selectors(
+        &mut self,
+        offset: usize,
+        size: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        let mut reader = self.section(offset, size)?;
+        let model = reader.read_model()?;
+        self.color_selectors = Vec::with_capacity(count);
+
+        if self.variant == Variant::Original {
+            // each symbol is a pair of deltas of linear selectors in [-3, 3], and adding 1
+            // wraps the same as subtracting 3
+            let mut linear = [0u32; 16];
+            for _ in 0..count {
+                for pair in linear.chunks_mut(2) {
+                    let sym = reader.decode(&model)?;
+                    if sym >= 49 {
+                        return Err(Error::InvalidData);
+                    }
+                    pair[0] = (pair[0] + sym % 7 + 1) & 3;
+                    pair[1] = (pair[1] + sym / 7 + 1) & 3;
+                }
+                let selector = linear.iter().enumerate().fold(0, |acc, (idx, &s)| {
+                    acc | u32::from(DXT1_FROM_LINEAR[s as usize]) << (idx * 2)
+                });
+                self.color_selectors.push(selector);
+            }
+            return Ok(());
+        }
+
+        // each symbol is XORed into 4 bits of the previous selectors
+        let mut s = 0u32;
+        for _ in 0..count {
+            for shift in (0..32).step_by(4) {
+                s ^= reader.decode(&model)? << shift;
+            }
+            if !self.format.is_etc() {
+                self.color_selectors
+                    .push(((s ^ (s << 1)) & 0xaaaa_aaaa) | ((s >> 1) & 0x5555_5555));
+                continue;
+            }
+
+            // ETC selectors are stored as MSB and LSB planes in column-major order. Blocks with
+            // subblocks keep separate selectors for each orientation, since the selectors are
+            // coded in the pixel order of the subblocks
+            let selector = (!s & 0xaaaa_aaaa) | (!(s ^ (s >> 1)) & 0x5555_5555);
+            let mut unflipped = 0u32;
+            let mut flipped = 0u32;
+            let mut t = 8;
+            for h in 0..4 {
+                for w in 0..4 {
+                    let s0 = selector >> ((w << 3) | (h << 1));
+                    unflipped |= (((s0 >> 1) & 1) | ((s0 & 1) << 16)) << (t & 15);
+                    let s1 = selector >> ((h << 3) | (w << 1));
+                    flipped |= (((s1 >> 1) & 1) | ((s1 & 1) << 16)) << (t & 15);
+                    t += 4;
+                }
+                t -= 15;
+            }
+            if self.format.has_subblocks() {
+                self.color_selectors.push(unflipped);
+            }
+            self.color_selectors.push(flipped);
+        }
+        Ok(())
+    }
+
+    fn read_alpha_endpoints(
+        &mut self,
+        offset: usize,
+        size: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        let mut reader = self.section(offset, size)?;
+        let model = reader.read_model()?;
+        self.alpha_endpoints = Vec::with_capacity(count);
+        let (mut a, mut b) = (0u32, 0u32);
+        for _ in 0..count {
+            a = (a + reader.decode(&model)?) & 0xff;
+            b = (b + reader.decode(&model)?) & 0xff;
+            self.alpha_endpoints.push((a | (b << 8)) as u16);
+        }
+        Ok(())
+    }
+
+    fn read_alpha_selectors(
+        &mut self,
+        offset: usize,
+        size: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        let mut reader = self.section(offset, size)?;
+        let model = reader.read_model()?;
+        self.alpha_selectors = Vec::with_capacity(count);
+
+        if self.variant == Variant::Original {
+            // each symbol is a pair of deltas of linear selectors in [-7, 7], and adding 1
+            // wraps the same as subtracting 7
+            let mut linear = [0u32; 16];
+            for _ in 0..count {
+                for pair in linear.chunks_mut(2) {
+                    let sym = reader.decode(&model)?;
+                    if sym >= 225 {
+                        return Err(Error::InvalidData);
+                    }
+                    pair[0] = (pair[0] + sym % 15 + 1) & 7;
+                    pair[1] = (pair[1] + sym / 15 + 1) & 7;
+                }
+                let selector = linear.iter().enumerate().fold(0u64, |acc, (idx, &s)| {
+                    acc | u64::from(DXT5_FROM_LINEAR[s as usize]) << (idx * 3)
+                });
+                let mut bytes = [0u8; 6];
+                bytes.copy_from_slice(&selector.to_le_bytes()[..6]);
+                self.alpha_selectors.push(bytes);
+            }
+            return Ok(());
+        }
+
+        if !self.format.is_etc() {
+            // each symbol is XORed into 6 bits of the previous selectors
+            let mut linear = [0u32; 2];
+            for _ in 0..count {
+                let mut selector = 0u64;
+                for (half, linear) in linear.iter_mut().enumerate() {
+                    for shift in (0..24).step_by(6) {
+                        *linear ^= reader.decode(&model)? << shift;
+                        let pair = ((*linear >> shift) & 0x3f) as usize;
+                        let s = DXT5_FROM_LINEAR[pair & 7] | (DXT5_FROM_LINEAR[pair >> 3] << 3);
+                        selector |= u64::from(s) << (half * 24 + shift);
+                    }
+                }
+                let mut bytes = [0u8; 6];
+                bytes.copy_from_slice(&selector.to_le_bytes()[..6]);
+                self.alpha_selectors.push(bytes);
+            }
+            return Ok(());
+        }
+
+        // ETC2 alpha selectors are stored MSB first in column-major order, with separate
+        // selectors for each orientation like the color selectors
+        let mut linear = [0u8; 8];
+        for _ in 0..count {
+            let mut bytes = [0u8; 13];
+            let mut group = 0u32;
+            for p in 0..16 {
+                if p % 2 == 1 {
+                    group >>= 3;
+                } else {
+                    linear[p / 2] ^= reader.decode(&model)? as u8;
+                    group = u32::from(linear[p / 2]);
+                }
+                let s = match (group & 7) as u8 {
+                    s if s <= 3 => 3 - s,
+                    s => s,
+                };
+                let end = 3 * (p + 1);
+                put_etc_alpha_selector(&mut bytes[..7], end, s);
+                let end = end + 9 * (p & 3) - 9 * (p >> 2);
+                put_etc_alpha_selector(&mut bytes[6..], end, s);
+            }
+            let mut selector = [0u8; 6];
+            if self.format.has_subblocks() {
+                selector.copy_from_slice(&bytes[..6]);
+                self.alpha_selectors.push(selector);
+            }
+            selector.copy_from_slice(&bytes[6..12]);
+            self.alpha_selectors.push(selector);
+        }
+        Ok(())
+    }
+
+    /// Unpacks the blocks of `level` in row-major order. Blocks of each face follow the
+    /// previous face.
+    pub fn unpack_level(&self, level: usize) -> Result<Vec<u8>, Error> {
+        let start = *self
+            .level_offsets
+            .get(level)
+            .ok_or(Error::InvalidLevel(level))?;
+        let end = self
+            .level_offsets
+            .get(level + 1)
+            .copied()
+            .unwrap_or(self.data.len());
+        if end <= start {
+            return Err(Error::InvalidData);
+        }
+        let mut reader = self.section(start, end - start)?;
+
+        let width = std::cmp::max(self.width >> level, 1) as usize;
+        let height = std::cmp::max(self.height >> level, 1) as usize;
+        let blocks_x = width.div_ceil(4);
+        let blocks_y = height.div_ceil(4);
+        let mut out = vec![0u8; blocks_x * blocks_y * self.format.block_size() * self.faces];
+        match (self.variant, self.format) {
+            (Variant::Original, _) => {
+                self.unpack_original(&mut reader, blocks_x, blocks_y, &mut out)?
+            }
+            (Variant::Unity, Format::Etc1)
+            | (Variant::Unity, Format::Etc2)
+            | (Variant::Unity, Format::Etc2A) => {
+                self.unpack_unity_etc(&mut reader, blocks_x, blocks_y, &mut out)?
+            }
+            (Variant::Unity, _) => self.unpack_unity(&mut reader, blocks_x, blocks_y, &mut out)?,
+        }
+        Ok(out)
+    }
+
+    fn write_field(
+        &self,
+        field: Field,
+        endpoint: usize,
+        selector: usize,
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        if field == Field::Color {
+            let (endpoint, selector) = match (
+                self.color_endpoints.get(endpoint),
+                self.color_selectors.get(selector),
+            ) {
+                (Some(endpoint), Some(selector)) => (endpoint, selector),
+                _ => return Err(Error::InvalidData),
+            };
+            out[..4].copy_from_slice(&endpoint.to_le_bytes());
+            out[4..8].copy_from_slice(&selector.to_le_bytes());
+        } else {
+            let (endpoint, selector) = match (
+                self.alpha_endpoints.get(endpoint),
+                self.alpha_selectors.get(selector),
+            ) {
+                (Some(endpoint), Some(selector)) => (endpoint, selector),
+                _ => return Err(Error::InvalidData),
+            };
+            out[..2].copy_from_slice(&endpoint.to_le_bytes());
+            out[2..8].copy_from_slice(selector);
+        }
+        Ok(())
+    }
+
+    /// Endpoint palette sizes of color and alpha.
+    fn endpoint_counts(&self) -> [usize; 2] {
+        [self.color_endpoints.len(), self.alpha_endpoints.len()]
+    }
+
+    /// Unpacks the original format, which codes chunks of 2x2 blocks in a serpentine order.
+    /// Endpoints are shared by the tiles of a chunk, and both endpoints and selectors are
+    /// coded as deltas of the previous indices.
+    fn unpack_original(
+        &self,
+        reader: &mut BitReader<'_>,
+        blocks_x: usize,
+        blocks_y: usize,
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        let fields = self.format.fields();
+        let block_size = self.format.block_size();
+        let face_size = blocks_x * blocks_y * block_size;
+        let chunks_x = blocks_x.div_ceil(2);
+        let chunks_y = blocks_y.div_ceil(2);
+        let endpoint_counts = self.endpoint_counts();
+        let selector_counts = [self.color_selectors.len(), self.alpha_selectors.len()];
+
+        // alpha is coded before color
+        let mut order = fields.iter().enumerate().collect::<Vec<_>>();
+        order.sort_by_key(|&(_, &field)| field == Field::Color);
+
+        let mut chunk_encodings = 1;
+        let mut endpoints = [0usize; 3];
+        let mut selectors = [0usize; 3];
+        for face in out.chunks_mut(face_size) {
+            for chunk_y in 0..chunks_y {
+                for idx in 0..chunks_x {
+                    let chunk_x = if chunk_y % 2 == 0 {
+                        idx
+                    } else {
+                        chunks_x - 1 - idx
+                    };
+                    if chunk_encodings == 1 {
+                        chunk_encodings = reader.decode(&self.reference_model)? | 512;
+                    }
+                    let (tiles, num_tiles) = CHUNK_ENCODINGS[(chunk_encodings & 7) as usize];
+                    chunk_encodings >>= 3;
+
+                    let mut tile_endpoints = [[0usize; 4]; 3];
+                    for &(_, &field) in &order {
+                        let palette = field.palette();
+                        for tile in tile_endpoints[field as usize][..num_tiles].iter_mut() {
+                            let idx = field as usize;
+                            endpoints[idx] = next_index(
+                                reader,
+                                &self.endpoint_models[palette],
+                                endpoints[idx],
+                                endpoint_counts[palette],
+                            )?;
+                            *tile = endpoints[idx];
+                        }
+                    }
+
+                    for (tile_idx, &tile) in tiles.iter().enumerate() {
+                        for &(_, &field) in &order {
+                            let palette = field.palette();
+                            let idx = field as usize;
+                            selectors[idx] = next_index(
+                                reader,
+                                &self.selector_models[palette],
+                                selectors[idx],
+                                selector_counts[palette],
+                            )?;
+                        }
+                        let x = chunk_x * 2 + tile_idx % 2;
+                        let y = chunk_y * 2 + tile_idx / 2;
+                        if x >= blocks_x || y >= blocks_y {
+                            continue;
+                        }
+                        let block = &mut face[(y * blocks_x + x) * block_size..];
+                        for &(pos, &field) in &order {
+                            let idx = field as usize;
+                            self.write_field(
+                                field,
+                                tile_endpoints[idx][tile],
+                                selectors[idx],
+                                &mut block[pos * 8..],
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Unpacks DXT and single color ETC blocks of the Unity variant. Each block either codes
+    /// new endpoints or references the endpoints of the block on the left or above, and
+    /// selectors are coded as palette indices.
+    fn unpack_unity(
+        &self,
+        reader: &mut BitReader<'_>,
+        blocks_x: usize,
+        blocks_y: usize,
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        let fields = self.format.fields();
+        let block_size = self.format.block_size();
+        let face_size = blocks_x * blocks_y * block_size;
+        let endpoint_counts = self.endpoint_counts();
+
+        // color is coded before alpha
+        let mut order = fields.iter().enumerate().collect::<Vec<_>>();
+        order.sort_by_key(|&(_, &field)| field != Field::Color);
+
+        // references are coded for 2x2 blocks at once
+        let width = blocks_x + blocks_x % 2;
+        let height = blocks_y + blocks_y % 2;
+        let mut columns = vec![Column::default(); width];
+        let mut endpoints = [0usize; 3];
+        let mut group = 0;
+        for face in out.chunks_mut(face_size) {
+            for y in 0..height {
+                for (x, column) in columns.iter_mut().enumerate() {
+                    if y % 2 == 0 && x % 2 == 0 {
+                        group = reader.decode(&self.reference_model)?;
+                    }
+                    let reference = if y % 2 == 1 {
+                        column.reference
+                    } else {
+                        column.reference = (group >> 2) & 3;
+                        let reference = group & 3;
+                        group >>= 4;
+                        reference
+                    };
+                    match reference {
+                        0 => {
+                            for &(_, &field) in &order {
+                                let palette = field.palette();
+                                let idx = field as usize;
+                                endpoints[idx] = next_index(
+                                    reader,
+                                    &self.endpoint_models[palette],
+                                    endpoints[idx],
+                                    endpoint_counts[palette],
+                                )?;
+                            }
+                            column.endpoints = endpoints;
+                        }
+                        1 => column.endpoints = endpoints,
+                        _ => endpoints = column.endpoints,
+                    }
+
+                    let mut selectors = [0usize; 3];
+                    for &(_, &field) in &order {
+                        let palette = field.palette();
+                        selectors[field as usize] =
+                            reader.decode(&self.selector_models[palette])? as usize;
+                    }
+                    if x >= blocks_x || y >= blocks_y {
+                        continue;
+                    }
+                    let block = &mut face[(y * blocks_x + x) * block_size..];
+                    for &(pos, &field) in &order {
+                        let idx = field as usize;
+                        self.write_field(
+                            field,
+                            endpoints[idx],
+                            selectors[idx],
+                            &mut block[pos * 8..],
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Unpacks ETC blocks with subblocks of the Unity variant. Endpoints of the first subblock
+    /// may also reference the second subblock of the block on the upper left, and the second
+    /// subblock either shares the endpoints or codes new ones.
+    fn unpack_unity_etc(
+        &self,
+        reader: &mut BitReader<'_>,
+        blocks_x: usize,
+        blocks_y: usize,
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        let has_alpha = self.format == Format::Etc2A;
+        let block_size = self.format.block_size();
+        let face_size = blocks_x * blocks_y * block_size;
+        let endpoint_counts = self.endpoint_counts();
+
+        let width = blocks_x + blocks_x % 2;
+        let height = blocks_y + blocks_y % 2;
+        let mut columns = vec![Column::default(); width];
+        // color and alpha endpoints
+        let mut endpoints = [0usize; 3];
+        let mut diagonal = [0usize; 3];
+        for face in out.chunks_mut(face_size) {
+            for y in 0..height {
+                for (x, column) in columns.iter_mut().enumerate() {
+                    let mut reference = if y % 2 == 1 {
+                        column.reference
+                    } else {
+                        let group = reader.decode(&self.reference_model)?;
+                        column.reference = ((group >> 2) & 3) | ((group >> 4) & 12);
+                        (group & 3) | ((group >> 2) & 12)
+                    };
+                    match reference & 3 {
+                        0 => {
+                            endpoints[0] = next_index(
+                                reader,
+                                &self.endpoint_models[0],
+                                endpoints[0],
+                                endpoint_counts[0],
+                            )?;
+                            if has_alpha {
+                                endpoints[1] = next_index(
+                                    reader,
+                                    &self.endpoint_models[1],
+                                    endpoints[1],
+                                    endpoint_counts[1],
+                                )?;
+                            }
+                            column.endpoints = endpoints;
+                        }
+                        1 => column.endpoints = endpoints,
+                        3 => {
+                            endpoints = diagonal;
+                            column.endpoints = endpoints;
+                        }
+                        _ => endpoints = column.endpoints,
+                    }
+                    reference >>= 2;
+
+                    let e0 = self.color_endpoint(endpoints[0])?;
+                    let color_selector = reader.decode(&self.selector_models[0])? as usize;
+                    let alpha_selector = if has_alpha {
+                        reader.decode(&self.selector_models[1])? as usize
+                    } else {
+                        0
+                    };
+                    if reference != 0 {
+                        endpoints[0] = next_index(
+                            reader,
+                            &self.endpoint_models[0],
+                            endpoints[0],
+                            endpoint_counts[0],
+                        )?;
+                    }
+                    let e1 = self.color_endpoint(endpoints[0])?;
+                    diagonal = column.second_endpoints;
+                    column.second_endpoints = endpoints;
+
+                    if x >= blocks_x || y >= blocks_y {
+                        continue;
+                    }
+                    let flip = ((reference >> 1) ^ 1) as usize;
+                    let mut block = &mut face[(y * blocks_x + x) * block_size..];
+                    if has_alpha {
+                        self.write_field(
+                            Field::Alpha0,
+                            endpoints[1],
+                            alpha_selector * 2 + flip,
+                            block,
+                        )?;
+                        block = &mut block[8..];
+                    }
+                    block[..4].copy_from_slice(&etc_endpoints(e0, e1, flip as u8));
+                    let selector = self
+                        .color_selectors
+                        .get(color_selector * 2 + flip)
+                        .ok_or(Error::InvalidData)?;
+                    block[4..8].copy_from_slice(&selector.to_le_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn color_endpoint(&self, idx: usize) -> Result<[u8; 4], Error> {
+        self.color_endpoints
+            .get(idx)
+            .map(|e| e.to_le_bytes())
+            .ok_or(Error::InvalidData)
+    }
+}
+
+/// ORs the 3-bit ETC2 alpha selector `s` ending at bit `end`, counted from the MSB of `bytes`.
+fn put_etc_alpha_selector(bytes: &mut [u8], end: usize, s: u8) {
+    let (byte, bit) = (end / 8, end % 8);
+    bytes[byte] |= (u32::from(s) << (8 - bit)) as u8;
+    if bit < 3 {
+        bytes[byte - 1] |= s >> bit;
+    }
+}
+
+/// Header of an ETC1 block with RGB555 colors and intensity tables `e0` and `e1`, using the
+/// differential mode if the colors are close enough.
+fn etc_endpoints(e0: [u8; 4], e1: [u8; 4], flip: u8) -> [u8; 4] {
+    let diff = (0..3).all(|c| e0[c] + 3 >= e1[c] && e1[c] + 4 >= e0[c]);
+    let mut out = [0u8; 4];
+    for c in 0..3 {
+        out[c] = if diff {
+            (e0[c] << 3) | (e1[c].wrapping_sub(e0[c]) & 7)
+        } else {
+            ((e0[c] << 3) & 0xf0) | (e1[c] >> 1)
+        };
+    }
+    out[3] = ((u32::from(e0[3]) << 5) | (u32::from(e1[3]) << 2)) as u8 | ((diff as u8) << 1) | flip;
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 12x8 DXT5 textures packed by hand following the crunch format, one per variant. Both
+    // hold the same palettes and the same 3x2 blocks, the original one in chunks of 2x2 blocks
+    // and the Unity one with endpoint references.
+    const ORIGINAL_DXT5: &[u8] = &[
+        0x48, 0x78, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00, 0x01, 0x09, 0x00, 0x00, 0x00, 0x0c, 0x00,
+        0x08, 0x01, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x8b, 0x00, 0x00, 0x2a, 0x00, 0x02, 0x00, 0x00, 0xb5, 0x00,
+        0x00, 0x1c, 0x00, 0x03, 0x00, 0x00, 0xd1, 0x00, 0x00, 0x14, 0x00, 0x02, 0x00, 0x00, 0xe5,
+        0x00, 0x00, 0x1f, 0x00, 0x02, 0x00, 0x41, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x01, 0x04, 0x08,
+        0x02, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x64, 0x00, 0x32, 0xff, 0x2f, 0xf2,
+        0xff, 0x29, 0x60, 0x01, 0x56, 0xd0, 0x2d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x68, 0x42, 0x00,
+        0x1d, 0x6d, 0x02, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0x88, 0x42, 0x00, 0x0a, 0xb6, 0x81,
+        0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x42, 0x10, 0x00, 0xab, 0x68, 0x16, 0xdb, 0x6d, 0xb6,
+        0xdb, 0x6d, 0xb4, 0x21, 0x00, 0x82, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x46,
+        0x00, 0x0c, 0x00, 0x1c, 0x48, 0xe4, 0x08, 0x71, 0x43, 0x01, 0x02, 0xb6, 0x81, 0x6d, 0xb6,
+        0xdb, 0x6d, 0xb6, 0xdb, 0x45, 0x21, 0x22, 0x90, 0x71, 0x48, 0x58, 0xaf, 0x40, 0x1a, 0x8d,
+        0x00, 0x00, 0xc6, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x64, 0x40, 0x71, 0x43,
+        0x8a, 0x18, 0xe2, 0x47, 0x10, 0x41, 0x04, 0x10, 0xdb, 0x6d, 0xb6, 0xa0, 0x2a, 0x02, 0x04,
+        0x02, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x45, 0x22, 0x62, 0x97, 0xf9, 0x13,
+        0x14, 0x95, 0x8b, 0x18, 0x03, 0x86, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x65,
+        0xdc, 0x72, 0x02, 0x31, 0xc8, 0x98, 0xe4, 0x1c, 0x72, 0x02, 0x31, 0xc6, 0x8e, 0x33, 0x97,
+        0x79, 0x77, 0x08, 0xb0, 0x88, 0x4a, 0x2d, 0xb2, 0x86, 0x00,
+    ];
+    const UNITY_DXT5: &[u8] = &[
+        0x48, 0x78, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x0c, 0x00,
+        0x08, 0x01, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x89, 0x00, 0x00, 0x2a, 0x00, 0x02, 0x00, 0x00, 0xb3, 0x00,
+        0x00, 0x1c, 0x00, 0x03, 0x00, 0x00, 0xcf, 0x00, 0x00, 0x14, 0x00, 0x02, 0x00, 0x00, 0xe3,
+        0x00, 0x00, 0x20, 0x00, 0x02, 0x00, 0x3f, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x01, 0x03, 0x04,
+        0x02, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x64, 0x34, 0x32, 0xd8, 0x19, 0x64,
+        0x00, 0x0a, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x40, 0x10, 0x00, 0xeb, 0x68,
+        0x16, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb4, 0x42, 0x10, 0x00, 0x55, 0xb4, 0x0b, 0x6d, 0xb6,
+        0xdb, 0x6d, 0xb6, 0xda, 0x10, 0x80, 0x05, 0x5b, 0x40, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d,
+        0xa1, 0x08, 0x00, 0x82, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x46, 0x00, 0x0c,
+        0x00, 0x1c, 0x48, 0xe4, 0x08, 0x71, 0x43, 0x01, 0x02, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d,
+        0xb6, 0xdb, 0x45, 0x21, 0x22, 0x90, 0x71, 0x48, 0x58, 0xaf, 0x40, 0x1a, 0x8d, 0x00, 0x00,
+        0x42, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x62, 0x06, 0x00, 0x0c, 0x00, 0x18,
+        0xc0, 0x38, 0x82, 0x08, 0x20, 0x86, 0xdb, 0x6d, 0xb5, 0x01, 0x50, 0x10, 0x04, 0x02, 0xb6,
+        0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x45, 0x22, 0x62, 0x97, 0xf9, 0x13, 0x14, 0x95,
+        0x8b, 0x18, 0x01, 0x02, 0xb6, 0x81, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x64, 0x08, 0x71,
+        0x03, 0x89, 0x1c, 0x80, 0x8e, 0x28, 0x71, 0xa3, 0x00, 0x06, 0x00, 0x0e, 0x33, 0x97, 0xb9,
+        0x79, 0x09, 0x50, 0x94, 0x21, 0xda, 0x20, 0x54, 0x00,
+    ];
+
+    const COLOR_ENDPOINTS: [u32; 2] = [0xffff, 0x1d19_5285];
+    const COLOR_SELECTORS: [u32; 3] = [0x1b1b_1b1b, 0xe4e4_e4e4, 0x55aa_55aa];
+    const ALPHA_ENDPOINTS: [u16; 2] = [0x00ff, 0xc81e];
+    const ALPHA_SELECTORS: [u64; 2] = [0xfac6_88fa_c688, 0x0539_7705_3977];
+
+    /// Blocks of the textures as (color endpoint, color selector, alpha endpoint, alpha
+    /// selector) palette indices.
+    const BLOCKS: [(usize, usize, usize, usize); 6] = [
+        (1, 0, 1, 0),
+        (1, 1, 1, 1),
+        (0, 2, 0, 1),
+        (1, 2, 1, 0),
+        (0, 0, 1, 1),
+        (1, 1, 0, 0),
+    ];
+
+    fn expected_blocks() -> Vec<u8> {
+        let mut out = Vec::new();
+        for &(color, color_selector, alpha, alpha_selector) in &BLOCKS {
+            out.extend_from_slice(&ALPHA_ENDPOINTS[alpha].to_le_bytes());
+            out.extend_from_slice(&ALPHA_SELECTORS[alpha_selector].to_le_bytes()[..6]);
+            out.extend_from_slice(&COLOR_ENDPOINTS[color].to_le_bytes());
+            out.extend_from_slice(&COLOR_SELECTORS[color_selector].to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn original_dxt5() {
+        let texture = Texture::parse(ORIGINAL_DXT5, Variant::Original).unwrap();
+        assert_eq!((texture.width(), texture.height()), (12, 8));
+        assert_eq!((texture.levels(), texture.faces()), (1, 1));
+        assert_eq!(texture.format(), Format::Dxt5);
+        assert_eq!(texture.unpack_level(0).unwrap(), expected_blocks());
+        assert_eq!(texture.unpack_level(1), Err(Error::InvalidLevel(1)));
+    }
+
+    #[test]
+    fn unity_dxt5() {
+        let texture = Texture::parse(UNITY_DXT5, Variant::Unity).unwrap();
+        assert_eq!((texture.width(), texture.height()), (12, 8));
+        assert_eq!(texture.format(), Format::Dxt5);
+        assert_eq!(texture.unpack_level(0).unwrap(), expected_blocks());
+    }
+
+    #[test]
+    fn variant_mismatch() {
+        // selectors and levels are coded differently, so the other variant reads garbage
+        let unpacked = Texture::parse(ORIGINAL_DXT5, Variant::Unity)
+            .and_then(|texture| texture.unpack_level(0));
+        assert_ne!(unpacked, Ok(expected_blocks()));
+        let unpacked = Texture::parse(UNITY_DXT5, Variant::Original)
+            .and_then(|texture| texture.unpack_level(0));
+        assert_ne!(unpacked, Ok(expected_blocks()));
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(matches!(
+            Texture::parse(&ORIGINAL_DXT5[..40], Variant::Original),
+            Err(Error::InvalidHeader)
+        ));
+        let mut data = ORIGINAL_DXT5.to_vec();
+        data[18] = 10;
+        assert!(matches!(
+            Texture::parse(&data, Variant::Original),
+            Err(Error::UnsupportedFormat(10))
+        ));
+    }
+}
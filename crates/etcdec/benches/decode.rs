@@ -0,0 +1,63 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use etcdec::DecodeFormat;
+
+const SIZE: usize = 1024;
+
+fn pseudo_random_blocks(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+/// Baseline: decodes the image one block at a time through `decode_single_block`, which doesn't
+/// go through the batched decoder.
+fn decode_per_block(data: &[u8], format: DecodeFormat) -> Vec<u8> {
+    let blocks = SIZE / 4;
+    let scanline = SIZE * 4;
+    let mut input = data;
+    let mut ret = vec![0u8; scanline * SIZE];
+    for block_y in 0..blocks {
+        for block_x in 0..blocks {
+            let block = etcdec::decode_single_block(&mut input, format).unwrap();
+            for (y, row) in block.iter().enumerate() {
+                let start = (block_y * 4 + y) * scanline + block_x * 16;
+                ret[start..start + 16].copy_from_slice(row);
+            }
+        }
+    }
+    ret
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements((SIZE * SIZE) as u64));
+    for &format in &[
+        DecodeFormat::EtcRgb4,
+        DecodeFormat::Etc2Rgb,
+        DecodeFormat::Etc2Rgba8,
+        DecodeFormat::EacRg,
+    ] {
+        let data = pseudo_random_blocks((SIZE / 4) * (SIZE / 4) * format.block_size());
+        let name = format!("{:?}", format);
+        group.bench_with_input(BenchmarkId::new("per_block", &name), &data, |b, data| {
+            b.iter(|| decode_per_block(data, format))
+        });
+        group.bench_with_input(BenchmarkId::new("blocks", &name), &data, |b, data| {
+            b.iter(|| etcdec::decode_blocks(data, format))
+        });
+        group.bench_with_input(BenchmarkId::new("image", &name), &data, |b, data| {
+            b.iter(|| etcdec::decode_image(data, SIZE, SIZE, format).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,899 @@
+use super::{
+    calculate_paint_colors, decompress_color, extend_u8_3bit, saturating_add_u8_i32, Block,
+    DecodeFormat, Pattern, ALPHA_TABLE, BLOCK_HEIGHT, BLOCK_WIDTH, CHANNELS, COMPRESS_PARAMS,
+};
+
+/// Inverse of `UNSCRAMBLE`, maps modifier indices to pixel index bits.
+const SCRAMBLE: [u64; 4] = [3, 2, 0, 1];
+
+/// Pixels of a block in pixel index order, i.e. column by column.
+type Pixels = [[u8; 4]; BLOCK_WIDTH * BLOCK_HEIGHT];
+
+/// Search effort of the encoder.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Quality {
+    /// Tries individual and differential modes with base colors quantized from subblock averages.
+    Fast,
+    /// Tries every mode the format allows, refining base colors around the averages.
+    High,
+}
+
+#[derive(Copy, Clone)]
+struct Candidate {
+    error: u32,
+    block: u64,
+}
+
+impl Candidate {
+    const NONE: Candidate = Candidate {
+        error: u32::MAX,
+        block: 0,
+    };
+
+    fn pick(&mut self, other: Candidate) {
+        if other.error < self.error {
+            *self = other;
+        }
+    }
+}
+
+fn color_error(pixel: [u8; 4], color: [u8; 3]) -> u32 {
+    let mut ret = 0;
+    for c in 0..3 {
+        let d = i32::from(pixel[c]) - i32::from(color[c]);
+        ret += (d * d) as u32;
+    }
+    ret
+}
+
+/// Picks the closest palette entry for each pixel in `set`, returning the total error.
+///
+/// If `transparent` is given, transparent pixels map to it and opaque pixels never do.
+fn fit_palette(
+    pixels: &Pixels,
+    set: &[usize],
+    palette: &[[u8; 3]; 4],
+    transparent: Option<usize>,
+    indices: &mut [u8; 16],
+) -> u32 {
+    let mut total = 0;
+    for &i in set {
+        let pixel = pixels[i];
+        if let Some(transparent) = transparent {
+            if pixel[3] < 128 {
+                indices[i] = transparent as u8;
+                continue;
+            }
+        }
+        let mut best = (u32::MAX, 0);
+        for (idx, &color) in palette.iter().enumerate() {
+            if Some(idx) == transparent {
+                continue;
+            }
+            let error = color_error(pixel, color);
+            if error < best.0 {
+                best = (error, idx);
+            }
+        }
+        indices[i] = best.1 as u8;
+        total += best.0;
+    }
+    total
+}
+
+fn average(pixels: &Pixels, set: &[usize], transparent: bool) -> [f32; 3] {
+    let mut sum = [0f32; 3];
+    let mut count = 0;
+    for &i in set {
+        if transparent && pixels[i][3] < 128 {
+            continue;
+        }
+        for c in 0..3 {
+            sum[c] += f32::from(pixels[i][c]);
+        }
+        count += 1;
+    }
+    if count != 0 {
+        for v in sum.iter_mut() {
+            *v /= count as f32;
+        }
+    }
+    sum
+}
+
+fn quantize(v: f32, bits: u32) -> u8 {
+    let max = (1 << bits) - 1;
+    let q = (v * max as f32 / 255.0).round() as i32;
+    if q < 0 {
+        0
+    } else if q > max {
+        max as u8
+    } else {
+        q as u8
+    }
+}
+
+fn expand(v: u8, bits: u32) -> u8 {
+    (v << (8 - bits)) | (v >> (2 * bits - 8))
+}
+
+/// Quantized colors to try around `color`.
+fn neighbors(color: [u8; 3], bits: [u32; 3], quality: Quality) -> Vec<[u8; 3]> {
+    if quality == Quality::Fast {
+        return vec![color];
+    }
+    let mut ret = Vec::with_capacity(27);
+    for dr in -1..=1 {
+        for dg in -1..=1 {
+            for db in -1..=1 {
+                let mut candidate = color;
+                let mut valid = true;
+                for (c, &d) in [dr, dg, db].iter().enumerate() {
+                    let v = i32::from(color[c]) + d;
+                    if v < 0 || v >= 1 << bits[c] {
+                        valid = false;
+                    }
+                    candidate[c] = v as u8;
+                }
+                if valid {
+                    ret.push(candidate);
+                }
+            }
+        }
+    }
+    ret
+}
+
+#[derive(Clone)]
+struct SubblockFit {
+    color: [u8; 3],
+    table: u64,
+    error: u32,
+    indices: [u8; 16],
+}
+
+/// Finds the best modifier table for a subblock with the given quantized base color.
+fn fit_subblock(
+    pixels: &Pixels,
+    set: &[usize],
+    color: [u8; 3],
+    bits: u32,
+    transparent: bool,
+) -> SubblockFit {
+    let base = [
+        expand(color[0], bits),
+        expand(color[1], bits),
+        expand(color[2], bits),
+    ];
+    let mut best = SubblockFit {
+        color,
+        table: 0,
+        error: u32::MAX,
+        indices: [0; 16],
+    };
+    let mut indices = [0u8; 16];
+    for table in 0..8 {
+        let params = COMPRESS_PARAMS[table * 2];
+        let mut palette = [[0u8; 3]; 4];
+        for (idx, color) in palette.iter_mut().enumerate() {
+            // Punch-through blocks with transparent pixels lose the small modifiers
+            let param = if transparent && (idx == 1 || idx == 2) {
+                0
+            } else {
+                params[idx]
+            };
+            for c in 0..3 {
+                color[c] = saturating_add_u8_i32(base[c], param);
+            }
+        }
+        let transparent_idx = if transparent { Some(1) } else { None };
+        let error = fit_palette(pixels, set, &palette, transparent_idx, &mut indices);
+        if error < best.error {
+            best.table = table as u64;
+            best.error = error;
+            best.indices = indices;
+        }
+    }
+    best
+}
+
+fn pack_etc1(
+    fits: [&SubblockFit; 2],
+    differential: bool,
+    flip: bool,
+    flag: bool,
+    sets: [&[usize]; 2],
+) -> u64 {
+    let mut block = 0u64;
+    for c in 0..3 {
+        let shift = 8 * (2 - c) as u64 + 40;
+        let (c0, c1) = (u64::from(fits[0].color[c]), u64::from(fits[1].color[c]));
+        if differential {
+            block |= c0 << (shift + 3);
+            block |= (c1.wrapping_sub(c0) & 7) << shift;
+        } else {
+            block |= c0 << (shift + 4);
+            block |= c1 << shift;
+        }
+    }
+    block |= fits[0].table << 37;
+    block |= fits[1].table << 34;
+    block |= u64::from(flag) << 33;
+    block |= u64::from(flip) << 32;
+    for (fit, set) in fits.iter().zip(sets.iter()) {
+        for &i in set.iter() {
+            let v = SCRAMBLE[fit.indices[i] as usize];
+            block |= (v >> 1) << (16 + i);
+            block |= (v & 1) << i;
+        }
+    }
+    block
+}
+
+fn subblock_sets(flip: bool) -> [[usize; 8]; 2] {
+    let mut ret = [[0usize; 8]; 2];
+    let mut counts = [0usize; 2];
+    for x in 0..BLOCK_WIDTH {
+        for y in 0..BLOCK_HEIGHT {
+            let second = if flip { y >= 2 } else { x >= 2 };
+            let sub = second as usize;
+            ret[sub][counts[sub]] = x * BLOCK_HEIGHT + y;
+            counts[sub] += 1;
+        }
+    }
+    ret
+}
+
+/// Tries individual and differential modes with both subblock orientations.
+fn encode_etc1(
+    pixels: &Pixels,
+    quality: Quality,
+    individual: bool,
+    transparent: Option<bool>,
+) -> Candidate {
+    let has_transparent = transparent == Some(true);
+    let mut best = Candidate::NONE;
+    for &flip in &[false, true] {
+        let sets = subblock_sets(flip);
+        let averages = [
+            average(pixels, &sets[0], has_transparent),
+            average(pixels, &sets[1], has_transparent),
+        ];
+
+        let fits = |set: &[usize], average: [f32; 3], bits: u32| -> Vec<SubblockFit> {
+            let color = [
+                quantize(average[0], bits),
+                quantize(average[1], bits),
+                quantize(average[2], bits),
+            ];
+            neighbors(color, [bits; 3], quality)
+                .into_iter()
+                .map(|color| fit_subblock(pixels, set, color, bits, has_transparent))
+                .collect()
+        };
+
+        let first = fits(&sets[0], averages[0], 5);
+        let second = fits(&sets[1], averages[1], 5);
+        let target = [
+            quantize(averages[1][0], 5),
+            quantize(averages[1][1], 5),
+            quantize(averages[1][2], 5),
+        ];
+        for fit0 in &first {
+            let in_range = |color: &[u8; 3]| {
+                (0..3).all(|c| {
+                    let d = i32::from(color[c]) - i32::from(fit0.color[c]);
+                    (-4..=3).contains(&d)
+                })
+            };
+            let mut best_second = None::<SubblockFit>;
+            for fit1 in second.iter().filter(|fit| in_range(&fit.color)) {
+                if best_second.as_ref().is_none_or(|b| fit1.error < b.error) {
+                    best_second = Some(fit1.clone());
+                }
+            }
+            // The second color clamped into reach of the first one, so that a differential
+            // candidate always exists
+            let mut clamped = target;
+            for c in 0..3 {
+                let base = i32::from(fit0.color[c]);
+                let v = i32::from(target[c]);
+                let v = if v < base - 4 {
+                    base - 4
+                } else if v > base + 3 {
+                    base + 3
+                } else {
+                    v
+                };
+                clamped[c] = if v < 0 {
+                    0
+                } else if v > 31 {
+                    31
+                } else {
+                    v as u8
+                };
+            }
+            let fit1 = fit_subblock(pixels, &sets[1], clamped, 5, has_transparent);
+            if best_second.as_ref().is_none_or(|b| fit1.error < b.error) {
+                best_second = Some(fit1);
+            }
+            if let Some(fit1) = best_second {
+                let flag = transparent != Some(true);
+                best.pick(Candidate {
+                    error: fit0.error + fit1.error,
+                    block: pack_etc1([fit0, &fit1], true, flip, flag, [&sets[0], &sets[1]]),
+                });
+            }
+        }
+
+        if individual {
+            let pick = |fits: Vec<SubblockFit>| fits.into_iter().min_by_key(|fit| fit.error);
+            let fit0 = pick(fits(&sets[0], averages[0], 4));
+            let fit1 = pick(fits(&sets[1], averages[1], 4));
+            if let (Some(fit0), Some(fit1)) = (fit0, fit1) {
+                best.pick(Candidate {
+                    error: fit0.error + fit1.error,
+                    block: pack_etc1([&fit0, &fit1], false, flip, false, [&sets[0], &sets[1]]),
+                });
+            }
+        }
+    }
+    best
+}
+
+fn overflows(block: u64, shift: u32) -> bool {
+    let raw = ((block >> (shift + 3)) & 0x1f) as i32;
+    let diff = i32::from(extend_u8_3bit(((block >> shift) & 7) as u8) as i8);
+    raw + diff < 0 || raw + diff > 31
+}
+
+/// Moves bit ranges `(from, to, len)` of `v` into place and sets the unused bits so that the
+/// decoder picks the mode overflowing the channel at `shift`.
+fn stuff(v: u64, ranges: &[(u32, u32, u32)], flag: bool, shift: u32) -> Option<u64> {
+    let mut block = u64::from(flag) << 33;
+    let mut used = 1u64 << 33;
+    for &(from, to, len) in ranges {
+        let mask = (1u64 << len) - 1;
+        block |= ((v >> from) & mask) << to;
+        used |= mask << to;
+    }
+    let free: Vec<u32> = (0..64).filter(|&bit| used & (1 << bit) == 0).collect();
+    (0..1u64 << free.len())
+        .map(|combo| {
+            let mut block = block;
+            for (i, &bit) in free.iter().enumerate() {
+                block |= ((combo >> i) & 1) << bit;
+            }
+            block
+        })
+        .find(|&block| {
+            [56, 48, 40]
+                .iter()
+                .take_while(|&&s| s >= shift)
+                .all(|&s| overflows(block, s) == (s == shift))
+        })
+}
+
+fn principal_order(pixels: &Pixels, set: &[usize]) -> Vec<usize> {
+    let mean = average(pixels, set, false);
+    let mut cov = [[0f32; 3]; 3];
+    for &i in set {
+        let mut d = [0f32; 3];
+        for c in 0..3 {
+            d[c] = f32::from(pixels[i][c]) - mean[c];
+        }
+        for a in 0..3 {
+            for b in 0..3 {
+                cov[a][b] += d[a] * d[b];
+            }
+        }
+    }
+    let mut axis = [1f32, 1.0, 1.0];
+    for _ in 0..8 {
+        let mut next = [0f32; 3];
+        for a in 0..3 {
+            next[a] = cov[a][0] * axis[0] + cov[a][1] * axis[1] + cov[a][2] * axis[2];
+        }
+        let norm = next.iter().fold(0f32, |acc, v| acc.max(v.abs()));
+        if norm == 0.0 {
+            break;
+        }
+        for a in 0..3 {
+            axis[a] = next[a] / norm;
+        }
+    }
+    let project = |i: usize| -> f32 { (0..3).map(|c| f32::from(pixels[i][c]) * axis[c]).sum() };
+    let mut ret = set.to_vec();
+    ret.sort_by(|&a, &b| {
+        project(a)
+            .partial_cmp(&project(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    ret
+}
+
+struct ThumbFit {
+    error: u32,
+    colors: [[u8; 3]; 2],
+    dist: u8,
+    indices: [u8; 16],
+}
+
+fn packed_444(color: [u8; 3]) -> u32 {
+    (u32::from(color[0]) << 8) | (u32::from(color[1]) << 4) | u32::from(color[2])
+}
+
+fn fit_thumb(
+    pixels: &Pixels,
+    pattern: Pattern,
+    colors: [[u8; 3]; 2],
+    transparent: bool,
+    best: &mut ThumbFit,
+) {
+    let all: Vec<usize> = (0..16).collect();
+    let expanded = decompress_color(4, 4, 4, colors);
+    let mut indices = [0u8; 16];
+    for dist in 0..8u8 {
+        // The lowest bit of the H mode distance is implied by the order of the colors
+        if pattern == Pattern::H
+            && (packed_444(colors[0]) >= packed_444(colors[1])) != (dist & 1 == 1)
+        {
+            continue;
+        }
+        let palette = calculate_paint_colors(dist, pattern, expanded);
+        let transparent_idx = if transparent { Some(2) } else { None };
+        let error = fit_palette(pixels, &all, &palette, transparent_idx, &mut indices);
+        if error < best.error {
+            *best = ThumbFit {
+                error,
+                colors,
+                dist,
+                indices,
+            };
+        }
+    }
+}
+
+/// Tries T or H mode, splitting the pixels into two groups along their principal axis.
+fn encode_thumb(pixels: &Pixels, pattern: Pattern, transparent: Option<bool>) -> Candidate {
+    let has_transparent = transparent == Some(true);
+    let set: Vec<usize> = (0..16)
+        .filter(|&i| !has_transparent || pixels[i][3] >= 128)
+        .collect();
+    let order = principal_order(pixels, &set);
+    let mut best = ThumbFit {
+        error: u32::MAX,
+        colors: [[0; 3]; 2],
+        dist: 0,
+        indices: [0; 16],
+    };
+    for split in 1..order.len().max(2) {
+        let split = split.min(order.len());
+        let groups = [&order[..split], &order[split..]];
+        let mut colors = [[0u8; 3]; 2];
+        for (color, group) in colors.iter_mut().zip(groups.iter()) {
+            let group = if group.is_empty() { &order[..] } else { group };
+            let average = average(pixels, group, false);
+            for c in 0..3 {
+                color[c] = quantize(average[c], 4);
+            }
+        }
+        fit_thumb(pixels, pattern, colors, has_transparent, &mut best);
+        fit_thumb(
+            pixels,
+            pattern,
+            [colors[1], colors[0]],
+            has_transparent,
+            &mut best,
+        );
+    }
+    if best.error == u32::MAX {
+        return Candidate::NONE;
+    }
+    for which in 0..2 {
+        for color in neighbors(best.colors[which], [4; 3], Quality::High) {
+            let mut colors = best.colors;
+            colors[which] = color;
+            fit_thumb(pixels, pattern, colors, has_transparent, &mut best);
+        }
+    }
+
+    let mut v = 0u64;
+    for i in 0..16 {
+        let idx = u64::from(best.indices[i]);
+        v |= (idx >> 1) << (16 + i);
+        v |= (idx & 1) << i;
+    }
+    let mut colors = 0u64;
+    for color in best.colors.iter() {
+        colors = (colors << 12) | u64::from(packed_444(*color));
+    }
+    let flag = transparent != Some(true);
+    let block = match pattern {
+        Pattern::T => {
+            v |= u64::from(best.dist) << 32;
+            v |= colors << 35;
+            stuff(v, &[(0, 0, 33), (33, 34, 24), (57, 59, 2)], flag, 56)
+        }
+        Pattern::H => {
+            v |= u64::from(best.dist >> 1) << 32;
+            v |= colors << 34;
+            stuff(
+                v,
+                &[(0, 0, 33), (33, 34, 16), (49, 51, 2), (51, 56, 7)],
+                flag,
+                48,
+            )
+        }
+    };
+    match block {
+        Some(block) => Candidate {
+            error: best.error,
+            block,
+        },
+        None => Candidate::NONE,
+    }
+}
+
+fn planar_value(o: u8, h: u8, v: u8, x: usize, y: usize) -> u8 {
+    let (o, h, v) = (o as isize, h as isize, v as isize);
+    let (x, y) = (x as isize, y as isize);
+    let val = (x * (h - o) + y * (v - o) + 4 * o + 2) >> 2;
+    if val < 0 {
+        0
+    } else if val > 0xff {
+        0xff
+    } else {
+        val as u8
+    }
+}
+
+/// Tries planar mode with a least squares fit of each channel.
+fn encode_planar(pixels: &Pixels, quality: Quality) -> Candidate {
+    // Origin, horizontal and vertical colors
+    let mut colors = [[0u8; 3]; 3];
+    let mut error = 0;
+    for c in 0..3 {
+        let bits = if c == 1 { 7 } else { 6 };
+        let mut mean = 0f32;
+        let mut dx = 0f32;
+        let mut dy = 0f32;
+        for x in 0..BLOCK_WIDTH {
+            for y in 0..BLOCK_HEIGHT {
+                let p = f32::from(pixels[x * BLOCK_HEIGHT + y][c]);
+                mean += p / 16.0;
+                dx += (x as f32 - 1.5) * p / 20.0;
+                dy += (y as f32 - 1.5) * p / 20.0;
+            }
+        }
+        let o = mean - 1.5 * (dx + dy);
+        let fitted = [
+            quantize(o, bits),
+            quantize(o + 4.0 * dx, bits),
+            quantize(o + 4.0 * dy, bits),
+        ];
+        let mut best = (u32::MAX, fitted);
+        for candidate in neighbors(fitted, [bits; 3], quality) {
+            let o = expand(candidate[0], bits);
+            let h = expand(candidate[1], bits);
+            let v = expand(candidate[2], bits);
+            let mut channel_error = 0;
+            for x in 0..BLOCK_WIDTH {
+                for y in 0..BLOCK_HEIGHT {
+                    let p = i32::from(pixels[x * BLOCK_HEIGHT + y][c]);
+                    let d = p - i32::from(planar_value(o, h, v, x, y));
+                    channel_error += (d * d) as u32;
+                }
+            }
+            if channel_error < best.0 {
+                best = (channel_error, candidate);
+            }
+        }
+        error += best.0;
+        for (color, &q) in colors.iter_mut().zip(best.1.iter()) {
+            color[c] = q;
+        }
+    }
+
+    // Vertical, horizontal and origin colors from the lowest bits, each stored as BGR
+    let mut v = 0u64;
+    let mut shift = 7;
+    for color in colors.iter().rev() {
+        for (c, &bits) in [2, 1, 0].iter().zip([6, 7, 6].iter()) {
+            v |= u64::from(color[*c]) << shift;
+            shift += bits;
+        }
+    }
+    let ranges = [
+        (7, 0, 33),
+        (40, 34, 8),
+        (48, 43, 2),
+        (50, 48, 7),
+        (57, 56, 7),
+    ];
+    match stuff(v, &ranges, true, 40) {
+        Some(block) => Candidate { error, block },
+        None => Candidate::NONE,
+    }
+}
+
+fn encode_color(pixels: &Pixels, format: DecodeFormat, quality: Quality) -> u64 {
+    let punchthrough = format == DecodeFormat::Etc2Rgba1;
+    let transparent = if punchthrough {
+        Some(pixels.iter().any(|p| p[3] < 128))
+    } else {
+        None
+    };
+    let mut best = encode_etc1(pixels, quality, !punchthrough, transparent);
+    if format != DecodeFormat::EtcRgb4 && quality == Quality::High {
+        best.pick(encode_thumb(pixels, Pattern::T, transparent));
+        best.pick(encode_thumb(pixels, Pattern::H, transparent));
+        if transparent != Some(true) {
+            best.pick(encode_planar(pixels, quality));
+        }
+    }
+    best.block
+}
+
+fn encode_alpha(pixels: &Pixels, quality: Quality) -> u64 {
+    let min = pixels.iter().map(|p| p[3]).min().unwrap_or(0);
+    let max = pixels.iter().map(|p| p[3]).max().unwrap_or(0);
+    let mut best = Candidate::NONE;
+    for table in 0..16usize {
+        let modifiers = &ALPHA_TABLE[16 + table];
+        let low = modifiers.iter().copied().min().unwrap_or(0);
+        let high = modifiers.iter().copied().max().unwrap_or(0);
+        let range = i32::from(max - min);
+        let multipliers = if quality == Quality::Fast {
+            let m = (range + (high - low) / 2) / (high - low);
+            let m = if m < 1 { 1 } else { m.min(15) };
+            m..=m
+        } else {
+            1..=15
+        };
+        for multiplier in multipliers {
+            let center =
+                (i32::from(min) - multiplier * low + i32::from(max) - multiplier * high) / 2;
+            let spread = if quality == Quality::Fast { 0 } else { 2 };
+            for base in center - spread..=center + spread {
+                if !(0..=255).contains(&base) {
+                    continue;
+                }
+                let modifiers = &ALPHA_TABLE[multiplier as usize * 16 + table];
+                let mut error = 0u32;
+                let mut indices = 0u64;
+                for (i, pixel) in pixels.iter().enumerate() {
+                    let (e, idx) = modifiers
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, &m)| {
+                            let d = i32::from(pixel[3])
+                                - i32::from(saturating_add_u8_i32(base as u8, m));
+                            ((d * d) as u32, idx as u64)
+                        })
+                        .min()
+                        .unwrap_or((0, 0));
+                    error += e;
+                    indices |= idx << (45 - 3 * i);
+                }
+                best.pick(Candidate {
+                    error,
+                    block: ((base as u64) << 56)
+                        | ((multiplier as u64) << 52)
+                        | ((table as u64) << 48)
+                        | indices,
+                });
+            }
+        }
+    }
+    best.block
+}
+
+/// Encodes a block, returning it in the first `format.block_size()` bytes.
+fn encode_block(block: &Block, format: DecodeFormat, quality: Quality) -> [u8; 16] {
+    let mut pixels = [[0u8; 4]; 16];
+    for x in 0..BLOCK_WIDTH {
+        for y in 0..BLOCK_HEIGHT {
+            pixels[x * BLOCK_HEIGHT + y]
+                .copy_from_slice(&block[y][CHANNELS * x..CHANNELS * (x + 1)]);
+        }
+    }
+    let mut ret = [0u8; 16];
+    let color = encode_color(&pixels, format, quality).to_be_bytes();
+    if format == DecodeFormat::Etc2Rgba8 {
+        ret[..8].copy_from_slice(&encode_alpha(&pixels, quality).to_be_bytes());
+        ret[8..].copy_from_slice(&color);
+    } else {
+        ret[..8].copy_from_slice(&color);
+    }
+    ret
+}
+
+fn check_format(format: DecodeFormat) -> std::io::Result<()> {
+    match format {
+        DecodeFormat::EtcRgb4
+        | DecodeFormat::Etc2Rgb
+        | DecodeFormat::Etc2Rgba1
+        | DecodeFormat::Etc2Rgba8 => Ok(()),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "only ETC1 and ETC2 formats can be encoded",
+        )),
+    }
+}
+
+/// Encodes a block in the layout returned by `decode_single_block`.
+pub fn encode_single_block<W: std::io::Write>(
+    output: &mut W,
+    block: &Block,
+    format: DecodeFormat,
+    quality: Quality,
+) -> std::io::Result<()> {
+    check_format(format)?;
+    output.write_all(&encode_block(block, format, quality)[..format.block_size()])
+}
+
+fn encode_block_row(
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    block_y: usize,
+    format: DecodeFormat,
+    quality: Quality,
+    output: &mut [u8],
+) {
+    let block_size = format.block_size();
+    for (block_x, output) in output.chunks_exact_mut(block_size).enumerate() {
+        let mut block = [[0u8; BLOCK_WIDTH * CHANNELS]; BLOCK_HEIGHT];
+        for (y, row) in block.iter_mut().enumerate() {
+            // Edge blocks repeat the last row and column of the image
+            let y = std::cmp::min(block_y * BLOCK_HEIGHT + y, height - 1);
+            for (x, texel) in row.chunks_mut(CHANNELS).enumerate() {
+                let x = std::cmp::min(block_x * BLOCK_WIDTH + x, width - 1);
+                let start = (y * width + x) * CHANNELS;
+                texel.copy_from_slice(&pixels[start..start + CHANNELS]);
+            }
+        }
+        output.copy_from_slice(&encode_block(&block, format, quality)[..block_size]);
+    }
+}
+
+/// Encodes an RGBA8 image, keeping its row order.
+///
+/// Block rows are encoded in parallel if the `rayon` feature is enabled.
+pub fn encode_image(
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    format: DecodeFormat,
+    quality: Quality,
+) -> std::io::Result<Vec<u8>> {
+    check_format(format)?;
+    if pixels.len() < width * height * CHANNELS {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    let blocks_x = width.div_ceil(BLOCK_WIDTH);
+    let blocks_y = height.div_ceil(BLOCK_HEIGHT);
+    let row_size = blocks_x * format.block_size();
+    let mut ret = vec![0u8; row_size * blocks_y];
+    if ret.is_empty() {
+        return Ok(ret);
+    }
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        ret.par_chunks_mut(row_size)
+            .enumerate()
+            .for_each(|(block_y, output)| {
+                encode_block_row(pixels, width, height, block_y, format, quality, output)
+            });
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        for (block_y, output) in ret.chunks_mut(row_size).enumerate() {
+            encode_block_row(pixels, width, height, block_y, format, quality, output);
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 30;
+    const HEIGHT: usize = 22;
+
+    /// Smooth gradients with a hard edge and some noise, with alpha depending on `format`.
+    fn test_image(format: DecodeFormat) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut noise = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 16) as usize
+        };
+        let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * CHANNELS);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let edge = if x * 2 + y > WIDTH { 96 } else { 0 };
+                let r = x * 255 / WIDTH;
+                let g = (y * 255 / HEIGHT + edge) % 256;
+                let b = std::cmp::min(255, (x + y) * 4 + noise());
+                let a = match format {
+                    DecodeFormat::Etc2Rgba8 => (x + y) * 255 / (WIDTH + HEIGHT),
+                    DecodeFormat::Etc2Rgba1 if (x / 5 + y / 3) % 4 == 0 => 0,
+                    _ => 255,
+                };
+                pixels.extend_from_slice(&[r as u8, g as u8, b as u8, a as u8]);
+            }
+        }
+        pixels
+    }
+
+    /// PSNR of the channels in `channels`, skipping pixels which are transparent in `expected`.
+    fn psnr(expected: &[u8], actual: &[u8], channels: std::ops::Range<usize>) -> f64 {
+        let mut error = 0.0;
+        let mut count = 0;
+        for (expected, actual) in expected.chunks(CHANNELS).zip(actual.chunks(CHANNELS)) {
+            if expected[3] == 0 && channels.start < 3 {
+                continue;
+            }
+            for c in channels.clone() {
+                let d = f64::from(expected[c]) - f64::from(actual[c]);
+                error += d * d;
+                count += 1;
+            }
+        }
+        let mse = error / count as f64;
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+
+    fn round_trip(format: DecodeFormat, quality: Quality) -> (Vec<u8>, Vec<u8>) {
+        let image = test_image(format);
+        let encoded = encode_image(&image, WIDTH, HEIGHT, format, quality).unwrap();
+        let decoded = crate::decode_image(&encoded, WIDTH, HEIGHT, format).unwrap();
+        (image, decoded)
+    }
+
+    #[test]
+    fn color_psnr() {
+        // thresholds in dB for the fast and high quality modes, a bit below the current results
+        for &(format, fast, high) in &[
+            (DecodeFormat::EtcRgb4, 27.5, 27.5),
+            (DecodeFormat::Etc2Rgb, 27.5, 33.5),
+            (DecodeFormat::Etc2Rgba1, 20.5, 32.5),
+            (DecodeFormat::Etc2Rgba8, 27.5, 33.5),
+        ] {
+            let mut results = Vec::new();
+            for &(quality, threshold) in &[(Quality::Fast, fast), (Quality::High, high)] {
+                let (image, decoded) = round_trip(format, quality);
+                let rgb = psnr(&image, &decoded, 0..3);
+                assert!(
+                    rgb >= threshold,
+                    "{:?} {:?}: {:.2} dB",
+                    format,
+                    quality,
+                    rgb
+                );
+                results.push(rgb);
+            }
+            assert!(results[1] >= results[0], "{:?}: {:?}", format, results);
+        }
+    }
+
+    #[test]
+    fn alpha_psnr() {
+        for &(quality, threshold) in &[(Quality::Fast, 45.0), (Quality::High, 47.0)] {
+            let (image, decoded) = round_trip(DecodeFormat::Etc2Rgba8, quality);
+            let alpha = psnr(&image, &decoded, 3..4);
+            assert!(alpha >= threshold, "{:?}: {:.2} dB", quality, alpha);
+        }
+        for &quality in &[Quality::Fast, Quality::High] {
+            let (image, decoded) = round_trip(DecodeFormat::Etc2Rgba1, quality);
+            for (expected, actual) in image.chunks(CHANNELS).zip(decoded.chunks(CHANNELS)) {
+                assert_eq!(expected[3], actual[3]);
+            }
+        }
+    }
+}
@@ -1,3 +1,7 @@
+mod encode;
+
+pub use encode::{encode_image, encode_single_block, Quality};
+
 const TABLE_59T: [u8; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
 const TABLE_58H: [u8; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
 const COMPRESS_PARAMS: [[i32; 4]; 16] = [
@@ -37,28 +41,25 @@ const ALPHA_BASE: [[i32; 4]; 16] = [
     [-9, -8, -6, -4],
     [-9, -7, -5, -3],
 ];
-lazy_static::lazy_static! {
-    static ref ALPHA_TABLE: [[i32; 8]; 256] = {
-        let mut table = [[0; 8]; 256];
-        for i in 0..16 {
-            for j in 0..4 {
-                let data = ALPHA_BASE[i][3 - j % 4];
-                table[i + 16][j] = data;
-                table[i + 16][j + 4] = -data - 1;
-            }
-        }
-        let (base, target) = table.split_at_mut(32);
-        let base = &mut base[16..];
-        for i in 0..224 {
-            let mul = 2 + (i / 16) as i32;
-            let source = &base[i % 16];
-            let target = &mut target[i];
-            for (target, source) in target.iter_mut().zip(source.iter()) {
-                *target = *source * mul;
-            }
+
+/// Modifiers of alpha and EAC blocks, indexed by the byte of multiplier and table index.
+static ALPHA_TABLE: [[i32; 8]; 256] = alpha_table();
+
+const fn alpha_table() -> [[i32; 8]; 256] {
+    let mut table = [[0; 8]; 256];
+    let mut i = 0;
+    while i < 256 {
+        let multiplier = (i / 16) as i32;
+        let base = ALPHA_BASE[i % 16];
+        let mut j = 0;
+        while j < 4 {
+            table[i][j] = base[3 - j] * multiplier;
+            table[i][j + 4] = (-base[3 - j] - 1) * multiplier;
+            j += 1;
         }
-        table
-    };
+        i += 1;
+    }
+    table
 }
 
 #[inline]
@@ -214,7 +215,7 @@ fn decompress_block_thumb(block: u64, pattern: Pattern, alpha: bool) -> Block {
             let col0 = (color_data >> 14) & 0x0fff;
             let col1 = (color_data >> 2) & 0x0fff;
             let extra_bit = if col0 >= col1 { 1 } else { 0 };
-            extract_and_shift!(color_data >> 2) as u8 | extra_bit
+            ((extract_and_shift!(color_data >> 2) as u8) << 1) | extra_bit
         }
         Pattern::T => extract_and_shift!(color_data >> 3) as u8,
     };
@@ -389,29 +390,75 @@ fn decompress_block_etc2(block: u64, alpha: bool) -> Block {
     }
 }
 
-fn decompress_block_alpha(block: u64) -> SingleChannelBlock {
-    let alpha = ((block & (0xff << 56)) >> 56) as u8;
-    let table = ((block & (0xff << 48)) >> 48) as usize;
-    let mut bits = (block & 0xffffffffffff).reverse_bits() >> 16;
+/// Modifier index of the pixel at `(x, y)` of an alpha or EAC block.
+#[inline(always)]
+fn alpha_index(block: u64, x: usize, y: usize) -> usize {
+    // indices are stored column by column, from the most significant bits
+    ((block >> (45 - 3 * (x * BLOCK_HEIGHT + y))) & 7) as usize
+}
+
+/// The eight values an ETC2 alpha block can pick from.
+#[inline]
+fn alpha_values(block: u64) -> [u8; 8] {
+    let base = (block >> 56) as i32;
+    let modifiers = ALPHA_TABLE[((block >> 48) & 0xff) as usize];
+    let mut values = [0; 8];
+    for (value, modifier) in values.iter_mut().zip(&modifiers) {
+        *value = (base + modifier).clamp(0, 0xff) as u8;
+    }
+    values
+}
+
+/// The eight values an 11-bit EAC block can pick from, scaled down to 8 bits.
+///
+/// Signed values are mapped from `[-1023, 1023]` to `[0, 255]`.
+#[inline]
+fn eac_values(block: u64, signed: bool) -> [u8; 8] {
+    let (base, bias, min, max) = if signed {
+        (
+            i32::from((block >> 56) as u8 as i8).max(-127),
+            0,
+            -1023,
+            1023,
+        )
+    } else {
+        ((block >> 56) as i32, 4, 0, 2047)
+    };
+    let multiplier = ((block >> 52) & 0xf) as i32;
+    let scale = if multiplier == 0 { 1 } else { multiplier * 8 };
+    let modifiers = ALPHA_TABLE[16 + ((block >> 48) & 0xf) as usize];
+    let mut values = [0; 8];
+    for (value, modifier) in values.iter_mut().zip(&modifiers) {
+        let v = (base * 8 + bias + modifier * scale).clamp(min, max) - min;
+        *value = ((v * 255 + 1023) / (max - min)) as u8;
+    }
+    values
+}
+
+fn decompress_block_single_channel(block: u64, values: [u8; 8]) -> SingleChannelBlock {
     let mut ret = [[0u8; BLOCK_WIDTH]; BLOCK_HEIGHT];
-    for x in 0..4 {
-        for y in 0..4 {
-            let idx = extract_and_shift!(bits >> 3) as u8;
-            let idx = idx.reverse_bits() >> 5;
-            ret[y][x] = saturating_add_u8_i32(alpha, ALPHA_TABLE[table][idx as usize]);
+    for (y, row) in ret.iter_mut().enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = values[alpha_index(block, x, y)];
         }
     }
     ret
 }
 
-fn combine_color_alpha(color: Block, alpha: SingleChannelBlock) -> Block {
-    let mut ret = color;
-    for y in 0..BLOCK_HEIGHT {
-        for x in 0..BLOCK_WIDTH {
-            ret[y][CHANNELS * x + 3] = alpha[y][x];
+fn decompress_block_alpha(block: u64) -> SingleChannelBlock {
+    decompress_block_single_channel(block, alpha_values(block))
+}
+
+fn decompress_block_eac(block: u64, signed: bool) -> SingleChannelBlock {
+    decompress_block_single_channel(block, eac_values(block, signed))
+}
+
+fn set_channel(block: &mut Block, values: &SingleChannelBlock, channel: usize) {
+    for (row, values) in block.iter_mut().zip(values) {
+        for (pixel, &value) in row.chunks_exact_mut(CHANNELS).zip(values) {
+            pixel[channel] = value;
         }
     }
-    ret
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -420,30 +467,315 @@ pub enum DecodeFormat {
     Etc2Rgb,
     Etc2Rgba8,
     Etc2Rgba1,
+    EacR,
+    EacRSigned,
+    EacRg,
+    EacRgSigned,
+}
+
+impl DecodeFormat {
+    /// Size of a compressed 4x4 block in bytes.
+    pub fn block_size(self) -> usize {
+        match self {
+            DecodeFormat::EtcRgb4
+            | DecodeFormat::Etc2Rgb
+            | DecodeFormat::Etc2Rgba1
+            | DecodeFormat::EacR
+            | DecodeFormat::EacRSigned => 8,
+            DecodeFormat::Etc2Rgba8 | DecodeFormat::EacRg | DecodeFormat::EacRgSigned => 16,
+        }
+    }
+}
+
+fn read_u64(data: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&data[..8]);
+    u64::from_be_bytes(buf)
+}
+
+const OPAQUE_BLACK: [u8; BLOCK_WIDTH * CHANNELS] =
+    [0, 0, 0, 0xff, 0, 0, 0, 0xff, 0, 0, 0, 0xff, 0, 0, 0, 0xff];
+
+/// Number of blocks decoded together by [`decode_batch`].
+const LANES: usize = 8;
+
+/// Decodes the complete blocks in `data`, at most [`LANES`] of them, into `out`.
+///
+/// Blocks are decoded one by one with the same code as [`decode_block`]; batches only save
+/// the per-block dispatch on `format` and let [`decode_block_row`] copy whole runs of blocks.
+fn decode_batch(data: &[u8], format: DecodeFormat, out: &mut [Block]) {
+    let block_size = format.block_size();
+    let mut words = [[0u64; LANES]; 2];
+    let mut count = 0;
+    for (idx, block) in data.chunks_exact(block_size).take(LANES).enumerate() {
+        for (word, data) in words.iter_mut().zip(block.chunks_exact(8)) {
+            word[idx] = read_u64(data);
+        }
+        count = idx + 1;
+    }
+    let out = &mut out[..count];
+    match format {
+        DecodeFormat::EacR
+        | DecodeFormat::EacRSigned
+        | DecodeFormat::EacRg
+        | DecodeFormat::EacRgSigned => {
+            let signed = format == DecodeFormat::EacRSigned || format == DecodeFormat::EacRgSigned;
+            for block in out.iter_mut() {
+                *block = [OPAQUE_BLACK; BLOCK_HEIGHT];
+            }
+            for (c, words) in words.iter().take(block_size / 8).enumerate() {
+                for (block, &word) in out.iter_mut().zip(words) {
+                    set_channel(block, &decompress_block_eac(word, signed), c);
+                }
+            }
+        }
+        DecodeFormat::Etc2Rgba8 => {
+            for ((block, &alpha), &color) in out.iter_mut().zip(&words[0]).zip(&words[1]) {
+                *block = decompress_block_etc2(color, false);
+                set_channel(block, &decompress_block_alpha(alpha), 3);
+            }
+        }
+        DecodeFormat::Etc2Rgba1 => {
+            for (block, &color) in out.iter_mut().zip(&words[0]) {
+                *block = decompress_block_etc2(color, true);
+            }
+        }
+        DecodeFormat::EtcRgb4 | DecodeFormat::Etc2Rgb => {
+            for (block, &color) in out.iter_mut().zip(&words[0]) {
+                *block = decompress_block_etc2(color, false);
+            }
+        }
+    }
+}
+
+/// Decodes a block from `data` on its own, which must be exactly `format.block_size()` bytes
+/// long. [`decode_single_block`] uses this, and the benchmark compares it against batches.
+fn decode_block(data: &[u8], format: DecodeFormat) -> Block {
+    match format {
+        DecodeFormat::EacR
+        | DecodeFormat::EacRSigned
+        | DecodeFormat::EacRg
+        | DecodeFormat::EacRgSigned => {
+            let signed = format == DecodeFormat::EacRSigned || format == DecodeFormat::EacRgSigned;
+            let mut block = [OPAQUE_BLACK; BLOCK_HEIGHT];
+            for (c, data) in data.chunks_exact(8).enumerate() {
+                set_channel(&mut block, &decompress_block_eac(read_u64(data), signed), c);
+            }
+            block
+        }
+        DecodeFormat::Etc2Rgba8 => {
+            let mut block = decompress_block_etc2(read_u64(&data[8..]), false);
+            set_channel(&mut block, &decompress_block_alpha(read_u64(data)), 3);
+            block
+        }
+        DecodeFormat::Etc2Rgba1 => decompress_block_etc2(read_u64(data), true),
+        DecodeFormat::EtcRgb4 | DecodeFormat::Etc2Rgb => {
+            decompress_block_etc2(read_u64(data), false)
+        }
+    }
 }
 
 pub fn decode_single_block<R: std::io::Read>(
     input: &mut R,
     format: DecodeFormat,
 ) -> std::io::Result<Block> {
-    let mut buf = [0u8; 8];
-    let alpha_block = if format == DecodeFormat::Etc2Rgba8 {
-        input.read_exact(&mut buf)?;
-        let block = u64::from_be_bytes(buf);
-        Some(decompress_block_alpha(block))
-    } else {
-        None
-    };
-    let has_1bit_alpha = format == DecodeFormat::Etc2Rgba1;
-    let color_block = {
-        input.read_exact(&mut buf)?;
-        let block = u64::from_be_bytes(buf);
-        decompress_block_etc2(block, has_1bit_alpha)
-    };
-    let ret = if let Some(alpha_block) = alpha_block {
-        combine_color_alpha(color_block, alpha_block)
-    } else {
-        color_block
-    };
+    let mut buf = [0u8; 16];
+    let buf = &mut buf[..format.block_size()];
+    input.read_exact(buf)?;
+    Ok(decode_block(buf, format))
+}
+
+/// Decodes every complete block in `data`.
+///
+/// Blocks are decoded in batches of [`LANES`], in parallel if the `rayon` feature is enabled.
+pub fn decode_blocks(data: &[u8], format: DecodeFormat) -> Vec<Block> {
+    let count = data.len() / format.block_size();
+    let batch = format.block_size() * LANES;
+    let mut ret = vec![[[0u8; BLOCK_WIDTH * CHANNELS]; BLOCK_HEIGHT]; count];
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        ret.par_chunks_mut(LANES)
+            .zip(data.par_chunks(batch))
+            .for_each(|(out, data)| decode_batch(data, format, out));
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        for (out, data) in ret.chunks_mut(LANES).zip(data.chunks(batch)) {
+            decode_batch(data, format, out);
+        }
+    }
+    ret
+}
+
+/// Decodes a row of blocks into the (up to four) scanlines of `rows`.
+fn decode_block_row(data: &[u8], width: usize, format: DecodeFormat, rows: &mut [u8]) {
+    let scanline = width * CHANNELS;
+    let mut blocks = [[[0u8; BLOCK_WIDTH * CHANNELS]; BLOCK_HEIGHT]; LANES];
+    for (batch_x, data) in data.chunks(format.block_size() * LANES).enumerate() {
+        decode_batch(data, format, &mut blocks);
+        let count = data.len() / format.block_size();
+        for (idx, block) in blocks.iter().take(count).enumerate() {
+            let start = (batch_x * LANES + idx) * BLOCK_WIDTH * CHANNELS;
+            let copy_width = std::cmp::min(BLOCK_WIDTH * CHANNELS, scanline - start);
+            for (target, texels) in rows.chunks_mut(scanline).zip(block.iter()) {
+                target[start..start + copy_width].copy_from_slice(&texels[..copy_width]);
+            }
+        }
+    }
+}
+
+fn decode_surface(
+    data: &mut &[u8],
+    width: usize,
+    height: usize,
+    format: DecodeFormat,
+    flip: bool,
+) -> std::io::Result<Vec<u8>> {
+    let blocks_x = width.div_ceil(BLOCK_WIDTH);
+    let blocks_y = height.div_ceil(BLOCK_HEIGHT);
+    let row_size = blocks_x * format.block_size();
+    if data.len() < row_size * blocks_y {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    let (image, rest) = data.split_at(row_size * blocks_y);
+    *data = rest;
+
+    let scanline = width * CHANNELS;
+    let mut ret = vec![0u8; scanline * height];
+    if ret.is_empty() {
+        return Ok(ret);
+    }
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        ret.par_chunks_mut(scanline * BLOCK_HEIGHT)
+            .zip(image.par_chunks_exact(row_size))
+            .for_each(|(rows, data)| decode_block_row(data, width, format, rows));
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        for (rows, data) in ret
+            .chunks_mut(scanline * BLOCK_HEIGHT)
+            .zip(image.chunks_exact(row_size))
+        {
+            decode_block_row(data, width, format, rows);
+        }
+    }
+    if flip {
+        for y in 0..height / 2 {
+            let (top, bottom) = ret.split_at_mut((height - 1 - y) * scanline);
+            top[y * scanline..(y + 1) * scanline].swap_with_slice(&mut bottom[..scanline]);
+        }
+    }
     Ok(ret)
 }
+
+fn decode_levels(
+    mut data: &[u8],
+    width: usize,
+    height: usize,
+    mip_count: usize,
+    format: DecodeFormat,
+    flip: bool,
+) -> std::io::Result<Vec<Vec<u8>>> {
+    (0..mip_count)
+        .map(|level| {
+            let width = std::cmp::max(width >> level, 1);
+            let height = std::cmp::max(height >> level, 1);
+            decode_surface(&mut data, width, height, format, flip)
+        })
+        .collect()
+}
+
+/// Decodes an image as RGBA8, keeping the row order of `data`.
+pub fn decode_image(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    format: DecodeFormat,
+) -> std::io::Result<Vec<u8>> {
+    decode_surface(&mut &*data, width, height, format, false)
+}
+
+/// Decodes an image as RGBA8, reversing the row order of `data`.
+///
+/// Unity stores textures bottom row first, so this yields images with the top row first.
+pub fn decode_image_flipped(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    format: DecodeFormat,
+) -> std::io::Result<Vec<u8>> {
+    decode_surface(&mut &*data, width, height, format, true)
+}
+
+/// Decodes `mip_count` consecutive mip levels as RGBA8, keeping the row order of `data`.
+///
+/// Each level is half the size of the previous one, down to 1x1.
+pub fn decode_mipmaps(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    mip_count: usize,
+    format: DecodeFormat,
+) -> std::io::Result<Vec<Vec<u8>>> {
+    decode_levels(data, width, height, mip_count, format, false)
+}
+
+/// Decodes `mip_count` consecutive mip levels as RGBA8, reversing the row order of `data`.
+pub fn decode_mipmaps_flipped(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    mip_count: usize,
+    format: DecodeFormat,
+) -> std::io::Result<Vec<Vec<u8>>> {
+    decode_levels(data, width, height, mip_count, format, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_match_single_blocks() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data = (0..16 * 21)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+        for &format in &[
+            DecodeFormat::EtcRgb4,
+            DecodeFormat::Etc2Rgb,
+            DecodeFormat::Etc2Rgba8,
+            DecodeFormat::Etc2Rgba1,
+            DecodeFormat::EacR,
+            DecodeFormat::EacRSigned,
+            DecodeFormat::EacRg,
+            DecodeFormat::EacRgSigned,
+        ] {
+            let blocks = decode_blocks(&data, format);
+            assert_eq!(blocks.len(), data.len() / format.block_size());
+            let mut input = &data[..];
+            for block in blocks {
+                assert_eq!(block, decode_single_block(&mut input, format).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn eac_values() {
+        // base 0x80, multiplier 1, table 0 and every index 4, the first positive modifier
+        let block = 0x8010_9249_2492_4924u64.to_be_bytes();
+        let pixel = |format| decode_single_block(&mut &block[..], format).unwrap()[0][0];
+        // 128 * 8 + 4 + 2 * 8, scaled from 11 bits
+        assert_eq!(pixel(DecodeFormat::EacR), 130);
+        // -128 is clamped to -127, then (-127 * 8 + 2 * 8) is mapped to [0, 255]
+        assert_eq!(pixel(DecodeFormat::EacRSigned), 3);
+    }
+}
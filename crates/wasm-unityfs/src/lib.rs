@@ -1,7 +1,8 @@
-use js_sys::{Array, Error, Object, Reflect, TypeError, Uint8Array};
+use js_sys::{Array, Error, Object, Reflect, Uint8Array};
 use wasm_bindgen::prelude::*;
 
-use image::dxt;
+use unityfs::classes::{self, StreamingInfo, TextureFormat};
+use unityfs::texture::{self, CrunchVariant};
 use unityfs::Data;
 
 #[wasm_bindgen]
@@ -20,14 +21,16 @@ impl UnityFs {
     pub fn main_asset(&self) -> Result<Object, JsValue> {
         let (_, meta) = unityfs::UnityFsMeta::parse(&self.input)
             .map_err(|e| Error::new(&format!("parse failed: {:?}", e)))?;
-        let fs = meta.read_unityfs();
+        let fs = meta
+            .read_unityfs()
+            .map_err(|e| Error::new(&format!("read failed: {}", e)))?;
 
         let asset = fs.main_asset();
         let name = asset.name();
         let objects = asset
             .objects()
             .iter()
-            .map(UnityObject::from_object)
+            .map(|object| UnityObject::from_object(object, asset.unity_version()))
             .map(JsValue::from)
             .collect::<Array>();
         let obj = Object::new();
@@ -35,21 +38,44 @@ impl UnityFs {
         Reflect::set(&obj, &"objects".into(), &objects.into())?;
         Ok(obj)
     }
+
+    /// Decodes the sprite with `path_id` in the main asset, cut out of its texture or atlas.
+    #[wasm_bindgen(js_name = decodeSprite)]
+    pub fn decode_sprite(&self, path_id: i64) -> Result<Texture2D, JsValue> {
+        let (_, meta) = unityfs::UnityFsMeta::parse(&self.input)
+            .map_err(|e| Error::new(&format!("parse failed: {:?}", e)))?;
+        let fs = meta
+            .read_unityfs()
+            .map_err(|e| Error::new(&format!("read failed: {}", e)))?;
+        let asset = fs.main_asset();
+
+        let object = asset
+            .object(path_id as u64)
+            .ok_or_else(|| Error::new("sprite not found"))?;
+        let sprite =
+            classes::Sprite::from_data(&object.data).map_err(|e| Error::new(&e.to_string()))?;
+        let image = sprite
+            .decode(asset, &fs)
+            .map_err(|e| Error::new(&e.to_string()))?;
+        Texture2D::from_image(sprite.name, image)
+    }
 }
 
 #[wasm_bindgen]
 pub struct UnityObject {
     data: Data<'static>,
+    unity_version: String,
 }
 
 impl UnityObject {
-    fn from_object(val: &unityfs::Object<'_>) -> Self {
-        Self::from_data(&val.data)
+    fn from_object(val: &unityfs::Object<'_>, unity_version: &str) -> Self {
+        Self::from_data(&val.data, unity_version)
     }
 
-    fn from_data(val: &Data<'_>) -> Self {
+    fn from_data(val: &Data<'_>, unity_version: &str) -> Self {
         Self {
             data: val.clone_owned(),
+            unity_version: unity_version.to_owned(),
         }
     }
 }
@@ -58,30 +84,11 @@ impl UnityObject {
 impl UnityObject {
     #[wasm_bindgen(getter, js_name = "type")]
     pub fn type_name(&self) -> String {
-        match &self.data {
-            Data::Bool(_) => "bool".into(),
-            Data::UInt8(_) => "UInt8".into(),
-            Data::UInt16(_) => "UInt16".into(),
-            Data::UInt32(_) => "UInt32".into(),
-            Data::UInt64(_) => "UInt64".into(),
-            Data::SInt8(_) => "SInt8".into(),
-            Data::SInt16(_) => "SInt16".into(),
-            Data::SInt32(_) => "SInt32".into(),
-            Data::SInt64(_) => "SInt64".into(),
-            Data::Float(_) => "float".into(),
-            Data::Double(_) => "double".into(),
-            Data::UInt8Array(_) => "ByteArray".into(),
-            Data::String(_) => "string".into(),
-            Data::Pair(..) => "pair".into(),
-            Data::GenericArray(_) => "Array".into(),
-            Data::GenericStruct { type_name, .. } | Data::GenericPrimitive { type_name, .. } => {
-                type_name.clone().into_owned()
-            }
-        }
+        self.data.type_name().to_owned()
     }
 
     pub fn data(&self) -> Result<JsValue, JsValue> {
-        convert_data(&self.data)
+        convert_data(&self.data, &self.unity_version)
     }
 }
 
@@ -95,34 +102,6 @@ pub struct Texture2D {
     image_data: ImageData,
 }
 
-struct StreamingInfo {
-    path: String,
-    offset: u32,
-    size: u32,
-}
-
-impl StreamingInfo {
-    fn from_data(data: &Data<'_>) -> Result<Self, JsValue> {
-        let fields = match data {
-            Data::GenericStruct { type_name, fields } if type_name == "StreamingInfo" => fields,
-            _ => return Err(TypeError::new("StreamingInfo type mismatch").into()),
-        };
-        let path = match fields.get("path") {
-            Some(Data::String(s)) => String::from_utf8_lossy(s).into_owned(),
-            _ => return Err(TypeError::new("StreamingInfo type mismatch").into()),
-        };
-        let offset = match fields.get("offset") {
-            Some(Data::UInt32(v)) => *v,
-            _ => return Err(TypeError::new("StreamingInfo type mismatch").into()),
-        };
-        let size = match fields.get("size") {
-            Some(Data::UInt32(v)) => *v,
-            _ => return Err(TypeError::new("StreamingInfo type mismatch").into()),
-        };
-        Ok(Self { path, offset, size })
-    }
-}
-
 enum ImageData {
     Loaded(Vec<u8>),
     Streaming(DecodeFormat, StreamingInfo),
@@ -130,67 +109,47 @@ enum ImageData {
 }
 
 #[derive(Copy, Clone)]
-enum DecodeFormat {
-    Etc(etcdec::DecodeFormat),
-    Dxt(dxt::DXTVariant),
+struct DecodeFormat {
+    format: TextureFormat,
+    crunch: CrunchVariant,
 }
 
-impl Texture2D {
-    fn read_etc(
-        width: u32,
-        height: u32,
-        format: etcdec::DecodeFormat,
-        mut image_data: impl std::io::Read,
-    ) -> Result<Vec<u8>, JsValue> {
-        let block_width = (width + 3) / 4;
-        let block_height = (height + 3) / 4;
-        let scanline = (width * 4) as usize;
-        let mut buf = vec![0u8; scanline * height as usize];
-        for block_y in 0..block_height {
-            let y = block_y * 4;
-            for block_x in 0..block_width {
-                let x = block_x * 4;
-                let block = etcdec::decode_single_block(&mut image_data, format)
-                    .map_err(|_| Error::new("read error"))?;
-                for (block_raw, target) in block.iter().zip(
-                    buf[(4 * x as usize)..]
-                        .chunks_mut(scanline)
-                        .rev()
-                        .skip(y as usize)
-                        .take(4),
-                ) {
-                    target[..16].copy_from_slice(block_raw);
-                }
-            }
-        }
-        Ok(buf)
+fn decode_format(format: TextureFormat, unity_crunch: bool) -> Option<DecodeFormat> {
+    if !texture::is_supported(format) {
+        return None;
     }
+    let crunch = if unity_crunch {
+        CrunchVariant::Unity
+    } else {
+        CrunchVariant::Original
+    };
+    Some(DecodeFormat { format, crunch })
+}
 
-    fn read_dxt(
+impl Texture2D {
+    fn decode(
         width: u32,
         height: u32,
-        variant: dxt::DXTVariant,
-        image_data: impl std::io::Read,
+        format: DecodeFormat,
+        image_data: &[u8],
     ) -> Result<Vec<u8>, JsValue> {
-        let dec = dxt::DxtDecoder::new(image_data, width, height, variant)
-            .map_err(|e| Error::new(&format!("failed to build decoder: {}", e)))?;
-        let image = image::DynamicImage::from_decoder(dec)
+        let DecodeFormat { format, crunch } = format;
+        let image = texture::decode_with_crunch(format, width, height, image_data, crunch)
             .map_err(|e| Error::new(&format!("failed to decode: {}", e)))?;
-        let image = image.flipv().into_rgba();
-        Ok(image.into_vec())
+        Ok(image.into_rgba8())
     }
 
     fn read(
         width: u32,
         height: u32,
         format: DecodeFormat,
-        image_data: impl std::io::Read,
+        image_data: &[u8],
     ) -> Result<Vec<u8>, JsValue> {
-        let raw = match format {
-            DecodeFormat::Etc(format) => Self::read_etc(width, height, format, image_data),
-            DecodeFormat::Dxt(variant) => Self::read_dxt(width, height, variant, image_data),
-        }?;
+        let raw = Self::decode(width, height, format, image_data)?;
+        Self::encode_png(width, height, &raw)
+    }
 
+    fn encode_png(width: u32, height: u32, raw: &[u8]) -> Result<Vec<u8>, JsValue> {
         let mut buf = Vec::new();
         let w = std::io::BufWriter::new(&mut buf);
         let mut encoder = png::Encoder::new(w, width, height);
@@ -200,7 +159,7 @@ impl Texture2D {
         let mut w = encoder
             .write_header()
             .map_err(|e| Error::new(&format!("error initializing encoder: {}", e)))?;
-        w.write_image_data(&raw)
+        w.write_image_data(raw)
             .map_err(|e| Error::new(&format!("error while encoding: {}", e)))?;
         drop(w);
         Ok(buf)
@@ -211,7 +170,7 @@ impl Texture2D {
         width: u32,
         height: u32,
         format: DecodeFormat,
-        image_data: impl std::io::Read,
+        image_data: &[u8],
     ) -> Result<Self, JsValue> {
         let image_data = Texture2D::read(width, height, format, image_data)?;
         Ok(Self {
@@ -222,6 +181,17 @@ impl Texture2D {
         })
     }
 
+    fn from_image(name: String, image: texture::Image) -> Result<Self, JsValue> {
+        let (width, height) = (image.width, image.height);
+        let png = Self::encode_png(width, height, &image.into_rgba8())?;
+        Ok(Self {
+            name,
+            width,
+            height,
+            image_data: ImageData::Loaded(png),
+        })
+    }
+
     fn defer(
         name: String,
         width: u32,
@@ -282,37 +252,112 @@ impl Texture2D {
     pub fn try_resolve(&mut self, fs: &UnityFs) -> Result<(), JsValue> {
         let (_, meta) = unityfs::UnityFsMeta::parse(&fs.input)
             .map_err(|e| Error::new(&format!("parse failed: {:?}", e)))?;
-        let fs = meta.read_unityfs();
+        let fs = meta
+            .read_unityfs()
+            .map_err(|e| Error::new(&format!("read failed: {}", e)))?;
 
         let (format, streaming_info) = match &self.image_data {
             ImageData::Streaming(format, val) => (format, val),
             _ => return Ok(()),
         };
-        if !streaming_info.path.starts_with("archive:/") {
-            return Ok(());
-        }
-        let mut path_segments = streaming_info.path[9..].split('/');
-        let (bundle_name, resource_name) = match (path_segments.next(), path_segments.next()) {
-            (Some(x), Some(y)) => (x, y),
-            _ => return Ok(()),
-        };
-        if fs.name() != bundle_name {
-            return Ok(());
-        }
-        let resource = if let Some(buf) = fs.resource(resource_name) {
-            buf
-        } else {
-            return Ok(());
+        let buf = match streaming_info.resolve(&fs) {
+            Some(buf) => buf,
+            None => return Ok(()),
         };
-        let buf = &resource[streaming_info.offset as usize..][..streaming_info.size as usize];
-        let image_data =
-            Texture2D::read(self.width, self.height, *format, std::io::Cursor::new(buf))?;
+        let image_data = Texture2D::read(self.width, self.height, *format, buf)?;
         self.image_data = ImageData::Loaded(image_data);
         Ok(())
     }
 }
 
-fn convert_shallow(data: &Data<'_>) -> JsValue {
+/// Faces of a cubemap or slices of a texture array, each decoded as a `Texture2D`.
+#[wasm_bindgen]
+pub struct TextureSlices {
+    name: String,
+    #[wasm_bindgen(readonly)]
+    pub width: u32,
+    #[wasm_bindgen(readonly)]
+    pub height: u32,
+    slices: Array,
+}
+
+impl TextureSlices {
+    #[allow(clippy::too_many_arguments)]
+    fn load(
+        name: String,
+        width: u32,
+        height: u32,
+        format: Option<DecodeFormat>,
+        image_data: &[u8],
+        streaming_info: Option<&StreamingInfo>,
+        count: usize,
+        stride: Option<usize>,
+    ) -> Result<Self, JsValue> {
+        let slice_name = |idx| format!("{}_{}", name, idx);
+        let slices = match (format, stride) {
+            (Some(format), Some(stride)) => match streaming_info {
+                Some(info) => (0..count)
+                    .map(|idx| {
+                        let info = StreamingInfo {
+                            path: info.path.clone(),
+                            offset: info.offset + (idx * stride) as u64,
+                            size: stride as u64,
+                        };
+                        JsValue::from(Texture2D::defer(
+                            slice_name(idx),
+                            width,
+                            height,
+                            format,
+                            info,
+                        ))
+                    })
+                    .collect::<Array>(),
+                None => {
+                    let images = texture::decode_slices(
+                        format.format,
+                        width,
+                        height,
+                        image_data,
+                        count,
+                        stride,
+                    )
+                    .map_err(|e| Error::new(&format!("failed to decode: {}", e)))?;
+                    images
+                        .into_iter()
+                        .enumerate()
+                        .map(|(idx, image)| {
+                            Texture2D::from_image(slice_name(idx), image).map(JsValue::from)
+                        })
+                        .collect::<Result<Array, _>>()?
+                }
+            },
+            _ => (0..count)
+                .map(|idx| JsValue::from(Texture2D::unknown(slice_name(idx), width, height)))
+                .collect::<Array>(),
+        };
+        Ok(Self {
+            name,
+            width,
+            height,
+            slices,
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl TextureSlices {
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn slices(&self) -> Array {
+        self.slices.clone()
+    }
+}
+
+fn convert_shallow(data: &Data<'_>, unity_version: &str) -> JsValue {
     match data {
         Data::Bool(b) => JsValue::from_bool(*b),
         Data::UInt8(v) => JsValue::from_f64((*v).into()),
@@ -328,73 +373,73 @@ fn convert_shallow(data: &Data<'_>) -> JsValue {
         Data::String(s) => std::str::from_utf8(&**s)
             .map(JsValue::from_str)
             .unwrap_or_else(|_| Uint8Array::from(&**s).into()),
-        v => UnityObject::from_data(v).into(),
+        v => UnityObject::from_data(v, unity_version).into(),
     }
 }
 
-fn convert_data(data: &Data<'_>) -> Result<JsValue, JsValue> {
+fn convert_data(data: &Data<'_>, unity_version: &str) -> Result<JsValue, JsValue> {
     Ok(match data {
         Data::GenericPrimitive { data, .. } => Uint8Array::from(&**data).into(),
         Data::GenericStruct { type_name, fields } => {
             if type_name == "Texture2D" {
-                let name = match fields.get("m_Name") {
-                    Some(Data::String(s)) => String::from_utf8_lossy(s).into_owned(),
-                    Some(_) => return Err(Error::new("m_Name type mismatch").into()),
-                    None => return Err(Error::new("m_Name not found").into()),
-                };
-                let width = match fields.get("m_Width") {
-                    Some(Data::SInt32(width)) => (*width) as u32,
-                    Some(_) => return Err(Error::new("m_Width type mismatch").into()),
-                    None => return Err(Error::new("m_Width not found").into()),
-                };
-                let height = match fields.get("m_Height") {
-                    Some(Data::SInt32(height)) => (*height) as u32,
-                    Some(_) => return Err(Error::new("m_Height type mismatch").into()),
-                    None => return Err(Error::new("m_Height not found").into()),
-                };
-                let image_data = match fields.get("image data") {
-                    Some(Data::UInt8Array(buf)) => buf,
-                    Some(_) => return Err(Error::new("image data type mismatch").into()),
-                    None => return Err(Error::new("image data not found").into()),
-                };
-                let image_data = std::io::Cursor::new(image_data);
-                let format = match fields.get("m_TextureFormat") {
-                    Some(Data::SInt32(34)) => {
-                        Some(DecodeFormat::Etc(etcdec::DecodeFormat::EtcRgb4))
-                    }
-                    Some(Data::SInt32(45)) => {
-                        Some(DecodeFormat::Etc(etcdec::DecodeFormat::Etc2Rgb))
-                    }
-                    Some(Data::SInt32(46)) => {
-                        Some(DecodeFormat::Etc(etcdec::DecodeFormat::Etc2Rgba1))
-                    }
-                    Some(Data::SInt32(47)) => {
-                        Some(DecodeFormat::Etc(etcdec::DecodeFormat::Etc2Rgba8))
-                    }
-                    Some(Data::SInt32(10)) => Some(DecodeFormat::Dxt(dxt::DXTVariant::DXT1)),
-                    Some(Data::SInt32(12)) => Some(DecodeFormat::Dxt(dxt::DXTVariant::DXT5)),
-                    Some(Data::SInt32(_)) => None,
-                    Some(_) => return Err(Error::new("m_TextureFormat type mismatch").into()),
-                    None => return Err(Error::new("m_TextureFormat not found").into()),
-                };
+                let texture =
+                    classes::Texture2D::from_data(data).map_err(|e| Error::new(&e.to_string()))?;
+                let format = texture.format().and_then(|format| {
+                    decode_format(format, texture.is_unity_crunch(unity_version))
+                });
+                let (name, width, height) = (texture.name.clone(), texture.width, texture.height);
                 if let Some(format) = format {
-                    let streaming_info = fields
-                        .get("m_StreamData")
-                        .ok_or_else(|| Error::new("m_StreamData not found").into())
-                        .and_then(StreamingInfo::from_data)?;
-                    if streaming_info.path.is_empty() {
-                        Texture2D::load(name, width, height, format, image_data)?.into()
+                    if texture.is_streamed() {
+                        Texture2D::defer(name, width, height, format, texture.stream_data).into()
                     } else {
-                        Texture2D::defer(name, width, height, format, streaming_info).into()
+                        let image_data = texture.inline_image_data();
+                        Texture2D::load(name, width, height, format, image_data)?.into()
                     }
                 } else {
                     Texture2D::unknown(name, width, height).into()
                 }
+            } else if type_name == "Cubemap" {
+                let cubemap =
+                    classes::Cubemap::from_data(data).map_err(|e| Error::new(&e.to_string()))?;
+                let texture = &cubemap.texture;
+                let format = texture.format().and_then(|format| {
+                    decode_format(format, texture.is_unity_crunch(unity_version))
+                });
+                let streaming_info = Some(&texture.stream_data).filter(|_| texture.is_streamed());
+                TextureSlices::load(
+                    texture.name.clone(),
+                    texture.width,
+                    texture.height,
+                    format,
+                    texture.inline_image_data(),
+                    streaming_info,
+                    cubemap.face_count(),
+                    Some(cubemap.face_stride()),
+                )?
+                .into()
+            } else if type_name == "Texture2DArray" || type_name == "Texture3D" {
+                let texture = classes::TextureArray::from_data(data)
+                    .map_err(|e| Error::new(&e.to_string()))?;
+                let format = texture
+                    .format()
+                    .and_then(|format| decode_format(format, true));
+                let streaming_info = Some(&texture.stream_data).filter(|_| texture.is_streamed());
+                TextureSlices::load(
+                    texture.name.clone(),
+                    texture.width,
+                    texture.height,
+                    format,
+                    texture.inline_image_data(),
+                    streaming_info,
+                    texture.slice_count(),
+                    texture.slice_stride(),
+                )?
+                .into()
             } else {
                 let fields: Array = fields
                     .iter()
                     .map(|(k, v)| -> Result<Array, JsValue> {
-                        let v = convert_shallow(v);
+                        let v = convert_shallow(v, unity_version);
                         Ok(Array::of2(&JsValue::from_str(k), &v))
                     })
                     .collect::<Result<_, _>>()?;
@@ -402,7 +447,10 @@ fn convert_data(data: &Data<'_>) -> Result<JsValue, JsValue> {
             }
         }
         Data::GenericArray(arr) => {
-            let arr = arr.iter().map(convert_shallow).collect::<Array>();
+            let arr = arr
+                .iter()
+                .map(|v| convert_shallow(v, unity_version))
+                .collect::<Array>();
             arr.into()
         }
         Data::Bool(b) => JsValue::from_bool(*b),
@@ -417,11 +465,11 @@ fn convert_data(data: &Data<'_>) -> Result<JsValue, JsValue> {
         Data::Float(v) => JsValue::from_f64((*v).into()),
         Data::Double(v) => JsValue::from_f64((*v).into()),
         Data::Pair(fst, snd) => {
-            let fst = UnityObject::from_data(fst).into();
-            let snd = UnityObject::from_data(snd).into();
+            let fst = UnityObject::from_data(fst, unity_version).into();
+            let snd = UnityObject::from_data(snd, unity_version).into();
             Array::of2(&fst, &snd).into()
         }
-        Data::UInt8Array(s) => Uint8Array::from(&**s).into(),
+        Data::UInt8Array(s) | Data::Raw { data: s, .. } => Uint8Array::from(&**s).into(),
         Data::String(s) => std::str::from_utf8(&**s)
             .map(JsValue::from_str)
             .unwrap_or_else(|_| Uint8Array::from(&**s).into()),
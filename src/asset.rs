@@ -1,17 +1,25 @@
+mod builder;
+mod query;
 mod type_tree;
 
 use crate::common_parser::read_string;
 use crate::util::align;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use nom::{
+    bytes::complete::take,
+    error::ErrorKind,
     i16, i32,
     number::{complete as nom_number, Endianness},
     u32, u64, IResult,
 };
 
-pub use type_tree::Data;
+pub use builder::{AssetBuilder, AssetBuilderError};
+pub use query::Query;
 use type_tree::TypeMetadata;
+pub use type_tree::{Data, Fields, LayoutError, TypeLayouts, TypeTree, VisitFlow, Visitor};
 
 #[derive(Debug)]
 pub struct Asset<'b> {
@@ -20,31 +28,100 @@ pub struct Asset<'b> {
     file_size: u32,
     format: u32,
     data_offset: u32,
+    endianness: Endianness,
     tree: TypeMetadata<'b>,
+    long_object_ids: bool,
     objects: Vec<Object<'b>>,
+    /// Index in `objects` of each path ID.
+    object_index: HashMap<u64, usize>,
     adds: Vec<(u64, i32)>,
     refs: Vec<AssetRef<'b>>,
+    user_information: Cow<'b, str>,
 }
 
-#[derive(Debug)]
 pub struct Object<'b> {
     pub path_id: u64,
     pub type_id: i32,
     pub class_id: i32,
     pub is_destroyed: bool,
     pub data: Data<'b>,
+    raw: &'b [u8],
+    /// External layout the data was decoded with.
+    layout: Option<Arc<TypeTree<'static>>>,
+    type_idx: u32,
+    script_type_idx: i16,
+    is_stripped: bool,
 }
 
-#[derive(Debug)]
+impl std::fmt::Debug for Object<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_struct("Object")
+            .field("path_id", &self.path_id)
+            .field("type_id", &self.type_id)
+            .field("class_id", &self.class_id)
+            .field("is_destroyed", &self.is_destroyed)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+impl<'b> Object<'b> {
+    /// Serialized bytes of the object, as stored in the asset.
+    pub fn raw_data(&self) -> &'b [u8] {
+        self.raw
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct AssetRef<'b> {
     asset_path: Cow<'b, str>,
-    guid: &'b [u8],
+    guid: [u8; 16],
     ty: u32,
     file_path: Cow<'b, str>,
 }
 
+impl<'b> AssetRef<'b> {
+    pub fn new(
+        asset_path: impl Into<Cow<'b, str>>,
+        guid: [u8; 16],
+        ty: u32,
+        file_path: impl Into<Cow<'b, str>>,
+    ) -> Self {
+        Self {
+            asset_path: asset_path.into(),
+            guid,
+            ty,
+            file_path: file_path.into(),
+        }
+    }
+
+    pub fn asset_path(&self) -> &str {
+        &self.asset_path
+    }
+
+    pub fn guid(&self) -> [u8; 16] {
+        self.guid
+    }
+
+    pub fn ty(&self) -> u32 {
+        self.ty
+    }
+
+    pub fn file_path(&self) -> &str {
+        &self.file_path
+    }
+}
+
 impl<'b> Asset<'b> {
-    pub(crate) fn parse(name: String, input: &'b [u8], offset: u64) -> IResult<&[u8], Self> {
+    /// Parses a serialized file. Unless `decode` is set, objects keep `Data::Raw` and are only
+    /// decoded on request.
+    pub(crate) fn parse(
+        name: String,
+        input: &'b [u8],
+        offset: u64,
+        layouts: &TypeLayouts,
+        decode: bool,
+    ) -> IResult<&'b [u8], Self> {
         let base = input;
         let (input, metadata_size) = nom_number::be_u32(input)?;
         let (input, file_size) = nom_number::be_u32(input)?;
@@ -84,40 +161,72 @@ impl<'b> Asset<'b> {
                 };
                 let (input, object_data_offset) = u32!(input, endianness)?;
                 let (input, size) = u32!(input, endianness)?;
-                let start = data_offset + object_data_offset;
-                let end = start + size;
+                let start = data_offset as usize + object_data_offset as usize;
+                let end = start + size as usize;
 
-                let (input, type_id, class_id) = if format < 17 {
+                let (input, type_idx, type_id, class_id) = if format < 17 {
                     let (input, type_id) = i32!(input, endianness)?;
                     let (input, class_id) = i16!(input, endianness)?;
-                    (input, type_id, class_id.into())
+                    (input, 0, type_id, class_id.into())
                 } else {
-                    let (input, type_id) = u32!(input, endianness)?;
-                    let class_id = tree.class_id_from_idx(type_id as usize);
-                    (input, class_id, class_id)
+                    let (input, type_idx) = u32!(input, endianness)?;
+                    let class_id = tree.class_id_from_idx(type_idx as usize);
+                    (input, type_idx, class_id, class_id)
                 };
-                let data = {
-                    let type_tree = tree.type_tree_from_id(type_id, class_id).unwrap();
-                    let (_, data) =
-                        type_tree.read(&base[start as usize..end as usize], endianness, 0)?;
-                    data
+                let raw = match base.get(start..end) {
+                    Some(v) => v,
+                    None => return Err(nom::Err::Error((input, ErrorKind::Eof))),
+                };
+                let hash_layout = tree
+                    .type_hash(type_id)
+                    .and_then(|hash| layouts.hash_layout(hash));
+                let (data, layout) = if !decode {
+                    let embedded = tree.embedded_type_tree(type_id);
+                    let layout = hash_layout
+                        .filter(|layout| embedded.is_none() && layout.fits(raw, endianness))
+                        .cloned();
+                    let type_name: Cow<str> = match embedded.or(layout.as_deref()) {
+                        Some(type_tree) => type_tree.type_name().to_owned().into(),
+                        None if type_id < 0 => "MonoBehaviour".into(),
+                        None => match TypeMetadata::default_type_tree(class_id) {
+                            Some(type_tree) => type_tree.type_name().into(),
+                            None => "UnknownType".into(),
+                        },
+                    };
+                    (raw_data(type_name, raw), layout)
+                } else if let Some(type_tree) = tree.embedded_type_tree(type_id) {
+                    let (_, data) = type_tree.read(raw, endianness, 0)?;
+                    (data, None)
+                } else if let Some(data) =
+                    hash_layout.and_then(|layout| layout.read_exact(raw, endianness))
+                {
+                    (data, hash_layout.cloned())
+                } else if type_id < 0 {
+                    // script types may still be found by name once the scripts are read
+                    (raw_data("MonoBehaviour", raw), None)
+                } else if let Some(type_tree) = TypeMetadata::default_type_tree(class_id) {
+                    let (_, data) = type_tree.read(raw, endianness, 0)?;
+                    (data, None)
+                } else {
+                    (raw_data("UnknownType", raw), None)
                 };
 
                 let (input, is_destroyed) = if format <= 10 {
-                    let val = input[0] != 0;
-                    (&input[1..], val)
+                    let (input, val) = nom_number::be_u8(input)?;
+                    (input, val != 0)
                 } else {
                     (input, false)
                 };
-                let input = if (11..=16).contains(&format) {
-                    &input[2..]
+                let (input, script_type_idx) = if (11..=16).contains(&format) {
+                    i16!(input, endianness)?
                 } else {
-                    input
+                    (input, -1)
                 };
-                let input = if (15..=16).contains(&format) {
-                    &input[1..]
+                let (input, is_stripped) = if (15..=16).contains(&format) {
+                    let (input, val) = nom_number::be_u8(input)?;
+                    (input, val != 0)
                 } else {
-                    input
+                    (input, false)
                 };
                 input_out = input;
                 Ok(Object {
@@ -126,6 +235,11 @@ impl<'b> Asset<'b> {
                     class_id,
                     is_destroyed,
                     data,
+                    raw,
+                    layout,
+                    type_idx,
+                    script_type_idx,
+                    is_stripped,
                 })
             })
             .collect::<Result<Vec<_>, _>>()?;
@@ -160,7 +274,9 @@ impl<'b> Asset<'b> {
             let refs = (0..refs_count)
                 .map(|_| {
                     let (input, asset_path) = read_string(input_out, None)?;
-                    let (guid, input) = input.split_at(0x10);
+                    let (input, guid_slice) = take(0x10usize)(input)?;
+                    let mut guid = [0; 16];
+                    guid.copy_from_slice(guid_slice);
                     let (input, ty) = u32!(input, endianness)?;
                     let (input, file_path) = read_string(input, None)?;
                     input_out = input;
@@ -176,21 +292,76 @@ impl<'b> Asset<'b> {
         } else {
             (input, Vec::new())
         };
-        let (input, _) = read_string(input, None)?;
+        let (input, user_information) = read_string(input, None)?;
 
-        let asset = Asset {
+        let mut object_index = HashMap::with_capacity(objects.len());
+        for (idx, object) in objects.iter().enumerate() {
+            object_index.entry(object.path_id).or_insert(idx);
+        }
+        let mut asset = Asset {
             name,
             metadata_size,
             file_size,
             format,
             data_offset,
+            endianness,
             tree,
+            long_object_ids,
             objects,
+            object_index,
             adds,
             refs,
+            user_information,
         };
+        asset.apply_script_layouts(layouts, decode);
         Ok((input, asset))
     }
+
+    /// Decodes raw `MonoBehaviour`s with the layouts named after their scripts. Without
+    /// `decode`, only checks that the layouts fit and attaches them.
+    fn apply_script_layouts(&mut self, layouts: &TypeLayouts, decode: bool) {
+        if layouts.is_empty() {
+            return;
+        }
+        for idx in 0..self.objects.len() {
+            let layout = {
+                let object = &self.objects[idx];
+                if object.type_id >= 0
+                    || object.layout.is_some()
+                    || self.tree.embedded_type_tree(object.type_id).is_some()
+                {
+                    continue;
+                }
+                self.behaviour_header(object)
+                    .and_then(|header| header.field("m_Script")?.as_pptr())
+                    .filter(|&(file_id, _)| file_id == 0)
+                    .and_then(|(_, path_id)| self.object(path_id as u64))
+                    .and_then(|script| match &script.data {
+                        Data::Raw { .. } => layouts.script_layout(&self.decode_object(script)?),
+                        data => layouts.script_layout(data),
+                    })
+                    .cloned()
+            };
+            let object = &mut self.objects[idx];
+            if let Some(layout) = layout {
+                if !decode {
+                    if layout.fits(object.raw, self.endianness) {
+                        object.layout = Some(layout);
+                    }
+                } else if let Some(data) = layout.read_exact(object.raw, self.endianness) {
+                    object.data = data;
+                    object.layout = Some(layout);
+                }
+            }
+        }
+    }
+}
+
+fn raw_data<'b>(type_name: impl Into<Cow<'b, str>>, raw: &'b [u8]) -> Data<'b> {
+    Data::Raw {
+        type_name: type_name.into(),
+        data: raw.into(),
+    }
 }
 
 impl<'b> Asset<'b> {
@@ -201,4 +372,220 @@ impl<'b> Asset<'b> {
     pub fn objects(&self) -> &[Object<'b>] {
         &self.objects
     }
+
+    /// Finds the object with `path_id`.
+    pub fn object(&self, path_id: u64) -> Option<&Object<'b>> {
+        self.object_index
+            .get(&path_id)
+            .map(|&idx| &self.objects[idx])
+    }
+
+    /// Unity version which wrote the asset, like `2019.4.1f1`.
+    pub fn unity_version(&self) -> &str {
+        self.tree.generator_version()
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Type tree `object` was decoded with, either embedded, built-in or an external layout.
+    pub fn type_tree<'s>(&'s self, object: &'s Object<'_>) -> Option<&'s TypeTree<'b>> {
+        match &object.layout {
+            Some(layout) => Some(layout),
+            None => self.tree.type_tree_from_id(object.type_id, object.class_id),
+        }
+    }
+
+    /// Reads `m_GameObject`, `m_Enabled`, `m_Script` and `m_Name` of a `MonoBehaviour`, which
+    /// are known even if the rest of it decoded to `Data::Raw`.
+    pub fn behaviour_header(&self, object: &Object<'b>) -> Option<Data<'b>> {
+        // the built-in layout has 64-bit path IDs
+        if self.format < 14 {
+            return None;
+        }
+        type_tree::read_behaviour_header(object.raw, self.endianness)
+    }
+
+    /// Decodes `object` with its type tree. Needed for assets read without decoding, see
+    /// [`UnityFsMeta::read_unityfs_undecoded`](crate::UnityFsMeta::read_unityfs_undecoded).
+    ///
+    /// Returns `None` if the object has no type tree or its data is malformed.
+    pub fn decode_object(&self, object: &Object<'b>) -> Option<Data<'b>> {
+        let type_tree = self.type_tree(object)?;
+        let (_, data) = type_tree.read(object.raw, self.endianness, 0).ok()?;
+        Some(data)
+    }
+
+    /// Walks the serialized data of `object` with `visitor`, without building `Data`.
+    ///
+    /// Returns `None` if the object has no type tree or its data is malformed.
+    pub fn visit_object<V: Visitor<'b>>(
+        &self,
+        object: &Object<'b>,
+        visitor: &mut V,
+    ) -> Option<VisitFlow> {
+        let type_tree = self.type_tree(object)?;
+        let (_, flow) = type_tree
+            .visit(object.raw, self.endianness, 0, visitor)
+            .ok()?;
+        Some(flow)
+    }
+
+    pub fn externals(&self) -> &[AssetRef<'b>] {
+        &self.refs
+    }
+
+    pub fn user_information(&self) -> &str {
+        &self.user_information
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::util::write_align;
+
+    fn le32(out: &mut Vec<u8>, v: u32) {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+
+    /// Format 17 serialized file without type trees, holding a `TextAsset` with path ID 1 and
+    /// a `MonoBehaviour` of script type 0 with path ID 2.
+    pub(crate) fn serialized_file(text_asset: &[u8], behaviour: &[u8]) -> Vec<u8> {
+        serialized_objects(&[(1, 49, text_asset), (2, 114, behaviour)])
+    }
+
+    /// Format 17 serialized file without type trees, holding objects given as path ID, class ID
+    /// and data. Objects of class 114 are `MonoBehaviour`s of script type 0.
+    pub(crate) fn serialized_objects(objects: &[(u64, i32, &[u8])]) -> Vec<u8> {
+        let mut classes = Vec::new();
+        for &(_, class_id, _) in objects {
+            if !classes.contains(&class_id) {
+                classes.push(class_id);
+            }
+        }
+        let mut meta = Vec::new();
+        meta.extend_from_slice(b"2018.4.0f1\0");
+        le32(&mut meta, 19);
+        meta.push(0);
+        le32(&mut meta, classes.len() as u32);
+        for &class_id in &classes {
+            le32(&mut meta, class_id as u32);
+            meta.push(0);
+            if class_id == 114 {
+                meta.extend_from_slice(&0i16.to_le_bytes());
+                meta.extend_from_slice(&[0x72; 32]);
+            } else {
+                meta.extend_from_slice(&(-1i16).to_le_bytes());
+                meta.extend_from_slice(&[0x49; 16]);
+            }
+        }
+        le32(&mut meta, objects.len() as u32);
+        let mut data = Vec::new();
+        for &(path_id, class_id, bytes) in objects {
+            let type_idx = classes.iter().position(|&c| c == class_id).unwrap();
+            write_align(&mut meta, 4);
+            write_align(&mut data, 8);
+            meta.extend_from_slice(&path_id.to_le_bytes());
+            le32(&mut meta, data.len() as u32);
+            le32(&mut meta, bytes.len() as u32);
+            le32(&mut meta, type_idx as u32);
+            data.extend_from_slice(bytes);
+        }
+        le32(&mut meta, 0);
+        le32(&mut meta, 0);
+        meta.push(0);
+
+        let data_offset = (20 + meta.len()).div_ceil(16) * 16;
+        let mut out = Vec::new();
+        out.extend_from_slice(&(meta.len() as u32).to_be_bytes());
+        out.extend_from_slice(&((data_offset + data.len()) as u32).to_be_bytes());
+        out.extend_from_slice(&17u32.to_be_bytes());
+        out.extend_from_slice(&(data_offset as u32).to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&meta);
+        out.resize(data_offset, 0);
+        out.extend_from_slice(&data);
+        out
+    }
+
+    fn string(out: &mut Vec<u8>, s: &str) {
+        le32(out, s.len() as u32);
+        out.extend_from_slice(s.as_bytes());
+        write_align(out, 4);
+    }
+
+    #[derive(Default)]
+    struct Strings(Vec<String>);
+
+    impl Visitor<'_> for Strings {
+        fn visit_primitive(&mut self, _tree: &TypeTree<'_>, value: &Data<'_>) -> VisitFlow {
+            if let Some(s) = value.as_str() {
+                self.0.push(s.to_owned());
+            }
+            VisitFlow::Continue
+        }
+    }
+
+    #[test]
+    fn undecoded_objects() {
+        let mut text_asset = Vec::new();
+        string(&mut text_asset, "notes");
+        string(&mut text_asset, "hello");
+        string(&mut text_asset, "");
+        let behaviour = (0..40).collect::<Vec<u8>>();
+        let file = serialized_file(&text_asset, &behaviour);
+        let layouts = TypeLayouts::default();
+        let (_, decoded) = Asset::parse("test".into(), &file, 0, &layouts, true).unwrap();
+        let (_, undecoded) = Asset::parse("test".into(), &file, 0, &layouts, false).unwrap();
+
+        assert!(undecoded.object(3).is_none());
+        let object = undecoded.object(1).unwrap();
+        match &object.data {
+            Data::Raw { type_name, data } => {
+                assert_eq!(type_name, "TextAsset");
+                assert_eq!(&data[..], &text_asset[..]);
+            }
+            data => panic!("decoded {:?}", data),
+        }
+        let data = undecoded.decode_object(object).unwrap();
+        assert_eq!(data.field("m_Name").and_then(Data::as_str), Some("notes"));
+        assert_eq!(
+            format!("{:?}", data),
+            format!("{:?}", decoded.object(1).unwrap().data)
+        );
+
+        let mut strings = Strings::default();
+        let flow = undecoded.visit_object(object, &mut strings);
+        assert_eq!(flow, Some(VisitFlow::Continue));
+        assert_eq!(strings.0, ["notes", "hello", ""]);
+
+        // script objects have no type tree to decode or visit with
+        let behaviour_object = undecoded.object(2).unwrap();
+        assert!(undecoded.decode_object(behaviour_object).is_none());
+        assert!(undecoded
+            .visit_object(behaviour_object, &mut Strings::default())
+            .is_none());
+
+        let rebuilt = AssetBuilder::from_asset(&undecoded).build().unwrap();
+        assert_eq!(rebuilt, AssetBuilder::from_asset(&decoded).build().unwrap());
+    }
+
+    #[test]
+    fn truncated_files() {
+        let mut text_asset = Vec::new();
+        string(&mut text_asset, "notes");
+        string(&mut text_asset, "hello");
+        string(&mut text_asset, "");
+        let file = serialized_file(&text_asset, &[0; 40]);
+        let layouts = TypeLayouts::default();
+        assert!(Asset::parse("test".into(), &file, 0, &layouts, true).is_ok());
+        for len in 0..file.len() {
+            for &decode in &[false, true] {
+                let parsed = Asset::parse("test".into(), &file[..len], 0, &layouts, decode);
+                assert!(parsed.is_err(), "parsed {} of {} bytes", len, file.len());
+            }
+        }
+    }
 }
@@ -0,0 +1,345 @@
+use super::type_tree::TypeMetadata;
+use super::{Asset, AssetRef, Data, TypeTree};
+use crate::util::{write_align, write_u16, write_u32, write_u64};
+use nom::number::Endianness;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AssetBuilderError {
+    UnknownType(i32),
+    MissingTypeTree(i32),
+}
+
+impl std::fmt::Display for AssetBuilderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetBuilderError::UnknownType(class_id) => {
+                write!(f, "class {} is not in the type metadata", class_id)
+            }
+            AssetBuilderError::MissingTypeTree(class_id) => {
+                write!(f, "type tree of class {} is not available", class_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssetBuilderError {}
+
+struct ObjectEntry<'a> {
+    type_idx: u32,
+    type_id: i32,
+    class_id: i32,
+    script_type_idx: i16,
+    is_stripped: bool,
+    is_destroyed: bool,
+    data: Data<'a>,
+    layout: Option<Arc<TypeTree<'static>>>,
+}
+
+/// Re-emits a serialized file after its objects, externals or script types are modified.
+pub struct AssetBuilder<'a> {
+    format: u32,
+    endianness: Endianness,
+    tree: TypeMetadata<'a>,
+    long_object_ids: bool,
+    objects: BTreeMap<u64, ObjectEntry<'a>>,
+    adds: Vec<(u64, i32)>,
+    refs: Vec<AssetRef<'a>>,
+    user_information: Cow<'a, str>,
+}
+
+impl<'a> AssetBuilder<'a> {
+    pub fn from_asset(asset: &Asset<'a>) -> Self {
+        let objects = asset
+            .objects
+            .iter()
+            .map(|object| {
+                let entry = ObjectEntry {
+                    type_idx: object.type_idx,
+                    type_id: object.type_id,
+                    class_id: object.class_id,
+                    script_type_idx: object.script_type_idx,
+                    is_stripped: object.is_stripped,
+                    is_destroyed: object.is_destroyed,
+                    data: object.data.clone(),
+                    layout: object.layout.clone(),
+                };
+                (object.path_id, entry)
+            })
+            .collect();
+        Self {
+            format: asset.format,
+            endianness: asset.endianness,
+            tree: asset.tree.clone(),
+            long_object_ids: asset.long_object_ids,
+            objects,
+            adds: asset.adds.clone(),
+            refs: asset.refs.clone(),
+            user_information: asset.user_information.clone(),
+        }
+    }
+
+    /// Chooses whether type trees are embedded. Types without a tree fall back to the built-in
+    /// ones when embedding.
+    pub fn set_embed_type_trees(&mut self, embed: bool) -> &mut Self {
+        self.tree.set_has_type_trees(embed);
+        self
+    }
+
+    /// Adds an object, or replaces the object with the same path ID.
+    ///
+    /// `class_id` follows `Object::class_id` and must be present in the type metadata.
+    pub fn insert_object(
+        &mut self,
+        path_id: u64,
+        class_id: i32,
+        data: Data<'a>,
+    ) -> Result<&mut Self, AssetBuilderError> {
+        if let Some(object) = self.objects.get_mut(&path_id) {
+            if object.class_id == class_id {
+                object.data = data;
+                return Ok(self);
+            }
+        }
+        let type_idx = self
+            .tree
+            .idx_from_class_id(class_id)
+            .ok_or(AssetBuilderError::UnknownType(class_id))?;
+        let entry = ObjectEntry {
+            type_idx: type_idx as u32,
+            type_id: class_id,
+            class_id,
+            script_type_idx: -1,
+            is_stripped: false,
+            is_destroyed: false,
+            data,
+            layout: None,
+        };
+        self.objects.insert(path_id, entry);
+        Ok(self)
+    }
+
+    pub fn object_data_mut(&mut self, path_id: u64) -> Option<&mut Data<'a>> {
+        self.objects
+            .get_mut(&path_id)
+            .map(|object| &mut object.data)
+    }
+
+    pub fn remove_object(&mut self, path_id: u64) -> bool {
+        self.objects.remove(&path_id).is_some()
+    }
+
+    pub fn script_types_mut(&mut self) -> &mut Vec<(u64, i32)> {
+        &mut self.adds
+    }
+
+    pub fn externals_mut(&mut self) -> &mut Vec<AssetRef<'a>> {
+        &mut self.refs
+    }
+
+    pub fn set_user_information(&mut self, user_information: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.user_information = user_information.into();
+        self
+    }
+
+    fn write_path_id(&self, out: &mut Vec<u8>, path_id: u64) {
+        if self.format >= 14 || self.long_object_ids {
+            write_u64(out, path_id, self.endianness);
+        } else {
+            write_u32(out, path_id as u32, self.endianness);
+        }
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>, AssetBuilderError> {
+        let format = self.format;
+        let endianness = self.endianness;
+        let header_size = if format >= 9 { 20 } else { 16 };
+
+        let mut object_data = Vec::new();
+        let mut object_table = Vec::with_capacity(self.objects.len());
+        for (&path_id, object) in &self.objects {
+            write_align(&mut object_data, 8);
+            let start = object_data.len();
+            match (&object.data, &object.layout) {
+                // objects without a known layout are written back untouched
+                (Data::Raw { data, .. }, _) => object_data.extend_from_slice(data),
+                (data, Some(layout)) => {
+                    object_data.extend_from_slice(&layout.write(data, endianness))
+                }
+                (data, None) => {
+                    let type_tree = self
+                        .tree
+                        .type_tree_from_id(object.type_id, object.class_id)
+                        .ok_or(AssetBuilderError::MissingTypeTree(object.class_id))?;
+                    object_data.extend_from_slice(&type_tree.write(data, endianness));
+                }
+            }
+            object_table.push((path_id, start, object_data.len() - start, object));
+        }
+
+        let mut out = vec![0u8; header_size];
+        self.tree
+            .write(endianness, format, &mut out)
+            .map_err(AssetBuilderError::MissingTypeTree)?;
+        if (7..=13).contains(&format) {
+            write_u32(&mut out, self.long_object_ids as u32, endianness);
+        }
+
+        write_u32(&mut out, object_table.len() as u32, endianness);
+        for (path_id, start, size, object) in object_table {
+            if format >= 14 {
+                write_align(&mut out, 4);
+            }
+            self.write_path_id(&mut out, path_id);
+            write_u32(&mut out, start as u32, endianness);
+            write_u32(&mut out, size as u32, endianness);
+            if format < 17 {
+                write_u32(&mut out, object.type_id as u32, endianness);
+                write_u16(&mut out, object.class_id as u16, endianness);
+            } else {
+                write_u32(&mut out, object.type_idx, endianness);
+            }
+            if format <= 10 {
+                out.push(object.is_destroyed as u8);
+            }
+            if (11..=16).contains(&format) {
+                write_u16(&mut out, object.script_type_idx as u16, endianness);
+            }
+            if (15..=16).contains(&format) {
+                out.push(object.is_stripped as u8);
+            }
+        }
+
+        if format >= 11 {
+            write_u32(&mut out, self.adds.len() as u32, endianness);
+            for &(add_id, value) in &self.adds {
+                if format >= 14 {
+                    write_align(&mut out, 4);
+                    write_u64(&mut out, add_id, endianness);
+                } else {
+                    write_u32(&mut out, add_id as u32, endianness);
+                }
+                write_u32(&mut out, value as u32, endianness);
+            }
+        }
+
+        if format >= 16 {
+            write_u32(&mut out, self.refs.len() as u32, endianness);
+            for asset_ref in &self.refs {
+                out.extend_from_slice(asset_ref.asset_path.as_bytes());
+                out.push(0);
+                out.extend_from_slice(&asset_ref.guid);
+                write_u32(&mut out, asset_ref.ty, endianness);
+                out.extend_from_slice(asset_ref.file_path.as_bytes());
+                out.push(0);
+            }
+        }
+        out.extend_from_slice(self.user_information.as_bytes());
+        out.push(0);
+
+        let metadata_size = out.len() - header_size;
+        write_align(&mut out, 16);
+        let data_offset = out.len();
+        out.extend_from_slice(&object_data);
+        let file_size = out.len();
+
+        let mut header = Vec::with_capacity(header_size);
+        header.extend_from_slice(&(metadata_size as u32).to_be_bytes());
+        header.extend_from_slice(&(file_size as u32).to_be_bytes());
+        header.extend_from_slice(&format.to_be_bytes());
+        header.extend_from_slice(&(data_offset as u32).to_be_bytes());
+        if format >= 9 {
+            let is_big_endian = match endianness {
+                Endianness::Big => 1,
+                Endianness::Little => 0,
+            };
+            header.extend_from_slice(&[is_big_endian, 0, 0, 0]);
+        }
+        out[..header_size].copy_from_slice(&header);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::tests::serialized_file;
+    use crate::asset::TypeLayouts;
+
+    fn parse(bytes: &[u8]) -> Asset<'_> {
+        let (_, asset) =
+            Asset::parse("test".into(), bytes, 0, &TypeLayouts::default(), true).unwrap();
+        asset
+    }
+
+    fn text_asset<'a>(template: &Data<'a>, name: &'static str, script: &'static str) -> Data<'a> {
+        let mut data = template.clone();
+        match &mut data {
+            Data::GenericStruct { fields, .. } => {
+                fields.insert("m_Name".into(), Data::String(name.as_bytes().into()));
+                fields.insert("m_Script".into(), Data::String(script.as_bytes().into()));
+            }
+            _ => panic!("TextAsset is not a struct"),
+        }
+        data
+    }
+
+    #[test]
+    fn build_round_trip() {
+        let behaviour = (0..40).collect::<Vec<u8>>();
+        let original = serialized_file(&[0; 12], &behaviour);
+        let asset = parse(&original);
+        let template = asset.object(1).unwrap().data.clone();
+
+        let mut builder = AssetBuilder::from_asset(&asset);
+        builder
+            .insert_object(1, 49, text_asset(&template, "notes", "hello"))
+            .unwrap()
+            .insert_object(5, 49, text_asset(&template, "other", "bye"))
+            .unwrap()
+            .set_user_information("built");
+        assert_eq!(
+            builder.insert_object(6, 28, template.clone()).err(),
+            Some(AssetBuilderError::UnknownType(28))
+        );
+        builder.script_types_mut().push((2, 11500000));
+        builder.externals_mut().push(AssetRef::new(
+            "",
+            [0xab; 16],
+            3,
+            "library/unity default resources",
+        ));
+        let bytes = builder.build().unwrap();
+
+        let rebuilt = parse(&bytes);
+        let path_ids = rebuilt
+            .objects()
+            .iter()
+            .map(|o| o.path_id)
+            .collect::<Vec<_>>();
+        assert_eq!(path_ids, [1, 2, 5]);
+        for &(path_id, name, script) in &[(1, "notes", "hello"), (5, "other", "bye")] {
+            let data = &rebuilt.object(path_id).unwrap().data;
+            assert_eq!(data.field("m_Name").and_then(Data::as_str), Some(name));
+            assert_eq!(data.field("m_Script").and_then(Data::as_str), Some(script));
+        }
+        let script_object = rebuilt.object(2).unwrap();
+        assert!(script_object.type_id < 0);
+        assert!(matches!(script_object.data, Data::Raw { .. }));
+        assert_eq!(script_object.raw_data(), &behaviour[..]);
+        assert_eq!(rebuilt.adds, [(2, 11500000)]);
+        let externals = rebuilt.externals();
+        assert_eq!(externals.len(), 1);
+        assert_eq!(externals[0].guid(), [0xab; 16]);
+        assert_eq!(externals[0].ty(), 3);
+        assert_eq!(externals[0].file_path(), "library/unity default resources");
+        assert_eq!(rebuilt.user_information(), "built");
+        assert_eq!(rebuilt.unity_version(), "2018.4.0f1");
+
+        // re-emitting an unmodified asset reproduces it
+        let again = AssetBuilder::from_asset(&rebuilt).build().unwrap();
+        assert_eq!(again, bytes);
+    }
+}
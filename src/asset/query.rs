@@ -0,0 +1,199 @@
+use super::Data;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+    Wildcard,
+    Invalid,
+}
+
+fn parse_path(path: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    if path.is_empty() {
+        return segments;
+    }
+    for part in path.split('.') {
+        let (name, mut rest) = match part.find('[') {
+            Some(idx) => part.split_at(idx),
+            None => (part, ""),
+        };
+        match name {
+            "" if rest.is_empty() => segments.push(Segment::Invalid),
+            "" => {}
+            "*" => segments.push(Segment::Wildcard),
+            name => segments.push(Segment::Field(name.to_owned())),
+        }
+        while !rest.is_empty() {
+            let end = match rest.find(']') {
+                Some(end) if rest.starts_with('[') => end,
+                _ => {
+                    segments.push(Segment::Invalid);
+                    break;
+                }
+            };
+            let segment = match &rest[1..end] {
+                "*" => Segment::Wildcard,
+                idx => idx.parse().map(Segment::Index).unwrap_or(Segment::Invalid),
+            };
+            segments.push(segment);
+            rest = &rest[end + 1..];
+        }
+    }
+    segments
+}
+
+/// Iterator over values matching a path, created by [`Data::query`].
+pub struct Query<'d, 'b> {
+    segments: Vec<Segment>,
+    stack: Vec<(usize, &'d Data<'b>)>,
+}
+
+impl<'d, 'b> Iterator for Query<'d, 'b> {
+    type Item = &'d Data<'b>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((depth, data)) = self.stack.pop() {
+            let segment = match self.segments.get(depth) {
+                Some(segment) => segment,
+                None => return Some(data),
+            };
+            let depth = depth + 1;
+            match (segment, data) {
+                (Segment::Field(name), Data::GenericStruct { fields, .. }) => {
+                    if let Some(field) = fields.get(name.as_str()) {
+                        self.stack.push((depth, field));
+                    }
+                }
+                (Segment::Field(name), Data::Pair(fst, snd)) => match name.as_str() {
+                    "first" => self.stack.push((depth, fst)),
+                    "second" => self.stack.push((depth, snd)),
+                    _ => {}
+                },
+                (Segment::Index(idx), Data::GenericArray(v)) => {
+                    if let Some(item) = v.get(*idx) {
+                        self.stack.push((depth, item));
+                    }
+                }
+                (Segment::Wildcard, Data::GenericArray(v)) => {
+                    self.stack.extend(v.iter().rev().map(|item| (depth, item)));
+                }
+                (Segment::Wildcard, Data::GenericStruct { fields, .. }) => {
+                    self.stack
+                        .extend(fields.values().rev().map(|field| (depth, field)));
+                }
+                (Segment::Wildcard, Data::Pair(fst, snd)) => {
+                    self.stack.push((depth, snd));
+                    self.stack.push((depth, fst));
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+impl<'b> Data<'b> {
+    /// Finds values by a path such as `m_Container[*].second.asset.m_PathID`.
+    ///
+    /// Fields are separated by `.` and array elements are selected with `[index]`. `*` and
+    /// `[*]` match every array element or struct field. Pairs have `first` and `second` fields.
+    /// A path that doesn't match yields nothing.
+    pub fn query<'d>(&'d self, path: &str) -> Query<'d, 'b> {
+        Query {
+            segments: parse_path(path),
+            stack: vec![(0, self)],
+        }
+    }
+
+    /// Returns the first value matching `path`. See [`Data::query`] for the syntax.
+    pub fn get_path(&self, path: &str) -> Option<&Data<'b>> {
+        self.query(path).next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(fields: Vec<(&'static str, Data<'static>)>) -> Data<'static> {
+        Data::GenericStruct {
+            type_name: "Object".into(),
+            fields: fields.into_iter().collect(),
+        }
+    }
+
+    fn pptr(path_id: i64) -> Data<'static> {
+        object(vec![
+            ("m_FileID", Data::SInt32(0)),
+            ("m_PathID", Data::SInt64(path_id)),
+        ])
+    }
+
+    fn sample() -> Data<'static> {
+        let entry = |key: &'static str, path_id| {
+            Data::Pair(
+                Box::new(Data::String(key.as_bytes().into())),
+                Box::new(object(vec![("asset", pptr(path_id))])),
+            )
+        };
+        object(vec![
+            ("m_Name", Data::String(b"bundle"[..].into())),
+            (
+                "m_Container",
+                Data::GenericArray(vec![entry("a", 10), entry("b", 20), entry("c", 30)]),
+            ),
+            ("m_Zebra", Data::UInt8(1)),
+            ("m_Alpha", Data::UInt8(2)),
+            ("m_Middle", Data::UInt8(3)),
+        ])
+    }
+
+    fn ints(data: &Data<'_>, path: &str) -> Vec<i64> {
+        data.query(path).filter_map(Data::as_i64).collect()
+    }
+
+    #[test]
+    fn field_path() {
+        let data = sample();
+        assert_eq!(
+            data.get_path("m_Name").and_then(Data::as_str),
+            Some("bundle")
+        );
+        assert_eq!(ints(&data, "m_Container[1].second.asset.m_PathID"), [20]);
+        assert_eq!(ints(&data, "m_Missing"), [] as [i64; 0]);
+        assert_eq!(ints(&data, "m_Name.m_PathID"), [] as [i64; 0]);
+        assert_eq!(data.get_path("").map(Data::type_name), Some("Object"));
+    }
+
+    #[test]
+    fn index_path() {
+        let data = sample();
+        let first = data.get_path("m_Container[0].first").and_then(Data::as_str);
+        assert_eq!(first, Some("a"));
+        assert_eq!(ints(&data, "m_Container[2].second.asset.m_PathID"), [30]);
+        assert!(data.get_path("m_Container[3]").is_none());
+        assert!(data.get_path("m_Container[x]").is_none());
+        assert!(data.get_path("m_Container[0").is_none());
+        assert!(data.get_path("m_Container..first").is_none());
+    }
+
+    #[test]
+    fn wildcard_path() {
+        let data = sample();
+        assert_eq!(
+            ints(&data, "m_Container[*].second.asset.m_PathID"),
+            [10, 20, 30]
+        );
+        let keys = data
+            .query("m_Container[*].first")
+            .filter_map(Data::as_str)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["a", "b", "c"]);
+        // struct fields come out in type tree order
+        assert_eq!(ints(&data, "*"), [1, 2, 3]);
+        assert_eq!(ints(&data, "m_Container[0].second.asset.*"), [0, 10]);
+        assert_eq!(ints(&data, "m_Container[1].*.asset.m_PathID"), [20]);
+        assert_eq!(data.query("m_Container[*].*").count(), 6);
+    }
+}
@@ -1,5 +1,9 @@
+mod fields;
+mod layouts;
+mod visitor;
+
 use crate::common_parser::read_string;
-use crate::util::align;
+use crate::util::{align, write_align, write_u16, write_u32, write_u64};
 use nom::{
     i16, i32, i64,
     number::{complete as nom_number, Endianness},
@@ -8,8 +12,13 @@ use nom::{
 use serde::Serialize;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+
+pub use fields::Fields;
+pub use layouts::{LayoutError, TypeLayouts};
+pub use visitor::{VisitFlow, Visitor};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TypeTree<'a> {
     version: u32,
     is_array: bool,
@@ -18,9 +27,14 @@ pub struct TypeTree<'a> {
     size: u32,
     index: u32,
     flags: u32,
+    ref_type_hash: u64,
     children: Vec<TypeTree<'a>>,
 }
 
+fn take_bytes(input: &[u8], len: usize) -> IResult<&[u8], &[u8]> {
+    nom::bytes::complete::take(len)(input)
+}
+
 fn parse_old(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], TypeTree> {
     move |input| {
         let (input, type_name) = read_string(input, None)?;
@@ -43,6 +57,7 @@ fn parse_old(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], TypeTre
             size,
             index,
             flags,
+            ref_type_hash: 0,
             children,
         };
         Ok((input, ret))
@@ -55,13 +70,13 @@ fn parse_blob(input: &[u8], endianness: Endianness, format: u32) -> IResult<&[u8
     let (input, node_count) = u32!(input, endianness)?;
     let (input, buffer_bytes) = u32!(input, endianness)?;
     let node_bytes = if format >= 19 { 32 } else { 24 };
-    let (mut node_data, input) = input.split_at((node_count as usize) * node_bytes);
-    let (data, input) = input.split_at(buffer_bytes as usize);
+    let (input, mut node_data) = take_bytes(input, (node_count as usize) * node_bytes)?;
+    let (input, data) = take_bytes(input, buffer_bytes as usize)?;
 
     let get_string = |offset: u32| -> IResult<&[u8], Cow<'_, str>> {
         let slice = if offset >= 0x80000000 {
             let offset = (offset & 0x7fffffff) as usize;
-            &STRINGS_DAT[offset..]
+            STRINGS_DAT.get(offset..).unwrap_or_default()
         } else if offset < data.len() as u32 {
             &data[(offset as usize)..]
         } else {
@@ -81,7 +96,11 @@ fn parse_blob(input: &[u8], endianness: Endianness, format: u32) -> IResult<&[u8
         let (input, depth) = nom_number::be_u8(input)?;
         while tree_stack.len() > depth as usize {
             let node = tree_stack.pop().unwrap();
-            tree_stack.last_mut().unwrap().children.push(node);
+            match tree_stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                // more than one root node
+                None => return Err(nom::Err::Failure((input, nom::error::ErrorKind::Verify))),
+            }
         }
         let (input, is_array) = nom_number::be_u8(input)?;
         let is_array = is_array != 0;
@@ -92,7 +111,11 @@ fn parse_blob(input: &[u8], endianness: Endianness, format: u32) -> IResult<&[u8
         let (input, size) = u32!(input, endianness)?;
         let (input, index) = u32!(input, endianness)?;
         let (input, flags) = u32!(input, endianness)?;
-        let input = &input[node_bytes - 24..];
+        let (input, ref_type_hash) = if node_bytes == 32 {
+            u64!(input, endianness)?
+        } else {
+            (input, 0)
+        };
         let node = TypeTree {
             version: version as u32,
             is_array,
@@ -101,12 +124,16 @@ fn parse_blob(input: &[u8], endianness: Endianness, format: u32) -> IResult<&[u8
             size,
             index,
             flags,
+            ref_type_hash,
             children: Vec::new(),
         };
         tree_stack.push(node);
         node_data = input;
     }
-    let mut node = tree_stack.pop().unwrap();
+    let mut node = match tree_stack.pop() {
+        Some(v) => v,
+        None => return Err(nom::Err::Failure((input, nom::error::ErrorKind::Verify))),
+    };
     while let Some(mut parent) = tree_stack.pop() {
         parent.children.push(node);
         node = parent;
@@ -114,6 +141,81 @@ fn parse_blob(input: &[u8], endianness: Endianness, format: u32) -> IResult<&[u8
     Ok((input, node))
 }
 
+lazy_static::lazy_static! {
+    static ref COMMON_STRINGS: HashMap<&'static str, u32> = {
+        let mut offset = 0;
+        STRINGS_DAT
+            .split(|&b| b == 0)
+            .filter_map(|s| {
+                let ret = std::str::from_utf8(s).ok().map(|s| (s, offset));
+                offset += s.len() as u32 + 1;
+                ret
+            })
+            .filter(|(s, _)| !s.is_empty())
+            .collect()
+    };
+}
+
+fn write_blob(tree: &TypeTree<'_>, endianness: Endianness, format: u32, out: &mut Vec<u8>) {
+    fn flatten<'t, 'a>(tree: &'t TypeTree<'a>, depth: u8, nodes: &mut Vec<(u8, &'t TypeTree<'a>)>) {
+        nodes.push((depth, tree));
+        for child in &tree.children {
+            flatten(child, depth + 1, nodes);
+        }
+    }
+
+    let mut nodes = Vec::new();
+    flatten(tree, 0, &mut nodes);
+    let mut buffer = Vec::new();
+    let mut local_strings = HashMap::new();
+    let mut get_offset = |s: &str| -> u32 {
+        if let Some(&offset) = COMMON_STRINGS.get(s) {
+            return offset | 0x80000000;
+        }
+        *local_strings.entry(s.to_owned()).or_insert_with(|| {
+            let offset = buffer.len() as u32;
+            buffer.extend_from_slice(s.as_bytes());
+            buffer.push(0);
+            offset
+        })
+    };
+
+    let mut node_data = Vec::new();
+    for (depth, node) in &nodes {
+        write_u16(&mut node_data, node.version as u16, endianness);
+        node_data.push(*depth);
+        node_data.push(node.is_array as u8);
+        write_u32(&mut node_data, get_offset(&node.type_name), endianness);
+        write_u32(&mut node_data, get_offset(&node.name), endianness);
+        write_u32(&mut node_data, node.size, endianness);
+        write_u32(&mut node_data, node.index, endianness);
+        write_u32(&mut node_data, node.flags, endianness);
+        if format >= 19 {
+            write_u64(&mut node_data, node.ref_type_hash, endianness);
+        }
+    }
+    write_u32(out, nodes.len() as u32, endianness);
+    write_u32(out, buffer.len() as u32, endianness);
+    out.extend_from_slice(&node_data);
+    out.extend_from_slice(&buffer);
+}
+
+fn write_old(tree: &TypeTree<'_>, endianness: Endianness, out: &mut Vec<u8>) {
+    out.extend_from_slice(tree.type_name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(tree.name.as_bytes());
+    out.push(0);
+    write_u32(out, tree.size, endianness);
+    write_u32(out, tree.index, endianness);
+    write_u32(out, tree.is_array as u32, endianness);
+    write_u32(out, tree.version, endianness);
+    write_u32(out, tree.flags, endianness);
+    write_u32(out, tree.children.len() as u32, endianness);
+    for child in &tree.children {
+        write_old(child, endianness, out);
+    }
+}
+
 impl<'a> TypeTree<'a> {
     fn parse(input: &'a [u8], endianness: Endianness, format: u32) -> IResult<&[u8], Self> {
         if format == 10 || format >= 12 {
@@ -123,10 +225,68 @@ impl<'a> TypeTree<'a> {
         }
     }
 
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.is_array
+    }
+
+    pub fn children(&self) -> &[TypeTree<'a>] {
+        &self.children
+    }
+
+    fn write_tree(&self, endianness: Endianness, format: u32, out: &mut Vec<u8>) {
+        if format == 10 || format >= 12 {
+            write_blob(self, endianness, format, out)
+        } else {
+            write_old(self, endianness, out)
+        }
+    }
+
     fn needs_align(&self) -> bool {
         self.flags & 0x4000 != 0
     }
 
+    fn read_primitive(
+        &self,
+        base: &'a [u8],
+        input: &'a [u8],
+        endianness: Endianness,
+        offset: u64,
+    ) -> IResult<&'a [u8], Data<'a>> {
+        let length = self.size;
+        let input = if self.type_name == "float" || self.type_name == "double" {
+            align(offset as usize, base, input)
+        } else {
+            input
+        };
+        let (input, data) = take_bytes(input, length as usize)?;
+        let data = match self.type_name.as_ref() {
+            "bool" => Data::Bool(nom_number::be_u8(data)?.1 != 0),
+            "UInt8" => Data::UInt8(nom_number::be_u8(data)?.1),
+            "UInt16" => Data::UInt16(u16!(data, endianness)?.1),
+            "UInt32" | "unsigned int" => Data::UInt32(u32!(data, endianness)?.1),
+            "UInt64" => Data::UInt64(u64!(data, endianness)?.1),
+            "SInt8" => Data::SInt8(nom_number::be_i8(data)?.1),
+            "SInt16" => Data::SInt16(i16!(data, endianness)?.1),
+            "SInt32" | "int" => Data::SInt32(i32!(data, endianness)?.1),
+            "SInt64" => Data::SInt64(i64!(data, endianness)?.1),
+            "float" => Data::Float(f32::from_bits(u32!(data, endianness)?.1)),
+            "double" => Data::Double(f64::from_bits(u64!(data, endianness)?.1)),
+            _ => Data::GenericPrimitive {
+                type_name: self.type_name.clone(),
+                data: data.into(),
+            },
+        };
+        Ok((input, data))
+    }
+
     pub fn read(
         &self,
         input: &'a [u8],
@@ -139,7 +299,7 @@ impl<'a> TypeTree<'a> {
             debug_assert_eq!(self.children.len(), 1);
             needs_align |= self.children[0].needs_align();
             let (input, length) = u32!(input, endianness)?;
-            let (bytes, input) = input.split_at(length as usize);
+            let (input, bytes) = take_bytes(input, length as usize)?;
             (input, Data::String(bytes.into()))
         } else if self.type_name == "pair" {
             debug_assert_eq!(self.children.len(), 2);
@@ -147,15 +307,18 @@ impl<'a> TypeTree<'a> {
             let offset = offset + (input.as_ptr() as usize - base.as_ptr() as usize) as u64;
             let (input, snd) = self.children[1].read(input, endianness, offset)?;
             (input, Data::Pair(Box::new(fst), Box::new(snd)))
-        } else if let Some(child) = self.children.get(0).filter(|child| child.is_array) {
+        } else if let Some(child) = self.children.first().filter(|child| child.is_array) {
             child.read(input, endianness, offset)?
         } else if self.is_array {
             debug_assert_eq!(self.children.len(), 2);
             let element_type = &self.children[1];
             let (input, length) = u32!(input, endianness)?;
             if element_type.type_name == "UInt8" {
-                let (bytes, input) = input.split_at(length as usize);
+                let (input, bytes) = take_bytes(input, length as usize)?;
                 (input, Data::UInt8Array(bytes.into()))
+            } else if length as usize > input.len() {
+                // every element takes at least a byte, don't allocate for a bogus length
+                return Err(nom::Err::Error((input, nom::error::ErrorKind::Count)));
             } else {
                 let mut input = input;
                 let v = (0..length)
@@ -169,32 +332,8 @@ impl<'a> TypeTree<'a> {
                     .collect::<Result<Vec<_>, _>>()?;
                 (input, Data::GenericArray(v))
             }
-        } else if self.children.len() == 0 {
-            let length = self.size;
-            let input = if self.type_name == "float" || self.type_name == "double" {
-                align(offset as usize, base, input)
-            } else {
-                input
-            };
-            let (data, input) = input.split_at(length as usize);
-            let data = match self.type_name.as_ref() {
-                "bool" => Data::Bool(data[0] != 0),
-                "UInt8" => Data::UInt8(nom_number::be_u8(data)?.1),
-                "UInt16" => Data::UInt16(u16!(data, endianness)?.1),
-                "UInt32" | "unsigned int" => Data::UInt32(u32!(data, endianness)?.1),
-                "UInt64" => Data::UInt64(u64!(data, endianness)?.1),
-                "SInt8" => Data::SInt8(nom_number::be_i8(data)?.1),
-                "SInt16" => Data::SInt16(i16!(data, endianness)?.1),
-                "SInt32" | "int" => Data::SInt32(i32!(data, endianness)?.1),
-                "SInt64" => Data::SInt64(i64!(data, endianness)?.1),
-                "float" => Data::Float(f32::from_bits(u32!(data, endianness)?.1)),
-                "double" => Data::Double(f64::from_bits(u64!(data, endianness)?.1)),
-                _ => Data::GenericPrimitive {
-                    type_name: self.type_name.clone(),
-                    data: data.into(),
-                },
-            };
-            (input, data)
+        } else if self.children.is_empty() {
+            self.read_primitive(base, input, endianness, offset)?
         } else {
             let mut input = input;
             let fields = self
@@ -206,7 +345,7 @@ impl<'a> TypeTree<'a> {
                     input = left;
                     Ok((field_type.name.clone(), data))
                 })
-                .collect::<Result<HashMap<_, _>, _>>()?;
+                .collect::<Result<Fields, _>>()?;
             (
                 input,
                 Data::GenericStruct {
@@ -224,7 +363,99 @@ impl<'a> TypeTree<'a> {
     }
 }
 
-#[derive(Serialize)]
+impl TypeTree<'_> {
+    /// Serializes `data` with this type tree. Panics if `data` doesn't match the tree.
+    pub fn write(&self, data: &Data<'_>, endianness: Endianness) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_into(data, endianness, &mut out);
+        out
+    }
+
+    fn write_into(&self, data: &Data<'_>, endianness: Endianness, out: &mut Vec<u8>) {
+        let mut needs_align = self.needs_align();
+        if self.type_name == "string" {
+            debug_assert_eq!(self.children.len(), 1);
+            needs_align |= self.children[0].needs_align();
+            let bytes = match data {
+                Data::String(bytes) => bytes,
+                _ => panic!("type mismatch at {}: expected string", self.name),
+            };
+            write_u32(out, bytes.len() as u32, endianness);
+            out.extend_from_slice(bytes);
+        } else if self.type_name == "pair" {
+            debug_assert_eq!(self.children.len(), 2);
+            let (fst, snd) = match data {
+                Data::Pair(fst, snd) => (fst, snd),
+                _ => panic!("type mismatch at {}: expected pair", self.name),
+            };
+            self.children[0].write_into(fst, endianness, out);
+            self.children[1].write_into(snd, endianness, out);
+        } else if let Some(child) = self.children.first().filter(|child| child.is_array) {
+            child.write_into(data, endianness, out);
+        } else if self.is_array {
+            debug_assert_eq!(self.children.len(), 2);
+            let element_type = &self.children[1];
+            match data {
+                Data::UInt8Array(bytes) if element_type.type_name == "UInt8" => {
+                    write_u32(out, bytes.len() as u32, endianness);
+                    out.extend_from_slice(bytes);
+                }
+                Data::GenericArray(v) if element_type.type_name != "UInt8" => {
+                    write_u32(out, v.len() as u32, endianness);
+                    for item in v {
+                        element_type.write_into(item, endianness, out);
+                    }
+                }
+                _ => panic!("type mismatch at {}: expected array", self.name),
+            }
+        } else if self.children.is_empty() {
+            if self.type_name == "float" || self.type_name == "double" {
+                write_align(out, 4);
+            }
+            let start = out.len();
+            match (self.type_name.as_ref(), data) {
+                ("bool", Data::Bool(v)) => out.push(*v as u8),
+                ("UInt8", Data::UInt8(v)) => out.push(*v),
+                ("UInt16", Data::UInt16(v)) => write_u16(out, *v, endianness),
+                ("UInt32", Data::UInt32(v)) | ("unsigned int", Data::UInt32(v)) => {
+                    write_u32(out, *v, endianness)
+                }
+                ("UInt64", Data::UInt64(v)) => write_u64(out, *v, endianness),
+                ("SInt8", Data::SInt8(v)) => out.push(*v as u8),
+                ("SInt16", Data::SInt16(v)) => write_u16(out, *v as u16, endianness),
+                ("SInt32", Data::SInt32(v)) | ("int", Data::SInt32(v)) => {
+                    write_u32(out, *v as u32, endianness)
+                }
+                ("SInt64", Data::SInt64(v)) => write_u64(out, *v as u64, endianness),
+                ("float", Data::Float(v)) => write_u32(out, v.to_bits(), endianness),
+                ("double", Data::Double(v)) => write_u64(out, v.to_bits(), endianness),
+                (_, Data::GenericPrimitive { data, .. }) => out.extend_from_slice(data),
+                (type_name, _) => panic!("type mismatch at {}: expected {}", self.name, type_name),
+            }
+            // Keep the declared size even if the primitive is wider than its value.
+            out.resize(start + self.size as usize, 0);
+        } else {
+            let fields = match data {
+                Data::GenericStruct { fields, .. } => fields,
+                _ => panic!(
+                    "type mismatch at {}: expected {}",
+                    self.name, self.type_name
+                ),
+            };
+            for field_type in &self.children {
+                let field = fields
+                    .get(&field_type.name)
+                    .unwrap_or_else(|| panic!("field {} not found", field_type.name));
+                field_type.write_into(field, endianness, out);
+            }
+        }
+        if needs_align {
+            write_align(out, 4);
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Data<'b> {
     GenericPrimitive {
@@ -234,7 +465,7 @@ pub enum Data<'b> {
     GenericArray(Vec<Data<'b>>),
     GenericStruct {
         type_name: Cow<'b, str>,
-        fields: HashMap<Cow<'b, str>, Data<'b>>,
+        fields: Fields<'b>,
     },
     Bool(bool),
     UInt8(u8),
@@ -250,6 +481,12 @@ pub enum Data<'b> {
     String(Cow<'b, [u8]>),
     UInt8Array(Cow<'b, [u8]>),
     Pair(Box<Data<'b>>, Box<Data<'b>>),
+    /// Serialized bytes of an object whose layout is unknown, like a `MonoBehaviour` of a
+    /// stripped build. See [`TypeLayouts`].
+    Raw {
+        type_name: Cow<'b, str>,
+        data: Cow<'b, [u8]>,
+    },
 }
 
 impl std::fmt::Debug for Data<'_> {
@@ -298,6 +535,16 @@ impl std::fmt::Debug for Data<'_> {
                 )
             }
             Data::Pair(fst, snd) => fmt.debug_tuple("Pair").field(fst).field(snd).finish(),
+            Data::Raw { type_name, data } => {
+                let len = data.len();
+                write!(
+                    fmt,
+                    "Raw({}, {} byte{})",
+                    type_name,
+                    len,
+                    if len == 1 { "" } else { "s" }
+                )
+            }
         }
     }
 }
@@ -313,7 +560,7 @@ impl Data<'_> {
                 type_name: type_name.clone().into_owned().into(),
                 fields: fields
                     .iter()
-                    .map(|(k, v)| (k.clone().into_owned().into(), v.clone_owned()))
+                    .map(|(k, v)| (k.clone().into_owned(), v.clone_owned()))
                     .collect(),
             },
             Data::GenericPrimitive { type_name, data } => Data::GenericPrimitive {
@@ -331,13 +578,139 @@ impl Data<'_> {
             Data::SInt64(v) => Data::SInt64(*v),
             Data::Float(v) => Data::Float(*v),
             Data::Double(v) => Data::Double(*v),
+            Data::Raw { type_name, data } => Data::Raw {
+                type_name: type_name.clone().into_owned().into(),
+                data: data.clone().into_owned().into(),
+            },
         }
     }
 }
 
-#[derive(Debug)]
+impl<'b> Data<'b> {
+    pub fn type_name(&self) -> &str {
+        match self {
+            Data::Bool(_) => "bool",
+            Data::UInt8(_) => "UInt8",
+            Data::UInt16(_) => "UInt16",
+            Data::UInt32(_) => "UInt32",
+            Data::UInt64(_) => "UInt64",
+            Data::SInt8(_) => "SInt8",
+            Data::SInt16(_) => "SInt16",
+            Data::SInt32(_) => "SInt32",
+            Data::SInt64(_) => "SInt64",
+            Data::Float(_) => "float",
+            Data::Double(_) => "double",
+            Data::UInt8Array(_) => "ByteArray",
+            Data::String(_) => "string",
+            Data::Pair(..) => "pair",
+            Data::GenericArray(_) => "Array",
+            Data::GenericStruct { type_name, .. }
+            | Data::GenericPrimitive { type_name, .. }
+            | Data::Raw { type_name, .. } => type_name,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Data::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of any integer variant, if it fits in `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Data::UInt8(v) => Some(v.into()),
+            Data::UInt16(v) => Some(v.into()),
+            Data::UInt32(v) => Some(v.into()),
+            Data::UInt64(v) => i64::try_from(v).ok(),
+            Data::SInt8(v) => Some(v.into()),
+            Data::SInt16(v) => Some(v.into()),
+            Data::SInt32(v) => Some(v.into()),
+            Data::SInt64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of any integer variant, if it fits in `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Data::UInt8(v) => Some(v.into()),
+            Data::UInt16(v) => Some(v.into()),
+            Data::UInt32(v) => Some(v.into()),
+            Data::UInt64(v) => Some(v),
+            _ => self.as_i64().and_then(|v| u64::try_from(v).ok()),
+        }
+    }
+
+    /// Returns the value of any float or integer variant.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Data::Float(v) => Some(v.into()),
+            Data::Double(v) => Some(v),
+            Data::UInt64(v) => Some(v as f64),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    /// Returns the raw bytes of strings, byte arrays, unknown primitives and undecoded objects.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Data::String(b) | Data::UInt8Array(b) => Some(b),
+            Data::GenericPrimitive { data, .. } | Data::Raw { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Returns the string as `&str` if it's valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Data::String(b) => std::str::from_utf8(b).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Data<'b>]> {
+        match self {
+            Data::GenericArray(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_pair(&self) -> Option<(&Data<'b>, &Data<'b>)> {
+        match self {
+            Data::Pair(fst, snd) => Some((fst, snd)),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&Fields<'b>> {
+        match self {
+            Data::GenericStruct { fields, .. } => Some(fields),
+            _ => None,
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Data<'b>> {
+        self.as_struct().and_then(|fields| fields.get(name))
+    }
+
+    /// Returns `(m_FileID, m_PathID)` of a `PPtr`.
+    pub fn as_pptr(&self) -> Option<(i32, i64)> {
+        if !self.type_name().starts_with("PPtr<") {
+            return None;
+        }
+        let file_id = self.field("m_FileID")?.as_i64()?;
+        let path_id = self.field("m_PathID")?.as_i64()?;
+        Some((i32::try_from(file_id).ok()?, path_id))
+    }
+}
+
+#[derive(Clone, Debug)]
 struct TypeMetadataEntry<'a> {
     class_id: i32,
+    is_stripped: bool,
+    script_id: i16,
     hash: Option<&'a [u8]>,
     tree: Option<TypeTree<'a>>,
 }
@@ -350,28 +723,24 @@ impl<'a> TypeMetadataEntry<'a> {
         has_type_trees: bool,
     ) -> IResult<&'a [u8], Self> {
         let (input, class_id) = i32!(input, endianness)?;
-        let (input, class_id) = if format >= 17 {
-            let input = &input[1..];
+        let (input, class_id, is_stripped, script_id) = if format >= 17 {
+            let (input, is_stripped) = nom_number::be_u8(input)?;
+            let is_stripped = is_stripped != 0;
             let (input, script_id) = i16!(input, endianness)?;
-            let script_id: i32 = script_id.into();
             let class_id = if class_id == 114 {
                 if script_id >= 0 {
-                    -2 - script_id
+                    -2 - i32::from(script_id)
                 } else {
                     -1
                 }
             } else {
                 class_id
             };
-            (input, class_id)
-        } else {
-            (input, class_id)
-        };
-        let (hash, input) = if class_id < 0 {
-            input.split_at(0x20)
+            (input, class_id, is_stripped, script_id)
         } else {
-            input.split_at(0x10)
+            (input, class_id, false, -1)
         };
+        let (input, hash) = take_bytes(input, if class_id < 0 { 0x20 } else { 0x10 })?;
         let (input, tree) = if has_type_trees {
             let (input, tree) = TypeTree::parse(input, endianness, format)?;
             (input, Some(tree))
@@ -382,6 +751,8 @@ impl<'a> TypeMetadataEntry<'a> {
             input,
             Self {
                 class_id,
+                is_stripped,
+                script_id,
                 hash: Some(hash),
                 tree,
             },
@@ -395,6 +766,8 @@ impl<'a> TypeMetadataEntry<'a> {
             input,
             Self {
                 class_id,
+                is_stripped: false,
+                script_id: -1,
                 hash: None,
                 tree: Some(tree),
             },
@@ -402,12 +775,56 @@ impl<'a> TypeMetadataEntry<'a> {
     }
 }
 
-#[derive(Debug)]
+impl TypeMetadataEntry<'_> {
+    fn write(
+        &self,
+        endianness: Endianness,
+        format: u32,
+        embed_type_trees: bool,
+        out: &mut Vec<u8>,
+    ) -> Result<(), i32> {
+        if format >= 17 {
+            let class_id = if self.class_id < 0 {
+                114
+            } else {
+                self.class_id
+            };
+            write_u32(out, class_id as u32, endianness);
+            out.push(self.is_stripped as u8);
+            write_u16(out, self.script_id as u16, endianness);
+        } else {
+            write_u32(out, self.class_id as u32, endianness);
+        }
+        if format >= 13 {
+            let hash_len = if self.class_id < 0 { 0x20 } else { 0x10 };
+            match self.hash {
+                Some(hash) => out.extend_from_slice(hash),
+                None => out.resize(out.len() + hash_len, 0),
+            }
+        }
+        if embed_type_trees {
+            let tree = self
+                .tree
+                .as_ref()
+                .or_else(|| {
+                    DEFAULT_TYPES
+                        .entry_from_id(self.class_id)
+                        .and_then(|entry| entry.tree.as_ref())
+                })
+                .ok_or(self.class_id)?;
+            tree.write_tree(endianness, format, out);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct TypeMetadata<'a> {
     generator_version: Cow<'a, str>,
     target_platform: u32,
-    class_ids: Vec<i32>,
-    entries: HashMap<i32, TypeMetadataEntry<'a>>,
+    has_type_trees: bool,
+    entries: Vec<TypeMetadataEntry<'a>>,
+    index: HashMap<i32, usize>,
 }
 
 impl<'a> TypeMetadata<'a> {
@@ -415,9 +832,9 @@ impl<'a> TypeMetadata<'a> {
         let (input, generator_version) = read_string(input, None)?;
         let (input, target_platform) = u32!(input, endianness)?;
 
-        let (input, entries) = if format >= 13 {
-            let has_type_trees = input[0] != 0;
-            let input = &input[1..];
+        let (input, has_type_trees, entries) = if format >= 13 {
+            let (input, has_type_trees) = nom_number::be_u8(input)?;
+            let has_type_trees = has_type_trees != 0;
             let (mut input, num_types) = u32!(input, endianness)?;
 
             let entries = (0..num_types)
@@ -428,7 +845,7 @@ impl<'a> TypeMetadata<'a> {
                     Ok(entry)
                 })
                 .collect::<Result<Vec<_>, _>>()?;
-            (input, entries)
+            (input, has_type_trees, entries)
         } else {
             let (mut input, fields_count) = u32!(input, endianness)?;
             let entries = (0..fields_count)
@@ -438,12 +855,12 @@ impl<'a> TypeMetadata<'a> {
                     Ok(entry)
                 })
                 .collect::<Result<Vec<_>, _>>()?;
-            (input, entries)
+            (input, true, entries)
         };
-        let class_ids = entries.iter().map(|entry| entry.class_id).collect();
-        let entries = entries
-            .into_iter()
-            .map(|entry| (entry.class_id, entry))
+        let index = entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (entry.class_id, idx))
             .collect();
 
         Ok((
@@ -451,22 +868,79 @@ impl<'a> TypeMetadata<'a> {
             Self {
                 generator_version,
                 target_platform,
-                class_ids,
+                has_type_trees,
                 entries,
+                index,
             },
         ))
     }
 
+    pub fn generator_version(&self) -> &str {
+        &self.generator_version
+    }
+
+    pub fn set_has_type_trees(&mut self, has_type_trees: bool) {
+        self.has_type_trees = has_type_trees;
+    }
+
+    /// Writes the type metadata back. Fails with the class ID of a type whose tree is needed
+    /// but not available.
+    pub fn write(&self, endianness: Endianness, format: u32, out: &mut Vec<u8>) -> Result<(), i32> {
+        out.extend_from_slice(self.generator_version.as_bytes());
+        out.push(0);
+        write_u32(out, self.target_platform, endianness);
+        if format >= 13 {
+            out.push(self.has_type_trees as u8);
+        }
+        write_u32(out, self.entries.len() as u32, endianness);
+        let embed_type_trees = format < 13 || self.has_type_trees;
+        for entry in &self.entries {
+            entry.write(endianness, format, embed_type_trees, out)?;
+        }
+        Ok(())
+    }
+
     pub fn class_id_from_idx(&self, idx: usize) -> i32 {
-        self.class_ids[idx]
+        self.entries[idx].class_id
+    }
+
+    pub fn idx_from_class_id(&self, class_id: i32) -> Option<usize> {
+        self.index.get(&class_id).copied()
+    }
+
+    fn entry_from_id(&self, id: i32) -> Option<&TypeMetadataEntry<'a>> {
+        self.index.get(&id).map(|&idx| &self.entries[idx])
     }
 
     pub fn type_tree_from_id(&self, type_id: i32, class_id: i32) -> Option<&TypeTree<'a>> {
-        self.entries
-            .get(&type_id)
-            .or_else(|| DEFAULT_TYPES.entries.get(&class_id))
+        self.embedded_type_tree(type_id)
+            .or_else(|| Self::default_type_tree(class_id))
+    }
+
+    /// Type tree stored in the file, `None` for stripped builds.
+    pub fn embedded_type_tree(&self, type_id: i32) -> Option<&TypeTree<'a>> {
+        self.entry_from_id(type_id)
+            .and_then(|entry| entry.tree.as_ref())
+    }
+
+    /// Built-in type tree of a class, which has no script types.
+    pub fn default_type_tree(class_id: i32) -> Option<&'static TypeTree<'static>> {
+        DEFAULT_TYPES
+            .entry_from_id(class_id)
             .and_then(|entry| entry.tree.as_ref())
     }
+
+    /// Type hash of a type, 32 bytes for script types and 16 bytes for the others.
+    pub fn type_hash(&self, type_id: i32) -> Option<&'a [u8]> {
+        self.entry_from_id(type_id).and_then(|entry| entry.hash)
+    }
+}
+
+/// Reads the fields every `MonoBehaviour` starts with: `m_GameObject`, `m_Enabled`,
+/// `m_Script` and `m_Name`. The rest of `input` is left unread.
+pub(super) fn read_behaviour_header(input: &[u8], endianness: Endianness) -> Option<Data<'_>> {
+    let tree = TypeMetadata::default_type_tree(114)?;
+    tree.read(input, endianness, 0).ok().map(|(_, data)| data)
 }
 
 const DEFAULT_STRUCTS: &'static [u8] = include_bytes!("structs.dat");
@@ -476,3 +950,277 @@ lazy_static::lazy_static! {
         TypeMetadata::parse(DEFAULT_STRUCTS, Endianness::Little, 15).unwrap().1
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(
+        type_name: &'static str,
+        name: &'static str,
+        size: i32,
+        flags: u32,
+    ) -> TypeTree<'static> {
+        TypeTree {
+            version: 1,
+            is_array: false,
+            type_name: type_name.into(),
+            name: name.into(),
+            size: size as u32,
+            index: 0,
+            flags,
+            ref_type_hash: 0,
+            children: Vec::new(),
+        }
+    }
+
+    fn parent(mut tree: TypeTree<'static>, children: Vec<TypeTree<'static>>) -> TypeTree<'static> {
+        tree.children = children;
+        tree
+    }
+
+    fn array(name: &'static str, flags: u32, element: TypeTree<'static>) -> TypeTree<'static> {
+        let mut array = node("Array", "Array", -1, flags);
+        array.is_array = true;
+        array.children = vec![node("int", "size", 4, 0), element];
+        parent(node("vector", name, -1, 0), vec![array])
+    }
+
+    fn string(name: &'static str) -> TypeTree<'static> {
+        let mut array = node("Array", "Array", -1, 0x4000);
+        array.is_array = true;
+        array.children = vec![node("int", "size", 4, 0), node("char", "data", 1, 0)];
+        parent(node("string", name, -1, 0x8000), vec![array])
+    }
+
+    /// Reads `bytes` with `tree` and checks that writing the result gives the same bytes.
+    fn round_trip<'a>(tree: &TypeTree<'a>, bytes: &'a [u8], endianness: Endianness) -> Data<'a> {
+        let (rest, data) = tree.read(bytes, endianness, 0).unwrap();
+        assert!(rest.is_empty(), "{} bytes left", rest.len());
+        assert_eq!(tree.write(&data, endianness), bytes);
+        data
+    }
+
+    #[test]
+    fn string_alignment() {
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![string("m_Name"), node("int", "m_Value", 4, 0)],
+        );
+        let bytes = [
+            5, 0, 0, 0, b'h', b'e', b'l', b'l', b'o', 0, 0, 0, 7, 0, 0, 0,
+        ];
+        let data = round_trip(&tree, &bytes, Endianness::Little);
+        assert_eq!(data.field("m_Name").unwrap().as_str(), Some("hello"));
+        assert_eq!(data.field("m_Value").unwrap().as_i64(), Some(7));
+    }
+
+    #[test]
+    fn array_alignment() {
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![
+                array("m_Values", 0x4000, node("SInt16", "data", 2, 0)),
+                node("int", "m_Count", 4, 0),
+            ],
+        );
+        let bytes = [3, 0, 0, 0, 1, 0, 2, 0, 0xfd, 0xff, 0, 0, 3, 0, 0, 0];
+        let data = round_trip(&tree, &bytes, Endianness::Little);
+        match data.field("m_Values").unwrap() {
+            Data::GenericArray(v) => {
+                let v = v.iter().map(|v| v.as_i64().unwrap()).collect::<Vec<_>>();
+                assert_eq!(v, [1, 2, -3]);
+            }
+            v => panic!("unexpected {:?}", v),
+        }
+    }
+
+    #[test]
+    fn unaligned_array() {
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![
+                array("m_Flags", 0, node("bool", "data", 1, 0)),
+                node("UInt8", "m_Tail", 1, 0),
+            ],
+        );
+        let bytes = [2, 0, 0, 0, 1, 0, 9];
+        let data = round_trip(&tree, &bytes, Endianness::Little);
+        assert_eq!(data.field("m_Tail").unwrap().as_i64(), Some(9));
+    }
+
+    #[test]
+    fn uint8_array() {
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![
+                array("m_Data", 0x4000, node("UInt8", "data", 1, 0)),
+                node("UInt32", "m_Size", 4, 0),
+            ],
+        );
+        let bytes = [5, 0, 0, 0, 1, 2, 3, 4, 5, 0, 0, 0, 5, 0, 0, 0];
+        let data = round_trip(&tree, &bytes, Endianness::Little);
+        match data.field("m_Data").unwrap() {
+            Data::UInt8Array(v) => assert_eq!(&v[..], [1, 2, 3, 4, 5]),
+            v => panic!("unexpected {:?}", v),
+        }
+    }
+
+    #[test]
+    fn pair_map() {
+        let pair = parent(
+            node("pair", "data", -1, 0),
+            vec![string("first"), node("SInt64", "second", 8, 0)],
+        );
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![array("m_Container", 0x4000, pair)],
+        );
+        let mut bytes = vec![2, 0, 0, 0];
+        bytes.extend_from_slice(&[1, 0, 0, 0, b'a', 0, 0, 0]);
+        bytes.extend_from_slice(&10i64.to_le_bytes());
+        bytes.extend_from_slice(&[4, 0, 0, 0, b'a', b'b', b'c', b'd']);
+        bytes.extend_from_slice(&(-1i64).to_le_bytes());
+        let data = round_trip(&tree, &bytes, Endianness::Little);
+        match data.field("m_Container").unwrap() {
+            Data::GenericArray(v) => match &v[1] {
+                Data::Pair(k, v) => {
+                    assert_eq!(k.as_str(), Some("abcd"));
+                    assert_eq!(v.as_i64(), Some(-1));
+                }
+                v => panic!("unexpected {:?}", v),
+            },
+            v => panic!("unexpected {:?}", v),
+        }
+    }
+
+    #[test]
+    fn aligned_primitives() {
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![
+                node("bool", "m_Enabled", 1, 0x4000),
+                node("UInt8", "m_Layer", 1, 0),
+                node("float", "m_Weight", 4, 0),
+                node("SInt16", "m_Short", 2, 0),
+                node("double", "m_Time", 8, 0),
+            ],
+        );
+        let mut bytes = vec![1, 0, 0, 0, 3, 0, 0, 0];
+        bytes.extend_from_slice(&1.5f32.to_le_bytes());
+        bytes.extend_from_slice(&[0xff, 0x7f, 0, 0]);
+        bytes.extend_from_slice(&0.25f64.to_le_bytes());
+        let data = round_trip(&tree, &bytes, Endianness::Little);
+        assert_eq!(data.field("m_Layer").unwrap().as_i64(), Some(3));
+        assert_eq!(data.field("m_Weight").unwrap().as_f64(), Some(1.5));
+        assert_eq!(data.field("m_Short").unwrap().as_i64(), Some(0x7fff));
+        assert_eq!(data.field("m_Time").unwrap().as_f64(), Some(0.25));
+    }
+
+    #[test]
+    fn big_endian() {
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![
+                string("m_Name"),
+                node("UInt16", "m_Short", 2, 0x4000),
+                array("m_Values", 0x4000, node("UInt32", "data", 4, 0)),
+                node("float", "m_Value", 4, 0),
+            ],
+        );
+        let mut bytes = vec![0, 0, 0, 2, b'h', b'i', 0, 0, 0x12, 0x34, 0, 0];
+        bytes.extend_from_slice(&[0, 0, 0, 2, 0, 0, 1, 0, 0xde, 0xad, 0xbe, 0xef]);
+        bytes.extend_from_slice(&(-2.0f32).to_be_bytes());
+        let data = round_trip(&tree, &bytes, Endianness::Big);
+        assert_eq!(data.field("m_Name").unwrap().as_str(), Some("hi"));
+        assert_eq!(data.field("m_Short").unwrap().as_i64(), Some(0x1234));
+        assert_eq!(data.field("m_Value").unwrap().as_f64(), Some(-2.0));
+        match data.field("m_Values").unwrap() {
+            Data::GenericArray(v) => assert_eq!(v[1].as_i64(), Some(0xdeadbeef)),
+            v => panic!("unexpected {:?}", v),
+        }
+    }
+
+    #[test]
+    fn tree_round_trip() {
+        let tree = parent(
+            node("Base", "Base", -1, 0),
+            vec![string("m_Name"), node("MyCustomType", "m_Custom", 4, 0)],
+        );
+        for &format in &[9, 17, 22] {
+            let mut out = Vec::new();
+            tree.write_tree(Endianness::Little, format, &mut out);
+            let (rest, parsed) = TypeTree::parse(&out, Endianness::Little, format).unwrap();
+            assert!(rest.is_empty());
+            let mut again = Vec::new();
+            parsed.write_tree(Endianness::Little, format, &mut again);
+            assert_eq!(out, again);
+            assert_eq!(parsed.children()[1].type_name(), "MyCustomType");
+        }
+    }
+
+    #[test]
+    fn integer_accessors() {
+        assert_eq!(Data::UInt8(200).as_i64(), Some(200));
+        assert_eq!(Data::SInt16(-3).as_i64(), Some(-3));
+        assert_eq!(Data::UInt64(u64::MAX).as_i64(), None);
+        assert_eq!(Data::UInt64(u64::MAX).as_u64(), Some(u64::MAX));
+        assert_eq!(Data::SInt32(7).as_u64(), Some(7));
+        assert_eq!(Data::SInt32(-1).as_u64(), None);
+        assert_eq!(Data::SInt64(i64::MIN).as_u64(), None);
+        assert_eq!(Data::Bool(true).as_i64(), None);
+        assert_eq!(Data::Float(1.0).as_i64(), None);
+    }
+
+    #[test]
+    fn float_accessors() {
+        assert_eq!(Data::Float(0.5).as_f64(), Some(0.5));
+        assert_eq!(Data::Double(-2.25).as_f64(), Some(-2.25));
+        assert_eq!(Data::SInt8(-4).as_f64(), Some(-4.0));
+        assert_eq!(Data::UInt64(1 << 40).as_f64(), Some((1u64 << 40) as f64));
+        assert_eq!(Data::String(b"1.0"[..].into()).as_f64(), None);
+    }
+
+    #[test]
+    fn string_and_array_accessors() {
+        assert_eq!(Data::String(b"abc"[..].into()).as_str(), Some("abc"));
+        assert_eq!(Data::String(b"\xff"[..].into()).as_str(), None);
+        assert_eq!(Data::UInt8Array(b"abc"[..].into()).as_str(), None);
+        assert_eq!(
+            Data::UInt8Array(b"abc"[..].into()).as_bytes(),
+            Some(&b"abc"[..])
+        );
+
+        let array = Data::GenericArray(vec![Data::SInt32(1), Data::SInt32(2)]);
+        let values: Vec<_> = array.as_array().unwrap().iter().map(Data::as_i64).collect();
+        assert_eq!(values, [Some(1), Some(2)]);
+        assert!(Data::UInt8Array(b"ab"[..].into()).as_array().is_none());
+    }
+
+    #[test]
+    fn struct_accessors() {
+        let pptr = |type_name: &'static str, file_id: i64| Data::GenericStruct {
+            type_name: type_name.into(),
+            fields: vec![
+                ("m_FileID", Data::SInt64(file_id)),
+                ("m_PathID", Data::SInt64(-5)),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let data = pptr("PPtr<GameObject>", 1);
+        assert_eq!(data.field("m_PathID").unwrap().as_i64(), Some(-5));
+        assert!(data.field("m_Missing").is_none());
+        assert!(Data::SInt32(0).field("m_FileID").is_none());
+        assert_eq!(data.as_pptr(), Some((1, -5)));
+
+        // not a PPtr, or a file id out of range
+        assert_eq!(pptr("Object", 1).as_pptr(), None);
+        assert_eq!(pptr("PPtr<Object>", 1 << 40).as_pptr(), None);
+        let missing = Data::GenericStruct {
+            type_name: "PPtr<Object>".into(),
+            fields: vec![("m_FileID", Data::SInt32(0))].into_iter().collect(),
+        };
+        assert_eq!(missing.as_pptr(), None);
+    }
+}
@@ -0,0 +1,149 @@
+use super::Data;
+use serde::ser::{Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+/// Fields of a `Data::GenericStruct`, kept in type tree order.
+///
+/// Works like a `HashMap` keyed by field name, but iterates in insertion order like an
+/// `IndexMap`: the entries are kept in a list and found through a hash index.
+#[derive(Clone, Default)]
+pub struct Fields<'b> {
+    entries: Vec<(Cow<'b, str>, Data<'b>)>,
+    /// Position in `entries` of each name.
+    index: HashMap<Cow<'b, str>, usize>,
+}
+
+impl<'b> Fields<'b> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Data<'b>> {
+        let &idx = self.index.get(name)?;
+        Some(&self.entries[idx].1)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Data<'b>> {
+        let &idx = self.index.get(name)?;
+        Some(&mut self.entries[idx].1)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// Sets a field, keeping its position if it exists and appending it otherwise. Returns the
+    /// previous value.
+    pub fn insert(&mut self, name: Cow<'b, str>, value: Data<'b>) -> Option<Data<'b>> {
+        match self.get_mut(&name) {
+            Some(old) => Some(std::mem::replace(old, value)),
+            None => {
+                self.index.insert(name.clone(), self.entries.len());
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+
+    /// Removes a field, shifting the ones after it to keep their order.
+    pub fn remove(&mut self, name: &str) -> Option<Data<'b>> {
+        let idx = self.index.remove(name)?;
+        let (_, value) = self.entries.remove(idx);
+        for (key, _) in &self.entries[idx..] {
+            *self.index.get_mut(key.as_ref()).unwrap() -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Cow<'b, str>, &Data<'b>)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.entries.iter().map(|(key, _)| key.as_ref())
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &Data<'b>> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Data<'b>> {
+        self.entries.iter_mut().map(|(_, value)| value)
+    }
+}
+
+impl<'b> std::ops::Index<&str> for Fields<'b> {
+    type Output = Data<'b>;
+
+    fn index(&self, name: &str) -> &Data<'b> {
+        self.get(name)
+            .unwrap_or_else(|| panic!("field {} not found", name))
+    }
+}
+
+impl<'b, K: Into<Cow<'b, str>>> FromIterator<(K, Data<'b>)> for Fields<'b> {
+    fn from_iter<I: IntoIterator<Item = (K, Data<'b>)>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut fields = Fields::with_capacity(iter.size_hint().0);
+        for (name, value) in iter {
+            fields.insert(name.into(), value);
+        }
+        fields
+    }
+}
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(fields: &Fields<'_>, name: &str) -> Option<i64> {
+        fields.get(name).and_then(Data::as_i64)
+    }
+
+    #[test]
+    fn ordered_lookups() {
+        let mut fields = (0..5)
+            .map(|i| (format!("f{}", i), Data::SInt32(i)))
+            .collect::<Fields>();
+        let old = fields.insert("f1".into(), Data::SInt32(10));
+        assert_eq!(old.as_ref().and_then(Data::as_i64), Some(1));
+        assert!(fields.insert("f5".into(), Data::SInt32(5)).is_none());
+        assert_eq!(fields.remove("f2").as_ref().and_then(Data::as_i64), Some(2));
+        assert!(fields.remove("f2").is_none());
+        assert_eq!(
+            fields.keys().collect::<Vec<_>>(),
+            ["f0", "f1", "f3", "f4", "f5"]
+        );
+        // the index follows the entries shifted by the removal
+        for &(name, expected) in &[("f0", 0), ("f1", 10), ("f3", 3), ("f4", 4), ("f5", 5)] {
+            assert_eq!(value(&fields, name), Some(expected));
+        }
+        *fields.get_mut("f4").unwrap() = Data::SInt32(40);
+        assert_eq!(fields["f4"].as_i64(), Some(40));
+        assert!(!fields.contains_key("f2"));
+        assert_eq!(fields.len(), 5);
+    }
+}
@@ -0,0 +1,534 @@
+use super::{parse_blob, take_bytes, write_blob, Data, TypeTree};
+use crate::common_parser::read_string;
+use crate::util::write_u32;
+use crate::Asset;
+use nom::{
+    number::{complete as nom_number, Endianness},
+    IResult,
+};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+const BINARY_MAGIC: &[u8] = b"UFTL";
+const BINARY_VERSION: u32 = 1;
+/// Serialized file format whose type tree blobs are used in binary dumps.
+const BLOB_FORMAT: u32 = 19;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    /// Malformed JSON, or nodes missing a required field, with the message of the parser.
+    Json(String),
+    /// The nodes given for a key don't make a valid type tree.
+    InvalidTree(String),
+    InvalidBinary,
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::Json(message) => write!(f, "invalid JSON layouts: {}", message),
+            LayoutError::InvalidTree(key) => write!(f, "invalid type tree for {}", key),
+            LayoutError::InvalidBinary => write!(f, "invalid binary type layouts"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Type trees supplied from outside of serialized files, for builds which don't embed them.
+///
+/// Stripped builds only carry the layouts of built-in classes, so the fields of a
+/// `MonoBehaviour` can't be decoded without the layout of its script class. A layout is looked
+/// up by the 32-byte type hash of the script type first, then by the full name of the
+/// `MonoScript` like `Namespace.ClassName`, then by its bare class name. Objects left without a
+/// layout decode to [`Data::Raw`].
+///
+/// JSON dumps map keys to the nodes of a tree in depth-first order:
+///
+/// ```json
+/// {
+///   "Game.Inventory": [
+///     { "m_Type": "MonoBehaviour", "m_Name": "Base", "m_Level": 0, "m_ByteSize": -1, "m_MetaFlag": 0 },
+///     { "m_Type": "PPtr<GameObject>", "m_Name": "m_GameObject", "m_Level": 1, "m_ByteSize": 12, "m_MetaFlag": 0 },
+///     ...
+///   ]
+/// }
+/// ```
+///
+/// A key of 64 hex digits is a type hash, anything else a class name. Nodes may also use
+/// `type`, `name`, `level`, `byte_size` and `meta_flag`. `m_Version` defaults to 1, and
+/// `m_TypeFlags`, whose bit 0 marks arrays, defaults to the nodes named `Array`.
+#[derive(Clone, Debug, Default)]
+pub struct TypeLayouts {
+    by_name: HashMap<String, Arc<TypeTree<'static>>>,
+    by_hash: HashMap<[u8; 32], Arc<TypeTree<'static>>>,
+}
+
+impl TypeLayouts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len() + self.by_hash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty() && self.by_hash.is_empty()
+    }
+
+    pub fn insert_by_name(&mut self, name: impl Into<String>, tree: &TypeTree<'_>) {
+        self.by_name
+            .insert(name.into(), Arc::new(tree.to_owned_tree()));
+    }
+
+    pub fn insert_by_hash(&mut self, hash: [u8; 32], tree: &TypeTree<'_>) {
+        self.by_hash.insert(hash, Arc::new(tree.to_owned_tree()));
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&TypeTree<'static>> {
+        self.by_name.get(name).map(|tree| &**tree)
+    }
+
+    pub fn by_hash(&self, hash: &[u8]) -> Option<&TypeTree<'static>> {
+        self.hash_layout(hash).map(|tree| &**tree)
+    }
+
+    pub(crate) fn hash_layout(&self, hash: &[u8]) -> Option<&Arc<TypeTree<'static>>> {
+        let hash = <[u8; 32]>::try_from(hash).ok()?;
+        self.by_hash.get(&hash)
+    }
+
+    /// Layout for the class of a `MonoScript`.
+    pub(crate) fn script_layout(&self, script: &Data<'_>) -> Option<&Arc<TypeTree<'static>>> {
+        let (full_name, class_name) = script_names(script)?;
+        self.by_name
+            .get(full_name.as_ref())
+            .or_else(|| self.by_name.get(class_name))
+    }
+
+    /// Adds the layouts of a JSON dump, replacing existing ones with the same key.
+    pub fn load_json(&mut self, json: &str) -> Result<(), LayoutError> {
+        let entries: BTreeMap<String, Vec<JsonNode>> =
+            serde_json::from_str(json).map_err(|err| LayoutError::Json(err.to_string()))?;
+        for (key, nodes) in entries {
+            let tree = match tree_from_json(nodes) {
+                Some(v) => v,
+                None => return Err(LayoutError::InvalidTree(key)),
+            };
+            match parse_hex_hash(&key) {
+                Some(hash) => self.by_hash.insert(hash, Arc::new(tree)),
+                None => self.by_name.insert(key, Arc::new(tree)),
+            };
+        }
+        Ok(())
+    }
+
+    /// Adds the layouts of a dump written by [`TypeLayouts::to_binary`].
+    pub fn load_binary(&mut self, data: &[u8]) -> Result<(), LayoutError> {
+        let (_, entries) = parse_binary(data).map_err(|_| LayoutError::InvalidBinary)?;
+        for (key, tree) in entries {
+            match key {
+                BinaryKey::Name(name) => self.by_name.insert(name, Arc::new(tree)),
+                BinaryKey::Hash(hash) => self.by_hash.insert(hash, Arc::new(tree)),
+            };
+        }
+        Ok(())
+    }
+
+    /// Writes the layouts in a compact binary form, keeping names and hashes.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = BINARY_MAGIC.to_vec();
+        write_u32(&mut out, BINARY_VERSION, Endianness::Little);
+        write_u32(&mut out, self.len() as u32, Endianness::Little);
+
+        let mut names = self.by_name.iter().collect::<Vec<_>>();
+        names.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, tree) in names {
+            out.push(0);
+            out.extend_from_slice(name.as_bytes());
+            out.push(0);
+            write_blob(tree, Endianness::Little, BLOB_FORMAT, &mut out);
+        }
+        let mut hashes = self.by_hash.iter().collect::<Vec<_>>();
+        hashes.sort_by(|a, b| a.0.cmp(b.0));
+        for (hash, tree) in hashes {
+            out.push(1);
+            out.extend_from_slice(hash);
+            write_blob(tree, Endianness::Little, BLOB_FORMAT, &mut out);
+        }
+        out
+    }
+
+    /// Collects the script type trees embedded in `asset`, to decode stripped builds of the
+    /// same project later. Trees are keyed by type hash, and by class name when the
+    /// `MonoScript` is in `asset` too. Returns the number of new layouts.
+    pub fn add_from_asset(&mut self, asset: &Asset<'_>) -> usize {
+        let before = self.len();
+        for entry in asset.tree.entries.iter().filter(|entry| entry.class_id < 0) {
+            let hash = entry.hash.and_then(|hash| <[u8; 32]>::try_from(hash).ok());
+            if let (Some(hash), Some(tree)) = (hash, &entry.tree) {
+                self.by_hash.insert(hash, Arc::new(tree.to_owned_tree()));
+            }
+        }
+        for object in asset.objects().iter().filter(|object| object.type_id < 0) {
+            let tree = match asset.tree.embedded_type_tree(object.type_id) {
+                Some(v) => v,
+                None => continue,
+            };
+            let script = object
+                .data
+                .field("m_Script")
+                .and_then(Data::as_pptr)
+                .filter(|&(file_id, _)| file_id == 0)
+                .and_then(|(_, path_id)| asset.object(path_id as u64));
+            if let Some((full_name, _)) = script.and_then(|script| script_names(&script.data)) {
+                self.by_name
+                    .entry(full_name.into_owned())
+                    .or_insert_with(|| Arc::new(tree.to_owned_tree()));
+            }
+        }
+        self.len() - before
+    }
+}
+
+impl TypeTree<'_> {
+    fn to_owned_tree(&self) -> TypeTree<'static> {
+        TypeTree {
+            version: self.version,
+            is_array: self.is_array,
+            type_name: Cow::Owned(self.type_name.clone().into_owned()),
+            name: Cow::Owned(self.name.clone().into_owned()),
+            size: self.size,
+            index: self.index,
+            flags: self.flags,
+            ref_type_hash: self.ref_type_hash,
+            children: self.children.iter().map(Self::to_owned_tree).collect(),
+        }
+    }
+
+    /// Checks the shape [`TypeTree::read`] relies on, which embedded trees always have.
+    fn is_well_formed(&self) -> bool {
+        let expected_children = if self.type_name == "pair" || self.is_array {
+            Some(2)
+        } else if self.type_name == "string" {
+            Some(1)
+        } else {
+            None
+        };
+        match expected_children {
+            Some(count) if self.children.len() != count => false,
+            _ => self.children.iter().all(Self::is_well_formed),
+        }
+    }
+}
+
+impl TypeTree<'static> {
+    /// Reads an object with an external layout, which must account for every byte.
+    pub(crate) fn read_exact<'i>(
+        &self,
+        input: &'i [u8],
+        endianness: Endianness,
+    ) -> Option<Data<'i>> {
+        match self.read(input, endianness, 0) {
+            Ok(([], data)) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Checks that the layout accounts for every byte of `input`, without building `Data`.
+    pub(crate) fn fits(&self, input: &[u8], endianness: Endianness) -> bool {
+        match self.skip(input, endianness, 0) {
+            Ok((rest, ())) => rest.is_empty(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Class name of a `MonoScript` qualified with its namespace, and the bare class name.
+fn script_names<'d>(script: &'d Data<'_>) -> Option<(Cow<'d, str>, &'d str)> {
+    let class_name = script.field("m_ClassName")?.as_str()?;
+    let namespace = script
+        .field("m_Namespace")
+        .and_then(Data::as_str)
+        .unwrap_or("");
+    if namespace.is_empty() {
+        Some((Cow::Borrowed(class_name), class_name))
+    } else {
+        Some((
+            Cow::Owned(format!("{}.{}", namespace, class_name)),
+            class_name,
+        ))
+    }
+}
+
+fn parse_hex_hash(key: &str) -> Option<[u8; 32]> {
+    if key.len() != 64 {
+        return None;
+    }
+    let mut hash = [0; 32];
+    for (idx, b) in hash.iter_mut().enumerate() {
+        *b = u8::from_str_radix(key.get(idx * 2..idx * 2 + 2)?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// A node of a JSON dump, accepting both the field names of Unity's own dumps and snake case.
+#[derive(Deserialize)]
+struct JsonNode {
+    #[serde(rename = "m_Level", alias = "level")]
+    level: usize,
+    #[serde(rename = "m_Type", alias = "type")]
+    type_name: String,
+    #[serde(rename = "m_Name", alias = "name")]
+    name: String,
+    #[serde(rename = "m_ByteSize", alias = "byte_size", default = "unknown_size")]
+    byte_size: i64,
+    #[serde(rename = "m_MetaFlag", alias = "meta_flag", default)]
+    meta_flag: u32,
+    #[serde(rename = "m_Version", alias = "version", default = "first_version")]
+    version: u32,
+    #[serde(rename = "m_TypeFlags", alias = "type_flags")]
+    type_flags: Option<u32>,
+}
+
+fn unknown_size() -> i64 {
+    -1
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+fn tree_from_json(nodes: Vec<JsonNode>) -> Option<TypeTree<'static>> {
+    let mut tree_stack: Vec<TypeTree<'static>> = Vec::new();
+    for node in nodes {
+        let depth = node.level;
+        let is_array = match node.type_flags {
+            Some(flags) => flags & 1 != 0,
+            None => node.type_name == "Array",
+        };
+        if depth > tree_stack.len() {
+            return None;
+        }
+        while tree_stack.len() > depth {
+            let node = tree_stack.pop().unwrap();
+            tree_stack.last_mut()?.children.push(node);
+        }
+        tree_stack.push(TypeTree {
+            version: node.version,
+            is_array,
+            type_name: node.type_name.into(),
+            name: node.name.into(),
+            size: node.byte_size as u32,
+            index: 0,
+            flags: node.meta_flag,
+            ref_type_hash: 0,
+            children: Vec::new(),
+        });
+    }
+    let mut node = tree_stack.pop()?;
+    while let Some(mut parent) = tree_stack.pop() {
+        parent.children.push(node);
+        node = parent;
+    }
+    Some(node).filter(TypeTree::is_well_formed)
+}
+
+enum BinaryKey {
+    Name(String),
+    Hash([u8; 32]),
+}
+
+fn parse_binary(input: &[u8]) -> IResult<&[u8], Vec<(BinaryKey, TypeTree<'static>)>> {
+    let (input, _) = nom::bytes::complete::tag(BINARY_MAGIC)(input)?;
+    let (input, _) = nom::combinator::verify(nom_number::le_u32, |&v| v == BINARY_VERSION)(input)?;
+    let (mut input, count) = nom_number::le_u32(input)?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let (left, kind) = nom_number::le_u8(input)?;
+        let (left, key) = match kind {
+            0 => {
+                let (left, name) = read_string(left, None)?;
+                (left, BinaryKey::Name(name.into_owned()))
+            }
+            1 => {
+                let (left, hash) = take_bytes(left, 32)?;
+                (left, BinaryKey::Hash(<[u8; 32]>::try_from(hash).unwrap()))
+            }
+            _ => return Err(nom::Err::Failure((input, nom::error::ErrorKind::Switch))),
+        };
+        let (left, tree) = parse_blob(left, Endianness::Little, BLOB_FORMAT)?;
+        let tree = tree.to_owned_tree();
+        if !tree.is_well_formed() {
+            return Err(nom::Err::Failure((input, nom::error::ErrorKind::Verify)));
+        }
+        entries.push((key, tree));
+        input = left;
+    }
+    Ok((input, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::tests::serialized_file;
+    use crate::AssetBuilder;
+
+    /// Layout of the script type of the `MonoBehaviour` in `serialized_file`.
+    const BEHAVIOUR_JSON: &str = r#"{
+        "7272727272727272727272727272727272727272727272727272727272727272": [
+            { "m_Type": "MonoBehaviour", "m_Name": "Base", "m_Level": 0 },
+            { "m_Type": "UInt8", "m_Name": "m_Enabled", "m_Level": 1, "m_ByteSize": 1, "m_MetaFlag": 16384 },
+            { "type": "string", "name": "m_Name", "level": 1 },
+            { "type": "Array", "name": "Array", "level": 2, "meta_flag": 16384 },
+            { "type": "int", "name": "size", "level": 3, "byte_size": 4 },
+            { "type": "char", "name": "data", "level": 3, "byte_size": 1 },
+            { "m_Type": "int", "m_Name": "m_Count", "m_Level": 1, "m_ByteSize": 4 }
+        ]
+    }"#;
+
+    fn text_asset() -> Vec<u8> {
+        let mut out = Vec::new();
+        for s in &["notes", "hello", ""] {
+            out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+            out.resize(out.len().div_ceil(4) * 4, 0);
+        }
+        out
+    }
+
+    fn behaviour() -> Vec<u8> {
+        let mut out = vec![1, 0, 0, 0];
+        out.extend_from_slice(&6u32.to_le_bytes());
+        out.extend_from_slice(b"speedy\0\0");
+        out.extend_from_slice(&7i32.to_le_bytes());
+        out
+    }
+
+    fn parse<'f>(file: &'f [u8], layouts: &TypeLayouts, decode: bool) -> Asset<'f> {
+        Asset::parse("test".into(), file, 0, layouts, decode)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn json_layouts() {
+        let mut layouts = TypeLayouts::new();
+        layouts.load_json(BEHAVIOUR_JSON).unwrap();
+        assert_eq!(layouts.len(), 1);
+        assert!(layouts.by_hash(&[0x72; 32]).is_some());
+
+        let file = serialized_file(&text_asset(), &behaviour());
+        let asset = parse(&file, &layouts, true);
+        let data = &asset.object(2).unwrap().data;
+        assert_eq!(data.type_name(), "MonoBehaviour");
+        assert_eq!(data.field("m_Name").and_then(Data::as_str), Some("speedy"));
+        assert_eq!(data.field("m_Count").and_then(Data::as_i64), Some(7));
+
+        // the layout is attached without decoding, and used to write the object back
+        let undecoded = parse(&file, &layouts, false);
+        let object = undecoded.object(2).unwrap();
+        assert!(matches!(object.data, Data::Raw { .. }));
+        assert_eq!(
+            format!("{:?}", undecoded.decode_object(object).unwrap()),
+            format!("{:?}", data)
+        );
+        assert_eq!(AssetBuilder::from_asset(&asset).build().unwrap(), file);
+
+        // embedded script trees are collected by type hash
+        let mut embedded = parse(&file, &TypeLayouts::new(), true);
+        for entry in embedded.tree.entries.iter_mut() {
+            if entry.class_id < 0 {
+                entry.tree = layouts.by_hash(&[0x72; 32]).cloned();
+            }
+        }
+        let mut collected = TypeLayouts::new();
+        assert_eq!(collected.add_from_asset(&embedded), 1);
+        assert_eq!(collected.add_from_asset(&embedded), 0);
+        assert_eq!(collected.to_binary(), layouts.to_binary());
+    }
+
+    #[test]
+    fn invalid_json() {
+        let mut layouts = TypeLayouts::new();
+        assert!(matches!(layouts.load_json("[]"), Err(LayoutError::Json(_))));
+        assert!(matches!(
+            layouts.load_json(r#"{ "A": [{ "m_Type": "int", "m_Level": 0 }] }"#),
+            Err(LayoutError::Json(_))
+        ));
+        // a string must have one child
+        let tree = r#"{ "A": [{ "type": "string", "name": "Base", "level": 0 }] }"#;
+        assert_eq!(
+            layouts.load_json(tree),
+            Err(LayoutError::InvalidTree("A".into()))
+        );
+        // levels can't skip a generation
+        let tree = r#"{ "A": [
+            { "type": "Base", "name": "Base", "level": 0 },
+            { "type": "int", "name": "x", "level": 2 }
+        ] }"#;
+        assert_eq!(
+            layouts.load_json(tree),
+            Err(LayoutError::InvalidTree("A".into()))
+        );
+        assert!(layouts.is_empty());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut layouts = TypeLayouts::new();
+        layouts.load_json(BEHAVIOUR_JSON).unwrap();
+        let tree = layouts.by_hash(&[0x72; 32]).unwrap().clone();
+        layouts.insert_by_name("Game.Inventory", &tree);
+
+        let binary = layouts.to_binary();
+        let mut loaded = TypeLayouts::new();
+        loaded.load_binary(&binary).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.by_name("Game.Inventory").is_some());
+        assert_eq!(loaded.to_binary(), binary);
+
+        let file = serialized_file(&text_asset(), &behaviour());
+        let asset = parse(&file, &loaded, true);
+        let data = &asset.object(2).unwrap().data;
+        assert_eq!(data.field("m_Count").and_then(Data::as_i64), Some(7));
+
+        assert_eq!(
+            loaded.load_binary(&binary[..binary.len() - 1]),
+            Err(LayoutError::InvalidBinary)
+        );
+        assert_eq!(loaded.load_binary(b"UFTL"), Err(LayoutError::InvalidBinary));
+    }
+
+    #[test]
+    fn unknown_layouts() {
+        let file = serialized_file(&text_asset(), &behaviour());
+        let raw = |asset: &Asset<'_>| match &asset.object(2).unwrap().data {
+            Data::Raw { type_name, data } => {
+                assert_eq!(type_name, "MonoBehaviour");
+                assert_eq!(&data[..], &behaviour()[..]);
+            }
+            data => panic!("decoded {:?}", data),
+        };
+        raw(&parse(&file, &TypeLayouts::new(), true));
+
+        // layouts for other types or which don't fit the data are ignored
+        let mut layouts = TypeLayouts::new();
+        layouts
+            .load_json(&BEHAVIOUR_JSON.replace("72727272", "00000000"))
+            .unwrap();
+        raw(&parse(&file, &layouts, true));
+        let mut short = behaviour();
+        short.truncate(12);
+        let mut layouts = TypeLayouts::new();
+        layouts.load_json(BEHAVIOUR_JSON).unwrap();
+        let file = serialized_file(&text_asset(), &short);
+        let asset = parse(&file, &layouts, true);
+        assert!(matches!(asset.object(2).unwrap().data, Data::Raw { .. }));
+        assert!(asset.object(1).unwrap().data.field("m_Name").is_some());
+    }
+}
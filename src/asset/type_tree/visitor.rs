@@ -0,0 +1,340 @@
+use super::{take_bytes, Data, TypeTree};
+use crate::util::align;
+use nom::{number::Endianness, u32, IResult};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VisitFlow {
+    Continue,
+    /// Skips the current struct, field or array elements without reporting them.
+    Skip,
+    /// Stops visiting altogether.
+    Stop,
+}
+
+/// Receives events while [`TypeTree::visit`] walks object data.
+///
+/// Values are borrowed from the input, so nothing is allocated unless the visitor does so.
+/// Strings, byte arrays and unknown primitives are reported through `visit_primitive` as
+/// `Data::String`, `Data::UInt8Array` and `Data::GenericPrimitive`. Pairs are reported as
+/// structs with `first` and `second` fields.
+pub trait Visitor<'a> {
+    fn visit_struct_start(&mut self, _tree: &TypeTree<'a>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn visit_struct_end(&mut self, _tree: &TypeTree<'a>) {}
+
+    fn visit_field(&mut self, _tree: &TypeTree<'a>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn visit_array_len(&mut self, _tree: &TypeTree<'a>, _len: usize) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn visit_array_end(&mut self, _tree: &TypeTree<'a>) {}
+
+    fn visit_primitive(&mut self, _tree: &TypeTree<'a>, _value: &Data<'a>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+}
+
+struct SkipVisitor;
+
+impl Visitor<'_> for SkipVisitor {}
+
+fn distance(base: &[u8], input: &[u8]) -> u64 {
+    (input.as_ptr() as usize - base.as_ptr() as usize) as u64
+}
+
+impl<'a> TypeTree<'a> {
+    pub(super) fn skip(
+        &self,
+        input: &'a [u8],
+        endianness: Endianness,
+        offset: u64,
+    ) -> IResult<&'a [u8], ()> {
+        let (input, _) = self.visit(input, endianness, offset, &mut SkipVisitor)?;
+        Ok((input, ()))
+    }
+
+    /// Walks `input` like [`TypeTree::read`], reporting values to `visitor` instead of
+    /// building `Data`.
+    ///
+    /// Returns `VisitFlow::Stop` if the visitor stopped early, in which case the remaining
+    /// input is where it stopped.
+    pub fn visit<V: Visitor<'a>>(
+        &self,
+        input: &'a [u8],
+        endianness: Endianness,
+        offset: u64,
+        visitor: &mut V,
+    ) -> IResult<&'a [u8], VisitFlow> {
+        let base = input;
+        let mut needs_align = self.needs_align();
+        let (input, flow) = if self.type_name == "string" {
+            debug_assert_eq!(self.children.len(), 1);
+            needs_align |= self.children[0].needs_align();
+            let (input, length) = u32!(input, endianness)?;
+            let (input, bytes) = take_bytes(input, length as usize)?;
+            let flow = visitor.visit_primitive(self, &Data::String(bytes.into()));
+            (input, flow)
+        } else if let Some(child) = self.children.first().filter(|child| child.is_array) {
+            child.visit(input, endianness, offset, visitor)?
+        } else if self.is_array {
+            debug_assert_eq!(self.children.len(), 2);
+            let element_type = &self.children[1];
+            let (mut input, length) = u32!(input, endianness)?;
+            if element_type.type_name == "UInt8" {
+                let (input, bytes) = take_bytes(input, length as usize)?;
+                let flow = visitor.visit_primitive(self, &Data::UInt8Array(bytes.into()));
+                (input, flow)
+            } else if length as usize > input.len() {
+                // every element takes at least a byte, don't loop over a bogus length
+                return Err(nom::Err::Error((input, nom::error::ErrorKind::Count)));
+            } else {
+                let flow = visitor.visit_array_len(self, length as usize);
+                if flow == VisitFlow::Stop {
+                    return Ok((input, flow));
+                }
+                for _ in 0..length {
+                    let offset = offset + distance(base, input);
+                    if flow == VisitFlow::Skip {
+                        input = element_type.skip(input, endianness, offset)?.0;
+                        continue;
+                    }
+                    let (left, flow) = element_type.visit(input, endianness, offset, visitor)?;
+                    if flow == VisitFlow::Stop {
+                        return Ok((left, flow));
+                    }
+                    input = left;
+                }
+                if flow == VisitFlow::Continue {
+                    visitor.visit_array_end(self);
+                }
+                (input, VisitFlow::Continue)
+            }
+        } else if self.children.is_empty() {
+            let (input, data) = self.read_primitive(base, input, endianness, offset)?;
+            (input, visitor.visit_primitive(self, &data))
+        } else {
+            match visitor.visit_struct_start(self) {
+                VisitFlow::Continue => {}
+                VisitFlow::Skip => {
+                    let (input, _) = self.skip(base, endianness, offset)?;
+                    return Ok((input, VisitFlow::Continue));
+                }
+                VisitFlow::Stop => return Ok((input, VisitFlow::Stop)),
+            }
+            let mut input = input;
+            for field_type in &self.children {
+                let offset = offset + distance(base, input);
+                let (left, flow) = match visitor.visit_field(field_type) {
+                    VisitFlow::Continue => field_type.visit(input, endianness, offset, visitor)?,
+                    VisitFlow::Skip => (
+                        field_type.skip(input, endianness, offset)?.0,
+                        VisitFlow::Continue,
+                    ),
+                    VisitFlow::Stop => return Ok((input, VisitFlow::Stop)),
+                };
+                if flow == VisitFlow::Stop {
+                    return Ok((left, flow));
+                }
+                input = left;
+            }
+            visitor.visit_struct_end(self);
+            (input, VisitFlow::Continue)
+        };
+        if flow == VisitFlow::Stop {
+            return Ok((input, flow));
+        }
+        let input = if needs_align {
+            align(offset as usize, base, input)
+        } else {
+            input
+        };
+        Ok((input, VisitFlow::Continue))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TypeLayouts;
+
+    const LAYOUT: &str = r#"{ "Thing": [
+        { "type": "Thing", "name": "Base", "level": 0 },
+        { "type": "int", "name": "a", "level": 1, "byte_size": 4 },
+        { "type": "UInt8", "name": "flag", "level": 1, "byte_size": 1, "meta_flag": 16384 },
+        { "type": "string", "name": "name", "level": 1 },
+        { "type": "Array", "name": "Array", "level": 2, "meta_flag": 16384 },
+        { "type": "int", "name": "size", "level": 3, "byte_size": 4 },
+        { "type": "char", "name": "data", "level": 3, "byte_size": 1 },
+        { "type": "vector", "name": "values", "level": 1 },
+        { "type": "Array", "name": "Array", "level": 2 },
+        { "type": "int", "name": "size", "level": 3, "byte_size": 4 },
+        { "type": "int", "name": "data", "level": 3, "byte_size": 4 },
+        { "type": "Inner", "name": "inner", "level": 1 },
+        { "type": "SInt16", "name": "x", "level": 2, "byte_size": 2, "meta_flag": 16384 },
+        { "type": "vector", "name": "bytes", "level": 2, "meta_flag": 16384 },
+        { "type": "Array", "name": "Array", "level": 3 },
+        { "type": "int", "name": "size", "level": 4, "byte_size": 4 },
+        { "type": "UInt8", "name": "data", "level": 4, "byte_size": 1 },
+        { "type": "int", "name": "tail", "level": 1, "byte_size": 4 }
+    ] }"#;
+
+    fn layouts() -> TypeLayouts {
+        let mut layouts = TypeLayouts::new();
+        layouts.load_json(LAYOUT).unwrap();
+        layouts
+    }
+
+    fn data() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&1i32.to_le_bytes());
+        out.extend_from_slice(&[1, 0, 0, 0]);
+        out.extend_from_slice(&5u32.to_le_bytes());
+        out.extend_from_slice(b"abcde\0\0\0");
+        for &v in &[2, 10, 20] {
+            out.extend_from_slice(&(v as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&7i16.to_le_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(&3u32.to_le_bytes());
+        out.extend_from_slice(&[1, 2, 3, 0]);
+        out.extend_from_slice(&99i32.to_le_bytes());
+        out
+    }
+
+    /// Collects primitives, answering with `flow` for the nodes named in `names`.
+    struct Collect {
+        names: &'static [&'static str],
+        flow: VisitFlow,
+        stop_at: Option<i64>,
+        values: Vec<String>,
+    }
+
+    impl Collect {
+        fn new(names: &'static [&'static str], flow: VisitFlow) -> Self {
+            Self {
+                names,
+                flow,
+                stop_at: None,
+                values: Vec::new(),
+            }
+        }
+
+        fn flow(&self, tree: &TypeTree<'_>) -> VisitFlow {
+            if self.names.contains(&tree.name.as_ref()) {
+                self.flow
+            } else {
+                VisitFlow::Continue
+            }
+        }
+    }
+
+    impl<'a> Visitor<'a> for Collect {
+        fn visit_struct_start(&mut self, tree: &TypeTree<'a>) -> VisitFlow {
+            self.flow(tree)
+        }
+
+        fn visit_field(&mut self, tree: &TypeTree<'a>) -> VisitFlow {
+            self.flow(tree)
+        }
+
+        fn visit_array_len(&mut self, tree: &TypeTree<'a>, _len: usize) -> VisitFlow {
+            self.flow(tree)
+        }
+
+        fn visit_primitive(&mut self, _tree: &TypeTree<'a>, value: &Data<'a>) -> VisitFlow {
+            let text = match (value.as_i64(), value.as_str()) {
+                (Some(v), _) => v.to_string(),
+                (_, Some(v)) => v.to_owned(),
+                _ => format!("{:?}", value),
+            };
+            self.values.push(text);
+            if value.as_i64().is_some() && value.as_i64() == self.stop_at {
+                VisitFlow::Stop
+            } else {
+                VisitFlow::Continue
+            }
+        }
+    }
+
+    fn visit(visitor: &mut Collect) -> (usize, VisitFlow) {
+        let layouts = layouts();
+        let tree = layouts.by_name("Thing").unwrap();
+        let data = data();
+        let (rest, flow) = tree.visit(&data, Endianness::Little, 0, visitor).unwrap();
+        (data.len() - rest.len(), flow)
+    }
+
+    #[test]
+    fn skip_matches_read() {
+        let layouts = layouts();
+        let tree = layouts.by_name("Thing").unwrap();
+        let data = data();
+        let rest = |result: IResult<&[u8], ()>| result.map(|(rest, _)| rest.len()).ok();
+        for offset in 0..4 {
+            let read = tree.read(&data, Endianness::Little, offset);
+            let read = rest(read.map(|(rest, _)| (rest, ())));
+            assert_eq!(rest(tree.skip(&data, Endianness::Little, offset)), read);
+        }
+        // field by field, as the fields of a struct are skipped
+        let mut read_input = &data[..];
+        let mut skip_input = &data[..];
+        for field in &tree.children {
+            let offset = (data.len() - read_input.len()) as u64;
+            read_input = field
+                .read(read_input, Endianness::Little, offset)
+                .unwrap()
+                .0;
+            skip_input = field
+                .skip(skip_input, Endianness::Little, offset)
+                .unwrap()
+                .0;
+            assert_eq!(skip_input.len(), read_input.len(), "{}", field.name);
+        }
+        assert!(skip_input.is_empty());
+    }
+
+    #[test]
+    fn visit_all() {
+        let mut visitor = Collect::new(&[], VisitFlow::Continue);
+        assert_eq!(visit(&mut visitor), (data().len(), VisitFlow::Continue));
+        assert_eq!(
+            visitor.values,
+            [
+                "1",
+                "1",
+                "abcde",
+                "10",
+                "20",
+                "7",
+                "Uint8Array(3 bytes)",
+                "99"
+            ]
+        );
+    }
+
+    #[test]
+    fn skip() {
+        // a string field, the elements of an array and a whole struct
+        let mut visitor = Collect::new(&["name", "values", "inner"], VisitFlow::Skip);
+        assert_eq!(visit(&mut visitor), (data().len(), VisitFlow::Continue));
+        assert_eq!(visitor.values, ["1", "1", "99"]);
+    }
+
+    #[test]
+    fn stop() {
+        let mut visitor = Collect::new(&[], VisitFlow::Continue);
+        visitor.stop_at = Some(10);
+        // stopped right after the first element of `values`
+        assert_eq!(visit(&mut visitor), (28, VisitFlow::Stop));
+        assert_eq!(visitor.values, ["1", "1", "abcde", "10"]);
+
+        let mut visitor = Collect::new(&["inner"], VisitFlow::Stop);
+        assert_eq!(visit(&mut visitor), (32, VisitFlow::Stop));
+        assert_eq!(visitor.values, ["1", "1", "abcde", "10", "20"]);
+    }
+}
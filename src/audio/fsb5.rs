@@ -0,0 +1,417 @@
+use super::{ima, ogg, wav, AudioError};
+
+const FREQUENCIES: [u32; 11] = [
+    4000, 8000, 11000, 11025, 16000, 22050, 24000, 32000, 44100, 48000, 96000,
+];
+
+const CHUNK_CHANNELS: u32 = 1;
+const CHUNK_FREQUENCY: u32 = 2;
+const CHUNK_LOOP: u32 = 3;
+const CHUNK_VORBIS_DATA: u32 = 11;
+
+/// Codec of the samples in an FSB5 bank, `FMOD_SOUND_FORMAT`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+    None,
+    Pcm8,
+    Pcm16,
+    Pcm24,
+    Pcm32,
+    PcmFloat,
+    GcAdpcm,
+    ImaAdpcm,
+    Vag,
+    HeVag,
+    Xma,
+    Mpeg,
+    Celt,
+    Atrac9,
+    Xwma,
+    Vorbis,
+    FAdpcm,
+    Opus,
+    Unknown(u32),
+}
+
+impl Codec {
+    pub fn from_id(id: u32) -> Self {
+        match id {
+            0 => Codec::None,
+            1 => Codec::Pcm8,
+            2 => Codec::Pcm16,
+            3 => Codec::Pcm24,
+            4 => Codec::Pcm32,
+            5 => Codec::PcmFloat,
+            6 => Codec::GcAdpcm,
+            7 => Codec::ImaAdpcm,
+            8 => Codec::Vag,
+            9 => Codec::HeVag,
+            10 => Codec::Xma,
+            11 => Codec::Mpeg,
+            12 => Codec::Celt,
+            13 => Codec::Atrac9,
+            14 => Codec::Xwma,
+            15 => Codec::Vorbis,
+            16 => Codec::FAdpcm,
+            17 => Codec::Opus,
+            id => Codec::Unknown(id),
+        }
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, AudioError> {
+    match data.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(AudioError::NotEnoughData {
+            expected: offset + 4,
+            found: data.len(),
+        }),
+    }
+}
+
+/// Extracts `len` bits of `v` starting from bit `start`.
+fn bits(v: u64, start: u32, len: u32) -> u64 {
+    (v >> start) & ((1 << len) - 1)
+}
+
+/// Sample of an FSB5 bank.
+#[derive(Clone, Debug)]
+pub struct Sample<'a> {
+    pub name: Option<String>,
+    pub codec: Codec,
+    pub frequency: u32,
+    pub channels: u32,
+    /// Number of samples per channel.
+    pub sample_count: u32,
+    /// Loop start and end in samples.
+    pub loop_range: Option<(u32, u32)>,
+    /// CRC32 of the Vorbis setup header, which is stripped from the bank.
+    pub vorbis_crc32: Option<u32>,
+    data: &'a [u8],
+}
+
+impl<'a> Sample<'a> {
+    /// Encoded data of the sample.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Extension of the file written by [`Sample::to_wav`] or [`Sample::to_ogg`].
+    pub fn extension(&self) -> Option<&'static str> {
+        match self.codec {
+            Codec::Pcm8 | Codec::Pcm16 | Codec::Pcm24 | Codec::Pcm32 | Codec::PcmFloat => {
+                Some("wav")
+            }
+            Codec::ImaAdpcm => Some("wav"),
+            Codec::Vorbis => Some("ogg"),
+            _ => None,
+        }
+    }
+
+    /// Converts a PCM or IMA ADPCM sample to a WAV file, decoding ADPCM to 16-bit PCM.
+    pub fn to_wav(&self) -> Result<Vec<u8>, AudioError> {
+        let frames = self.sample_count as usize;
+        let channels = self.channels as usize;
+        let pcm = |bits: u16| -> Result<Vec<u8>, AudioError> {
+            let len = frames * channels * bits as usize / 8;
+            let data = self.data.get(..len).ok_or(AudioError::NotEnoughData {
+                expected: len,
+                found: self.data.len(),
+            })?;
+            Ok(wav::write(
+                wav::FORMAT_PCM,
+                self.channels,
+                self.frequency,
+                bits,
+                data,
+            ))
+        };
+        match self.codec {
+            Codec::Pcm8 => {
+                let mut wav = pcm(8)?;
+                // FMOD stores signed samples, 8-bit WAV is unsigned
+                for b in &mut wav[wav::HEADER_SIZE..] {
+                    *b ^= 0x80;
+                }
+                Ok(wav)
+            }
+            Codec::Pcm16 => pcm(16),
+            Codec::Pcm24 => pcm(24),
+            Codec::Pcm32 => pcm(32),
+            Codec::PcmFloat => {
+                let len = frames * channels * 4;
+                let data = self.data.get(..len).ok_or(AudioError::NotEnoughData {
+                    expected: len,
+                    found: self.data.len(),
+                })?;
+                Ok(wav::write(
+                    wav::FORMAT_IEEE_FLOAT,
+                    self.channels,
+                    self.frequency,
+                    32,
+                    data,
+                ))
+            }
+            Codec::ImaAdpcm => {
+                let samples = ima::decode(self.data, self.channels, frames)?;
+                let mut data = Vec::with_capacity(samples.len() * 2);
+                for sample in samples {
+                    data.extend_from_slice(&sample.to_le_bytes());
+                }
+                Ok(wav::write(
+                    wav::FORMAT_PCM,
+                    self.channels,
+                    self.frequency,
+                    16,
+                    &data,
+                ))
+            }
+            codec => Err(AudioError::UnsupportedCodec(codec)),
+        }
+    }
+
+    /// Repacks a Vorbis sample into an Ogg file.
+    ///
+    /// FSB5 banks don't store the Vorbis setup header, which has to be looked up by
+    /// [`Sample::vorbis_crc32`] in the headers shipped with FMOD and given as `setup_header`.
+    pub fn to_ogg(&self, setup_header: &[u8]) -> Result<Vec<u8>, AudioError> {
+        if self.codec != Codec::Vorbis {
+            return Err(AudioError::UnsupportedCodec(self.codec));
+        }
+        ogg::write(
+            self.data,
+            self.channels,
+            self.frequency,
+            self.sample_count,
+            setup_header,
+        )
+    }
+}
+
+/// FSB5 sound bank, as stored in the resources of `AudioClip`s.
+#[derive(Clone, Debug)]
+pub struct Fsb5<'a> {
+    pub version: u32,
+    pub codec: Codec,
+    samples: Vec<Sample<'a>>,
+}
+
+impl<'a> Fsb5<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, AudioError> {
+        if data.len() < 4 {
+            return Err(AudioError::NotEnoughData {
+                expected: 4,
+                found: data.len(),
+            });
+        }
+        if &data[..4] != b"FSB5" {
+            return Err(AudioError::InvalidSignature);
+        }
+        let version = u32_at(data, 4)?;
+        let sample_count = u32_at(data, 8)? as usize;
+        let sample_headers_size = u32_at(data, 12)? as usize;
+        let name_table_size = u32_at(data, 16)? as usize;
+        let data_size = u32_at(data, 20)? as usize;
+        let codec = Codec::from_id(u32_at(data, 24)?);
+        // followed by flags, a hash and padding, version 0 has an extra field
+        let header_size = if version == 0 { 0x40 } else { 0x3c };
+        let name_table_start = header_size + sample_headers_size;
+        let data_start = name_table_start + name_table_size;
+        let sample_data =
+            data.get(data_start..data_start + data_size)
+                .ok_or(AudioError::NotEnoughData {
+                    expected: data_start + data_size,
+                    found: data.len(),
+                })?;
+
+        let mut offset = header_size;
+        let mut headers = Vec::with_capacity(sample_count);
+        for _ in 0..sample_count {
+            let raw = u64::from(u32_at(data, offset)?) | u64::from(u32_at(data, offset + 4)?) << 32;
+            offset += 8;
+            let mut sample = Sample {
+                name: None,
+                codec,
+                frequency: FREQUENCIES
+                    .get(bits(raw, 1, 4) as usize)
+                    .copied()
+                    .unwrap_or(44100),
+                channels: bits(raw, 5, 1) as u32 + 1,
+                sample_count: bits(raw, 34, 30) as u32,
+                loop_range: None,
+                vorbis_crc32: None,
+                data: &[],
+            };
+            let data_offset = bits(raw, 6, 28) as usize * 16;
+
+            let mut has_next_chunk = bits(raw, 0, 1) != 0;
+            while has_next_chunk {
+                let chunk = u64::from(u32_at(data, offset)?);
+                has_next_chunk = bits(chunk, 0, 1) != 0;
+                let chunk_size = bits(chunk, 1, 24) as usize;
+                let chunk_type = bits(chunk, 25, 7) as u32;
+                let chunk_start = offset + 4;
+                offset = chunk_start + chunk_size;
+                let chunk_data =
+                    data.get(chunk_start..offset)
+                        .ok_or(AudioError::NotEnoughData {
+                            expected: offset,
+                            found: data.len(),
+                        })?;
+                match chunk_type {
+                    CHUNK_CHANNELS if !chunk_data.is_empty() => {
+                        sample.channels = chunk_data[0].into()
+                    }
+                    CHUNK_FREQUENCY => sample.frequency = u32_at(chunk_data, 0)?,
+                    CHUNK_LOOP => {
+                        sample.loop_range = Some((u32_at(chunk_data, 0)?, u32_at(chunk_data, 4)?))
+                    }
+                    CHUNK_VORBIS_DATA => sample.vorbis_crc32 = Some(u32_at(chunk_data, 0)?),
+                    _ => {}
+                }
+            }
+            headers.push((sample, data_offset));
+        }
+
+        let ends = headers
+            .iter()
+            .skip(1)
+            .map(|(_, offset)| *offset)
+            .chain(std::iter::once(data_size))
+            .collect::<Vec<_>>();
+        let mut samples = Vec::with_capacity(sample_count);
+        for (idx, ((mut sample, start), end)) in headers.into_iter().zip(ends).enumerate() {
+            sample.data = sample_data.get(start..end).unwrap_or(&[]);
+            if name_table_size != 0 {
+                let name_offset = u32_at(data, name_table_start + idx * 4)? as usize;
+                let name = data
+                    .get(name_table_start + name_offset..data_start)
+                    .unwrap_or(&[]);
+                let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                sample.name = Some(String::from_utf8_lossy(&name[..len]).into_owned());
+            }
+            samples.push(sample);
+        }
+        Ok(Self {
+            version,
+            codec,
+            samples,
+        })
+    }
+
+    pub fn samples(&self) -> &[Sample<'a>] {
+        &self.samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn le32(out: &mut Vec<u8>, v: u32) {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn chunk(out: &mut Vec<u8>, chunk_type: u32, data: &[u8], has_next_chunk: bool) {
+        le32(
+            out,
+            chunk_type << 25 | (data.len() as u32) << 1 | has_next_chunk as u32,
+        );
+        out.extend_from_slice(data);
+    }
+
+    /// Sample header with a frequency index, a channel count of 1 or 2, the data offset and the
+    /// number of samples.
+    fn sample_header(
+        out: &mut Vec<u8>,
+        frequency: u64,
+        channels: u64,
+        offset: u64,
+        samples: u64,
+        has_next_chunk: bool,
+    ) {
+        let raw = samples << 34
+            | (offset / 16) << 6
+            | (channels - 1) << 5
+            | frequency << 1
+            | has_next_chunk as u64;
+        out.extend_from_slice(&raw.to_le_bytes());
+    }
+
+    /// Version 1 bank of 16-bit PCM with a stereo sample `first` overriding its frequency and
+    /// looping, and a mono sample `second`.
+    fn bank() -> Vec<u8> {
+        let mut headers = Vec::new();
+        sample_header(&mut headers, 8, 1, 0, 4, true);
+        chunk(&mut headers, CHUNK_CHANNELS, &[2], true);
+        chunk(&mut headers, CHUNK_FREQUENCY, &22050u32.to_le_bytes(), true);
+        chunk(&mut headers, CHUNK_LOOP, &[1, 0, 0, 0, 3, 0, 0, 0], false);
+        sample_header(&mut headers, 9, 1, 16, 3, false);
+        let mut names = Vec::new();
+        le32(&mut names, 8);
+        le32(&mut names, 14);
+        names.extend_from_slice(b"first\0second\0");
+        let data = (0..22).collect::<Vec<u8>>();
+
+        let mut out = b"FSB5".to_vec();
+        for &v in &[1, 2, headers.len(), names.len(), data.len(), 2] {
+            le32(&mut out, v as u32);
+        }
+        out.resize(0x3c, 0);
+        out.extend_from_slice(&headers);
+        out.extend_from_slice(&names);
+        out.extend_from_slice(&data);
+        out
+    }
+
+    #[test]
+    fn parse() {
+        let bank = bank();
+        let fsb = Fsb5::parse(&bank).unwrap();
+        assert_eq!(fsb.version, 1);
+        assert_eq!(fsb.codec, Codec::Pcm16);
+        let samples = fsb.samples();
+        assert_eq!(samples.len(), 2);
+
+        let first = &samples[0];
+        assert_eq!(first.name.as_deref(), Some("first"));
+        assert_eq!((first.channels, first.frequency), (2, 22050));
+        assert_eq!(first.sample_count, 4);
+        assert_eq!(first.loop_range, Some((1, 3)));
+        assert_eq!(first.data(), &(0..16).collect::<Vec<_>>()[..]);
+
+        let second = &samples[1];
+        assert_eq!(second.name.as_deref(), Some("second"));
+        assert_eq!((second.channels, second.frequency), (1, 48000));
+        assert_eq!(second.loop_range, None);
+        assert_eq!(second.data(), &(16..22).collect::<Vec<_>>()[..]);
+
+        assert_eq!(second.extension(), Some("wav"));
+        let wav = second.to_wav().unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[wav::HEADER_SIZE..], second.data());
+    }
+
+    #[test]
+    fn invalid_banks() {
+        let bank = bank();
+        assert_eq!(
+            Fsb5::parse(b"FSB4").err(),
+            Some(AudioError::InvalidSignature)
+        );
+        assert_eq!(
+            Fsb5::parse(&bank[..bank.len() - 1]).err(),
+            Some(AudioError::NotEnoughData {
+                expected: bank.len(),
+                found: bank.len() - 1
+            })
+        );
+        // a sample count past the headers
+        let mut bank = bank;
+        bank[8] = 9;
+        assert!(matches!(
+            Fsb5::parse(&bank),
+            Err(AudioError::NotEnoughData { .. })
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use super::AudioError;
+
+const INDEX_TABLE: [i32; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// Bytes of a block per channel, a 4-byte header followed by 64 samples.
+const BLOCK_SIZE: usize = 36;
+const SAMPLES_PER_BLOCK: usize = 65;
+
+struct Channel {
+    predictor: i32,
+    index: i32,
+}
+
+impl Channel {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = STEP_TABLE[self.index as usize];
+        let mut diff = step >> 3;
+        if nibble & 1 != 0 {
+            diff += step >> 2;
+        }
+        if nibble & 2 != 0 {
+            diff += step >> 1;
+        }
+        if nibble & 4 != 0 {
+            diff += step;
+        }
+        if nibble & 8 != 0 {
+            diff = -diff;
+        }
+        self.predictor = (self.predictor + diff).clamp(-32768, 32767);
+        self.index = (self.index + INDEX_TABLE[nibble as usize]).clamp(0, 88);
+        self.predictor as i16
+    }
+}
+
+/// Decodes Xbox-style IMA ADPCM to `frames` interleaved 16-bit samples per channel.
+///
+/// Blocks start with the initial sample and step index of every channel, then the samples of the
+/// channels are interleaved every four bytes.
+pub(super) fn decode(data: &[u8], channels: u32, frames: usize) -> Result<Vec<i16>, AudioError> {
+    if channels == 0 || channels > 2 {
+        return Err(AudioError::UnsupportedChannels(channels));
+    }
+    let channels = channels as usize;
+    let block_size = BLOCK_SIZE * channels;
+    let blocks = frames.div_ceil(SAMPLES_PER_BLOCK);
+    if data.len() < blocks * block_size {
+        return Err(AudioError::NotEnoughData {
+            expected: blocks * block_size,
+            found: data.len(),
+        });
+    }
+
+    let mut out = Vec::with_capacity(blocks * SAMPLES_PER_BLOCK * channels);
+    let mut block_samples = vec![0i16; SAMPLES_PER_BLOCK * channels];
+    for block in data.chunks_exact(block_size).take(blocks) {
+        let (headers, body) = block.split_at(4 * channels);
+        for (ch, header) in headers.chunks(4).enumerate() {
+            let mut state = Channel {
+                predictor: i16::from_le_bytes([header[0], header[1]]).into(),
+                index: i32::from(header[2]).min(88),
+            };
+            block_samples[ch] = state.predictor as i16;
+            let words = body.chunks(4).skip(ch).step_by(channels);
+            for (word_idx, word) in words.enumerate() {
+                for (byte_idx, &b) in word.iter().enumerate() {
+                    let sample = 1 + word_idx * 8 + byte_idx * 2;
+                    block_samples[sample * channels + ch] = state.decode(b & 0xf);
+                    block_samples[(sample + 1) * channels + ch] = state.decode(b >> 4);
+                }
+            }
+        }
+        out.extend_from_slice(&block_samples);
+    }
+    out.truncate(frames * channels);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Block of one channel with its header and the first bytes of its samples.
+    fn block(predictor: i16, index: u8, samples: &[u8]) -> Vec<u8> {
+        let mut block = predictor.to_le_bytes().to_vec();
+        block.extend_from_slice(&[index, 0]);
+        block.extend_from_slice(samples);
+        block.resize(BLOCK_SIZE, 0);
+        block
+    }
+
+    #[test]
+    fn mono() {
+        // +7 from the smallest step, then -1, +15 and +2 as the step index moves
+        let data = block(100, 0, &[0x84, 0x07]);
+        assert_eq!(decode(&data, 1, 5).unwrap(), [100, 107, 106, 121, 123]);
+        assert_eq!(decode(&data, 1, 65).unwrap().len(), 65);
+        // samples are clamped to 16 bits
+        let data = block(-32000, 88, &[0x7f, 0x07]);
+        assert_eq!(decode(&data, 1, 4).unwrap(), [-32000, -32768, 28668, 32767]);
+    }
+
+    #[test]
+    fn stereo() {
+        let left = block(100, 0, &[0x84]);
+        let right = block(-5, 0, &[0x0c]);
+        // headers first, then words of four bytes alternating between the channels
+        let mut data = [&left[..4], &right[..4]].concat();
+        for (left, right) in left[4..].chunks(4).zip(right[4..].chunks(4)) {
+            data.extend_from_slice(left);
+            data.extend_from_slice(right);
+        }
+        assert_eq!(decode(&data, 2, 3).unwrap(), [100, -5, 107, -12, 106, -11]);
+    }
+
+    #[test]
+    fn errors() {
+        let data = block(0, 0, &[]);
+        assert_eq!(decode(&data, 3, 1), Err(AudioError::UnsupportedChannels(3)));
+        assert_eq!(
+            decode(&data, 1, 66),
+            Err(AudioError::NotEnoughData {
+                expected: 72,
+                found: 36
+            })
+        );
+    }
+}
@@ -0,0 +1,44 @@
+mod fsb5;
+mod ima;
+mod ogg;
+mod wav;
+
+pub use fsb5::{Codec, Fsb5, Sample};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AudioError {
+    InvalidSignature,
+    NotEnoughData {
+        expected: usize,
+        found: usize,
+    },
+    UnsupportedCodec(Codec),
+    UnsupportedChannels(u32),
+    /// The Vorbis setup header doesn't match the sample or couldn't be parsed.
+    InvalidSetupHeader,
+    /// The Vorbis stream contains a packet which isn't an audio packet.
+    InvalidPacket,
+}
+
+impl std::fmt::Display for AudioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioError::InvalidSignature => write!(f, "not an FSB5 bank"),
+            AudioError::NotEnoughData { expected, found } => write!(
+                f,
+                "not enough audio data (expected {} bytes, found {})",
+                expected, found
+            ),
+            AudioError::UnsupportedCodec(codec) => {
+                write!(f, "converting {:?} is not supported", codec)
+            }
+            AudioError::UnsupportedChannels(channels) => {
+                write!(f, "unsupported channel count {}", channels)
+            }
+            AudioError::InvalidSetupHeader => write!(f, "invalid Vorbis setup header"),
+            AudioError::InvalidPacket => write!(f, "invalid Vorbis packet"),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
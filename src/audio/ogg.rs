@@ -0,0 +1,326 @@
+use super::AudioError;
+
+/// Block sizes of the Vorbis streams encoded by FMOD.
+const BLOCK_SIZES: [u32; 2] = [256, 2048];
+/// Size of the page body after which a page is flushed.
+const PAGE_SIZE: usize = 4096;
+const SERIAL: u32 = 1;
+
+lazy_static::lazy_static! {
+    static ref CRC_TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        for (idx, v) in table.iter_mut().enumerate() {
+            let mut crc = (idx as u32) << 24;
+            for _ in 0..8 {
+                crc = if crc & 0x8000_0000 != 0 {
+                    (crc << 1) ^ 0x04c1_1db7
+                } else {
+                    crc << 1
+                };
+            }
+            *v = crc;
+        }
+        table
+    };
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, &b| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ b) as usize]
+    })
+}
+
+struct PageWriter {
+    out: Vec<u8>,
+    sequence: u32,
+    segments: Vec<u8>,
+    body: Vec<u8>,
+    /// Granule position of the last packet finished in the current page.
+    granule: Option<u64>,
+    last_granule: u64,
+    continued: bool,
+}
+
+impl PageWriter {
+    fn new() -> Self {
+        Self {
+            out: Vec::new(),
+            sequence: 0,
+            segments: Vec::new(),
+            body: Vec::new(),
+            granule: None,
+            last_granule: 0,
+            continued: false,
+        }
+    }
+
+    fn packet(&mut self, packet: &[u8], granule: u64) {
+        let mut remaining = packet;
+        let mut started = false;
+        loop {
+            if self.segments.len() == 255 {
+                self.flush(false);
+                // only a packet split across pages continues on the next one
+                self.continued = started;
+            }
+            started = true;
+            let len = std::cmp::min(remaining.len(), 255);
+            self.segments.push(len as u8);
+            self.body.extend_from_slice(&remaining[..len]);
+            remaining = &remaining[len..];
+            if len < 255 {
+                break;
+            }
+        }
+        self.granule = Some(granule);
+        self.last_granule = granule;
+    }
+
+    fn flush(&mut self, last: bool) {
+        if self.segments.is_empty() && !last {
+            return;
+        }
+        let mut header_type = 0;
+        if self.continued {
+            header_type |= 1;
+        }
+        if self.sequence == 0 {
+            header_type |= 2;
+        }
+        if last {
+            header_type |= 4;
+        }
+        let start = self.out.len();
+        self.out.extend_from_slice(b"OggS");
+        self.out.push(0);
+        self.out.push(header_type);
+        let granule = match self.granule {
+            Some(v) => v as i64,
+            // an empty last page ends where the previous page did
+            None if last => self.last_granule as i64,
+            None => -1,
+        };
+        self.out.extend_from_slice(&granule.to_le_bytes());
+        self.out.extend_from_slice(&SERIAL.to_le_bytes());
+        self.out.extend_from_slice(&self.sequence.to_le_bytes());
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.push(self.segments.len() as u8);
+        self.out.extend_from_slice(&self.segments);
+        self.out.extend_from_slice(&self.body);
+        let crc = crc32(&self.out[start..]);
+        self.out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+
+        self.sequence += 1;
+        self.segments.clear();
+        self.body.clear();
+        self.granule = None;
+        self.continued = false;
+    }
+}
+
+fn identification_header(channels: u32, frequency: u32) -> Vec<u8> {
+    let mut out = b"\x01vorbis".to_vec();
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.push(channels as u8);
+    out.extend_from_slice(&frequency.to_le_bytes());
+    // maximum, nominal and minimum bitrate are unset
+    out.extend_from_slice(&[0; 12]);
+    let log2 = |v: u32| v.trailing_zeros() as u8;
+    out.push(log2(BLOCK_SIZES[1]) << 4 | log2(BLOCK_SIZES[0]));
+    out.push(1);
+    out
+}
+
+fn comment_header() -> Vec<u8> {
+    let vendor = b"unityfs";
+    let mut out = b"\x03vorbis".to_vec();
+    out.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    out.extend_from_slice(vendor);
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.push(1);
+    out
+}
+
+/// Reads the block flags of the modes of a Vorbis setup header.
+///
+/// Modes are stored at the end of the header, so they're read backwards from the framing bit
+/// instead of decoding the codebooks before them. A mode is 41 bits with zero window and
+/// transform types, preceded by the number of modes in 6 bits.
+fn mode_block_flags(setup_header: &[u8]) -> Option<Vec<bool>> {
+    let total = setup_header.len() * 8;
+    let bit = |pos: usize| {
+        let b = setup_header[setup_header.len() - 1 - pos / 8];
+        u32::from(b >> (7 - pos % 8)) & 1
+    };
+    let read = |pos: &mut usize, len: usize| {
+        let mut v = 0;
+        for _ in 0..len {
+            v = (v << 1) | bit(*pos);
+            *pos += 1;
+        }
+        v
+    };
+
+    let mut pos = 0;
+    loop {
+        if total - pos <= 97 {
+            return None;
+        }
+        if read(&mut pos, 1) == 1 {
+            break;
+        }
+    }
+    let modes_end = pos;
+    // count the modes until the number of modes before them matches
+    let mut mode_count = 0;
+    let mut last_mode_count = 0;
+    while total - pos >= 97 {
+        if read(&mut pos, 8) > 63 || read(&mut pos, 16) != 0 || read(&mut pos, 16) != 0 {
+            break;
+        }
+        pos += 1;
+        mode_count += 1;
+        if mode_count > 64 {
+            break;
+        }
+        let mut count_pos = pos;
+        if read(&mut count_pos, 6) as usize + 1 == mode_count {
+            last_mode_count = mode_count;
+        }
+    }
+    if last_mode_count == 0 {
+        return None;
+    }
+
+    let mut pos = modes_end;
+    let mut flags = vec![false; last_mode_count];
+    for flag in flags.iter_mut().rev() {
+        pos += 40;
+        *flag = read(&mut pos, 1) == 1;
+    }
+    Some(flags)
+}
+
+/// Number of bits needed to store `v`.
+fn ilog(v: usize) -> u32 {
+    0usize.leading_zeros() - v.leading_zeros()
+}
+
+/// Wraps the Vorbis packets of an FSB5 sample, each prefixed with its 16-bit size, in an Ogg
+/// stream with rebuilt identification and comment headers.
+///
+/// Granule positions are clamped to `sample_count`, so players trim the padding of the last
+/// packet.
+pub(super) fn write(
+    data: &[u8],
+    channels: u32,
+    frequency: u32,
+    sample_count: u32,
+    setup_header: &[u8],
+) -> Result<Vec<u8>, AudioError> {
+    let setup_header = if setup_header.starts_with(b"\x05vorbis") {
+        setup_header.to_vec()
+    } else {
+        let mut v = b"\x05vorbis".to_vec();
+        v.extend_from_slice(setup_header);
+        v
+    };
+    let block_flags = mode_block_flags(&setup_header).ok_or(AudioError::InvalidSetupHeader)?;
+    let mode_bits = ilog(block_flags.len() - 1);
+
+    let mut writer = PageWriter::new();
+    writer.packet(&identification_header(channels, frequency), 0);
+    writer.flush(false);
+    writer.packet(&comment_header(), 0);
+    writer.packet(&setup_header, 0);
+    writer.flush(false);
+
+    let mut granule = 0;
+    let mut prev_block_size = None;
+    let mut input = data;
+    while input.len() >= 2 {
+        let size = usize::from(u16::from_le_bytes([input[0], input[1]]));
+        if size == 0 {
+            break;
+        }
+        let packet = input.get(2..2 + size).ok_or(AudioError::NotEnoughData {
+            expected: data.len() - input.len() + 2 + size,
+            found: data.len(),
+        })?;
+        input = &input[2 + size..];
+
+        if packet[0] & 1 != 0 {
+            return Err(AudioError::InvalidPacket);
+        }
+        let mode = (packet[0] >> 1) as usize & ((1 << mode_bits) - 1);
+        let block_flag = *block_flags.get(mode).ok_or(AudioError::InvalidPacket)?;
+        let block_size = BLOCK_SIZES[block_flag as usize];
+        if let Some(prev) = prev_block_size {
+            granule += u64::from((prev + block_size) / 4);
+        }
+        prev_block_size = Some(block_size);
+
+        writer.packet(packet, std::cmp::min(granule, u64::from(sample_count)));
+        if writer.body.len() >= PAGE_SIZE {
+            writer.flush(false);
+        }
+    }
+    writer.flush(true);
+    Ok(writer.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header types and granule positions of the pages in `out`.
+    fn pages(mut out: &[u8]) -> Vec<(u8, i64)> {
+        let mut pages = Vec::new();
+        while !out.is_empty() {
+            assert_eq!(&out[..4], b"OggS");
+            let mut granule = [0; 8];
+            granule.copy_from_slice(&out[6..14]);
+            let segments = usize::from(out[26]);
+            let body = out[27..27 + segments]
+                .iter()
+                .map(|&v| usize::from(v))
+                .sum::<usize>();
+            pages.push((out[5], i64::from_le_bytes(granule)));
+            out = &out[27 + segments + body..];
+        }
+        pages
+    }
+
+    #[test]
+    fn continued_pages() {
+        let mut writer = PageWriter::new();
+        // fills the segment table exactly, so the next packet starts a fresh page
+        writer.packet(&vec![0; 254 * 255 + 100], 1);
+        writer.packet(&[0; 10], 2);
+        // spans two pages
+        writer.packet(&vec![0; 300 * 255], 3);
+        writer.flush(true);
+        let pages = pages(&writer.out);
+        assert_eq!(pages, [(2, 1), (0, 2), (5, 3)]);
+    }
+
+    #[test]
+    fn final_granule() {
+        // two modes with short and long blocks, then the framing bit, packed from the LSB
+        let mut bits = vec![1, 0, 0, 0, 0, 0];
+        for &flag in &[0, 1] {
+            bits.push(flag);
+            bits.extend_from_slice(&[0; 40]);
+        }
+        bits.push(1);
+        let mut header = vec![0; 16];
+        for chunk in bits.chunks(8) {
+            header.push(chunk.iter().rev().fold(0, |b, &bit| (b << 1) | bit));
+        }
+        // five short blocks, 128 samples apart and ending at 512
+        let data = [2, 0, 0, 0].repeat(5);
+        let out = write(&data, 1, 44100, 300, &header).unwrap();
+        let granules = pages(&out).iter().map(|&(_, g)| g).collect::<Vec<_>>();
+        assert_eq!(granules, [0, 0, 300]);
+    }
+}
@@ -0,0 +1,31 @@
+pub(super) const FORMAT_PCM: u16 = 1;
+pub(super) const FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Size of the header written by [`write`].
+pub(super) const HEADER_SIZE: usize = 44;
+
+/// Writes a RIFF WAVE file with interleaved `data`.
+pub(super) fn write(
+    format: u16,
+    channels: u32,
+    frequency: u32,
+    bits_per_sample: u16,
+    data: &[u8],
+) -> Vec<u8> {
+    let block_align = channels * u32::from(bits_per_sample) / 8;
+    let mut out = Vec::with_capacity(HEADER_SIZE + data.len());
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&format.to_le_bytes());
+    out.extend_from_slice(&(channels as u16).to_le_bytes());
+    out.extend_from_slice(&frequency.to_le_bytes());
+    out.extend_from_slice(&(frequency * block_align).to_le_bytes());
+    out.extend_from_slice(&(block_align as u16).to_le_bytes());
+    out.extend_from_slice(&bits_per_sample.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
@@ -0,0 +1,97 @@
+use unityfs::bundle_graph::DependencyGraph;
+use unityfs::classes::{AssetBundleInfo, AssetBundleManifest};
+
+fn read_bundle(filename: &str) -> Vec<u8> {
+    match std::fs::read(filename) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", filename, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let mut json = false;
+    let mut filenames = Vec::new();
+    for arg in std::env::args().skip(1) {
+        if arg == "--json" {
+            json = true;
+        } else {
+            filenames.push(arg);
+        }
+    }
+    if filenames.is_empty() {
+        eprintln!("Usage: unityfs-deps [--json] <manifest bundle> [bundle...]");
+        std::process::exit(1);
+    }
+
+    let buf = read_bundle(&filenames[0]);
+    let (_, meta) = match unityfs::UnityFsMeta::parse(&buf) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to parse {}: {:?}", filenames[0], e);
+            std::process::exit(1);
+        }
+    };
+    let fs = match meta.read_unityfs() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", filenames[0], e);
+            std::process::exit(1);
+        }
+    };
+    let mut graph = match AssetBundleManifest::from_asset(fs.main_asset()) {
+        Ok(Some(manifest)) => DependencyGraph::from_manifest(&manifest),
+        Ok(None) => {
+            eprintln!("Cannot find AssetBundleManifest object");
+            std::process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("Failed to read AssetBundleManifest: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // contents of the other bundles are only needed to find duplicate assets
+    for filename in &filenames[1..] {
+        let buf = read_bundle(filename);
+        let (_, meta) = match unityfs::UnityFsMeta::parse(&buf) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Failed to parse {}: {:?}", filename, e);
+                std::process::exit(1);
+            }
+        };
+        let fs = match meta.read_unityfs() {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", filename, e);
+                std::process::exit(1);
+            }
+        };
+        match AssetBundleInfo::from_asset(fs.main_asset()) {
+            Ok(Some(bundle)) => graph.add_contents(&bundle),
+            Ok(None) => eprintln!("{}: cannot find AssetBundle object", filename),
+            Err(e) => eprintln!("{}: failed to read AssetBundle: {}", filename, e),
+        }
+    }
+
+    if json {
+        println!("{}", graph.to_json());
+    } else {
+        print!("{}", graph.to_dot());
+    }
+
+    let cycles = graph.cycles();
+    for cycle in &cycles {
+        eprintln!("Cyclic dependency between {}", cycle.join(", "));
+    }
+    let duplicates = graph.duplicate_assets();
+    for (path, bundles) in &duplicates {
+        eprintln!("Duplicate: {} in {}", path, bundles.join(", "));
+    }
+    if !cycles.is_empty() || !duplicates.is_empty() {
+        std::process::exit(3);
+    }
+}
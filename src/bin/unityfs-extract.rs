@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use unityfs::classes::{AssetBundleInfo, PPtr, TextAsset};
+
+/// Turns a project path or an object name into a relative path without `..` components.
+fn sanitize(path: &str) -> PathBuf {
+    path.split(&['/', '\\'][..])
+        .filter(|component| !component.is_empty() && *component != "." && *component != "..")
+        .map(|component| component.replace(':', "_"))
+        .collect()
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let filename = args.next().expect("Expected filename");
+    let out_dir = PathBuf::from(args.next().unwrap_or_else(|| ".".to_owned()));
+    let buf = std::fs::read(filename).expect("Failed to read file");
+
+    let (_, meta) = match unityfs::UnityFsMeta::parse(&buf) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to parse: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    let fs = match meta.read_unityfs() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to read asset: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let asset = fs.main_asset();
+    let bundle = match AssetBundleInfo::from_asset(asset) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to read AssetBundle: {}", e);
+            None
+        }
+    };
+
+    let mut written = HashSet::new();
+    for object in asset.objects() {
+        if object.data.type_name() != "TextAsset" {
+            continue;
+        }
+        let text_asset = match TextAsset::from_data(&object.data) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{}: {}", object.path_id, e);
+                continue;
+            }
+        };
+        let format = text_asset.format();
+        let pptr = PPtr {
+            file_id: 0,
+            path_id: object.path_id as i64,
+        };
+        let mut path = match bundle.as_ref().and_then(|bundle| bundle.path_of(&pptr)) {
+            Some(path) => sanitize(path),
+            None => sanitize(&text_asset.name),
+        };
+        if path.as_os_str().is_empty() {
+            path = PathBuf::from(object.path_id.to_string());
+        }
+        path = format.with_extension(path);
+        if !written.insert(path.clone()) {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let extension = format.extension();
+            path.set_file_name(format!("{}_{}.{}", stem, object.path_id, extension));
+            written.insert(path.clone());
+        }
+
+        let out_path = out_dir.join(&path);
+        if let Err(e) = write(&out_path, text_asset.bytes()) {
+            eprintln!("{}: {}", out_path.display(), e);
+            std::process::exit(1);
+        }
+        println!("{}", path.display());
+    }
+}
+
+fn write(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, data)
+}
@@ -1,10 +1,20 @@
 fn main() {
     let mut args = std::env::args().skip(1);
     let filename = args.next().expect("Expected filename");
+    let path = args.next();
     let buf = std::fs::read(filename).expect("Failed to read file");
 
     let (_, meta) = unityfs::UnityFsMeta::parse(&buf).unwrap();
-    let fs = meta.read_unityfs();
+    let fs = meta.read_unityfs().unwrap();
     println!("{}", fs.name());
-    println!("{:#?}", fs.main_asset().objects());
+    match path {
+        Some(path) => {
+            for object in fs.main_asset().objects() {
+                for data in object.data.query(&path) {
+                    println!("{}: {:#?}", object.path_id, data);
+                }
+            }
+        }
+        None => println!("{:#?}", fs.main_asset().objects()),
+    }
 }
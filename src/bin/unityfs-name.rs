@@ -1,3 +1,5 @@
+use unityfs::classes::AssetBundleInfo;
+
 fn main() {
     let mut args = std::env::args().skip(1);
     let filename = args.next().expect("Expected filename");
@@ -10,29 +12,23 @@ fn main() {
             std::process::exit(1);
         }
     };
-    let fs = meta.read_unityfs();
+    let fs = match meta.read_unityfs() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to read asset: {}", e);
+            std::process::exit(1);
+        }
+    };
     let asset = fs.main_asset();
-    for object in asset.objects() {
-        match &object.data {
-            unityfs::Data::GenericStruct { type_name, fields } if type_name == "AssetBundle" => {
-                match fields.get("m_Name") {
-                    Some(unityfs::Data::String(s)) => {
-                        println!("{}", String::from_utf8_lossy(s));
-                        return;
-                    }
-                    Some(_) => {
-                        eprintln!("m_Name type mismatch");
-                        std::process::exit(1);
-                    }
-                    None => {
-                        eprintln!("m_Name not found at AssetBundle");
-                        std::process::exit(1);
-                    }
-                }
-            }
-            _ => {}
+    match AssetBundleInfo::from_asset(asset) {
+        Ok(Some(bundle)) => println!("{}", bundle.name),
+        Ok(None) => {
+            eprintln!("Cannot find AssetBundle object");
+            std::process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("Failed to read AssetBundle: {}", e);
+            std::process::exit(1);
         }
     }
-    eprintln!("Cannot find AssetBundle object");
-    std::process::exit(2);
 }
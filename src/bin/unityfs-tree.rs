@@ -0,0 +1,65 @@
+use unityfs::hierarchy::{Hierarchy, Node};
+
+fn describe(node: &Node) -> String {
+    let game_object = &node.game_object;
+    let mut line = node.name().to_owned();
+    if !game_object.is_active {
+        line.push_str(" (inactive)");
+    }
+    let tag = match game_object.tag_name() {
+        Some(name) => name.to_owned(),
+        None => game_object.tag.to_string(),
+    };
+    line.push_str(&format!(" layer={} tag={}", game_object.layer, tag));
+    if let Some(transform) = &node.transform {
+        let p = transform.local_position;
+        let r = transform.local_rotation;
+        let s = transform.local_scale;
+        line.push_str(&format!(
+            " position=({}, {}, {}) rotation=({}, {}, {}, {}) scale=({}, {}, {})",
+            p.x, p.y, p.z, r.x, r.y, r.z, r.w, s.x, s.y, s.z
+        ));
+    }
+    line
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let filename = args.next().expect("Expected filename");
+    let buf = std::fs::read(filename).expect("Failed to read file");
+
+    let (_, meta) = match unityfs::UnityFsMeta::parse(&buf) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to parse: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    let fs = match meta.read_unityfs() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to read asset: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let hierarchy = match Hierarchy::from_asset(fs.main_asset()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to read hierarchy: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    hierarchy.walk(|node, depth| {
+        let indent = "  ".repeat(depth);
+        println!("{}{}", indent, describe(node));
+        for component in &node.components {
+            let name = match (&component.script, component.class_name.as_str()) {
+                (Some(script), class_name) => format!("{} ({})", class_name, script),
+                (None, "") => format!("<external {}>", component.path_id),
+                (None, class_name) => class_name.to_owned(),
+            };
+            println!("{}  - {}", indent, name);
+        }
+    });
+}
@@ -0,0 +1,436 @@
+//! Dependency graph of the bundles of a build, for auditing its layout.
+
+use crate::classes::{AssetBundleInfo, AssetBundleManifest};
+use crate::util::json_escape;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Bundles of a build as nodes with an edge to each bundle they depend on.
+///
+/// Contents of the bundles can be added with [`DependencyGraph::add_contents`] to find assets
+/// included in more than one bundle.
+#[derive(Clone, Debug, Default)]
+pub struct DependencyGraph {
+    names: Vec<String>,
+    nodes: HashMap<String, usize>,
+    edges: Vec<Vec<usize>>,
+    /// Project paths of the assets and the bundles which contain them.
+    contents: BTreeMap<String, Vec<usize>>,
+}
+
+impl DependencyGraph {
+    pub fn from_manifest(manifest: &AssetBundleManifest) -> Self {
+        let mut graph = Self::default();
+        for bundle in manifest.bundles() {
+            graph.add_bundle(&bundle.name);
+        }
+        for bundle in manifest.bundles() {
+            for dependency in manifest.dependencies(bundle) {
+                graph.add_dependency(&bundle.name, dependency);
+            }
+        }
+        graph
+    }
+
+    /// Adds a bundle without dependencies, returning its node.
+    pub fn add_bundle(&mut self, name: &str) -> usize {
+        match self.node(name) {
+            Some(node) => node,
+            None => {
+                let node = self.names.len();
+                self.names.push(name.to_owned());
+                self.nodes.insert(name.to_owned(), node);
+                self.edges.push(Vec::new());
+                node
+            }
+        }
+    }
+
+    pub fn add_dependency(&mut self, bundle: &str, dependency: &str) {
+        let from = self.add_bundle(bundle);
+        let to = self.add_bundle(dependency);
+        if !self.edges[from].contains(&to) {
+            self.edges[from].push(to);
+        }
+    }
+
+    /// Records the assets listed in the container of `bundle`.
+    ///
+    /// The bundle is named after [`AssetBundleInfo::name`], which is the name used by the
+    /// manifest.
+    pub fn add_contents(&mut self, bundle: &AssetBundleInfo) {
+        let node = self.add_bundle(&bundle.name);
+        for path in bundle.paths() {
+            let bundles = self.contents.entry(path.to_owned()).or_default();
+            if !bundles.contains(&node) {
+                bundles.push(node);
+            }
+        }
+    }
+
+    fn node(&self, name: &str) -> Option<usize> {
+        self.nodes.get(name).copied()
+    }
+
+    pub fn bundles(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Names of the direct dependencies of `bundle`.
+    pub fn dependencies(&self, bundle: &str) -> Vec<&str> {
+        match self.node(bundle) {
+            Some(node) => self.edges[node]
+                .iter()
+                .map(|&to| self.names[to].as_str())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Names of all bundles `bundle` depends on, directly or not, in breadth-first order.
+    pub fn transitive_dependencies(&self, bundle: &str) -> Vec<&str> {
+        let start = match self.node(bundle) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+        let mut visited = vec![false; self.names.len()];
+        visited[start] = true;
+        let mut queue = vec![start];
+        let mut idx = 0;
+        while idx < queue.len() {
+            for &to in &self.edges[queue[idx]] {
+                if !visited[to] {
+                    visited[to] = true;
+                    queue.push(to);
+                }
+            }
+            idx += 1;
+        }
+        queue[1..]
+            .iter()
+            .map(|&node| self.names[node].as_str())
+            .collect()
+    }
+
+    /// Groups of bundles which depend on each other, including bundles depending on
+    /// themselves.
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        let mut tarjan = Tarjan {
+            edges: &self.edges,
+            index: vec![None; self.names.len()],
+            low_link: vec![0; self.names.len()],
+            on_stack: vec![false; self.names.len()],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        };
+        for node in 0..self.names.len() {
+            if tarjan.index[node].is_none() {
+                tarjan.connect(node);
+            }
+        }
+        tarjan
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.edges[component[0]].contains(&component[0])
+            })
+            .map(|mut component| {
+                component.sort();
+                component
+                    .into_iter()
+                    .map(|node| self.names[node].as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Assets contained in more than one bundle, with the names of those bundles.
+    pub fn duplicate_assets(&self) -> Vec<(&str, Vec<&str>)> {
+        self.contents
+            .iter()
+            .filter(|(_, bundles)| bundles.len() > 1)
+            .map(|(path, bundles)| {
+                let bundles = bundles
+                    .iter()
+                    .map(|&node| self.names[node].as_str())
+                    .collect();
+                (path.as_str(), bundles)
+            })
+            .collect()
+    }
+
+    /// Writes the graph in Graphviz DOT, with bundles in cycles highlighted.
+    pub fn to_dot(&self) -> String {
+        let in_cycle = self
+            .cycles()
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.node(name))
+            .collect::<Vec<_>>();
+        let mut out = String::from("digraph bundles {\n");
+        for (node, name) in self.names.iter().enumerate() {
+            let _ = write!(out, "    n{} [label=\"{}\"", node, json_escape(name));
+            if in_cycle.contains(&node) {
+                out.push_str(", color=red");
+            }
+            out.push_str("];\n");
+        }
+        for (from, edges) in self.edges.iter().enumerate() {
+            for to in edges {
+                let _ = writeln!(out, "    n{} -> n{};", from, to);
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Writes the bundles with their dependencies, the cycles and the duplicate assets as JSON.
+    pub fn to_json(&self) -> String {
+        let string = |s: &str| format!("\"{}\"", json_escape(s));
+        let list = |names: &[&str]| {
+            let names = names.iter().map(|&name| string(name)).collect::<Vec<_>>();
+            format!("[{}]", names.join(","))
+        };
+
+        let bundles = self
+            .names
+            .iter()
+            .map(|name| {
+                format!(
+                    "{{\"name\":{},\"dependencies\":{}}}",
+                    string(name),
+                    list(&self.dependencies(name))
+                )
+            })
+            .collect::<Vec<_>>();
+        let cycles = self
+            .cycles()
+            .iter()
+            .map(|cycle| list(cycle))
+            .collect::<Vec<_>>();
+        let duplicates = self
+            .duplicate_assets()
+            .iter()
+            .map(|(path, bundles)| {
+                format!(
+                    "{{\"path\":{},\"bundles\":{}}}",
+                    string(path),
+                    list(bundles)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"bundles\":[{}],\"cycles\":[{}],\"duplicates\":[{}]}}",
+            bundles.join(","),
+            cycles.join(","),
+            duplicates.join(",")
+        )
+    }
+}
+
+/// Tarjan's strongly connected components algorithm.
+struct Tarjan<'g> {
+    edges: &'g [Vec<usize>],
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn connect(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.low_link[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+
+        let edges = self.edges;
+        for &to in &edges[node] {
+            match self.index[to] {
+                None => {
+                    self.connect(to);
+                    self.low_link[node] = self.low_link[node].min(self.low_link[to]);
+                }
+                Some(index) if self.on_stack[to] => {
+                    self.low_link[node] = self.low_link[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_link[node]) == self.index[node] {
+            let mut component = Vec::new();
+            while let Some(top) = self.stack.pop() {
+                self.on_stack[top] = false;
+                component.push(top);
+                if top == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::tests::{pptr, string, structure};
+    use crate::Data;
+
+    fn pair(first: Data<'static>, second: Data<'static>) -> Data<'static> {
+        Data::Pair(Box::new(first), Box::new(second))
+    }
+
+    /// `main` depends on `ui` and `shared`, `ui` and `fonts` depend on each other and `shared`
+    /// on itself. `loose` has no dependencies.
+    fn manifest() -> AssetBundleManifest {
+        let names = ["main", "ui", "shared", "fonts", "loose"];
+        let dependencies: [&[i32]; 5] = [&[1, 2], &[3], &[2], &[1], &[]];
+        let info = |dependencies: &[i32]| {
+            structure(
+                "AssetBundleInfo",
+                vec![
+                    ("AssetBundleHash", structure("Hash128", Vec::new())),
+                    (
+                        "AssetBundleDependencies",
+                        Data::GenericArray(dependencies.iter().map(|&v| Data::SInt32(v)).collect()),
+                    ),
+                ],
+            )
+        };
+        let data = structure(
+            "AssetBundleManifest",
+            vec![
+                (
+                    "AssetBundleNames",
+                    Data::GenericArray(
+                        (0..)
+                            .zip(&names)
+                            .map(|(idx, name)| pair(Data::SInt32(idx), string(name)))
+                            .collect(),
+                    ),
+                ),
+                (
+                    "AssetBundleInfos",
+                    Data::GenericArray(
+                        (0..)
+                            .zip(&dependencies)
+                            .map(|(idx, dependencies)| pair(Data::SInt32(idx), info(dependencies)))
+                            .collect(),
+                    ),
+                ),
+            ],
+        );
+        AssetBundleManifest::from_data(&data).unwrap()
+    }
+
+    fn contents(name: &'static str, paths: &[&'static str]) -> AssetBundleInfo {
+        let entry = |(idx, path): (i64, &&'static str)| {
+            let info = structure(
+                "AssetInfo",
+                vec![
+                    ("preloadIndex", Data::SInt32(0)),
+                    ("preloadSize", Data::SInt32(0)),
+                    ("asset", pptr(0, idx + 1)),
+                ],
+            );
+            pair(string(path), info)
+        };
+        let data = structure(
+            "AssetBundle",
+            vec![
+                ("m_Name", string(name)),
+                ("m_PreloadTable", Data::GenericArray(Vec::new())),
+                (
+                    "m_Container",
+                    Data::GenericArray((0..).zip(paths).map(entry).collect()),
+                ),
+            ],
+        );
+        AssetBundleInfo::from_data(&data).unwrap()
+    }
+
+    fn graph() -> DependencyGraph {
+        let mut graph = DependencyGraph::from_manifest(&manifest());
+        graph.add_contents(&contents("ui", &["assets/logo.png", "assets/ui.prefab"]));
+        graph.add_contents(&contents("main", &["assets/logo.png", "assets/main.unity"]));
+        graph
+    }
+
+    #[test]
+    fn dependencies() {
+        let graph = graph();
+        assert_eq!(graph.bundles(), ["main", "ui", "shared", "fonts", "loose"]);
+        assert_eq!(graph.dependencies("main"), ["ui", "shared"]);
+        assert_eq!(
+            graph.transitive_dependencies("main"),
+            ["ui", "shared", "fonts"]
+        );
+        assert_eq!(graph.transitive_dependencies("fonts"), ["ui"]);
+        assert_eq!(graph.transitive_dependencies("shared"), [] as [&str; 0]);
+        assert_eq!(graph.transitive_dependencies("missing"), [] as [&str; 0]);
+    }
+
+    #[test]
+    fn cycles_and_duplicates() {
+        let graph = graph();
+        assert_eq!(graph.cycles(), [vec!["ui", "fonts"], vec!["shared"]]);
+        assert_eq!(
+            graph.duplicate_assets(),
+            [("assets/logo.png", vec!["ui", "main"])]
+        );
+        assert!(DependencyGraph::default().cycles().is_empty());
+    }
+
+    #[test]
+    fn exports() {
+        let graph = graph();
+        assert_eq!(
+            graph.to_dot(),
+            "digraph bundles {
+    n0 [label=\"main\"];
+    n1 [label=\"ui\", color=red];
+    n2 [label=\"shared\", color=red];
+    n3 [label=\"fonts\", color=red];
+    n4 [label=\"loose\"];
+    n0 -> n1;
+    n0 -> n2;
+    n1 -> n3;
+    n2 -> n2;
+    n3 -> n1;
+}
+"
+        );
+        assert_eq!(
+            graph.to_json(),
+            concat!(
+                "{\"bundles\":[",
+                "{\"name\":\"main\",\"dependencies\":[\"ui\",\"shared\"]},",
+                "{\"name\":\"ui\",\"dependencies\":[\"fonts\"]},",
+                "{\"name\":\"shared\",\"dependencies\":[\"shared\"]},",
+                "{\"name\":\"fonts\",\"dependencies\":[\"ui\"]},",
+                "{\"name\":\"loose\",\"dependencies\":[]}],",
+                "\"cycles\":[[\"ui\",\"fonts\"],[\"shared\"]],",
+                "\"duplicates\":[{\"path\":\"assets/logo.png\",\"bundles\":[\"ui\",\"main\"]}]}"
+            )
+        );
+
+        assert!(serde_json::from_str::<serde_json::Value>(&graph.to_json()).is_ok());
+
+        let mut graph = DependencyGraph::default();
+        graph.add_dependency("a\"b", "c\\d");
+        assert_eq!(
+            graph.to_dot(),
+            "digraph bundles {\n    n0 [label=\"a\\\"b\"];\n    n1 [label=\"c\\\\d\"];\n    n0 -> n1;\n}\n"
+        );
+        assert_eq!(
+            graph.to_json(),
+            "{\"bundles\":[{\"name\":\"a\\\"b\",\"dependencies\":[\"c\\\\d\"]},{\"name\":\"c\\\\d\",\"dependencies\":[]}],\"cycles\":[],\"duplicates\":[]}"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{expect_type, field, field_with, string_field, ClassError, PPtr};
+use crate::{Asset, Data, Object};
+
+/// Entry of `m_Container`, pointing to the objects of an asset in the preload table.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AssetInfo {
+    pub preload_index: i32,
+    pub preload_size: i32,
+    /// Main object of the asset.
+    pub asset: PPtr,
+}
+
+impl AssetInfo {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "AssetInfo")?;
+        let preload_index = field_with(data, "preloadIndex", Data::as_i64)? as i32;
+        let preload_size = field_with(data, "preloadSize", Data::as_i64)? as i32;
+        let asset = PPtr::from_data(field(data, "asset")?)?;
+        Ok(Self {
+            preload_index,
+            preload_size,
+            asset,
+        })
+    }
+}
+
+/// `AssetBundle`, the object listing the assets of a bundle by their project path.
+#[derive(Clone, Debug)]
+pub struct AssetBundleInfo {
+    pub name: String,
+    /// Project paths of the assets, a path being listed once for each of its main objects.
+    pub container: Vec<(String, AssetInfo)>,
+    /// Objects of all assets, indexed by [`AssetInfo::preload_index`].
+    pub preload_table: Vec<PPtr>,
+    /// Names of the bundles this bundle depends on.
+    pub dependencies: Vec<String>,
+    pub main_asset: Option<AssetInfo>,
+    pub is_streamed_scene_asset_bundle: bool,
+    /// Index in `container` of the first entry for each main object.
+    main_objects: HashMap<PPtr, usize>,
+}
+
+impl AssetBundleInfo {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "AssetBundle")?;
+        let name = string_field(data, "m_Name")?;
+        let container = field_with(data, "m_Container", Data::as_array)?
+            .iter()
+            .map(|entry| {
+                let (path, info) = entry
+                    .as_pair()
+                    .ok_or(ClassError::FieldTypeMismatch("m_Container"))?;
+                let path = path
+                    .as_bytes()
+                    .ok_or(ClassError::FieldTypeMismatch("m_Container"))?;
+                let path = String::from_utf8_lossy(path).into_owned();
+                Ok((path, AssetInfo::from_data(info)?))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let preload_table = field_with(data, "m_PreloadTable", Data::as_array)?
+            .iter()
+            .map(PPtr::from_data)
+            .collect::<Result<Vec<_>, _>>()?;
+        let dependencies = match data.field("m_Dependencies").and_then(Data::as_array) {
+            Some(dependencies) => dependencies
+                .iter()
+                .map(|v| {
+                    v.as_bytes()
+                        .map(|v| String::from_utf8_lossy(v).into_owned())
+                        .ok_or(ClassError::FieldTypeMismatch("m_Dependencies"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        let main_asset = match data.field("m_MainAsset") {
+            Some(v) => Some(AssetInfo::from_data(v)?),
+            None => None,
+        };
+        let is_streamed_scene_asset_bundle = data
+            .field("m_IsStreamedSceneAssetBundle")
+            .and_then(Data::as_bool)
+            .unwrap_or(false);
+        let mut main_objects = HashMap::with_capacity(container.len());
+        for (idx, (_, info)) in container.iter().enumerate() {
+            main_objects.entry(info.asset).or_insert(idx);
+        }
+        Ok(Self {
+            name,
+            container,
+            preload_table,
+            dependencies,
+            main_asset,
+            is_streamed_scene_asset_bundle,
+            main_objects,
+        })
+    }
+
+    /// Reads the `AssetBundle` object of `asset`. Returns `Ok(None)` if there is none.
+    pub fn from_asset(asset: &Asset<'_>) -> Result<Option<Self>, ClassError> {
+        asset
+            .objects()
+            .iter()
+            .find(|object| object.data.type_name() == "AssetBundle")
+            .map(|object| Self::from_data(&object.data))
+            .transpose()
+    }
+
+    /// Distinct project paths of the assets in the bundle, in container order.
+    pub fn paths(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.container
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .filter(|path| seen.insert(*path))
+            .collect()
+    }
+
+    /// Entries of the container with `path`.
+    pub fn assets<'s>(&'s self, path: &'s str) -> impl Iterator<Item = &'s AssetInfo> + 's {
+        self.container
+            .iter()
+            .filter(move |(p, _)| p == path)
+            .map(|(_, info)| info)
+    }
+
+    /// Preload table entries of `info`, holding the objects of the asset and their dependencies.
+    pub fn preloads(&self, info: &AssetInfo) -> &[PPtr] {
+        let start = std::cmp::max(info.preload_index, 0) as usize;
+        let end = start.saturating_add(std::cmp::max(info.preload_size, 0) as usize);
+        let start = std::cmp::min(start, self.preload_table.len());
+        let end = std::cmp::min(end, self.preload_table.len());
+        &self.preload_table[start..end]
+    }
+
+    /// Resolves the objects of the asset at `path` through the preload table.
+    ///
+    /// Objects in other assets of the bundle or in other bundles are skipped.
+    pub fn objects<'s, 'b>(&self, asset: &'s Asset<'b>, path: &str) -> Vec<&'s Object<'b>> {
+        let mut objects = Vec::new();
+        let mut seen = HashSet::new();
+        for info in self.assets(path) {
+            let main = std::iter::once(&info.asset);
+            for pptr in main.chain(self.preloads(info)) {
+                if let Some(object) = pptr.resolve(asset) {
+                    if seen.insert(object.path_id) {
+                        objects.push(object);
+                    }
+                }
+            }
+        }
+        objects
+    }
+
+    /// Project path of the asset whose main object is `pptr`.
+    ///
+    /// Looks `pptr` up in an index of `container` built by [`Self::from_data`].
+    pub fn path_of(&self, pptr: &PPtr) -> Option<&str> {
+        self.main_objects
+            .get(pptr)
+            .map(|&idx| self.container[idx].0.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::tests::{pptr, string, structure};
+
+    fn asset_info(preload_index: i32, preload_size: i32, path_id: i64) -> Data<'static> {
+        structure(
+            "AssetInfo",
+            vec![
+                ("preloadIndex", Data::SInt32(preload_index)),
+                ("preloadSize", Data::SInt32(preload_size)),
+                ("asset", pptr(0, path_id)),
+            ],
+        )
+    }
+
+    fn entry(path: &'static str, info: Data<'static>) -> Data<'static> {
+        Data::Pair(Box::new(string(path)), Box::new(info))
+    }
+
+    /// A prefab with a sub-asset and a texture, which the prefab also preloads.
+    fn bundle() -> Data<'static> {
+        structure(
+            "AssetBundle",
+            vec![
+                ("m_Name", string("characters")),
+                (
+                    "m_PreloadTable",
+                    Data::GenericArray(vec![pptr(0, 10), pptr(0, 20), pptr(0, 11), pptr(0, 20)]),
+                ),
+                (
+                    "m_Container",
+                    Data::GenericArray(vec![
+                        entry("assets/hero.prefab", asset_info(0, 2, 10)),
+                        entry("assets/hero.prefab", asset_info(2, 1, 11)),
+                        entry("assets/hero.png", asset_info(3, 1, 20)),
+                    ]),
+                ),
+                ("m_MainAsset", asset_info(0, 0, 0)),
+                ("m_Dependencies", Data::GenericArray(vec![string("shared")])),
+                ("m_IsStreamedSceneAssetBundle", Data::Bool(false)),
+            ],
+        )
+    }
+
+    #[test]
+    fn from_data() {
+        let info = AssetBundleInfo::from_data(&bundle()).unwrap();
+        assert_eq!(info.name, "characters");
+        assert_eq!(info.dependencies, ["shared"]);
+        assert_eq!(info.main_asset.map(|v| v.asset.is_null()), Some(true));
+        assert!(!info.is_streamed_scene_asset_bundle);
+        assert_eq!(info.paths(), ["assets/hero.prefab", "assets/hero.png"]);
+
+        let path_ids = |pptrs: &[PPtr]| pptrs.iter().map(|v| v.path_id).collect::<Vec<_>>();
+        let prefab = info.assets("assets/hero.prefab").collect::<Vec<_>>();
+        assert_eq!(prefab.len(), 2);
+        assert_eq!(path_ids(info.preloads(prefab[0])), [10, 20]);
+        assert_eq!(path_ids(info.preloads(prefab[1])), [11]);
+
+        let main = |path_id| PPtr {
+            file_id: 0,
+            path_id,
+        };
+        assert_eq!(info.path_of(&main(11)), Some("assets/hero.prefab"));
+        assert_eq!(info.path_of(&main(20)), Some("assets/hero.png"));
+        assert_eq!(info.path_of(&main(12)), None);
+    }
+
+    #[test]
+    fn optional_fields() {
+        let mut data = bundle();
+        if let Data::GenericStruct { fields, .. } = &mut data {
+            fields.remove("m_MainAsset");
+            fields.remove("m_Dependencies");
+            fields.remove("m_IsStreamedSceneAssetBundle");
+        }
+        let info = AssetBundleInfo::from_data(&data).unwrap();
+        assert!(info.main_asset.is_none());
+        assert!(info.dependencies.is_empty());
+
+        if let Data::GenericStruct { fields, .. } = &mut data {
+            fields.insert(
+                "m_Container".into(),
+                Data::GenericArray(vec![asset_info(0, 0, 10)]),
+            );
+        }
+        assert_eq!(
+            AssetBundleInfo::from_data(&data).err(),
+            Some(ClassError::FieldTypeMismatch("m_Container"))
+        );
+        assert_eq!(
+            AssetBundleInfo::from_data(&asset_info(0, 0, 10)).err(),
+            Some(ClassError::TypeMismatch {
+                expected: "AssetBundle",
+                found: "AssetInfo".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use super::{expect_type, field, field_with, ClassError};
+use crate::{Asset, Data};
+
+/// Bundle listed in an [`AssetBundleManifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ManifestBundle {
+    /// Key of the bundle in the manifest, used by dependencies.
+    pub index: i32,
+    pub name: String,
+    /// `AssetBundleHash`, the Hash128 of the bundle contents.
+    pub hash: [u8; 16],
+    /// Indices of the bundles this bundle depends on.
+    pub dependencies: Vec<i32>,
+    pub has_variant: bool,
+}
+
+impl ManifestBundle {
+    /// Hash formatted as hex digits, in byte order.
+    pub fn hash_hex(&self) -> String {
+        self.hash.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+fn read_hash(data: &Data<'_>) -> [u8; 16] {
+    let mut hash = [0; 16];
+    for (idx, b) in hash.iter_mut().enumerate() {
+        *b = data
+            .field(&format!("bytes[{}]", idx))
+            .and_then(Data::as_u64)
+            .unwrap_or(0) as u8;
+    }
+    hash
+}
+
+/// `AssetBundleManifest`, stored in the bundle named after the output folder of a build.
+#[derive(Clone, Debug, Default)]
+pub struct AssetBundleManifest {
+    bundles: Vec<ManifestBundle>,
+}
+
+impl AssetBundleManifest {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "AssetBundleManifest")?;
+        let mut bundles = field_with(data, "AssetBundleNames", Data::as_array)?
+            .iter()
+            .map(|entry| {
+                let (index, name) = entry
+                    .as_pair()
+                    .ok_or(ClassError::FieldTypeMismatch("AssetBundleNames"))?;
+                let index = index
+                    .as_i64()
+                    .ok_or(ClassError::FieldTypeMismatch("AssetBundleNames"))?;
+                let name = name
+                    .as_bytes()
+                    .ok_or(ClassError::FieldTypeMismatch("AssetBundleNames"))?;
+                Ok(ManifestBundle {
+                    index: index as i32,
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    hash: [0; 16],
+                    dependencies: Vec::new(),
+                    has_variant: false,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        bundles.sort_by_key(|bundle| bundle.index);
+
+        for entry in field_with(data, "AssetBundleInfos", Data::as_array)? {
+            let (index, info) = entry
+                .as_pair()
+                .ok_or(ClassError::FieldTypeMismatch("AssetBundleInfos"))?;
+            let index = index
+                .as_i64()
+                .ok_or(ClassError::FieldTypeMismatch("AssetBundleInfos"))?
+                as i32;
+            let bundle = match bundles.iter_mut().find(|bundle| bundle.index == index) {
+                Some(v) => v,
+                None => continue,
+            };
+            bundle.hash = read_hash(field(info, "AssetBundleHash")?);
+            bundle.dependencies = field_with(info, "AssetBundleDependencies", Data::as_array)?
+                .iter()
+                .map(|v| v.as_i64().map(|v| v as i32))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ClassError::FieldTypeMismatch("AssetBundleDependencies"))?;
+        }
+
+        if let Some(variants) = data
+            .field("AssetBundlesWithVariant")
+            .and_then(Data::as_array)
+        {
+            for index in variants.iter().filter_map(Data::as_i64) {
+                for bundle in bundles.iter_mut().filter(|b| i64::from(b.index) == index) {
+                    bundle.has_variant = true;
+                }
+            }
+        }
+        Ok(Self { bundles })
+    }
+
+    /// Reads the `AssetBundleManifest` object of `asset`. Returns `Ok(None)` if there is none.
+    pub fn from_asset(asset: &Asset<'_>) -> Result<Option<Self>, ClassError> {
+        asset
+            .objects()
+            .iter()
+            .find(|object| object.data.type_name() == "AssetBundleManifest")
+            .map(|object| Self::from_data(&object.data))
+            .transpose()
+    }
+
+    /// Bundles ordered by index.
+    pub fn bundles(&self) -> &[ManifestBundle] {
+        &self.bundles
+    }
+
+    pub fn bundle(&self, name: &str) -> Option<&ManifestBundle> {
+        self.bundles.iter().find(|bundle| bundle.name == name)
+    }
+
+    pub fn bundle_by_index(&self, index: i32) -> Option<&ManifestBundle> {
+        self.bundles.iter().find(|bundle| bundle.index == index)
+    }
+
+    /// Names of the direct dependencies of `bundle`.
+    pub fn dependencies<'s>(&'s self, bundle: &'s ManifestBundle) -> Vec<&'s str> {
+        bundle
+            .dependencies
+            .iter()
+            .filter_map(|&index| self.bundle_by_index(index))
+            .map(|bundle| bundle.name.as_str())
+            .collect()
+    }
+}
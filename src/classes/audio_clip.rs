@@ -0,0 +1,116 @@
+use super::{expect_type, string_field, ClassError, StreamingInfo};
+use crate::audio::{Fsb5, Sample};
+use crate::{Data, UnityFs};
+use std::borrow::Cow;
+
+/// `m_CompressionFormat` of audio clips.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AudioCompressionFormat {
+    Pcm,
+    Vorbis,
+    Adpcm,
+    Mp3,
+    Vag,
+    HeVag,
+    Xma,
+    Aac,
+    GcAdpcm,
+    Atrac9,
+    Unknown(i32),
+}
+
+impl AudioCompressionFormat {
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            0 => AudioCompressionFormat::Pcm,
+            1 => AudioCompressionFormat::Vorbis,
+            2 => AudioCompressionFormat::Adpcm,
+            3 => AudioCompressionFormat::Mp3,
+            4 => AudioCompressionFormat::Vag,
+            5 => AudioCompressionFormat::HeVag,
+            6 => AudioCompressionFormat::Xma,
+            7 => AudioCompressionFormat::Aac,
+            8 => AudioCompressionFormat::GcAdpcm,
+            9 => AudioCompressionFormat::Atrac9,
+            id => AudioCompressionFormat::Unknown(id),
+        }
+    }
+}
+
+/// `AudioClip`, whose data is an FSB5 bank since Unity 5.
+#[derive(Clone, Debug)]
+pub struct AudioClip<'a> {
+    pub name: String,
+    pub channels: u32,
+    pub frequency: u32,
+    pub bits_per_sample: u32,
+    /// Length in seconds.
+    pub length: f32,
+    /// Index of the sample of the clip in its FSB5 bank.
+    pub subsound_index: u32,
+    pub compression_format: AudioCompressionFormat,
+    /// `m_Resource`, pointing to the bank in a `.resource` node.
+    pub resource: StreamingInfo,
+    /// Audio data stored in the object before Unity 5.
+    audio_data: Cow<'a, [u8]>,
+}
+
+impl<'a> AudioClip<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        expect_type(data, "AudioClip")?;
+        let name = string_field(data, "m_Name")?;
+        let optional = |name| data.field(name).and_then(Data::as_u64).unwrap_or(0) as u32;
+        let length = data.field("m_Length").and_then(Data::as_f64).unwrap_or(0.0) as f32;
+        let compression_format = data
+            .field("m_CompressionFormat")
+            .and_then(Data::as_i64)
+            .map(|v| AudioCompressionFormat::from_id(v as i32))
+            .unwrap_or(AudioCompressionFormat::Unknown(-1));
+        let (resource, audio_data) = match (data.field("m_Resource"), data.field("m_AudioData")) {
+            (Some(v), _) => (
+                StreamingInfo::from_streamed_resource(v)?,
+                Cow::Borrowed(&[][..]),
+            ),
+            (None, Some(Data::UInt8Array(b))) => (StreamingInfo::default(), b.clone()),
+            (None, Some(_)) => return Err(ClassError::FieldTypeMismatch("m_AudioData")),
+            (None, None) => return Err(ClassError::MissingField("m_Resource")),
+        };
+        Ok(Self {
+            name,
+            channels: optional("m_Channels"),
+            frequency: optional("m_Frequency"),
+            bits_per_sample: optional("m_BitsPerSample"),
+            length,
+            subsound_index: optional("m_SubsoundIndex"),
+            compression_format,
+            resource,
+            audio_data,
+        })
+    }
+
+    /// Whether the audio data is stored in a resource node instead of the object.
+    pub fn is_streamed(&self) -> bool {
+        !self.resource.is_empty()
+    }
+
+    /// Audio data stored in the object itself. Empty since Unity 5.
+    pub fn inline_audio_data(&self) -> &[u8] {
+        &self.audio_data
+    }
+
+    /// Audio data of the clip, read from the resource nodes of `fs` if the clip is streamed.
+    ///
+    /// Since Unity 5 this is an FSB5 bank, see [`Fsb5::parse`].
+    pub fn audio_data<'s>(&'s self, fs: &UnityFs<'s>) -> Option<&'s [u8]> {
+        if self.is_streamed() {
+            self.resource.resolve(fs)
+        } else {
+            Some(&self.audio_data)
+        }
+    }
+
+    /// Finds the sample of the clip in `bank`.
+    pub fn sample<'b, 's>(&self, bank: &'b Fsb5<'s>) -> Option<&'b Sample<'s>> {
+        bank.samples().get(self.subsound_index as usize)
+    }
+}
@@ -0,0 +1,184 @@
+use super::{expect_type, field, field_with, string_field, ClassError, PPtr};
+use crate::{Asset, Data, Object};
+
+/// Built-in tags, indexed by `m_Tag`. Tags from 20000 on are defined by the project.
+const BUILTIN_TAGS: [&str; 8] = [
+    "Untagged",
+    "Respawn",
+    "Finish",
+    "EditorOnly",
+    "",
+    "MainCamera",
+    "Player",
+    "GameController",
+];
+
+/// `GameObject`, a named node of a scene or prefab holding components.
+#[derive(Clone, Debug)]
+pub struct GameObject {
+    pub name: String,
+    pub is_active: bool,
+    pub layer: u32,
+    /// Raw `m_Tag`, see [`GameObject::tag_name`].
+    pub tag: u16,
+    /// Components in inspector order, the first one being the transform.
+    pub components: Vec<PPtr>,
+}
+
+impl GameObject {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "GameObject")?;
+        let name = string_field(data, "m_Name")?;
+        let components = field_with(data, "m_Component", Data::as_array)?
+            .iter()
+            .map(|component| {
+                // a pair of class ID and component before Unity 5.5
+                let pptr = match component.as_pair() {
+                    Some((_, pptr)) => pptr,
+                    None => field(component, "component")?,
+                };
+                PPtr::from_data(pptr)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            name,
+            is_active: data
+                .field("m_IsActive")
+                .and_then(|v| v.as_bool().or_else(|| v.as_u64().map(|v| v != 0)))
+                .unwrap_or(true),
+            layer: data.field("m_Layer").and_then(Data::as_u64).unwrap_or(0) as u32,
+            tag: data.field("m_Tag").and_then(Data::as_u64).unwrap_or(0) as u16,
+            components,
+        })
+    }
+
+    /// Name of a built-in tag. Returns `None` for tags defined by the project, which are stored
+    /// in the tag manager of the player.
+    pub fn tag_name(&self) -> Option<&'static str> {
+        BUILTIN_TAGS
+            .get(self.tag as usize)
+            .copied()
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// `Vector3f`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3 {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        Ok(Self {
+            x: field_with(data, "x", Data::as_f64)? as f32,
+            y: field_with(data, "y", Data::as_f64)? as f32,
+            z: field_with(data, "z", Data::as_f64)? as f32,
+        })
+    }
+}
+
+/// `Quaternionf`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0,
+        }
+    }
+}
+
+impl Quaternion {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        Ok(Self {
+            x: field_with(data, "x", Data::as_f64)? as f32,
+            y: field_with(data, "y", Data::as_f64)? as f32,
+            z: field_with(data, "z", Data::as_f64)? as f32,
+            w: field_with(data, "w", Data::as_f64)? as f32,
+        })
+    }
+}
+
+/// `Transform` or `RectTransform`, placing a game object relative to its parent.
+#[derive(Clone, Debug)]
+pub struct Transform {
+    pub game_object: PPtr,
+    pub local_position: Vector3,
+    pub local_rotation: Quaternion,
+    pub local_scale: Vector3,
+    pub children: Vec<PPtr>,
+    /// Parent transform, null for roots.
+    pub father: PPtr,
+}
+
+impl Transform {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        if data.type_name() != "RectTransform" {
+            expect_type(data, "Transform")?;
+        }
+        let children = field_with(data, "m_Children", Data::as_array)?
+            .iter()
+            .map(PPtr::from_data)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            game_object: PPtr::from_data(field(data, "m_GameObject")?)?,
+            local_position: Vector3::from_data(field(data, "m_LocalPosition")?)?,
+            local_rotation: Quaternion::from_data(field(data, "m_LocalRotation")?)?,
+            local_scale: Vector3::from_data(field(data, "m_LocalScale")?)?,
+            children,
+            father: PPtr::from_data(field(data, "m_Father")?)?,
+        })
+    }
+}
+
+/// `MonoScript`, naming the class of `MonoBehaviour`s.
+#[derive(Clone, Debug)]
+pub struct MonoScript {
+    pub class_name: String,
+    pub namespace: String,
+    pub assembly_name: String,
+}
+
+impl MonoScript {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "MonoScript")?;
+        Ok(Self {
+            class_name: string_field(data, "m_ClassName")?,
+            namespace: string_field(data, "m_Namespace").unwrap_or_default(),
+            assembly_name: string_field(data, "m_AssemblyName").unwrap_or_default(),
+        })
+    }
+
+    /// Class name qualified with its namespace.
+    pub fn full_name(&self) -> String {
+        if self.namespace.is_empty() {
+            self.class_name.clone()
+        } else {
+            format!("{}.{}", self.namespace, self.class_name)
+        }
+    }
+
+    /// Finds the script of a `MonoBehaviour` in `asset`, even if the behaviour has no known
+    /// layout.
+    ///
+    /// Returns `None` if the script lives in another asset or can't be read.
+    pub fn of_behaviour<'b>(behaviour: &Object<'b>, asset: &Asset<'b>) -> Option<Self> {
+        let script = match behaviour.data.field("m_Script") {
+            Some(script) => PPtr::from_data(script).ok()?,
+            None => PPtr::from_data(asset.behaviour_header(behaviour)?.field("m_Script")?).ok()?,
+        };
+        Self::from_data(&script.resolve(asset)?.data).ok()
+    }
+}
@@ -0,0 +1,618 @@
+use super::mesh_data::{major_version, read_indices};
+use super::{expect_type, field, field_with, string_field, ClassError, StreamingInfo};
+use super::{SubMesh, VertexData};
+use crate::geometry::Geometry;
+use crate::{Asset, Data, Endianness, UnityFs};
+use std::borrow::Cow;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MeshError {
+    /// The vertex data is stored in a resource node which couldn't be found.
+    MissingStreamData,
+    /// The vertex data uses an unknown format or is shorter than its layout.
+    InvalidVertexData,
+}
+
+impl std::fmt::Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeshError::MissingStreamData => write!(f, "vertex data not found"),
+            MeshError::InvalidVertexData => write!(f, "invalid vertex data"),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Semantic of the vertex channels, which depends on the number of channels.
+struct ChannelLayout {
+    position: usize,
+    normal: usize,
+    tangent: usize,
+    color: usize,
+    uvs: &'static [usize],
+    blend: Option<(usize, usize)>,
+}
+
+impl ChannelLayout {
+    fn from_channel_count(count: usize) -> Self {
+        match count {
+            // Unity 4
+            0..=6 => ChannelLayout {
+                position: 0,
+                normal: 1,
+                tangent: 5,
+                color: 2,
+                uvs: &[3, 4],
+                blend: None,
+            },
+            // Unity 5 to 2017
+            7..=8 => ChannelLayout {
+                position: 0,
+                normal: 1,
+                tangent: 7,
+                color: 2,
+                uvs: &[3, 4, 5, 6],
+                blend: None,
+            },
+            // Unity 2018 and later
+            _ => ChannelLayout {
+                position: 0,
+                normal: 1,
+                tangent: 2,
+                color: 3,
+                uvs: &[4, 5, 6, 7, 8, 9, 10, 11],
+                blend: Some((12, 13)),
+            },
+        }
+    }
+}
+
+/// Items packed with a fixed number of bits, used by compressed meshes.
+#[derive(Clone, Debug, Default)]
+struct PackedBitVector<'a> {
+    item_count: usize,
+    range: f32,
+    start: f32,
+    bit_size: u32,
+    data: Cow<'a, [u8]>,
+}
+
+impl<'a> PackedBitVector<'a> {
+    fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        let item_count = field_with(data, "m_NumItems", Data::as_u64)? as usize;
+        let optional = |name| data.field(name).and_then(Data::as_f64).unwrap_or(0.0) as f32;
+        let bit_size = data.field("m_BitSize").and_then(Data::as_u64).unwrap_or(0) as u32;
+        if bit_size > 32 {
+            return Err(ClassError::FieldTypeMismatch("m_BitSize"));
+        }
+        let data = match field(data, "m_Data")? {
+            Data::UInt8Array(b) => b.clone(),
+            Data::GenericArray(items) => items
+                .iter()
+                .map(|v| v.as_u64().map(|v| v as u8))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ClassError::FieldTypeMismatch("m_Data"))?
+                .into(),
+            _ => return Err(ClassError::FieldTypeMismatch("m_Data")),
+        };
+        Ok(Self {
+            item_count,
+            range: optional("m_Range"),
+            start: optional("m_Start"),
+            bit_size,
+            data,
+        })
+    }
+
+    /// Reads `count` items starting at item `start`, least significant bits first.
+    ///
+    /// Stops early if the data is too short.
+    fn ints(&self, start: usize, count: usize) -> Vec<u32> {
+        let bit_size = self.bit_size as usize;
+        let mut values = Vec::with_capacity(count);
+        let mut pos = start * bit_size;
+        for _ in 0..count {
+            if (pos + bit_size).div_ceil(8) > self.data.len() {
+                break;
+            }
+            let mut v = 0u64;
+            for bit in 0..bit_size {
+                let p = pos + bit;
+                v |= u64::from(self.data[p / 8] >> (p % 8) & 1) << bit;
+            }
+            values.push(v as u32);
+            pos += bit_size;
+        }
+        values
+    }
+
+    /// Reads `count` items starting at item `start`, mapped to `start..start + range`.
+    fn floats(&self, start: usize, count: usize) -> Vec<f32> {
+        let max = ((1u64 << self.bit_size) - 1) as f32;
+        self.ints(start, count)
+            .into_iter()
+            .map(|v| {
+                if self.bit_size == 0 {
+                    self.start
+                } else {
+                    self.start + self.range * v as f32 / max
+                }
+            })
+            .collect()
+    }
+
+    fn all_ints(&self) -> Vec<u32> {
+        self.ints(0, self.item_count)
+    }
+
+    fn all_floats(&self) -> Vec<f32> {
+        self.floats(0, self.item_count)
+    }
+}
+
+/// `m_CompressedMesh`, storing the vertices and indices quantized when mesh compression is
+/// enabled.
+#[derive(Clone, Debug)]
+struct CompressedMesh<'a> {
+    vertices: PackedBitVector<'a>,
+    uv: PackedBitVector<'a>,
+    normals: PackedBitVector<'a>,
+    normal_signs: PackedBitVector<'a>,
+    tangents: PackedBitVector<'a>,
+    tangent_signs: PackedBitVector<'a>,
+    weights: PackedBitVector<'a>,
+    bone_indices: PackedBitVector<'a>,
+    triangles: PackedBitVector<'a>,
+    /// Colors quantized as floats since Unity 5.
+    float_colors: Option<PackedBitVector<'a>>,
+    /// Colors packed as 32-bit integers before Unity 5.
+    colors: Option<PackedBitVector<'a>>,
+    /// Dimension and presence of each UV set in 4 bits, since Unity 2017.
+    uv_info: u32,
+}
+
+impl<'a> CompressedMesh<'a> {
+    fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        let vector = |name| PackedBitVector::from_data(field(data, name)?);
+        let optional = |name| data.field(name).map(PackedBitVector::from_data).transpose();
+        Ok(Self {
+            vertices: vector("m_Vertices")?,
+            uv: vector("m_UV")?,
+            normals: vector("m_Normals")?,
+            normal_signs: vector("m_NormalSigns")?,
+            tangents: vector("m_Tangents")?,
+            tangent_signs: vector("m_TangentSigns")?,
+            weights: vector("m_Weights")?,
+            bone_indices: vector("m_BoneIndices")?,
+            triangles: vector("m_Triangles")?,
+            float_colors: optional("m_FloatColors")?,
+            colors: optional("m_Colors")?,
+            uv_info: data.field("m_UVInfo").and_then(Data::as_u64).unwrap_or(0) as u32,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.vertices.item_count == 0
+    }
+
+    /// Decompresses the vertices into `geometry`, returning the index buffer.
+    fn decompress(&self, geometry: &mut Geometry) -> Vec<u32> {
+        geometry.positions = self
+            .vertices
+            .all_floats()
+            .chunks_exact(3)
+            .map(|v| [v[0], v[1], v[2]])
+            .collect();
+        let vertex_count = geometry.positions.len();
+
+        if self.uv_info != 0 {
+            let mut offset = 0;
+            for (idx, uvs) in geometry.uvs.iter_mut().enumerate() {
+                let info = self.uv_info >> (idx * 4) & 0xf;
+                if info & 4 == 0 {
+                    continue;
+                }
+                let dimension = 1 + (info & 3) as usize;
+                *uvs = self
+                    .uv
+                    .floats(offset, dimension * vertex_count)
+                    .chunks_exact(dimension)
+                    .map(|v| [v[0], *v.get(1).unwrap_or(&0.0)])
+                    .collect();
+                offset += dimension * vertex_count;
+            }
+        } else if self.uv.item_count > 0 {
+            let uv = |start| {
+                self.uv
+                    .floats(start, 2 * vertex_count)
+                    .chunks_exact(2)
+                    .map(|v| [v[0], v[1]])
+                    .collect()
+            };
+            geometry.uvs[0] = uv(0);
+            if self.uv.item_count >= 4 * vertex_count {
+                geometry.uvs[1] = uv(2 * vertex_count);
+            }
+        }
+
+        // z is recovered from the unit length, its sign being stored separately
+        let unpack_z = |x: f32, y: f32, positive: bool| {
+            let z_squared = 1.0 - x * x - y * y;
+            let (x, y, z) = if z_squared >= 0.0 {
+                (x, y, z_squared.sqrt())
+            } else {
+                let len = (x * x + y * y).sqrt();
+                (x / len, y / len, 0.0)
+            };
+            [x, y, if positive { z } else { -z }]
+        };
+        let normal_signs = self.normal_signs.all_ints();
+        geometry.normals = self
+            .normals
+            .all_floats()
+            .chunks_exact(2)
+            .zip(normal_signs)
+            .map(|(v, sign)| unpack_z(v[0], v[1], sign != 0))
+            .collect();
+        let tangent_signs = self.tangent_signs.all_ints();
+        geometry.tangents = self
+            .tangents
+            .all_floats()
+            .chunks_exact(2)
+            .zip(tangent_signs.chunks_exact(2))
+            .map(|(v, signs)| {
+                let [x, y, z] = unpack_z(v[0], v[1], signs[0] != 0);
+                [x, y, z, if signs[1] != 0 { 1.0 } else { -1.0 }]
+            })
+            .collect();
+
+        if let Some(colors) = self.float_colors.as_ref().filter(|v| v.item_count > 0) {
+            geometry.colors = colors
+                .all_floats()
+                .chunks_exact(4)
+                .map(|c| [c[0], c[1], c[2], c[3]])
+                .collect();
+        } else if let Some(colors) = self.colors.as_ref().filter(|v| v.item_count > 0) {
+            // each color is stored as four 8-bit items
+            let bytes = PackedBitVector {
+                item_count: colors.item_count * 4,
+                bit_size: colors.bit_size / 4,
+                ..colors.clone()
+            };
+            geometry.colors = bytes
+                .all_ints()
+                .chunks_exact(4)
+                .map(|c| {
+                    let channel = |v: u32| v as f32 / 255.0;
+                    [channel(c[0]), channel(c[1]), channel(c[2]), channel(c[3])]
+                })
+                .collect();
+        }
+
+        if self.weights.item_count > 0 {
+            self.decompress_skin(geometry);
+        }
+        self.triangles.all_ints()
+    }
+
+    /// Weights are stored in 1/31 steps until they add up to one, the fourth weight of a
+    /// vertex being implicit.
+    fn decompress_skin(&self, geometry: &mut Geometry) {
+        let weights = self.weights.all_ints();
+        let mut bone_indices = self.bone_indices.all_ints().into_iter();
+        let mut weight = [0.0; 4];
+        let mut index = [0; 4];
+        let mut component = 0;
+        let mut sum = 0;
+        for w in weights {
+            weight[component] = w as f32 / 31.0;
+            index[component] = bone_indices.next().unwrap_or(0);
+            component += 1;
+            sum += w;
+            if sum >= 31 || component == 3 {
+                if sum < 31 {
+                    weight[component] = (31 - sum) as f32 / 31.0;
+                    index[component] = bone_indices.next().unwrap_or(0);
+                }
+                geometry.bone_weights.push(weight);
+                geometry.bone_indices.push(index);
+                weight = [0.0; 4];
+                index = [0; 4];
+                component = 0;
+                sum = 0;
+            }
+        }
+    }
+}
+
+/// `Mesh`, with vertex attributes stored in [`VertexData`] or in a compressed mesh.
+#[derive(Clone, Debug)]
+pub struct Mesh<'a> {
+    pub name: String,
+    pub sub_meshes: Vec<SubMesh>,
+    pub use_32bit_indices: bool,
+    pub vertex_data: VertexData<'a>,
+    /// `m_StreamData`, pointing to the vertex data in a `.resS` node since Unity 2018.3.
+    pub stream_data: StreamingInfo,
+    index_buffer: Cow<'a, [u8]>,
+    compressed_mesh: Option<CompressedMesh<'a>>,
+    /// `m_Skin` weights and bone indices, stored outside of the vertex data before Unity 2018.
+    skin: Vec<([f32; 4], [u32; 4])>,
+}
+
+impl<'a> Mesh<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        expect_type(data, "Mesh")?;
+        let name = string_field(data, "m_Name")?;
+        let sub_meshes = field_with(data, "m_SubMeshes", Data::as_array)?
+            .iter()
+            .map(SubMesh::from_data)
+            .collect::<Result<Vec<_>, _>>()?;
+        let use_32bit_indices = match (data.field("m_IndexFormat"), data.field("m_Use16BitIndices"))
+        {
+            (Some(v), _) => v.as_i64() == Some(1),
+            (None, Some(v)) => v.as_i64() == Some(0),
+            (None, None) => false,
+        };
+        let index_buffer = match field(data, "m_IndexBuffer")? {
+            Data::UInt8Array(b) => b.clone(),
+            _ => return Err(ClassError::FieldTypeMismatch("m_IndexBuffer")),
+        };
+        let vertex_data = VertexData::from_data(field(data, "m_VertexData")?)?;
+        let stream_data = match data.field("m_StreamData") {
+            Some(v) => StreamingInfo::from_data(v)?,
+            None => StreamingInfo::default(),
+        };
+        let compressed_mesh = match data.field("m_CompressedMesh") {
+            Some(v) => Some(CompressedMesh::from_data(v)?),
+            None => None,
+        };
+        let skin = match data.field("m_Skin").and_then(Data::as_array) {
+            Some(skin) => skin
+                .iter()
+                .map(|bone_weights| {
+                    let mut weight = [0.0; 4];
+                    let mut index = [0; 4];
+                    for (idx, (w, i)) in weight.iter_mut().zip(index.iter_mut()).enumerate() {
+                        let component = |name| {
+                            bone_weights
+                                .field(&format!("{}[{}]", name, idx))
+                                .ok_or(ClassError::MissingField("m_Skin"))
+                        };
+                        *w = component("weight")?.as_f64().unwrap_or(0.0) as f32;
+                        *i = component("boneIndex")?.as_u64().unwrap_or(0) as u32;
+                    }
+                    Ok((weight, index))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        Ok(Self {
+            name,
+            sub_meshes,
+            use_32bit_indices,
+            vertex_data,
+            stream_data,
+            index_buffer,
+            compressed_mesh,
+            skin,
+        })
+    }
+
+    /// Raw index buffer, with 16-bit or 32-bit indices depending on `use_32bit_indices`.
+    pub fn index_buffer(&self) -> &[u8] {
+        &self.index_buffer
+    }
+
+    /// Whether the vertices and indices are stored in the compressed mesh.
+    pub fn is_compressed(&self) -> bool {
+        self.vertex_data.vertex_count == 0
+            && self
+                .compressed_mesh
+                .as_ref()
+                .map(|mesh| !mesh.is_empty())
+                .unwrap_or(false)
+    }
+
+    /// Decodes the vertex attributes and the triangles of each submesh.
+    ///
+    /// Streamed vertex data is read from the resource nodes of `fs`.
+    pub fn geometry(&self, asset: &Asset<'_>, fs: &UnityFs<'_>) -> Result<Geometry, MeshError> {
+        let mut geometry = Geometry::default();
+        let (indices, index_size) = match &self.compressed_mesh {
+            Some(compressed_mesh) if self.is_compressed() => {
+                // compressed triangles are stored as 32-bit indices
+                (compressed_mesh.decompress(&mut geometry), 4)
+            }
+            _ => {
+                if self.stream_data.is_empty() {
+                    self.read_vertex_data(&self.vertex_data, asset, &mut geometry)?;
+                } else {
+                    let data = self
+                        .stream_data
+                        .resolve(fs)
+                        .ok_or(MeshError::MissingStreamData)?;
+                    let vertex_data = self.vertex_data.with_data(data);
+                    self.read_vertex_data(&vertex_data, asset, &mut geometry)?;
+                }
+                let index_size = if self.use_32bit_indices { 4 } else { 2 };
+                let indices = read_indices(
+                    &self.index_buffer,
+                    self.use_32bit_indices,
+                    asset.endianness(),
+                );
+                (indices, index_size)
+            }
+        };
+        if geometry.bone_weights.is_empty() && self.skin.len() == geometry.vertex_count() {
+            geometry.bone_weights = self.skin.iter().map(|(w, _)| *w).collect();
+            geometry.bone_indices = self.skin.iter().map(|(_, i)| *i).collect();
+        }
+        geometry.sub_meshes = self
+            .sub_meshes
+            .iter()
+            .map(|sub_mesh| sub_mesh.triangles(&indices, index_size))
+            .collect();
+        geometry.validate();
+        Ok(geometry)
+    }
+
+    fn read_vertex_data(
+        &self,
+        vertex_data: &VertexData<'_>,
+        asset: &Asset<'_>,
+        geometry: &mut Geometry,
+    ) -> Result<(), MeshError> {
+        let unity_version = asset.unity_version();
+        let endianness = asset.endianness();
+        let layout = ChannelLayout::from_channel_count(vertex_data.channels.len());
+        if vertex_data.vertex_count == 0 {
+            return Ok(());
+        }
+
+        geometry.positions = read_vectors(vertex_data, layout.position, unity_version, endianness)
+            .ok_or(MeshError::InvalidVertexData)?;
+        geometry.normals =
+            read_vectors(vertex_data, layout.normal, unity_version, endianness).unwrap_or_default();
+        geometry.tangents = read_vectors(vertex_data, layout.tangent, unity_version, endianness)
+            .unwrap_or_default();
+        let mut colors: Vec<[f32; 4]> =
+            read_vectors(vertex_data, layout.color, unity_version, endianness).unwrap_or_default();
+        if major_version(unity_version) >= 2018 {
+            // colors default to white when they have less than four components
+            let dimension = vertex_data
+                .channels
+                .get(layout.color)
+                .map(|channel| channel.dimension as usize)
+                .unwrap_or(4);
+            for color in colors.iter_mut() {
+                for c in color.iter_mut().skip(dimension) {
+                    *c = 1.0;
+                }
+            }
+        }
+        geometry.colors = colors;
+        for (uvs, &channel) in geometry.uvs.iter_mut().zip(layout.uvs) {
+            *uvs =
+                read_vectors(vertex_data, channel, unity_version, endianness).unwrap_or_default();
+        }
+        if let Some((weights, indices)) = layout.blend {
+            geometry.bone_weights =
+                read_vectors(vertex_data, weights, unity_version, endianness).unwrap_or_default();
+            let indices: Vec<[f32; 4]> =
+                read_vectors(vertex_data, indices, unity_version, endianness).unwrap_or_default();
+            geometry.bone_indices = indices
+                .iter()
+                .map(|i| [i[0] as u32, i[1] as u32, i[2] as u32, i[3] as u32])
+                .collect();
+        }
+        Ok(())
+    }
+}
+
+/// Reads a channel as `N`-component vectors, truncating or padding the components with zeros.
+///
+/// Returns `None` if the channel is absent or can't be read.
+fn read_vectors<T: Default + AsMut<[f32]>>(
+    vertex_data: &VertexData<'_>,
+    channel: usize,
+    unity_version: &str,
+    endianness: Endianness,
+) -> Option<Vec<T>> {
+    let values = vertex_data.read_channel(channel, unity_version, endianness)?;
+    let dimension = values.len() / (vertex_data.vertex_count as usize).max(1);
+    if dimension == 0 {
+        return None;
+    }
+    Some(
+        values
+            .chunks_exact(dimension)
+            .map(|v| {
+                let mut vector = T::default();
+                for (dst, src) in vector.as_mut().iter_mut().zip(v) {
+                    *dst = *src;
+                }
+                vector
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs `values` with `bit_size` bits each, least significant bits first.
+    fn packed(bit_size: u32, values: &[u32]) -> PackedBitVector<'static> {
+        let mut data = vec![0u8; (values.len() * bit_size as usize).div_ceil(8)];
+        for (idx, &v) in values.iter().enumerate() {
+            for bit in 0..bit_size as usize {
+                let p = idx * bit_size as usize + bit;
+                data[p / 8] |= ((v >> bit) as u8 & 1) << (p % 8);
+            }
+        }
+        PackedBitVector {
+            item_count: values.len(),
+            range: 0.0,
+            start: 0.0,
+            bit_size,
+            data: data.into(),
+        }
+    }
+
+    #[test]
+    fn packed_bit_vector() {
+        let values = [0, 31, 7, 16, 1, 30, 21];
+        let vector = packed(5, &values);
+        assert_eq!(vector.all_ints(), values);
+        assert_eq!(vector.ints(2, 3), [7, 16, 1]);
+        // the last byte pads 35 bits out to 40, so one zero item follows before the data ends
+        assert_eq!(vector.ints(6, 3), [21, 0]);
+
+        let vector = PackedBitVector {
+            range: 2.0,
+            start: -1.0,
+            ..packed(2, &[0, 3, 1])
+        };
+        assert_eq!(vector.all_floats(), [-1.0, 1.0, -1.0 + 2.0 / 3.0]);
+        let vector = PackedBitVector {
+            start: 0.5,
+            ..packed(0, &[0, 0])
+        };
+        assert_eq!(vector.all_floats(), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn skin_weights() {
+        let mesh = CompressedMesh {
+            vertices: PackedBitVector::default(),
+            uv: PackedBitVector::default(),
+            normals: PackedBitVector::default(),
+            normal_signs: PackedBitVector::default(),
+            tangents: PackedBitVector::default(),
+            tangent_signs: PackedBitVector::default(),
+            // a single bone, two bones adding up to one, and three bones with an implicit fourth
+            weights: packed(5, &[31, 10, 21, 5, 5, 5]),
+            bone_indices: packed(8, &[3, 1, 2, 4, 5, 6, 7]),
+            triangles: PackedBitVector::default(),
+            float_colors: None,
+            colors: None,
+            uv_info: 0,
+        };
+        let mut geometry = Geometry::default();
+        mesh.decompress_skin(&mut geometry);
+        assert_eq!(
+            geometry.bone_weights,
+            [
+                [1.0, 0.0, 0.0, 0.0],
+                [10.0 / 31.0, 21.0 / 31.0, 0.0, 0.0],
+                [5.0 / 31.0, 5.0 / 31.0, 5.0 / 31.0, 16.0 / 31.0],
+            ]
+        );
+        assert_eq!(
+            geometry.bone_indices,
+            [[3, 0, 0, 0], [1, 2, 0, 0], [4, 5, 6, 7]]
+        );
+    }
+}
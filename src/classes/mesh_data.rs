@@ -0,0 +1,338 @@
+use super::{field, field_with, ClassError};
+use crate::texture::half_to_f32;
+use crate::util::{read_u16, read_u32};
+use crate::{Data, Endianness};
+use std::borrow::Cow;
+
+/// Major version of a Unity version string like `2019.4.1f1`, or `u32::MAX` if it's unknown.
+pub(crate) fn major_version(unity_version: &str) -> u32 {
+    unity_version
+        .split('.')
+        .next()
+        .and_then(|v| v.parse().ok())
+        .filter(|&v| v != 0)
+        .unwrap_or(u32::MAX)
+}
+
+/// Component type of a vertex attribute.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VertexFormat {
+    Float,
+    Float16,
+    UNorm8,
+    SNorm8,
+    UNorm16,
+    SNorm16,
+    UInt8,
+    SInt8,
+    UInt16,
+    SInt16,
+    UInt32,
+    SInt32,
+}
+
+impl VertexFormat {
+    /// Converts the raw `format` of a channel, whose values were renumbered in Unity 2017 and
+    /// Unity 2019.
+    pub fn from_raw(format: u8, unity_version: &str) -> Option<Self> {
+        use VertexFormat::*;
+        let major = major_version(unity_version);
+        let formats: &[VertexFormat] = if major < 2017 {
+            &[Float, Float16, UNorm8, UInt8, UInt32]
+        } else if major < 2019 {
+            &[
+                Float, Float16, UNorm8, UNorm8, SNorm8, UNorm16, SNorm16, UInt8, SInt8, UInt16,
+                SInt16, UInt32, SInt32,
+            ]
+        } else {
+            &[
+                Float, Float16, UNorm8, SNorm8, UNorm16, SNorm16, UInt8, SInt8, UInt16, SInt16,
+                UInt32, SInt32,
+            ]
+        };
+        formats.get(format as usize).copied()
+    }
+
+    /// Size of a component in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            VertexFormat::UNorm8 | VertexFormat::SNorm8 => 1,
+            VertexFormat::UInt8 | VertexFormat::SInt8 => 1,
+            VertexFormat::Float16 | VertexFormat::UNorm16 | VertexFormat::SNorm16 => 2,
+            VertexFormat::UInt16 | VertexFormat::SInt16 => 2,
+            VertexFormat::Float | VertexFormat::UInt32 | VertexFormat::SInt32 => 4,
+        }
+    }
+
+    /// Reads a component as float, mapping normalized formats to `[0, 1]` or `[-1, 1]`.
+    fn read(self, data: &[u8], endianness: Endianness) -> f32 {
+        match self {
+            VertexFormat::Float => f32::from_bits(read_u32(data, endianness)),
+            VertexFormat::Float16 => half_to_f32(read_u16(data, endianness)),
+            VertexFormat::UNorm8 => f32::from(data[0]) / 255.0,
+            VertexFormat::SNorm8 => (f32::from(data[0] as i8) / 127.0).max(-1.0),
+            VertexFormat::UNorm16 => f32::from(read_u16(data, endianness)) / 65535.0,
+            VertexFormat::SNorm16 => {
+                (f32::from(read_u16(data, endianness) as i16) / 32767.0).max(-1.0)
+            }
+            VertexFormat::UInt8 => f32::from(data[0]),
+            VertexFormat::SInt8 => f32::from(data[0] as i8),
+            VertexFormat::UInt16 => f32::from(read_u16(data, endianness)),
+            VertexFormat::SInt16 => f32::from(read_u16(data, endianness) as i16),
+            VertexFormat::UInt32 => read_u32(data, endianness) as f32,
+            VertexFormat::SInt32 => read_u32(data, endianness) as i32 as f32,
+        }
+    }
+}
+
+/// Layout of a vertex attribute in [`VertexData`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelInfo {
+    pub stream: u8,
+    pub offset: u8,
+    /// Raw format, see [`VertexFormat::from_raw`].
+    pub format: u8,
+    /// Number of components, zero if the channel is absent.
+    pub dimension: u8,
+}
+
+impl ChannelInfo {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        let stream = field_with(data, "stream", Data::as_u64)? as u8;
+        let offset = field_with(data, "offset", Data::as_u64)? as u8;
+        let format = field_with(data, "format", Data::as_u64)? as u8;
+        // upper bits are flags since Unity 2019
+        let dimension = field_with(data, "dimension", Data::as_u64)? as u8 & 0xf;
+        Ok(Self {
+            stream,
+            offset,
+            format,
+            dimension,
+        })
+    }
+}
+
+/// Number of components of a channel. Colors were stored as a single 4-byte component before
+/// Unity 2018.
+fn channel_dimension(
+    channel: usize,
+    info: &ChannelInfo,
+    format: VertexFormat,
+    unity_version: &str,
+) -> usize {
+    if channel == 2 && format == VertexFormat::UNorm8 && major_version(unity_version) < 2018 {
+        4
+    } else {
+        info.dimension as usize
+    }
+}
+
+/// `VertexData` of meshes and sprites, storing vertex attributes in interleaved streams.
+#[derive(Clone, Debug)]
+pub struct VertexData<'a> {
+    pub vertex_count: u32,
+    /// Channels indexed by `ShaderChannel`, position being the first.
+    pub channels: Vec<ChannelInfo>,
+    /// `(offset, stride)` of the streams stored before Unity 5, computed from the channels since.
+    stored_streams: Option<Vec<(usize, usize)>>,
+    data: Cow<'a, [u8]>,
+}
+
+impl<'a> VertexData<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        let vertex_count = field_with(data, "m_VertexCount", Data::as_u64)? as u32;
+        let channels = match data.field("m_Channels").and_then(Data::as_array) {
+            Some(channels) => channels
+                .iter()
+                .map(ChannelInfo::from_data)
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        let stored_streams = match data.field("m_Streams").and_then(Data::as_array) {
+            Some(streams) => Some(
+                streams
+                    .iter()
+                    .map(|stream| {
+                        let offset = field_with(stream, "offset", Data::as_u64)?;
+                        let stride = field_with(stream, "stride", Data::as_u64)?;
+                        Ok((offset as usize, stride as usize))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+        let data = match field(data, "m_DataSize")? {
+            Data::UInt8Array(b) => b.clone(),
+            _ => return Err(ClassError::FieldTypeMismatch("m_DataSize")),
+        };
+        Ok(Self {
+            vertex_count,
+            channels,
+            stored_streams,
+            data,
+        })
+    }
+
+    /// Raw bytes of all streams.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Copies the layout of the vertex data with `data` as streams, used when the streams are
+    /// stored in a resource node.
+    pub(crate) fn with_data<'d>(&self, data: &'d [u8]) -> VertexData<'d> {
+        VertexData {
+            vertex_count: self.vertex_count,
+            channels: self.channels.clone(),
+            stored_streams: self.stored_streams.clone(),
+            data: Cow::Borrowed(data),
+        }
+    }
+
+    /// Returns `(offset, stride)` of each stream.
+    ///
+    /// Streams hold the channels with the same `stream` in order, each starting at a multiple of
+    /// 16 bytes.
+    fn streams(&self, unity_version: &str) -> Option<Vec<(usize, usize)>> {
+        if let Some(streams) = &self.stored_streams {
+            return Some(streams.clone());
+        }
+        let stream_count = self
+            .channels
+            .iter()
+            .map(|channel| channel.stream as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut offset = 0;
+        let mut streams = Vec::with_capacity(stream_count);
+        for stream in 0..stream_count {
+            let mut stride = 0;
+            for (idx, channel) in self.channels.iter().enumerate() {
+                if channel.stream as usize == stream && channel.dimension > 0 {
+                    let format = VertexFormat::from_raw(channel.format, unity_version)?;
+                    stride +=
+                        format.size() * channel_dimension(idx, channel, format, unity_version);
+                }
+            }
+            streams.push((offset, stride));
+            offset += self.vertex_count as usize * stride;
+            offset = (offset + 15) & !15;
+        }
+        Some(streams)
+    }
+
+    /// Reads `channel` of every vertex as floats, with `dimension` components per vertex.
+    ///
+    /// Returns `None` if the channel is absent, has an unknown format or the data is too short.
+    pub fn read_channel(
+        &self,
+        channel: usize,
+        unity_version: &str,
+        endianness: Endianness,
+    ) -> Option<Vec<f32>> {
+        let info = self
+            .channels
+            .get(channel)
+            .filter(|info| info.dimension > 0)?;
+        let format = VertexFormat::from_raw(info.format, unity_version)?;
+        let (stream_offset, stride) = *self.streams(unity_version)?.get(info.stream as usize)?;
+        let size = format.size();
+        let dimension = channel_dimension(channel, info, format, unity_version);
+        let mut values = Vec::with_capacity(self.vertex_count as usize * dimension);
+        for vertex in 0..self.vertex_count as usize {
+            let start = stream_offset + vertex * stride + info.offset as usize;
+            let data = self.data.get(start..start + size * dimension)?;
+            values.extend(data.chunks(size).map(|c| format.read(c, endianness)));
+        }
+        Some(values)
+    }
+}
+
+/// Range of the index buffer drawn with one material.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SubMesh {
+    pub first_byte: u32,
+    pub index_count: u32,
+    /// Raw `MeshTopology`, `0` being triangles, `1` triangle strips and `2` quads.
+    pub topology: i32,
+    /// Offset added to the indices, present since Unity 2017.3.
+    pub base_vertex: u32,
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+}
+
+impl SubMesh {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        let first_byte = field_with(data, "firstByte", Data::as_u64)? as u32;
+        let index_count = field_with(data, "indexCount", Data::as_u64)? as u32;
+        let topology = data
+            .field("topology")
+            .or_else(|| data.field("isTriStrip"))
+            .and_then(Data::as_i64)
+            .unwrap_or(0) as i32;
+        let optional = |name| data.field(name).and_then(Data::as_u64).unwrap_or(0) as u32;
+        Ok(Self {
+            first_byte,
+            index_count,
+            topology,
+            base_vertex: optional("baseVertex"),
+            first_vertex: optional("firstVertex"),
+            vertex_count: optional("vertexCount"),
+        })
+    }
+
+    /// Reads the triangles of the submesh from `indices`, an index buffer whose indices were
+    /// stored in `index_size` bytes.
+    ///
+    /// Triangle strips and quads are converted to triangles. Other topologies yield no triangles.
+    pub fn triangles(&self, indices: &[u32], index_size: usize) -> Vec<[u32; 3]> {
+        let start = std::cmp::min(self.first_byte as usize / index_size, indices.len());
+        let end = std::cmp::min(start + self.index_count as usize, indices.len());
+        let indices = indices[start..end]
+            .iter()
+            .map(|&idx| idx + self.base_vertex)
+            .collect::<Vec<_>>();
+        match self.topology {
+            0 => indices
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect(),
+            1 => indices
+                .windows(3)
+                .enumerate()
+                .filter(|(_, w)| w[0] != w[1] && w[1] != w[2] && w[0] != w[2])
+                .map(|(idx, w)| {
+                    if idx % 2 == 0 {
+                        [w[0], w[1], w[2]]
+                    } else {
+                        [w[1], w[0], w[2]]
+                    }
+                })
+                .collect(),
+            2 => indices
+                .chunks_exact(4)
+                .flat_map(|c| vec![[c[0], c[1], c[2]], [c[0], c[2], c[3]]])
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Reads an index buffer of 16-bit or 32-bit indices.
+pub(crate) fn read_indices(
+    index_buffer: &[u8],
+    use_32bit_indices: bool,
+    endianness: Endianness,
+) -> Vec<u32> {
+    if use_32bit_indices {
+        index_buffer
+            .chunks_exact(4)
+            .map(|c| read_u32(c, endianness))
+            .collect()
+    } else {
+        index_buffer
+            .chunks_exact(2)
+            .map(|c| u32::from(read_u16(c, endianness)))
+            .collect()
+    }
+}
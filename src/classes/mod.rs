@@ -0,0 +1,139 @@
+mod asset_bundle;
+mod asset_bundle_manifest;
+mod audio_clip;
+mod game_object;
+mod mesh;
+mod mesh_data;
+mod sprite;
+mod streaming_info;
+mod text_asset;
+mod texture2d;
+mod texture_array;
+
+use crate::{Asset, Data, Object};
+
+pub use asset_bundle::{AssetBundleInfo, AssetInfo};
+pub use asset_bundle_manifest::{AssetBundleManifest, ManifestBundle};
+pub use audio_clip::{AudioClip, AudioCompressionFormat};
+pub use game_object::{GameObject, MonoScript, Quaternion, Transform, Vector3};
+pub use mesh::{Mesh, MeshError};
+pub use mesh_data::{ChannelInfo, SubMesh, VertexData, VertexFormat};
+pub use sprite::{
+    Rect, RenderDataKey, Sprite, SpriteAtlas, SpriteError, SpritePackingMode,
+    SpritePackingRotation, SpriteRenderData, SpriteSettings, Vector2,
+};
+pub use streaming_info::StreamingInfo;
+pub use text_asset::{TextAsset, TextAssetFormat};
+pub use texture2d::{ColorSpace, Texture2D, TextureDimension, TextureFormat};
+pub use texture_array::{Cubemap, TextureArray};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ClassError {
+    TypeMismatch {
+        expected: &'static str,
+        found: String,
+    },
+    MissingField(&'static str),
+    FieldTypeMismatch(&'static str),
+}
+
+impl std::fmt::Display for ClassError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClassError::TypeMismatch { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            ClassError::MissingField(name) => write!(f, "{} not found", name),
+            ClassError::FieldTypeMismatch(name) => write!(f, "{} type mismatch", name),
+        }
+    }
+}
+
+impl std::error::Error for ClassError {}
+
+/// Reference to an object, in the same asset if `file_id` is zero or in an external asset
+/// otherwise.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PPtr {
+    pub file_id: i32,
+    pub path_id: i64,
+}
+
+impl PPtr {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        let (file_id, path_id) = data.as_pptr().ok_or_else(|| ClassError::TypeMismatch {
+            expected: "PPtr",
+            found: data.type_name().to_owned(),
+        })?;
+        Ok(Self { file_id, path_id })
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.path_id == 0
+    }
+
+    /// Finds the referenced object in `asset`. Returns `None` if it lives in an external asset.
+    pub fn resolve<'s, 'b>(&self, asset: &'s Asset<'b>) -> Option<&'s Object<'b>> {
+        if self.file_id != 0 || self.is_null() {
+            return None;
+        }
+        asset.object(self.path_id as u64)
+    }
+}
+
+fn expect_type(data: &Data<'_>, expected: &'static str) -> Result<(), ClassError> {
+    if data.type_name() == expected {
+        Ok(())
+    } else {
+        Err(ClassError::TypeMismatch {
+            expected,
+            found: data.type_name().to_owned(),
+        })
+    }
+}
+
+fn field<'d, 'b>(data: &'d Data<'b>, name: &'static str) -> Result<&'d Data<'b>, ClassError> {
+    data.field(name).ok_or(ClassError::MissingField(name))
+}
+
+fn field_with<'d, 'b, T>(
+    data: &'d Data<'b>,
+    name: &'static str,
+    f: impl FnOnce(&'d Data<'b>) -> Option<T>,
+) -> Result<T, ClassError> {
+    f(field(data, name)?).ok_or(ClassError::FieldTypeMismatch(name))
+}
+
+fn string_field(data: &Data<'_>, name: &'static str) -> Result<String, ClassError> {
+    field_with(data, name, Data::as_bytes).map(|s| String::from_utf8_lossy(s).into_owned())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Struct of class `type_name`, with its fields in order.
+    pub(crate) fn structure(
+        type_name: &'static str,
+        fields: Vec<(&'static str, Data<'static>)>,
+    ) -> Data<'static> {
+        Data::GenericStruct {
+            type_name: type_name.into(),
+            fields: fields.into_iter().collect(),
+        }
+    }
+
+    pub(crate) fn pptr(file_id: i32, path_id: i64) -> Data<'static> {
+        structure(
+            "PPtr<Object>",
+            vec![
+                ("m_FileID", Data::SInt32(file_id)),
+                ("m_PathID", Data::SInt64(path_id)),
+            ],
+        )
+    }
+
+    pub(crate) fn string(s: &'static str) -> Data<'static> {
+        Data::String(s.as_bytes().into())
+    }
+}
@@ -0,0 +1,705 @@
+use super::mesh_data::read_indices;
+use super::{
+    expect_type, field, field_with, string_field, ClassError, PPtr, SubMesh, Texture2D, VertexData,
+};
+use crate::texture::{self, CrunchVariant, DecodeError, Image, Pixels};
+use crate::{Asset, Data, Endianness, UnityFs};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// `Rectf`, with the origin at the bottom left.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        Ok(Self {
+            x: field_with(data, "x", Data::as_f64)? as f32,
+            y: field_with(data, "y", Data::as_f64)? as f32,
+            width: field_with(data, "width", Data::as_f64)? as f32,
+            height: field_with(data, "height", Data::as_f64)? as f32,
+        })
+    }
+}
+
+/// `Vector2f`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vector2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vector2 {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        Ok(Self {
+            x: field_with(data, "x", Data::as_f64)? as f32,
+            y: field_with(data, "y", Data::as_f64)? as f32,
+        })
+    }
+}
+
+/// How a sprite is packed into its texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SpritePackingMode {
+    /// Only the pixels covered by the sprite mesh belong to the sprite.
+    Tight,
+    Rectangle,
+}
+
+/// Transform applied to a sprite when it was packed into its texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SpritePackingRotation {
+    None,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate180,
+    /// Rotated 90 degrees clockwise.
+    Rotate90,
+}
+
+impl SpritePackingRotation {
+    pub fn from_id(id: u32) -> Self {
+        match id {
+            1 => SpritePackingRotation::FlipHorizontal,
+            2 => SpritePackingRotation::FlipVertical,
+            3 => SpritePackingRotation::Rotate180,
+            4 => SpritePackingRotation::Rotate90,
+            _ => SpritePackingRotation::None,
+        }
+    }
+}
+
+/// Bit fields of `settingsRaw`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteSettings {
+    pub packed: bool,
+    pub packing_mode: SpritePackingMode,
+    pub packing_rotation: SpritePackingRotation,
+    /// `0` for a full rect mesh, `1` for a tight mesh.
+    pub mesh_type: u32,
+}
+
+impl SpriteSettings {
+    pub fn from_raw(raw: u32) -> Self {
+        let packing_mode = if (raw >> 1) & 1 == 0 {
+            SpritePackingMode::Tight
+        } else {
+            SpritePackingMode::Rectangle
+        };
+        Self {
+            packed: raw & 1 != 0,
+            packing_mode,
+            packing_rotation: SpritePackingRotation::from_id((raw >> 2) & 0xf),
+            mesh_type: (raw >> 6) & 1,
+        }
+    }
+}
+
+/// Region of a texture holding a sprite, stored in `m_RD` of the sprite or in the
+/// `m_RenderDataMap` of its atlas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteRenderData {
+    pub texture: PPtr,
+    pub alpha_texture: PPtr,
+    pub texture_rect: Rect,
+    /// Offset of `texture_rect` from the bottom left of the untrimmed sprite rect.
+    pub texture_rect_offset: Vector2,
+    pub settings: SpriteSettings,
+}
+
+impl SpriteRenderData {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        let texture = PPtr::from_data(field(data, "texture")?)?;
+        let alpha_texture = match data.field("alphaTexture") {
+            Some(v) => PPtr::from_data(v)?,
+            None => PPtr::default(),
+        };
+        let texture_rect = Rect::from_data(field(data, "textureRect")?)?;
+        let texture_rect_offset = Vector2::from_data(field(data, "textureRectOffset")?)?;
+        let settings = field_with(data, "settingsRaw", Data::as_u64)?;
+        Ok(Self {
+            texture,
+            alpha_texture,
+            texture_rect,
+            texture_rect_offset,
+            settings: SpriteSettings::from_raw(settings as u32),
+        })
+    }
+}
+
+/// `m_RenderDataKey` of a sprite, a GUID and a local identifier.
+pub type RenderDataKey = ([u32; 4], i64);
+
+fn render_data_key(data: &Data<'_>) -> Option<RenderDataKey> {
+    let (guid, id) = data.as_pair()?;
+    let mut key = [0; 4];
+    for (idx, v) in key.iter_mut().enumerate() {
+        *v = guid.field(&format!("data[{}]", idx))?.as_u64()? as u32;
+    }
+    Some((key, id.as_i64()?))
+}
+
+#[derive(Clone, Debug)]
+enum SpriteMesh<'a> {
+    /// Mesh data since Unity 5.6.
+    VertexData {
+        sub_meshes: Vec<SubMesh>,
+        index_buffer: Cow<'a, [u8]>,
+        vertex_data: VertexData<'a>,
+    },
+    Vertices {
+        positions: Vec<[f32; 2]>,
+        indices: Vec<u16>,
+    },
+}
+
+impl<'a> SpriteMesh<'a> {
+    fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        if let Some(vertex_data) = data.field("m_VertexData") {
+            let sub_meshes = field_with(data, "m_SubMeshes", Data::as_array)?
+                .iter()
+                .map(SubMesh::from_data)
+                .collect::<Result<Vec<_>, _>>()?;
+            let index_buffer = match field(data, "m_IndexBuffer")? {
+                Data::UInt8Array(b) => b.clone(),
+                _ => return Err(ClassError::FieldTypeMismatch("m_IndexBuffer")),
+            };
+            return Ok(SpriteMesh::VertexData {
+                sub_meshes,
+                index_buffer,
+                vertex_data: VertexData::from_data(vertex_data)?,
+            });
+        }
+        let positions = field_with(data, "vertices", Data::as_array)?
+            .iter()
+            .map(|vertex| {
+                let pos = field(vertex, "pos")?;
+                let x = field_with(pos, "x", Data::as_f64)?;
+                let y = field_with(pos, "y", Data::as_f64)?;
+                Ok([x as f32, y as f32])
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let indices = field_with(data, "indices", Data::as_array)?
+            .iter()
+            .map(|v| v.as_u64().map(|v| v as u16))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ClassError::FieldTypeMismatch("indices"))?;
+        Ok(SpriteMesh::Vertices { positions, indices })
+    }
+
+    fn triangles(&self, unity_version: &str, endianness: Endianness) -> Vec<[[f32; 2]; 3]> {
+        let (positions, triangles) = match self {
+            SpriteMesh::VertexData {
+                sub_meshes,
+                index_buffer,
+                vertex_data,
+            } => {
+                let positions = match vertex_data.read_channel(0, unity_version, endianness) {
+                    Some(v) => v,
+                    None => return Vec::new(),
+                };
+                let dimension = vertex_data.channels[0].dimension as usize;
+                let positions = positions
+                    .chunks_exact(dimension)
+                    .map(|v| [v[0], *v.get(1).unwrap_or(&0.0)])
+                    .collect::<Vec<_>>();
+                let indices = read_indices(index_buffer, false, endianness);
+                let triangles = if sub_meshes.is_empty() {
+                    indices
+                        .chunks_exact(3)
+                        .map(|c| [c[0], c[1], c[2]])
+                        .collect()
+                } else {
+                    sub_meshes
+                        .iter()
+                        .flat_map(|sub_mesh| sub_mesh.triangles(&indices, 2))
+                        .collect::<Vec<_>>()
+                };
+                (Cow::Owned(positions), triangles)
+            }
+            SpriteMesh::Vertices { positions, indices } => {
+                let triangles = indices
+                    .chunks_exact(3)
+                    .map(|c| [c[0].into(), c[1].into(), c[2].into()])
+                    .collect::<Vec<[u32; 3]>>();
+                (Cow::Borrowed(positions), triangles)
+            }
+        };
+        triangles
+            .iter()
+            .filter_map(|t| {
+                let vertex = |idx: u32| positions.get(idx as usize).copied();
+                Some([vertex(t[0])?, vertex(t[1])?, vertex(t[2])?])
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SpriteError {
+    Class(ClassError),
+    /// The texture is null or stored in an external asset.
+    MissingTexture,
+    UnknownFormat(i32),
+    /// The image data is streamed from another bundle.
+    MissingImageData,
+    Decode(DecodeError),
+}
+
+impl std::fmt::Display for SpriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpriteError::Class(e) => write!(f, "{}", e),
+            SpriteError::MissingTexture => write!(f, "texture not found"),
+            SpriteError::UnknownFormat(id) => write!(f, "unknown texture format {}", id),
+            SpriteError::MissingImageData => write!(f, "image data not found"),
+            SpriteError::Decode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SpriteError {}
+
+#[derive(Clone, Debug)]
+pub struct Sprite<'a> {
+    pub name: String,
+    /// Untrimmed rect of the sprite in its source texture.
+    pub rect: Rect,
+    pub offset: Vector2,
+    /// Pivot relative to `rect`, `(0.5, 0.5)` being the center.
+    pub pivot: Vector2,
+    pub pixels_to_units: f32,
+    pub render_data_key: Option<RenderDataKey>,
+    pub sprite_atlas: PPtr,
+    /// Render data of the sprite itself, see [`Sprite::resolve_render_data`].
+    pub render_data: SpriteRenderData,
+    mesh: SpriteMesh<'a>,
+}
+
+impl<'a> Sprite<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        expect_type(data, "Sprite")?;
+        let name = string_field(data, "m_Name")?;
+        let rect = Rect::from_data(field(data, "m_Rect")?)?;
+        let offset = Vector2::from_data(field(data, "m_Offset")?)?;
+        let pivot = match data.field("m_Pivot") {
+            Some(v) => Vector2::from_data(v)?,
+            None => Vector2 { x: 0.5, y: 0.5 },
+        };
+        let pixels_to_units = field_with(data, "m_PixelsToUnits", Data::as_f64)? as f32;
+        let render_data_key = data.field("m_RenderDataKey").and_then(render_data_key);
+        let sprite_atlas = match data.field("m_SpriteAtlas") {
+            Some(v) => PPtr::from_data(v)?,
+            None => PPtr::default(),
+        };
+        let rd = field(data, "m_RD")?;
+        Ok(Self {
+            name,
+            rect,
+            offset,
+            pivot,
+            pixels_to_units,
+            render_data_key,
+            sprite_atlas,
+            render_data: SpriteRenderData::from_data(rd)?,
+            mesh: SpriteMesh::from_data(rd)?,
+        })
+    }
+
+    /// Render data of the sprite, taken from its atlas if the atlas is stored in `asset`.
+    pub fn resolve_render_data(&self, asset: &Asset<'_>) -> Result<SpriteRenderData, ClassError> {
+        let (atlas, key) = match (self.sprite_atlas.resolve(asset), &self.render_data_key) {
+            (Some(atlas), Some(key)) => (atlas, key),
+            _ => return Ok(self.render_data.clone()),
+        };
+        let atlas = SpriteAtlas::from_data(&atlas.data)?;
+        Ok(atlas
+            .render_data(key)
+            .cloned()
+            .unwrap_or_else(|| self.render_data.clone()))
+    }
+
+    /// Triangles of the sprite mesh, in units relative to the pivot.
+    ///
+    /// `unity_version` and `endianness` of the asset are needed to read the vertex data.
+    pub fn mesh_triangles(
+        &self,
+        unity_version: &str,
+        endianness: Endianness,
+    ) -> Vec<[[f32; 2]; 3]> {
+        self.mesh.triangles(unity_version, endianness)
+    }
+
+    /// Cuts the sprite out of `texture`, the decoded texture of `render_data`.
+    ///
+    /// The region is unrotated if the sprite was packed with a rotation, and pixels outside of
+    /// the sprite mesh are cleared if it was packed tightly.
+    pub fn cut(
+        &self,
+        texture: &Image,
+        render_data: &SpriteRenderData,
+        unity_version: &str,
+        endianness: Endianness,
+    ) -> Image {
+        let (texture_width, texture_height) = (texture.width as usize, texture.height as usize);
+        let rect = &render_data.texture_rect;
+        let x = std::cmp::min(rect.x.floor().max(0.0) as usize, texture_width);
+        let y = std::cmp::min(rect.y.floor().max(0.0) as usize, texture_height);
+        let right = std::cmp::min(
+            (rect.x + rect.width).ceil().max(0.0) as usize,
+            texture_width,
+        );
+        let top = std::cmp::min(
+            (rect.y + rect.height).ceil().max(0.0) as usize,
+            texture_height,
+        );
+
+        let settings = &render_data.settings;
+        let rotation = if settings.packed {
+            settings.packing_rotation
+        } else {
+            SpritePackingRotation::None
+        };
+        let mask = if settings.packing_mode == SpritePackingMode::Tight {
+            let scale = self.pixels_to_units;
+            let dx = self.rect.width * self.pivot.x - render_data.texture_rect_offset.x;
+            let dy = self.rect.height * self.pivot.y - render_data.texture_rect_offset.y;
+            let triangles = self
+                .mesh_triangles(unity_version, endianness)
+                .into_iter()
+                .map(|t| {
+                    let transform = |v: [f32; 2]| [v[0] * scale + dx, v[1] * scale + dy];
+                    [transform(t[0]), transform(t[1]), transform(t[2])]
+                })
+                .collect::<Vec<_>>();
+            Some(triangles).filter(|t| !t.is_empty())
+        } else {
+            None
+        };
+        let cut = Cut {
+            x,
+            y,
+            width: right.saturating_sub(x),
+            height: top.saturating_sub(y),
+            rotation,
+            mask: mask.as_ref().map(|t| &t[..]),
+        };
+
+        let (pixels, width, height) = match &texture.pixels {
+            Pixels::Rgba8(v) => {
+                let (v, w, h) = cut.apply(v, texture_width, texture_height);
+                (Pixels::Rgba8(v), w, h)
+            }
+            Pixels::Rgba32F(v) => {
+                let (v, w, h) = cut.apply(v, texture_width, texture_height);
+                (Pixels::Rgba32F(v), w, h)
+            }
+        };
+        Image {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+        }
+    }
+
+    /// Decodes the texture of the sprite in `asset` and cuts the sprite out of it.
+    ///
+    /// Only formats supported by [`texture::decode_with_crunch`] can be decoded.
+    pub fn decode(&self, asset: &Asset<'_>, fs: &UnityFs<'_>) -> Result<Image, SpriteError> {
+        let render_data = self
+            .resolve_render_data(asset)
+            .map_err(SpriteError::Class)?;
+        let object = render_data
+            .texture
+            .resolve(asset)
+            .ok_or(SpriteError::MissingTexture)?;
+        let texture = Texture2D::from_data(&object.data).map_err(SpriteError::Class)?;
+        let format = texture
+            .format()
+            .ok_or(SpriteError::UnknownFormat(texture.texture_format))?;
+        let data = texture
+            .image_data(fs)
+            .ok_or(SpriteError::MissingImageData)?;
+        let crunch = if texture.is_unity_crunch(asset.unity_version()) {
+            CrunchVariant::Unity
+        } else {
+            CrunchVariant::Original
+        };
+        let image =
+            texture::decode_with_crunch(format, texture.width, texture.height, data, crunch)
+                .map_err(SpriteError::Decode)?;
+        Ok(self.cut(
+            &image,
+            &render_data,
+            asset.unity_version(),
+            asset.endianness(),
+        ))
+    }
+}
+
+/// Region of a texture to cut out, in bottom-up coordinates.
+struct Cut<'t> {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    rotation: SpritePackingRotation,
+    /// Triangles in pixels relative to the bottom left of the unrotated region.
+    mask: Option<&'t [[[f32; 2]; 3]]>,
+}
+
+impl Cut<'_> {
+    /// Cuts the region out of top-down `pixels`, returning top-down pixels and their size.
+    fn apply<T: Copy + Default>(
+        &self,
+        pixels: &[T],
+        texture_width: usize,
+        texture_height: usize,
+    ) -> (Vec<T>, usize, usize) {
+        let (width, height) = (self.width, self.height);
+        let mut region = Vec::with_capacity(width * height * 4);
+        for row in 0..height {
+            let start = ((texture_height - 1 - (self.y + row)) * texture_width + self.x) * 4;
+            region.extend_from_slice(&pixels[start..start + width * 4]);
+        }
+
+        let remap = |new_width: usize, new_height: usize, f: &dyn Fn(usize, usize) -> usize| {
+            let mut out = Vec::with_capacity(new_width * new_height * 4);
+            for y in 0..new_height {
+                for x in 0..new_width {
+                    let idx = f(x, y) * 4;
+                    out.extend_from_slice(&region[idx..idx + 4]);
+                }
+            }
+            out
+        };
+        let (mut region, width, height) = match self.rotation {
+            SpritePackingRotation::None => (region, width, height),
+            SpritePackingRotation::FlipHorizontal => (
+                remap(width, height, &|x, y| y * width + (width - 1 - x)),
+                width,
+                height,
+            ),
+            SpritePackingRotation::FlipVertical => (
+                remap(width, height, &|x, y| (height - 1 - y) * width + x),
+                width,
+                height,
+            ),
+            SpritePackingRotation::Rotate180 => (
+                remap(width, height, &|x, y| {
+                    (height - 1 - y) * width + (width - 1 - x)
+                }),
+                width,
+                height,
+            ),
+            SpritePackingRotation::Rotate90 => (
+                remap(height, width, &|x, y| x * width + (width - 1 - y)),
+                height,
+                width,
+            ),
+        };
+
+        if let Some(triangles) = self.mask {
+            let mut covered = vec![false; width * height];
+            for t in triangles {
+                let min = |c: usize| t[0][c].min(t[1][c]).min(t[2][c]);
+                let max = |c: usize| t[0][c].max(t[1][c]).max(t[2][c]);
+                let x0 = min(0).floor().max(0.0) as usize;
+                let y0 = min(1).floor().max(0.0) as usize;
+                let x1 = std::cmp::min(max(0).ceil().max(0.0) as usize, width);
+                let y1 = std::cmp::min(max(1).ceil().max(0.0) as usize, height);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        if triangle_contains(t, x as f32 + 0.5, y as f32 + 0.5) {
+                            covered[y * width + x] = true;
+                        }
+                    }
+                }
+            }
+            for (pixel, &covered) in region.chunks_mut(4).zip(covered.iter()) {
+                if !covered {
+                    for c in pixel.iter_mut() {
+                        *c = T::default();
+                    }
+                }
+            }
+        }
+
+        let pixels = if width == 0 {
+            Vec::new()
+        } else {
+            region.chunks(width * 4).rev().flatten().copied().collect()
+        };
+        (pixels, width, height)
+    }
+}
+
+fn triangle_contains(t: &[[f32; 2]; 3], x: f32, y: f32) -> bool {
+    let side = |a: [f32; 2], b: [f32; 2]| (x - b[0]) * (a[1] - b[1]) - (a[0] - b[0]) * (y - b[1]);
+    let d = [side(t[0], t[1]), side(t[1], t[2]), side(t[2], t[0])];
+    let negative = d.iter().any(|&d| d < 0.0);
+    let positive = d.iter().any(|&d| d > 0.0);
+    !(negative && positive)
+}
+
+/// `SpriteAtlas`, holding the render data of the sprites packed into it.
+#[derive(Clone, Debug)]
+pub struct SpriteAtlas {
+    pub name: String,
+    pub packed_sprites: Vec<PPtr>,
+    pub is_variant: bool,
+    render_data_map: HashMap<RenderDataKey, SpriteRenderData>,
+}
+
+impl SpriteAtlas {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "SpriteAtlas")?;
+        let name = string_field(data, "m_Name")?;
+        let packed_sprites = match data.field("m_PackedSprites").and_then(Data::as_array) {
+            Some(v) => v.iter().map(PPtr::from_data).collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        let is_variant = data
+            .field("m_IsVariant")
+            .and_then(Data::as_bool)
+            .unwrap_or(false);
+        let render_data_map = field_with(data, "m_RenderDataMap", Data::as_array)?
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry
+                    .as_pair()
+                    .ok_or(ClassError::FieldTypeMismatch("m_RenderDataMap"))?;
+                let key =
+                    render_data_key(key).ok_or(ClassError::FieldTypeMismatch("m_RenderDataMap"))?;
+                Ok((key, SpriteRenderData::from_data(value)?))
+            })
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(Self {
+            name,
+            packed_sprites,
+            is_variant,
+            render_data_map,
+        })
+    }
+
+    /// Render data of the sprite with `m_RenderDataKey` of `key`.
+    pub fn render_data(&self, key: &RenderDataKey) -> Option<&SpriteRenderData> {
+        self.render_data_map.get(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTURE_WIDTH: usize = 4;
+    const TEXTURE_HEIGHT: usize = 3;
+
+    /// Top-down RGBA8 texture whose pixels hold their bottom-up coordinates in red and green.
+    fn texture() -> Vec<u8> {
+        (0..TEXTURE_HEIGHT)
+            .rev()
+            .flat_map(|y| (0..TEXTURE_WIDTH).flat_map(move |x| vec![x as u8, y as u8, 0, 0xff]))
+            .collect()
+    }
+
+    /// Coordinates held by the top-down rows of `pixels`, `None` for cleared pixels.
+    fn coordinates(pixels: &[u8], width: usize) -> Vec<Vec<Option<(u8, u8)>>> {
+        pixels
+            .chunks(width * 4)
+            .map(|row| {
+                row.chunks(4)
+                    .map(|p| Some((p[0], p[1])).filter(|_| p[3] != 0))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn cut(rotation: SpritePackingRotation, mask: Option<&[[[f32; 2]; 3]]>) -> Cut<'_> {
+        Cut {
+            x: 1,
+            y: 0,
+            width: 3,
+            height: 2,
+            rotation,
+            mask,
+        }
+    }
+
+    #[test]
+    fn rotations() {
+        let texture = texture();
+        let cases: [(SpritePackingRotation, &[&[(u8, u8)]]); 5] = [
+            (
+                SpritePackingRotation::None,
+                &[&[(1, 1), (2, 1), (3, 1)], &[(1, 0), (2, 0), (3, 0)]],
+            ),
+            (
+                SpritePackingRotation::FlipHorizontal,
+                &[&[(3, 1), (2, 1), (1, 1)], &[(3, 0), (2, 0), (1, 0)]],
+            ),
+            (
+                SpritePackingRotation::FlipVertical,
+                &[&[(1, 0), (2, 0), (3, 0)], &[(1, 1), (2, 1), (3, 1)]],
+            ),
+            (
+                SpritePackingRotation::Rotate180,
+                &[&[(3, 0), (2, 0), (1, 0)], &[(3, 1), (2, 1), (1, 1)]],
+            ),
+            // packed rotated, so rotating the region clockwise restores the sprite
+            (
+                SpritePackingRotation::Rotate90,
+                &[&[(1, 0), (1, 1)], &[(2, 0), (2, 1)], &[(3, 0), (3, 1)]],
+            ),
+        ];
+        for (rotation, expected) in cases.iter() {
+            let (pixels, width, height) =
+                cut(*rotation, None).apply(&texture, TEXTURE_WIDTH, TEXTURE_HEIGHT);
+            assert_eq!((width, height), (expected[0].len(), expected.len()));
+            let expected = expected
+                .iter()
+                .map(|row| row.iter().copied().map(Some).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            assert_eq!(coordinates(&pixels, width), expected, "{:?}", rotation);
+        }
+    }
+
+    #[test]
+    fn triangle_mask() {
+        // covers the centers of the two bottom left pixels and the one above them
+        let mask = [[[0.0, 0.0], [3.0, 0.0], [0.0, 2.0]]];
+        let (pixels, width, height) = cut(SpritePackingRotation::None, Some(&mask)).apply(
+            &texture(),
+            TEXTURE_WIDTH,
+            TEXTURE_HEIGHT,
+        );
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(
+            coordinates(&pixels, width),
+            [
+                [Some((1, 1)), None, None],
+                [Some((1, 0)), Some((2, 0)), None]
+            ]
+        );
+        // masked out pixels are cleared entirely
+        assert_eq!(pixels[4..8], [0; 4]);
+    }
+
+    #[test]
+    fn triangle_winding() {
+        let counter_clockwise = [[0.0, 0.0], [2.0, 0.0], [0.0, 2.0]];
+        let clockwise = [[0.0, 0.0], [0.0, 2.0], [2.0, 0.0]];
+        for t in &[counter_clockwise, clockwise] {
+            assert!(triangle_contains(t, 0.5, 0.5));
+            // points on an edge are inside
+            assert!(triangle_contains(t, 1.0, 1.0));
+            assert!(!triangle_contains(t, 1.5, 1.5));
+            assert!(!triangle_contains(t, -0.5, 0.5));
+        }
+    }
+}
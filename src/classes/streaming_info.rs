@@ -0,0 +1,41 @@
+use super::{expect_type, field_with, string_field, ClassError};
+use crate::{Data, UnityFs};
+
+/// Location of data stored outside of the object, usually in a `.resS` node.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StreamingInfo {
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl StreamingInfo {
+    pub fn from_data(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "StreamingInfo")?;
+        let path = string_field(data, "path")?;
+        let offset = field_with(data, "offset", Data::as_u64)?;
+        let size = field_with(data, "size", Data::as_u64)?;
+        Ok(Self { path, offset, size })
+    }
+
+    /// Reads a `StreamedResource`, which stores the same location with different field names.
+    pub fn from_streamed_resource(data: &Data<'_>) -> Result<Self, ClassError> {
+        expect_type(data, "StreamedResource")?;
+        let path = string_field(data, "m_Source")?;
+        let offset = field_with(data, "m_Offset", Data::as_u64)?;
+        let size = field_with(data, "m_Size", Data::as_u64)?;
+        Ok(Self { path, offset, size })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// Reads the referenced bytes from `fs`. Returns `None` if the data lives in another bundle.
+    pub fn resolve<'a>(&self, fs: &UnityFs<'a>) -> Option<&'a [u8]> {
+        let resource = fs.resolve_path(&self.path)?;
+        let start = self.offset as usize;
+        let end = start.checked_add(self.size as usize)?;
+        resource.get(start..end)
+    }
+}
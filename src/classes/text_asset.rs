@@ -0,0 +1,210 @@
+use super::{expect_type, field, string_field, ClassError};
+use crate::Data;
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/// Format of the contents of a [`TextAsset`], guessed from its bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextAssetFormat {
+    Json,
+    Xml,
+    Text,
+    /// Serialized protocol buffer message.
+    Protobuf,
+    Binary,
+}
+
+impl TextAssetFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TextAssetFormat::Json => "json",
+            TextAssetFormat::Xml => "xml",
+            TextAssetFormat::Text => "txt",
+            TextAssetFormat::Protobuf => "pb",
+            TextAssetFormat::Binary => "bytes",
+        }
+    }
+
+    /// Appends the extension unless `path` already ends with it, keeping dots in names like
+    /// `strings.en`.
+    pub fn with_extension(self, path: PathBuf) -> PathBuf {
+        let extension = self.extension();
+        let has_extension = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(extension));
+        if has_extension {
+            return path;
+        }
+        let mut path = path.into_os_string();
+        path.push(".");
+        path.push(extension);
+        path.into()
+    }
+
+    pub fn guess(data: &[u8]) -> Self {
+        let bom = b"\xef\xbb\xbf";
+        let data = if data.starts_with(bom) {
+            &data[bom.len()..]
+        } else {
+            data
+        };
+        if let Ok(text) = std::str::from_utf8(data) {
+            let text = text.trim();
+            if is_json(text) {
+                return TextAssetFormat::Json;
+            }
+            if text.starts_with('<') && text.ends_with('>') {
+                return TextAssetFormat::Xml;
+            }
+            let is_text = text
+                .chars()
+                .all(|c| !c.is_control() || c == '\t' || c == '\n' || c == '\r');
+            if is_text {
+                return TextAssetFormat::Text;
+            }
+        }
+        if is_protobuf(data) {
+            TextAssetFormat::Protobuf
+        } else {
+            TextAssetFormat::Binary
+        }
+    }
+}
+
+/// Checks that `text` is an object or an array whose brackets are balanced outside of strings.
+fn is_json(text: &str) -> bool {
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        if idx == 0 && c != '{' && c != '[' {
+            return false;
+        }
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                if stack.pop() != Some(c) {
+                    return false;
+                }
+                if stack.is_empty() && idx + 1 != text.len() {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+    }
+    !text.is_empty() && stack.is_empty() && !in_string
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&b, rest) = data.split_first()?;
+        *data = rest;
+        v |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+/// Checks that `data` is a sequence of well-formed protobuf fields.
+fn is_protobuf(mut data: &[u8]) -> bool {
+    if data.is_empty() {
+        return false;
+    }
+    while !data.is_empty() {
+        let key = match read_varint(&mut data) {
+            Some(v) => v,
+            None => return false,
+        };
+        if key >> 3 == 0 || key >> 3 > 0x1fff_ffff {
+            return false;
+        }
+        let len = match key & 7 {
+            0 => match read_varint(&mut data) {
+                Some(_) => 0,
+                None => return false,
+            },
+            1 => 8,
+            2 => match read_varint(&mut data) {
+                Some(v) => v as usize,
+                None => return false,
+            },
+            5 => 4,
+            _ => return false,
+        };
+        if len > data.len() {
+            return false;
+        }
+        data = &data[len..];
+    }
+    true
+}
+
+/// `TextAsset`, whose `m_Script` holds arbitrary bytes despite being a string.
+#[derive(Clone, Debug)]
+pub struct TextAsset<'a> {
+    pub name: String,
+    script: Cow<'a, [u8]>,
+}
+
+impl<'a> TextAsset<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        expect_type(data, "TextAsset")?;
+        let name = string_field(data, "m_Name")?;
+        let script = match field(data, "m_Script")? {
+            Data::String(b) | Data::UInt8Array(b) => b.clone(),
+            _ => return Err(ClassError::FieldTypeMismatch("m_Script")),
+        };
+        Ok(Self { name, script })
+    }
+
+    /// Raw contents of the asset.
+    pub fn bytes(&self) -> &[u8] {
+        &self.script
+    }
+
+    /// Contents of the asset if they're valid UTF-8.
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.script).ok()
+    }
+
+    pub fn format(&self) -> TextAssetFormat {
+        TextAssetFormat::guess(&self.script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_extension() {
+        let path = |format: TextAssetFormat, name: &str| {
+            format.with_extension(PathBuf::from(name)).into_os_string()
+        };
+        assert_eq!(path(TextAssetFormat::Binary, "foo.bytes"), "foo.bytes");
+        assert_eq!(path(TextAssetFormat::Binary, "foo.BYTES"), "foo.BYTES");
+        assert_eq!(path(TextAssetFormat::Binary, "foo"), "foo.bytes");
+        assert_eq!(path(TextAssetFormat::Text, "a.b.txt"), "a.b.txt");
+        assert_eq!(path(TextAssetFormat::Text, "a.b"), "a.b.txt");
+        assert_eq!(
+            path(TextAssetFormat::Json, "dir/strings.en"),
+            "dir/strings.en.json"
+        );
+        assert_eq!(path(TextAssetFormat::Json, "foo.txt"), "foo.txt.json");
+    }
+}
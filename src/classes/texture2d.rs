@@ -0,0 +1,484 @@
+use super::{expect_type, field, field_with, string_field, ClassError, StreamingInfo};
+use crate::{Data, UnityFs};
+use std::borrow::Cow;
+
+macro_rules! texture_formats {
+    ($($name:ident = $id:expr,)* ; $($(#[$meta:meta])* $graphics_name:ident,)*) => {
+        /// Pixel formats of `m_TextureFormat`.
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum TextureFormat {
+            $($name,)*
+            $($(#[$meta])* $graphics_name,)*
+        }
+
+        impl TextureFormat {
+            pub fn from_id(id: i32) -> Option<Self> {
+                match id {
+                    $($id => Some(TextureFormat::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns `None` for formats that only exist as a `GraphicsFormat`.
+            pub fn id(self) -> Option<i32> {
+                match self {
+                    $(TextureFormat::$name => Some($id),)*
+                    $(TextureFormat::$graphics_name => None,)*
+                }
+            }
+        }
+    };
+}
+
+texture_formats! {
+    Alpha8 = 1,
+    Argb4444 = 2,
+    Rgb24 = 3,
+    Rgba32 = 4,
+    Argb32 = 5,
+    ArgbFloat = 6,
+    Rgb565 = 7,
+    Bgr24 = 8,
+    R16 = 9,
+    Dxt1 = 10,
+    Dxt3 = 11,
+    Dxt5 = 12,
+    Rgba4444 = 13,
+    Bgra32 = 14,
+    RHalf = 15,
+    RgHalf = 16,
+    RgbaHalf = 17,
+    RFloat = 18,
+    RgFloat = 19,
+    RgbaFloat = 20,
+    Yuy2 = 21,
+    Rgb9e5Float = 22,
+    RgbFloat = 23,
+    Bc6h = 24,
+    Bc7 = 25,
+    Bc4 = 26,
+    Bc5 = 27,
+    Dxt1Crunched = 28,
+    Dxt5Crunched = 29,
+    PvrtcRgb2 = 30,
+    PvrtcRgba2 = 31,
+    PvrtcRgb4 = 32,
+    PvrtcRgba4 = 33,
+    EtcRgb4 = 34,
+    AtcRgb4 = 35,
+    AtcRgba8 = 36,
+    EacR = 41,
+    EacRSigned = 42,
+    EacRg = 43,
+    EacRgSigned = 44,
+    Etc2Rgb = 45,
+    Etc2Rgba1 = 46,
+    Etc2Rgba8 = 47,
+    AstcRgb4x4 = 48,
+    AstcRgb5x5 = 49,
+    AstcRgb6x6 = 50,
+    AstcRgb8x8 = 51,
+    AstcRgb10x10 = 52,
+    AstcRgb12x12 = 53,
+    AstcRgba4x4 = 54,
+    AstcRgba5x5 = 55,
+    AstcRgba6x6 = 56,
+    AstcRgba8x8 = 57,
+    AstcRgba10x10 = 58,
+    AstcRgba12x12 = 59,
+    EtcRgb4_3ds = 60,
+    EtcRgba8_3ds = 61,
+    Rg16 = 62,
+    R8 = 63,
+    EtcRgb4Crunched = 64,
+    Etc2Rgba8Crunched = 65,
+    AstcHdr4x4 = 66,
+    AstcHdr5x5 = 67,
+    AstcHdr6x6 = 68,
+    AstcHdr8x8 = 69,
+    AstcHdr10x10 = 70,
+    AstcHdr12x12 = 71,
+    Rg32 = 72,
+    Rgb48 = 73,
+    Rgba64 = 74,
+    R8Signed = 75,
+    Rg16Signed = 76,
+    Rgb24Signed = 77,
+    Rgba32Signed = 78,
+    R16Signed = 79,
+    Rg32Signed = 80,
+    Rgb48Signed = 81,
+    Rgba64Signed = 82,
+    ;
+    /// `BC4_SNorm`, only used through `GraphicsFormat`.
+    Bc4Signed,
+    /// `BC5_SNorm`, only used through `GraphicsFormat`.
+    Bc5Signed,
+    /// `BC6H_SFloat`, only used through `GraphicsFormat`.
+    Bc6hSigned,
+}
+
+impl TextureFormat {
+    /// Converts a `GraphicsFormat`, used by `m_Format` of texture arrays since Unity 2019.1.
+    pub fn from_graphics_format(id: i32) -> Option<Self> {
+        use TextureFormat::*;
+        Some(match id {
+            1 | 5 => R8,
+            2 | 6 => Rg16,
+            3 | 7 => Rgb24,
+            4 | 8 => Rgba32,
+            9 => R8Signed,
+            10 => Rg16Signed,
+            11 => Rgb24Signed,
+            12 => Rgba32Signed,
+            21 => R16,
+            22 => Rg32,
+            23 => Rgb48,
+            24 => Rgba64,
+            25 => R16Signed,
+            26 => Rg32Signed,
+            27 => Rgb48Signed,
+            28 => Rgba64Signed,
+            45 => RHalf,
+            46 => RgHalf,
+            48 => RgbaHalf,
+            49 => RFloat,
+            50 => RgFloat,
+            51 => RgbFloat,
+            52 => RgbaFloat,
+            // 56 and 58 are three-channel BGR, which has no texture format
+            57 | 59 => Bgra32,
+            96 | 97 => Dxt1,
+            98 | 99 => Dxt3,
+            100 | 101 => Dxt5,
+            102 => Bc4,
+            103 => Bc4Signed,
+            104 => Bc5,
+            105 => Bc5Signed,
+            106 => Bc6h,
+            107 => Bc6hSigned,
+            108 | 109 => Bc7,
+            110 | 111 => PvrtcRgb2,
+            112 | 113 => PvrtcRgb4,
+            114 | 115 => PvrtcRgba2,
+            116 | 117 => PvrtcRgba4,
+            118 => EtcRgb4,
+            119 | 120 => Etc2Rgb,
+            121 | 122 => Etc2Rgba1,
+            123 | 124 => Etc2Rgba8,
+            125 => EacR,
+            126 => EacRSigned,
+            127 => EacRg,
+            128 => EacRgSigned,
+            129 | 130 => AstcRgba4x4,
+            131 | 132 => AstcRgba5x5,
+            133 | 134 => AstcRgba6x6,
+            135 | 136 => AstcRgba8x8,
+            137 | 138 => AstcRgba10x10,
+            139 | 140 => AstcRgba12x12,
+            145 => AstcHdr4x4,
+            146 => AstcHdr5x5,
+            147 => AstcHdr6x6,
+            148 => AstcHdr8x8,
+            149 => AstcHdr10x10,
+            150 => AstcHdr12x12,
+            _ => return None,
+        })
+    }
+}
+
+/// `m_TextureDimension` of textures.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureDimension {
+    Unknown,
+    None,
+    Any,
+    Tex2D,
+    Tex3D,
+    Cube,
+    Tex2DArray,
+    CubeArray,
+}
+
+impl TextureDimension {
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            0 => TextureDimension::None,
+            1 => TextureDimension::Any,
+            2 => TextureDimension::Tex2D,
+            3 => TextureDimension::Tex3D,
+            4 => TextureDimension::Cube,
+            5 => TextureDimension::Tex2DArray,
+            6 => TextureDimension::CubeArray,
+            _ => TextureDimension::Unknown,
+        }
+    }
+}
+
+/// `m_ColorSpace` of textures.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+    SrgbXenon,
+    Unknown(i32),
+}
+
+impl ColorSpace {
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            0 => ColorSpace::Linear,
+            1 => ColorSpace::Srgb,
+            2 => ColorSpace::SrgbXenon,
+            id => ColorSpace::Unknown(id),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Texture2D<'a> {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Raw `m_TextureFormat`, see [`Texture2D::format`].
+    pub texture_format: i32,
+    /// Raw `m_ForcedFallbackFormat`, present since Unity 2017.3.
+    pub forced_fallback_format: Option<i32>,
+    pub mip_count: u32,
+    pub image_count: u32,
+    pub complete_image_size: u32,
+    pub texture_dimension: TextureDimension,
+    pub color_space: ColorSpace,
+    pub is_readable: bool,
+    pub stream_data: StreamingInfo,
+    image_data: Cow<'a, [u8]>,
+}
+
+fn full_mip_count(width: u32, height: u32) -> u32 {
+    32 - std::cmp::max(width, height).max(1).leading_zeros()
+}
+
+impl<'a> Texture2D<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        expect_type(data, "Texture2D")?;
+        Self::from_fields(data)
+    }
+
+    /// Reads fields shared by `Texture2D` and textures with the same layout.
+    pub(crate) fn from_fields(data: &Data<'a>) -> Result<Self, ClassError> {
+        let name = string_field(data, "m_Name")?;
+        let width = field_with(data, "m_Width", Data::as_u64)? as u32;
+        let height = field_with(data, "m_Height", Data::as_u64)? as u32;
+        let texture_format = field_with(data, "m_TextureFormat", Data::as_i64)? as i32;
+        let forced_fallback_format = data
+            .field("m_ForcedFallbackFormat")
+            .and_then(Data::as_i64)
+            .map(|v| v as i32);
+        let mip_count = match data.field("m_MipCount") {
+            Some(v) => v
+                .as_u64()
+                .ok_or(ClassError::FieldTypeMismatch("m_MipCount"))? as u32,
+            None => {
+                if field_with(data, "m_MipMap", Data::as_bool)? {
+                    full_mip_count(width, height)
+                } else {
+                    1
+                }
+            }
+        };
+        let image_count = data
+            .field("m_ImageCount")
+            .and_then(Data::as_u64)
+            .unwrap_or(1) as u32;
+        let complete_image_size = data
+            .field("m_CompleteImageSize")
+            .and_then(Data::as_u64)
+            .unwrap_or(0) as u32;
+        let texture_dimension = data
+            .field("m_TextureDimension")
+            .and_then(Data::as_i64)
+            .map(|v| TextureDimension::from_id(v as i32))
+            .unwrap_or(TextureDimension::Tex2D);
+        let color_space = data
+            .field("m_ColorSpace")
+            .and_then(Data::as_i64)
+            .map(|v| ColorSpace::from_id(v as i32))
+            .unwrap_or(ColorSpace::Srgb);
+        let is_readable = data
+            .field("m_IsReadable")
+            .and_then(Data::as_bool)
+            .unwrap_or(false);
+        let stream_data = match data.field("m_StreamData") {
+            Some(v) => StreamingInfo::from_data(v)?,
+            None => StreamingInfo::default(),
+        };
+        let image_data = match field(data, "image data")? {
+            Data::UInt8Array(b) => b.clone(),
+            _ => return Err(ClassError::FieldTypeMismatch("image data")),
+        };
+        Ok(Self {
+            name,
+            width,
+            height,
+            texture_format,
+            forced_fallback_format,
+            mip_count,
+            image_count,
+            complete_image_size,
+            texture_dimension,
+            color_space,
+            is_readable,
+            stream_data,
+            image_data,
+        })
+    }
+
+    pub fn format(&self) -> Option<TextureFormat> {
+        TextureFormat::from_id(self.texture_format)
+    }
+
+    /// Whether crunched image data uses the format revised in Unity 2017.3, given the
+    /// `unity_version` of the asset. Unknown versions are assumed to be recent.
+    pub fn is_unity_crunch(&self, unity_version: &str) -> bool {
+        match self.format() {
+            // ETC textures can only be crunched since the revision
+            Some(TextureFormat::EtcRgb4Crunched) | Some(TextureFormat::Etc2Rgba8Crunched) => true,
+            _ => {
+                let mut parts = unity_version.split('.').map(|v| v.parse::<u32>().ok());
+                match (parts.next().flatten(), parts.next().flatten()) {
+                    (Some(major), Some(minor)) if major != 0 => (major, minor) >= (2017, 3),
+                    _ => true,
+                }
+            }
+        }
+    }
+
+    /// Whether the image data is stored in a resource node instead of the object.
+    pub fn is_streamed(&self) -> bool {
+        !self.stream_data.is_empty()
+    }
+
+    /// Image data stored in the object itself. Empty if the texture is streamed.
+    pub fn inline_image_data(&self) -> &[u8] {
+        &self.image_data
+    }
+
+    /// Image data of all mips, read from the resource nodes of `fs` if the texture is streamed.
+    pub fn image_data<'s>(&'s self, fs: &UnityFs<'s>) -> Option<&'s [u8]> {
+        if self.is_streamed() {
+            self.stream_data.resolve(fs)
+        } else {
+            Some(&self.image_data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::tests::serialized_objects;
+    use crate::asset::Asset;
+    use crate::classes::tests::{string, structure};
+    use crate::TypeLayouts;
+    use std::collections::HashMap;
+
+    #[test]
+    fn format_ids() {
+        for &(id, format) in &[
+            (1, TextureFormat::Alpha8),
+            (5, TextureFormat::Argb32),
+            (10, TextureFormat::Dxt1),
+            (24, TextureFormat::Bc6h),
+            (28, TextureFormat::Dxt1Crunched),
+            (48, TextureFormat::AstcRgb4x4),
+            (66, TextureFormat::AstcHdr4x4),
+            (82, TextureFormat::Rgba64Signed),
+        ] {
+            assert_eq!(TextureFormat::from_id(id), Some(format));
+            assert_eq!(format.id(), Some(id));
+        }
+        assert_eq!(TextureFormat::from_id(0), None);
+        assert_eq!(TextureFormat::from_id(83), None);
+        assert_eq!(TextureFormat::Bc6hSigned.id(), None);
+
+        assert_eq!(
+            TextureFormat::from_graphics_format(4),
+            Some(TextureFormat::Rgba32)
+        );
+        assert_eq!(
+            TextureFormat::from_graphics_format(8),
+            Some(TextureFormat::Rgba32)
+        );
+        assert_eq!(TextureFormat::from_graphics_format(56), None);
+    }
+
+    fn texture(stream_path: &'static str, image_data: &'static [u8]) -> Data<'static> {
+        structure(
+            "Texture2D",
+            vec![
+                ("m_Name", string("tex")),
+                ("m_Width", Data::SInt32(2)),
+                ("m_Height", Data::SInt32(1)),
+                ("m_CompleteImageSize", Data::UInt32(8)),
+                ("m_TextureFormat", Data::SInt32(4)),
+                ("m_MipMap", Data::Bool(false)),
+                ("m_IsReadable", Data::Bool(false)),
+                ("m_ImageCount", Data::SInt32(1)),
+                ("m_TextureDimension", Data::SInt32(2)),
+                ("image data", Data::UInt8Array(image_data.into())),
+                (
+                    "m_StreamData",
+                    structure(
+                        "StreamingInfo",
+                        vec![
+                            ("offset", Data::UInt32(4)),
+                            ("size", Data::UInt32(8)),
+                            ("path", string(stream_path)),
+                        ],
+                    ),
+                ),
+            ],
+        )
+    }
+
+    fn unity_fs<'a>(file: &'a [u8], resource: &'a [u8], layouts: &TypeLayouts) -> UnityFs<'a> {
+        let (_, main_asset) = Asset::parse("CAB-0".into(), file, 0, layouts, true).unwrap();
+        let mut resources = HashMap::new();
+        resources.insert("CAB-0".to_owned(), file);
+        resources.insert("CAB-0.resS".to_owned(), resource);
+        UnityFs {
+            format_version: 6,
+            unity_version: "2018.4.0f1",
+            generator_version: "2018.4.0f1",
+            guid: [0; 16],
+            nodes: &[],
+            main_asset,
+            resources,
+        }
+    }
+
+    #[test]
+    fn image_data() {
+        let file = serialized_objects(&[]);
+        let resource: Vec<u8> = (0..16).collect();
+        let layouts = TypeLayouts::default();
+        let fs = unity_fs(&file, &resource, &layouts);
+
+        let inline = Texture2D::from_data(&texture("", &[7; 8])).unwrap();
+        assert_eq!(inline.name, "tex");
+        assert_eq!(inline.format(), Some(TextureFormat::Rgba32));
+        assert_eq!(inline.mip_count, 1);
+        assert_eq!(inline.texture_dimension, TextureDimension::Tex2D);
+        assert!(!inline.is_streamed());
+        assert_eq!(inline.image_data(&fs), Some(&[7; 8][..]));
+
+        let streamed = Texture2D::from_data(&texture("archive:/CAB-0/CAB-0.resS", &[])).unwrap();
+        assert!(streamed.is_streamed());
+        assert!(streamed.inline_image_data().is_empty());
+        assert_eq!(streamed.image_data(&fs), Some(&resource[4..12]));
+
+        // data in another bundle can't be resolved
+        let elsewhere = Texture2D::from_data(&texture("archive:/CAB-1/CAB-1.resS", &[])).unwrap();
+        assert_eq!(elsewhere.image_data(&fs), None);
+    }
+}
@@ -0,0 +1,267 @@
+use super::{
+    expect_type, field, field_with, string_field, ClassError, ColorSpace, StreamingInfo, Texture2D,
+    TextureDimension, TextureFormat,
+};
+use crate::{texture, Data, UnityFs};
+use std::borrow::Cow;
+
+/// Cubemap, whose faces are stored one after another with the layout of `Texture2D`.
+#[derive(Clone, Debug)]
+pub struct Cubemap<'a> {
+    pub texture: Texture2D<'a>,
+}
+
+impl<'a> Cubemap<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        expect_type(data, "Cubemap")?;
+        let texture = Texture2D::from_fields(data)?;
+        Ok(Self { texture })
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.texture.image_count as usize
+    }
+
+    /// Distance between the faces in bytes, each face containing all of its mips.
+    pub fn face_stride(&self) -> usize {
+        if self.texture.complete_image_size != 0 {
+            return self.texture.complete_image_size as usize;
+        }
+        let size = if self.texture.is_streamed() {
+            self.texture.stream_data.size as usize
+        } else {
+            self.texture.inline_image_data().len()
+        };
+        size / std::cmp::max(self.face_count(), 1)
+    }
+}
+
+/// `Texture2DArray` or `Texture3D`, which share their fields.
+#[derive(Clone, Debug)]
+pub struct TextureArray<'a> {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    /// Raw `m_Format`, see [`TextureArray::format`].
+    pub graphics_format: i32,
+    pub mip_count: u32,
+    pub data_size: u32,
+    /// `Tex2DArray` or `Tex3D`, depending on the class.
+    pub texture_dimension: TextureDimension,
+    pub color_space: ColorSpace,
+    pub is_readable: bool,
+    pub stream_data: StreamingInfo,
+    image_data: Cow<'a, [u8]>,
+}
+
+impl<'a> TextureArray<'a> {
+    pub fn from_data(data: &Data<'a>) -> Result<Self, ClassError> {
+        let texture_dimension = match data.type_name() {
+            "Texture3D" => TextureDimension::Tex3D,
+            _ => {
+                expect_type(data, "Texture2DArray")?;
+                TextureDimension::Tex2DArray
+            }
+        };
+        let name = string_field(data, "m_Name")?;
+        let width = field_with(data, "m_Width", Data::as_u64)? as u32;
+        let height = field_with(data, "m_Height", Data::as_u64)? as u32;
+        let depth = field_with(data, "m_Depth", Data::as_u64)? as u32;
+        let graphics_format = field_with(data, "m_Format", Data::as_i64)? as i32;
+        let mip_count = data.field("m_MipCount").and_then(Data::as_u64).unwrap_or(1) as u32;
+        let data_size = data.field("m_DataSize").and_then(Data::as_u64).unwrap_or(0) as u32;
+        let color_space = data
+            .field("m_ColorSpace")
+            .and_then(Data::as_i64)
+            .map(|v| ColorSpace::from_id(v as i32))
+            .unwrap_or(ColorSpace::Srgb);
+        let is_readable = data
+            .field("m_IsReadable")
+            .and_then(Data::as_bool)
+            .unwrap_or(false);
+        let stream_data = match data.field("m_StreamData") {
+            Some(v) => StreamingInfo::from_data(v)?,
+            None => StreamingInfo::default(),
+        };
+        let image_data = match field(data, "image data")? {
+            Data::UInt8Array(b) => b.clone(),
+            _ => return Err(ClassError::FieldTypeMismatch("image data")),
+        };
+        Ok(Self {
+            name,
+            width,
+            height,
+            depth,
+            graphics_format,
+            mip_count,
+            data_size,
+            texture_dimension,
+            color_space,
+            is_readable,
+            stream_data,
+            image_data,
+        })
+    }
+
+    /// Pixel format converted from the `GraphicsFormat` in `m_Format`.
+    pub fn format(&self) -> Option<TextureFormat> {
+        TextureFormat::from_graphics_format(self.graphics_format)
+    }
+
+    pub fn slice_count(&self) -> usize {
+        self.depth as usize
+    }
+
+    /// Distance between the slices of the first mip in bytes.
+    ///
+    /// Slices of a `Texture2DArray` contain all of their mips, while a `Texture3D` stores all
+    /// slices of a mip before the next mip. Returns `None` if the size of the format isn't known.
+    pub fn slice_stride(&self) -> Option<usize> {
+        if self.texture_dimension == TextureDimension::Tex3D {
+            texture::image_size(self.format()?, self.width, self.height)
+        } else {
+            let size = if self.data_size != 0 {
+                self.data_size as usize
+            } else if self.is_streamed() {
+                self.stream_data.size as usize
+            } else {
+                self.image_data.len()
+            };
+            Some(size / std::cmp::max(self.slice_count(), 1))
+        }
+    }
+
+    /// Whether the image data is stored in a resource node instead of the object.
+    pub fn is_streamed(&self) -> bool {
+        !self.stream_data.is_empty()
+    }
+
+    /// Image data stored in the object itself. Empty if the texture is streamed.
+    pub fn inline_image_data(&self) -> &[u8] {
+        &self.image_data
+    }
+
+    /// Image data of all slices, read from the resource nodes of `fs` if the texture is streamed.
+    pub fn image_data<'s>(&'s self, fs: &UnityFs<'s>) -> Option<&'s [u8]> {
+        if self.is_streamed() {
+            self.stream_data.resolve(fs)
+        } else {
+            Some(&self.image_data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::tests::{string, structure};
+    use crate::texture::Pixels;
+
+    fn texture_array(
+        type_name: &'static str,
+        mip_count: u32,
+        data_size: u32,
+        stream_data: (&'static str, u64),
+        image_data: Vec<u8>,
+    ) -> Data<'static> {
+        structure(
+            type_name,
+            vec![
+                ("m_Name", string("slices")),
+                ("m_Width", Data::SInt32(2)),
+                ("m_Height", Data::SInt32(1)),
+                ("m_Depth", Data::SInt32(2)),
+                // RGBA8_UNorm
+                ("m_Format", Data::SInt32(8)),
+                ("m_MipCount", Data::SInt32(mip_count as i32)),
+                ("m_DataSize", Data::UInt32(data_size)),
+                ("m_IsReadable", Data::Bool(true)),
+                (
+                    "m_StreamData",
+                    structure(
+                        "StreamingInfo",
+                        vec![
+                            ("offset", Data::UInt64(0)),
+                            ("size", Data::UInt32(stream_data.1 as u32)),
+                            ("path", string(stream_data.0)),
+                        ],
+                    ),
+                ),
+                ("image data", Data::UInt8Array(image_data.into())),
+            ],
+        )
+    }
+
+    #[test]
+    fn from_data() {
+        let data = texture_array("Texture2DArray", 1, 16, ("", 0), vec![0; 16]);
+        let array = TextureArray::from_data(&data).unwrap();
+        assert_eq!(array.name, "slices");
+        assert_eq!((array.width, array.height, array.slice_count()), (2, 1, 2));
+        assert_eq!(array.format(), Some(TextureFormat::Rgba32));
+        assert_eq!(array.texture_dimension, TextureDimension::Tex2DArray);
+        assert_eq!(array.color_space, ColorSpace::Srgb);
+        assert!(array.is_readable);
+        assert!(!array.is_streamed());
+        assert_eq!(array.inline_image_data().len(), 16);
+
+        // slices of a streamed Texture3D are one image of the first mip apart
+        let data = texture_array("Texture3D", 2, 0, ("archive:/CAB-0.resS", 40), vec![]);
+        let array = TextureArray::from_data(&data).unwrap();
+        assert_eq!(array.texture_dimension, TextureDimension::Tex3D);
+        assert!(array.is_streamed());
+        assert_eq!(array.stream_data.size, 40);
+        assert_eq!(array.slice_stride(), Some(8));
+
+        let data = texture_array("Texture2D", 1, 16, ("", 0), vec![0; 16]);
+        assert!(matches!(
+            TextureArray::from_data(&data),
+            Err(ClassError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn decode_slices() {
+        // each slice holds its 2x1 first mip followed by the 1x1 second mip
+        let mut image_data = Vec::new();
+        for slice in 0..2u8 {
+            for value in &[2 * slice + 1, 2 * slice + 2, 0xee] {
+                image_data.extend_from_slice(&[*value; 4]);
+            }
+        }
+        let data = texture_array("Texture2DArray", 2, 24, ("", 0), image_data);
+        let array = TextureArray::from_data(&data).unwrap();
+        let stride = array.slice_stride().unwrap();
+        assert_eq!(stride, 12);
+
+        let images = texture::decode_slices(
+            array.format().unwrap(),
+            array.width,
+            array.height,
+            array.inline_image_data(),
+            array.slice_count(),
+            stride,
+        )
+        .unwrap();
+        let pixels: Vec<_> = images.into_iter().map(|image| image.pixels).collect();
+        assert_eq!(
+            pixels,
+            [
+                Pixels::Rgba8(vec![1, 1, 1, 1, 2, 2, 2, 2]),
+                Pixels::Rgba8(vec![3, 3, 3, 3, 4, 4, 4, 4]),
+            ]
+        );
+
+        // a third slice would start past the end of the data
+        assert!(texture::decode_slices(
+            TextureFormat::Rgba32,
+            2,
+            1,
+            array.inline_image_data(),
+            3,
+            stride,
+        )
+        .is_err());
+    }
+}
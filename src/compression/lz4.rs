@@ -134,3 +134,123 @@ pub fn decode_block(input: &[u8]) -> Result<Vec<u8>, Lz4Error> {
     }
     Ok(out)
 }
+
+const HASH_LOG: u32 = 16;
+const WINDOW_SIZE: usize = 0x10000;
+const LAST_LITERALS: usize = 5;
+const MF_LIMIT: usize = 12;
+
+fn read_u32_le(input: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([input[pos], input[pos + 1], input[pos + 2], input[pos + 3]])
+}
+
+fn hash(v: u32) -> usize {
+    (v.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+struct MatchFinder<'a> {
+    input: &'a [u8],
+    head: Vec<usize>,
+    chain: Vec<usize>,
+    max_attempts: usize,
+}
+
+impl<'a> MatchFinder<'a> {
+    fn new(input: &'a [u8], max_attempts: usize) -> Self {
+        Self {
+            input,
+            head: vec![usize::MAX; 1 << HASH_LOG],
+            chain: vec![usize::MAX; WINDOW_SIZE],
+            max_attempts,
+        }
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if pos + 4 > self.input.len() {
+            return;
+        }
+        let h = hash(read_u32_le(self.input, pos));
+        self.chain[pos % WINDOW_SIZE] = self.head[h];
+        self.head[h] = pos;
+    }
+
+    fn find(&self, pos: usize, limit: usize) -> Option<MatchCopyInfo> {
+        let input = self.input;
+        let mut candidate = self.head[hash(read_u32_le(input, pos))];
+        let mut best: Option<MatchCopyInfo> = None;
+        for _ in 0..self.max_attempts {
+            if candidate == usize::MAX || pos - candidate >= WINDOW_SIZE {
+                break;
+            }
+            let length = input[candidate..limit]
+                .iter()
+                .zip(&input[pos..limit])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length >= MIN_MATCH_LEN && best.map(|b| length > b.length).unwrap_or(true) {
+                best = Some(MatchCopyInfo {
+                    offset: (pos - candidate) as u16,
+                    length,
+                });
+            }
+            candidate = self.chain[candidate % WINDOW_SIZE];
+        }
+        best
+    }
+}
+
+fn write_length(out: &mut Vec<u8>, mut length: usize) {
+    while length >= 0xff {
+        out.push(0xff);
+        length -= 0xff;
+    }
+    out.push(length as u8);
+}
+
+fn write_sequence(out: &mut Vec<u8>, literal: &[u8], match_copy: Option<MatchCopyInfo>) {
+    let lit_len = literal.len();
+    let match_len = match_copy.map(|m| m.length - MIN_MATCH_LEN).unwrap_or(0);
+    let token = (std::cmp::min(lit_len, 0x0f) << 4) | std::cmp::min(match_len, 0x0f);
+    out.push(token as u8);
+    if lit_len >= 0x0f {
+        write_length(out, lit_len - 0x0f);
+    }
+    out.extend_from_slice(literal);
+    if let Some(match_copy) = match_copy {
+        out.extend_from_slice(&match_copy.offset.to_le_bytes());
+        if match_len >= 0x0f {
+            write_length(out, match_len - 0x0f);
+        }
+    }
+}
+
+/// Compresses `input` into a single LZ4 block.
+///
+/// `max_attempts` bounds how many earlier positions are tried for each match; higher values
+/// compress better at the cost of speed.
+pub fn encode_block(input: &[u8], max_attempts: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() + input.len() / 0xff + 16);
+    let mut finder = MatchFinder::new(input, max_attempts);
+    let match_limit = input.len().saturating_sub(LAST_LITERALS);
+    let search_end = input.len().saturating_sub(MF_LIMIT);
+    let mut anchor = 0;
+    let mut pos = 0;
+    while pos < search_end {
+        match finder.find(pos, match_limit) {
+            Some(match_copy) => {
+                write_sequence(&mut out, &input[anchor..pos], Some(match_copy));
+                for p in pos..pos + match_copy.length {
+                    finder.insert(p);
+                }
+                pos += match_copy.length;
+                anchor = pos;
+            }
+            None => {
+                finder.insert(pos);
+                pos += 1;
+            }
+        }
+    }
+    write_sequence(&mut out, &input[anchor..], None);
+    out
+}
@@ -3,7 +3,7 @@ mod lz4;
 use std::cell::{Cell, UnsafeCell};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-enum CompressionType {
+pub enum CompressionType {
     Lzma,
     Lz4,
     Lz4Hc,
@@ -22,6 +22,15 @@ impl CompressionType {
         })
     }
 
+    pub(crate) fn to_u32(self) -> u32 {
+        match self {
+            CompressionType::Lzma => 1,
+            CompressionType::Lz4 => 2,
+            CompressionType::Lz4Hc => 3,
+            CompressionType::Lzham => 4,
+        }
+    }
+
     fn decompress(self, data: &[u8]) -> Vec<u8> {
         use CompressionType::*;
 
@@ -32,6 +41,11 @@ impl CompressionType {
     }
 }
 
+/// Compresses `data` into a raw LZ4 block. Higher levels search harder for matches.
+pub(crate) fn compress_lz4(data: &[u8], level: usize) -> Vec<u8> {
+    lz4::encode_block(data, level)
+}
+
 pub struct CompressedBlock<'a> {
     u_size: u32,
     compression: Option<CompressionType>,
@@ -0,0 +1,217 @@
+use super::Geometry;
+use crate::util::json_escape;
+use std::fmt::Write;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Formats a float as a JSON number.
+fn number(v: f32) -> String {
+    if v.is_finite() {
+        v.to_string()
+    } else {
+        "0".to_owned()
+    }
+}
+
+/// Binary buffer with one buffer view and accessor per attribute or index list.
+#[derive(Default)]
+struct Builder {
+    bin: Vec<u8>,
+    buffer_views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl Builder {
+    /// Appends `components`-sized elements and returns the index of their accessor.
+    fn push<T: Copy>(
+        &mut self,
+        values: &[T],
+        components: usize,
+        ty: &str,
+        component_type: u32,
+        to_bytes: impl Fn(T) -> [u8; 4],
+        bounds: Option<(&[f32], &[f32])>,
+    ) -> usize {
+        let offset = self.bin.len();
+        for &v in values {
+            self.bin.extend_from_slice(&to_bytes(v));
+        }
+        let target = match component_type {
+            UNSIGNED_INT => ELEMENT_ARRAY_BUFFER,
+            _ => ARRAY_BUFFER,
+        };
+        self.buffer_views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+            offset,
+            self.bin.len() - offset,
+            target
+        ));
+        let mut accessor = format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"{}""#,
+            self.buffer_views.len() - 1,
+            component_type,
+            values.len() / components,
+            ty
+        );
+        if let Some((min, max)) = bounds {
+            let list = |v: &[f32]| v.iter().map(|&v| number(v)).collect::<Vec<_>>().join(",");
+            let _ = write!(accessor, r#","min":[{}],"max":[{}]"#, list(min), list(max));
+        }
+        accessor.push('}');
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn floats(&mut self, values: &[f32], components: usize, ty: &str) -> usize {
+        self.push(values, components, ty, FLOAT, f32::to_le_bytes, None)
+    }
+}
+
+/// Writes the geometry as GLB, mirroring X to convert to right-handed coordinates and flipping
+/// texture coordinates vertically.
+pub(super) fn write(geometry: &Geometry, name: &str) -> Vec<u8> {
+    let name = json_escape(name);
+    let mut builder = Builder::default();
+    let mut attributes = Vec::new();
+
+    if !geometry.positions.is_empty() {
+        let positions = geometry
+            .positions
+            .iter()
+            .flat_map(|p| vec![-p[0], p[1], p[2]])
+            .collect::<Vec<_>>();
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for p in positions.chunks_exact(3) {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        let accessor = builder.push(
+            &positions,
+            3,
+            "VEC3",
+            FLOAT,
+            f32::to_le_bytes,
+            Some((&min, &max)),
+        );
+        attributes.push(format!(r#""POSITION":{}"#, accessor));
+    }
+    if !geometry.normals.is_empty() {
+        let normals = geometry
+            .normals
+            .iter()
+            .flat_map(|n| vec![-n[0], n[1], n[2]])
+            .collect::<Vec<_>>();
+        let accessor = builder.floats(&normals, 3, "VEC3");
+        attributes.push(format!(r#""NORMAL":{}"#, accessor));
+    }
+    if !geometry.tangents.is_empty() {
+        // mirroring X and flipping V both flip the handedness, which cancel out
+        let tangents = geometry
+            .tangents
+            .iter()
+            .flat_map(|t| vec![-t[0], t[1], t[2], t[3]])
+            .collect::<Vec<_>>();
+        let accessor = builder.floats(&tangents, 4, "VEC4");
+        attributes.push(format!(r#""TANGENT":{}"#, accessor));
+    }
+    if !geometry.colors.is_empty() {
+        let colors = geometry
+            .colors
+            .iter()
+            .flat_map(|c| c.to_vec())
+            .collect::<Vec<_>>();
+        let accessor = builder.floats(&colors, 4, "VEC4");
+        attributes.push(format!(r#""COLOR_0":{}"#, accessor));
+    }
+    for (idx, uvs) in geometry.uvs.iter().enumerate() {
+        if uvs.is_empty() {
+            continue;
+        }
+        let uvs = uvs
+            .iter()
+            .flat_map(|uv| vec![uv[0], 1.0 - uv[1]])
+            .collect::<Vec<_>>();
+        let accessor = builder.floats(&uvs, 2, "VEC2");
+        attributes.push(format!(r#""TEXCOORD_{}":{}"#, idx, accessor));
+    }
+
+    let attributes = attributes.join(",");
+    let mut primitives = Vec::new();
+    for triangles in &geometry.sub_meshes {
+        // mirroring flips the winding
+        let indices = triangles
+            .iter()
+            .flat_map(|t| vec![t[0], t[2], t[1]])
+            .collect::<Vec<_>>();
+        if indices.is_empty() {
+            continue;
+        }
+        let accessor = builder.push(&indices, 1, "SCALAR", UNSIGNED_INT, u32::to_le_bytes, None);
+        primitives.push(format!(
+            r#"{{"attributes":{{{}}},"indices":{},"mode":4}}"#,
+            attributes, accessor
+        ));
+    }
+    if primitives.is_empty() && !geometry.positions.is_empty() {
+        primitives.push(format!(r#"{{"attributes":{{{}}},"mode":4}}"#, attributes));
+    }
+
+    let mut json = String::from(r#"{"asset":{"version":"2.0","generator":"unityfs"}"#);
+    json.push_str(r#","scene":0,"scenes":[{"nodes":[0]}]"#);
+    if primitives.is_empty() {
+        let _ = write!(json, r#","nodes":[{{"name":"{}"}}]"#, name);
+    } else {
+        let _ = write!(
+            json,
+            r#","nodes":[{{"name":"{0}","mesh":0}}],"meshes":[{{"name":"{0}","primitives":[{1}]}}]"#,
+            name,
+            primitives.join(",")
+        );
+        let _ = write!(
+            json,
+            r#","buffers":[{{"byteLength":{}}}],"bufferViews":[{}],"accessors":[{}]"#,
+            builder.bin.len(),
+            builder.buffer_views.join(","),
+            builder.accessors.join(",")
+        );
+    }
+    json.push('}');
+
+    let mut json = json.into_bytes();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    let mut bin = builder.bin;
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+    let has_bin = !bin.is_empty();
+
+    let mut total = 12 + 8 + json.len();
+    if has_bin {
+        total += 8 + bin.len();
+    }
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(GLB_MAGIC);
+    out.extend_from_slice(&2u32.to_le_bytes());
+    out.extend_from_slice(&(total as u32).to_le_bytes());
+    out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    out.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+    out.extend_from_slice(&json);
+    if has_bin {
+        out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        out.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+        out.extend_from_slice(&bin);
+    }
+    out
+}
@@ -0,0 +1,67 @@
+mod gltf;
+mod obj;
+
+/// Number of texture coordinate sets of a mesh.
+pub const UV_SETS: usize = 8;
+
+/// Triangle mesh with per-vertex attributes, independent of how Unity stores it.
+///
+/// Attributes are empty if the mesh doesn't have them and have one value per position
+/// otherwise. Coordinates are left-handed as in Unity, the exporters convert them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Geometry {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// Tangents with the handedness of the bitangent in `w`.
+    pub tangents: Vec<[f32; 4]>,
+    pub colors: Vec<[f32; 4]>,
+    pub uvs: [Vec<[f32; 2]>; UV_SETS],
+    pub bone_weights: Vec<[f32; 4]>,
+    pub bone_indices: Vec<[u32; 4]>,
+    /// Triangles of each submesh, indexing the vertex attributes.
+    pub sub_meshes: Vec<Vec<[u32; 3]>>,
+}
+
+impl Geometry {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Converts the geometry to a Wavefront OBJ file with a group for each submesh.
+    pub fn to_obj(&self, name: &str) -> String {
+        obj::write(self, name)
+    }
+
+    /// Converts the geometry to a binary glTF 2.0 file with a primitive for each submesh.
+    ///
+    /// Bone weights are not exported since the bind poses are not part of the geometry.
+    pub fn to_glb(&self, name: &str) -> Vec<u8> {
+        gltf::write(self, name)
+    }
+
+    /// Drops attributes whose length doesn't match the number of positions.
+    pub(crate) fn validate(&mut self) {
+        let count = self.positions.len();
+        if self.normals.len() != count {
+            self.normals.clear();
+        }
+        if self.tangents.len() != count {
+            self.tangents.clear();
+        }
+        if self.colors.len() != count {
+            self.colors.clear();
+        }
+        for uv in self.uvs.iter_mut() {
+            if uv.len() != count {
+                uv.clear();
+            }
+        }
+        if self.bone_weights.len() != count || self.bone_indices.len() != count {
+            self.bone_weights.clear();
+            self.bone_indices.clear();
+        }
+        for triangles in self.sub_meshes.iter_mut() {
+            triangles.retain(|t| t.iter().all(|&idx| (idx as usize) < count));
+        }
+    }
+}
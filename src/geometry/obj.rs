@@ -0,0 +1,38 @@
+use super::Geometry;
+use std::fmt::Write;
+
+/// Writes the geometry as OBJ, mirroring X to convert to right-handed coordinates.
+pub(super) fn write(geometry: &Geometry, name: &str) -> String {
+    let has_uvs = !geometry.uvs[0].is_empty();
+    let has_normals = !geometry.normals.is_empty();
+    let mut out = String::new();
+    // writing to a String can't fail
+    let _ = writeln!(out, "o {}", name);
+    for p in &geometry.positions {
+        let _ = writeln!(out, "v {} {} {}", -p[0], p[1], p[2]);
+    }
+    for uv in &geometry.uvs[0] {
+        let _ = writeln!(out, "vt {} {}", uv[0], uv[1]);
+    }
+    for n in &geometry.normals {
+        let _ = writeln!(out, "vn {} {} {}", -n[0], n[1], n[2]);
+    }
+    for (idx, triangles) in geometry.sub_meshes.iter().enumerate() {
+        let _ = writeln!(out, "g {}_{}", name, idx);
+        for t in triangles {
+            out.push('f');
+            // mirroring flips the winding
+            for &vertex in &[t[0], t[2], t[1]] {
+                let vertex = vertex + 1;
+                let _ = match (has_uvs, has_normals) {
+                    (false, false) => write!(out, " {}", vertex),
+                    (true, false) => write!(out, " {0}/{0}", vertex),
+                    (false, true) => write!(out, " {0}//{0}", vertex),
+                    (true, true) => write!(out, " {0}/{0}/{0}", vertex),
+                };
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
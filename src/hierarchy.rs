@@ -0,0 +1,302 @@
+//! Scene and prefab hierarchies rebuilt from the flat objects of an asset.
+
+use crate::classes::{ClassError, GameObject, MonoScript, PPtr, Transform};
+use crate::Asset;
+use std::collections::HashMap;
+
+/// Component of a game object.
+#[derive(Clone, Debug)]
+pub struct Component {
+    pub path_id: i64,
+    /// Class name of the component, like `MeshRenderer`. Empty if it lives in another asset.
+    pub class_name: String,
+    /// Full class name of the script of a `MonoBehaviour`, if it could be resolved.
+    pub script: Option<String>,
+}
+
+/// Game object in a [`Hierarchy`].
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub path_id: i64,
+    pub game_object: GameObject,
+    pub components: Vec<Component>,
+    /// Local transform, `None` if the game object has no readable transform.
+    pub transform: Option<Transform>,
+    /// Indices of the child nodes, in transform order.
+    pub children: Vec<usize>,
+    pub parent: Option<usize>,
+}
+
+impl Node {
+    pub fn name(&self) -> &str {
+        &self.game_object.name
+    }
+}
+
+/// Tree of the game objects of an asset, linked through their transforms.
+#[derive(Clone, Debug, Default)]
+pub struct Hierarchy {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+}
+
+impl Hierarchy {
+    pub fn from_asset(asset: &Asset<'_>) -> Result<Self, ClassError> {
+        let mut nodes = Vec::new();
+        let mut node_of_transform = HashMap::new();
+        for object in asset.objects() {
+            if object.data.type_name() != "GameObject" {
+                continue;
+            }
+            let game_object = GameObject::from_data(&object.data)?;
+            let mut transform = None;
+            let mut components = Vec::with_capacity(game_object.components.len());
+            for pptr in &game_object.components {
+                let component = match pptr.resolve(asset) {
+                    Some(v) => v,
+                    None => {
+                        components.push(Component {
+                            path_id: pptr.path_id,
+                            class_name: String::new(),
+                            script: None,
+                        });
+                        continue;
+                    }
+                };
+                let class_name = component.data.type_name();
+                let mut script = None;
+                match class_name {
+                    "Transform" | "RectTransform" if transform.is_none() => {
+                        transform = Some(Transform::from_data(&component.data)?);
+                        node_of_transform.insert(pptr.path_id, nodes.len());
+                    }
+                    "MonoBehaviour" => {
+                        script = MonoScript::of_behaviour(component, asset).map(|s| s.full_name());
+                    }
+                    _ => {}
+                }
+                components.push(Component {
+                    path_id: pptr.path_id,
+                    class_name: class_name.to_owned(),
+                    script,
+                });
+            }
+            nodes.push(Node {
+                path_id: object.path_id as i64,
+                game_object,
+                components,
+                transform,
+                children: Vec::new(),
+                parent: None,
+            });
+        }
+
+        // transforms reference each other, the game objects holding them give the nodes
+        let node_of_transform = |pptr: &PPtr| -> Option<usize> {
+            match pptr.file_id {
+                0 => node_of_transform.get(&pptr.path_id).copied(),
+                _ => None,
+            }
+        };
+        for idx in 0..nodes.len() {
+            let children = match &nodes[idx].transform {
+                Some(transform) => transform
+                    .children
+                    .iter()
+                    .filter_map(&node_of_transform)
+                    .collect::<Vec<_>>(),
+                None => continue,
+            };
+            for &child in &children {
+                // skip links which would make a cycle in malformed data
+                let mut ancestor = Some(idx);
+                while let Some(node) = ancestor.filter(|&node| node != child) {
+                    ancestor = nodes[node].parent;
+                }
+                if ancestor.is_none() && nodes[child].parent.is_none() {
+                    nodes[child].parent = Some(idx);
+                    nodes[idx].children.push(child);
+                }
+            }
+        }
+        let roots = (0..nodes.len())
+            .filter(|&idx| nodes[idx].parent.is_none())
+            .collect();
+        Ok(Self { nodes, roots })
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn node(&self, idx: usize) -> Option<&Node> {
+        self.nodes.get(idx)
+    }
+
+    /// Indices of the nodes without parent.
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    /// Calls `f` with each node and its depth, parents before their children.
+    pub fn walk(&self, mut f: impl FnMut(&Node, usize)) {
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&idx| (idx, 0))
+            .collect::<Vec<_>>();
+        while let Some((idx, depth)) = stack.pop() {
+            let node = &self.nodes[idx];
+            f(node, depth);
+            stack.extend(node.children.iter().rev().map(|&child| (child, depth + 1)));
+        }
+    }
+
+    /// Path of a node from its root, names separated by `/`.
+    pub fn path(&self, idx: usize) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(idx);
+        while let Some(idx) = current {
+            let node = &self.nodes[idx];
+            names.push(node.name());
+            current = node.parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Finds a node by its path from a root, like `Canvas/Panel/Button`.
+    pub fn find(&self, path: &str) -> Option<usize> {
+        let mut names = path.split('/');
+        let first = names.next()?;
+        let mut current = *self
+            .roots
+            .iter()
+            .find(|&&idx| self.nodes[idx].name() == first)?;
+        for name in names {
+            current = *self.nodes[current]
+                .children
+                .iter()
+                .find(|&&idx| self.nodes[idx].name() == name)?;
+        }
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::tests::serialized_objects;
+    use crate::TypeLayouts;
+
+    fn pptr(out: &mut Vec<u8>, file_id: i32, path_id: i64) {
+        out.extend_from_slice(&file_id.to_le_bytes());
+        out.extend_from_slice(&path_id.to_le_bytes());
+    }
+
+    fn game_object(name: &str, components: &[(i32, i64)]) -> Vec<u8> {
+        let mut out = (components.len() as u32).to_le_bytes().to_vec();
+        for &(file_id, path_id) in components {
+            pptr(&mut out, file_id, path_id);
+        }
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.resize(out.len().div_ceil(4) * 4, 0);
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.push(1);
+        out
+    }
+
+    fn transform(game_object: i64, children: &[i64], father: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        pptr(&mut out, 0, game_object);
+        for v in &[0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        for &child in children {
+            pptr(&mut out, 0, child);
+        }
+        pptr(&mut out, 0, father);
+        out
+    }
+
+    /// `Root` with the children `Child` and `Sibling`, and `Leaf` under `Child`. The transform
+    /// of `Root` also lists itself as a child, and the one of `Leaf` lists `Root`.
+    fn hierarchy() -> Hierarchy {
+        let objects = [
+            (10, 1, game_object("Root", &[(0, 11), (1, 99)])),
+            (11, 4, transform(10, &[21, 11, 41], 0)),
+            (20, 1, game_object("Child", &[(0, 21)])),
+            (21, 4, transform(20, &[31], 11)),
+            (30, 1, game_object("Leaf", &[(0, 31)])),
+            (31, 4, transform(30, &[11], 21)),
+            (40, 1, game_object("Sibling", &[(0, 41)])),
+            (41, 4, transform(40, &[], 11)),
+        ];
+        let objects = objects
+            .iter()
+            .map(|(path_id, class_id, data)| (*path_id, *class_id, &data[..]))
+            .collect::<Vec<_>>();
+        let file = serialized_objects(&objects);
+        let (_, asset) =
+            Asset::parse("test".into(), &file, 0, &TypeLayouts::default(), true).unwrap();
+        Hierarchy::from_asset(&asset).unwrap()
+    }
+
+    #[test]
+    fn tree() {
+        let hierarchy = hierarchy();
+        let names = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|&idx| hierarchy.nodes()[idx].name())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hierarchy.nodes().len(), 4);
+        // the links from `Root` to itself and from `Leaf` back to `Root` are dropped
+        assert_eq!(names(hierarchy.roots()), ["Root"]);
+        let leaf = hierarchy.find("Root/Child/Leaf").unwrap();
+        assert!(hierarchy.nodes()[leaf].children.is_empty());
+        assert_eq!(hierarchy.path(leaf), "Root/Child/Leaf");
+        let child = hierarchy.nodes()[leaf].parent.unwrap();
+        assert_eq!(names(&hierarchy.nodes()[child].children), ["Leaf"]);
+        assert_eq!(
+            hierarchy
+                .find("Root/Sibling")
+                .map(|idx| hierarchy.path(idx))
+                .as_deref(),
+            Some("Root/Sibling")
+        );
+        assert_eq!(hierarchy.find("Root/Leaf"), None);
+        assert_eq!(hierarchy.find("Child"), None);
+
+        let root = &hierarchy.nodes()[hierarchy.roots()[0]];
+        assert_eq!(root.path_id, 10);
+        assert_eq!(root.transform.as_ref().map(|t| t.children.len()), Some(3));
+        assert_eq!(names(&root.children), ["Child", "Sibling"]);
+        let components = root
+            .components
+            .iter()
+            .map(|c| (c.path_id, c.class_name.as_str()))
+            .collect::<Vec<_>>();
+        // components in other files are kept without a class name
+        assert_eq!(components, [(11, "Transform"), (99, "")]);
+    }
+
+    #[test]
+    fn walk() {
+        let mut visited = Vec::new();
+        hierarchy().walk(|node, depth| visited.push((node.name().to_owned(), depth)));
+        let visited = visited
+            .iter()
+            .map(|(name, depth)| (name.as_str(), *depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            visited,
+            [("Root", 0), ("Child", 1), ("Leaf", 2), ("Sibling", 1)]
+        );
+    }
+}
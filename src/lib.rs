@@ -1,17 +1,52 @@
+pub mod audio;
+pub mod bundle_graph;
+pub mod classes;
+pub mod geometry;
+pub mod hierarchy;
+pub mod texture;
+
 mod asset;
 mod common_parser;
 mod compression;
 mod metadata;
 mod util;
+mod writer;
 
 use crate::common_parser::read_string;
 use nom::{number::complete as nom_number, IResult};
 use std::borrow::Cow;
 use std::collections::HashMap;
 
-pub use asset::{Asset, Data, Object};
-pub use compression::CompressedBlock;
+pub use asset::{
+    Asset, AssetBuilder, AssetBuilderError, AssetRef, Data, Fields, LayoutError, Object, Query,
+    TypeLayouts, TypeTree, VisitFlow, Visitor,
+};
+pub use compression::{CompressedBlock, CompressionType};
 pub use metadata::Metadata;
+pub use nom::number::Endianness;
+pub use writer::{BlockCompression, BundleWriter, NODE_SERIALIZED_FILE};
+
+/// Error reading the serialized file of a bundle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReadError {
+    /// The bundle has no nodes.
+    NoAsset,
+    /// The first node is not a valid serialized file.
+    InvalidAsset(nom::error::ErrorKind),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::NoAsset => write!(f, "bundle has no serialized file"),
+            ReadError::InvalidAsset(kind) => {
+                write!(f, "invalid serialized file: {}", kind.description())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
 
 pub struct UnityFsMeta<'a> {
     signature: Cow<'a, str>,
@@ -95,7 +130,33 @@ impl<'a> UnityFsMeta<'a> {
         ))
     }
 
-    pub fn read_unityfs(&'a self) -> UnityFs<'a> {
+    pub fn read_unityfs(&'a self) -> Result<UnityFs<'a>, ReadError> {
+        self.read_unityfs_with_layouts(&TypeLayouts::default())
+    }
+
+    /// Reads the bundle, decoding script objects without embedded type trees with `layouts`.
+    pub fn read_unityfs_with_layouts(
+        &'a self,
+        layouts: &TypeLayouts,
+    ) -> Result<UnityFs<'a>, ReadError> {
+        self.read_unityfs_impl(layouts, true)
+    }
+
+    /// Reads the bundle keeping only the object table: the data of every object stays
+    /// `Data::Raw`. Objects can be walked with `Asset::visit_object` or decoded one by one with
+    /// `Asset::decode_object`. `layouts` are attached to script objects that fit them.
+    pub fn read_unityfs_undecoded(
+        &'a self,
+        layouts: &TypeLayouts,
+    ) -> Result<UnityFs<'a>, ReadError> {
+        self.read_unityfs_impl(layouts, false)
+    }
+
+    fn read_unityfs_impl(
+        &'a self,
+        layouts: &TypeLayouts,
+        decode: bool,
+    ) -> Result<UnityFs<'a>, ReadError> {
         let resources = self
             .metadata
             .nodes
@@ -111,25 +172,40 @@ impl<'a> UnityFsMeta<'a> {
             name: main_asset_name,
             offset: main_asset_offset,
             ..
-        } = &self.metadata.nodes[0];
-        let main_asset_resource = *resources.get(main_asset_name).unwrap();
+        } = self.metadata.nodes.first().ok_or(ReadError::NoAsset)?;
+        let main_asset_resource = resources[main_asset_name];
         let (_, main_asset) = Asset::parse(
             main_asset_name.into(),
             main_asset_resource,
             *main_asset_offset,
+            layouts,
+            decode,
         )
-        .unwrap();
-        UnityFs {
+        .map_err(|e| match e {
+            nom::Err::Error((_, kind)) | nom::Err::Failure((_, kind)) => {
+                ReadError::InvalidAsset(kind)
+            }
+            nom::Err::Incomplete(_) => ReadError::InvalidAsset(nom::error::ErrorKind::Eof),
+        })?;
+        Ok(UnityFs {
+            format_version: self.format_version,
+            unity_version: &self.unity_version,
+            generator_version: &self.generator_version,
             guid: self.metadata.guid,
+            nodes: &self.metadata.nodes,
             main_asset,
             resources,
-        }
+        })
     }
 }
 
 #[derive(Debug)]
 pub struct UnityFs<'a> {
+    format_version: u32,
+    unity_version: &'a str,
+    generator_version: &'a str,
     guid: [u8; 16],
+    nodes: &'a [metadata::NodeInfo],
     main_asset: Asset<'a>,
     resources: HashMap<String, &'a [u8]>,
 }
@@ -150,4 +226,21 @@ impl<'a> UnityFs<'a> {
     pub fn resource(&self, name: &str) -> Option<&'a [u8]> {
         self.resources.get(name).copied()
     }
+
+    /// Finds a resource by a path like `archive:/CAB-xxx/CAB-xxx.resS`. Returns `None` if the
+    /// path points to another bundle.
+    pub fn resolve_path(&self, path: &str) -> Option<&'a [u8]> {
+        if !path.starts_with("archive:/") {
+            return self.resource(path);
+        }
+        let mut path_segments = path[9..].split('/');
+        let (bundle_name, resource_name) = match (path_segments.next(), path_segments.next()) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return None,
+        };
+        if self.name() != bundle_name {
+            return None;
+        }
+        self.resource(resource_name)
+    }
 }
@@ -68,3 +68,25 @@ impl Metadata {
         Ok((input, ret))
     }
 }
+
+impl Metadata {
+    pub fn write(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.guid);
+        out.extend_from_slice(&(self.blocks.len() as u32).to_be_bytes());
+        for block in &self.blocks {
+            out.extend_from_slice(&block.u_size.to_be_bytes());
+            out.extend_from_slice(&block.c_size.to_be_bytes());
+            out.extend_from_slice(&block.flags.to_be_bytes());
+        }
+        out.extend_from_slice(&(self.nodes.len() as u32).to_be_bytes());
+        for node in &self.nodes {
+            out.extend_from_slice(&node.offset.to_be_bytes());
+            out.extend_from_slice(&node.size.to_be_bytes());
+            out.extend_from_slice(&node.status.to_be_bytes());
+            out.extend_from_slice(node.name.as_bytes());
+            out.push(0);
+        }
+        out
+    }
+}
@@ -0,0 +1,93 @@
+use super::{DecodeError, Pixels};
+use crate::classes::TextureFormat;
+
+/// Returns the block size of an ASTC format and whether it is HDR.
+pub(super) fn block_size(format: TextureFormat) -> Option<(usize, bool)> {
+    Some(match format {
+        TextureFormat::AstcRgb4x4 | TextureFormat::AstcRgba4x4 => (4, false),
+        TextureFormat::AstcRgb5x5 | TextureFormat::AstcRgba5x5 => (5, false),
+        TextureFormat::AstcRgb6x6 | TextureFormat::AstcRgba6x6 => (6, false),
+        TextureFormat::AstcRgb8x8 | TextureFormat::AstcRgba8x8 => (8, false),
+        TextureFormat::AstcRgb10x10 | TextureFormat::AstcRgba10x10 => (10, false),
+        TextureFormat::AstcRgb12x12 | TextureFormat::AstcRgba12x12 => (12, false),
+        TextureFormat::AstcHdr4x4 => (4, true),
+        TextureFormat::AstcHdr5x5 => (5, true),
+        TextureFormat::AstcHdr6x6 => (6, true),
+        TextureFormat::AstcHdr8x8 => (8, true),
+        TextureFormat::AstcHdr10x10 => (10, true),
+        TextureFormat::AstcHdr12x12 => (12, true),
+        _ => return None,
+    })
+}
+
+fn flip_rows<T: Copy>(pixels: Vec<T>, width: usize) -> Vec<T> {
+    if width == 0 {
+        return pixels;
+    }
+    pixels.chunks(width * 4).rev().flatten().copied().collect()
+}
+
+pub(super) fn decode(
+    width: usize,
+    height: usize,
+    data: &[u8],
+    block_size: usize,
+    hdr: bool,
+) -> Result<Pixels, DecodeError> {
+    let expected = width.div_ceil(block_size) * height.div_ceil(block_size) * 16;
+    let not_enough_data = DecodeError::NotEnoughData {
+        expected,
+        found: data.len(),
+    };
+    if data.len() < expected {
+        return Err(not_enough_data);
+    }
+    // astcdec only fails when it runs out of input, and invalid blocks decode to magenta
+    let pixels = if hdr {
+        astcdec::decode_image_hdr(data, width, height, block_size, block_size)
+            .map(|pixels| Pixels::Rgba32F(flip_rows(pixels, width)))
+    } else {
+        astcdec::decode_image(data, width, height, block_size, block_size)
+            .map(|pixels| Pixels::Rgba8(flip_rows(pixels, width)))
+    };
+    pixels.map_err(|_| not_enough_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// LDR void-extent block of a single RGBA16 color.
+    fn constant_block(rgba: [u16; 4]) -> Vec<u8> {
+        let mut block = vec![0xfc, 0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        for c in &rgba {
+            block.extend_from_slice(&c.to_le_bytes());
+        }
+        block
+    }
+
+    #[test]
+    fn constant_blocks() {
+        let mut data = constant_block([0xffff, 0, 0, 0xffff]);
+        data.extend(constant_block([0, 0, 0xffff, 0x8080]));
+        let image = crate::texture::decode(TextureFormat::AstcRgba4x4, 3, 6, &data).unwrap();
+        let pixels = image.into_rgba8();
+        // the first block is the bottom one
+        assert_eq!(&pixels[..4], &[0, 0, 255, 128]);
+        assert_eq!(&pixels[pixels.len() - 4..], &[255, 0, 0, 255]);
+        assert_eq!(pixels.len(), 3 * 6 * 4);
+    }
+
+    #[test]
+    fn not_enough_data() {
+        let data = constant_block([0; 4]);
+        assert_eq!(
+            crate::texture::decode(TextureFormat::AstcRgb4x4, 4, 8, &data),
+            Err(DecodeError::NotEnoughData {
+                expected: 32,
+                found: 16
+            })
+        );
+        assert!(crate::texture::is_supported(TextureFormat::AstcHdr12x12));
+    }
+}
@@ -0,0 +1,1210 @@
+//! BC1 to BC7 block decoders.
+//!
+//! Each decoder writes a 4x4 block as 16 RGBA pixels in row-major order.
+
+use super::half_to_f32;
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Subset of each pixel in two-subset partitions, one bit per pixel starting from the LSB.
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subset of each pixel in three-subset partitions, two bits per pixel starting from the LSB.
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Anchor pixel of the second subset in two-subset partitions.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of the second and third subsets in three-subset partitions.
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+struct BitReader(u128);
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&block[..16]);
+        BitReader(u128::from_le_bytes(bytes))
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        let ret = (self.0 & ((1 << bits) - 1)) as u32;
+        self.0 >>= bits;
+        ret
+    }
+}
+
+fn subset_of(num_subsets: usize, partition: usize, pixel: usize) -> usize {
+    match num_subsets {
+        2 => (PARTITIONS_2[partition] >> pixel) as usize & 1,
+        3 => (PARTITIONS_3[partition] >> (2 * pixel)) as usize & 3,
+        _ => 0,
+    }
+}
+
+fn is_anchor(num_subsets: usize, partition: usize, pixel: usize) -> bool {
+    match (num_subsets, pixel) {
+        (_, 0) => true,
+        (2, pixel) => ANCHORS_2[partition] as usize == pixel,
+        (3, pixel) => ANCHORS_3[partition].contains(&(pixel as u8)),
+        _ => false,
+    }
+}
+
+fn weights(bits: u32) -> &'static [u32] {
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+fn rgb565(color: u16) -> [u32; 3] {
+    let r = u32::from(color >> 11);
+    let g = u32::from((color >> 5) & 0x3f);
+    let b = u32::from(color & 0x1f);
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// Decodes an 8-byte BC1 color block into `out`.
+///
+/// BC2 and BC3 always use four colors, BC1 switches to three colors and transparent black if
+/// the first endpoint is not greater than the second.
+fn decode_bc1_color(block: &[u8], out: &mut [u8], four_colors: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mut palette = [[0u8; 4]; 4];
+    for i in 0..3 {
+        palette[0][i] = e0[i] as u8;
+        palette[1][i] = e1[i] as u8;
+        if four_colors || c0 > c1 {
+            palette[2][i] = ((2 * e0[i] + e1[i] + 1) / 3) as u8;
+            palette[3][i] = ((e0[i] + 2 * e1[i] + 1) / 3) as u8;
+        } else {
+            palette[2][i] = (e0[i] + e1[i]).div_ceil(2) as u8;
+        }
+    }
+    palette[0][3] = 0xff;
+    palette[1][3] = 0xff;
+    palette[2][3] = 0xff;
+    if four_colors || c0 > c1 {
+        palette[3][3] = 0xff;
+    }
+    let mut indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for pixel in out.chunks_mut(4).take(16) {
+        pixel.copy_from_slice(&palette[(indices & 3) as usize]);
+        indices >>= 2;
+    }
+}
+
+pub(super) fn decode_bc1(block: &[u8], out: &mut [u8]) {
+    decode_bc1_color(block, out, false);
+}
+
+pub(super) fn decode_bc2(block: &[u8], out: &mut [u8]) {
+    decode_bc1_color(&block[8..], out, true);
+    let mut alpha = u64::from_le_bytes([
+        block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7],
+    ]);
+    for pixel in out.chunks_mut(4).take(16) {
+        pixel[3] = (alpha & 0xf) as u8 * 17;
+        alpha >>= 4;
+    }
+}
+
+pub(super) fn decode_bc3(block: &[u8], out: &mut [u8]) {
+    decode_bc1_color(&block[8..], out, true);
+    decode_bc4_channel(&block[..8], out, 3, false);
+}
+
+/// Decodes an 8-byte BC4 block into one channel of `out`. Signed values are offset by 128.
+fn decode_bc4_channel(block: &[u8], out: &mut [u8], channel: usize, signed: bool) {
+    let endpoint = |v: u8| {
+        if signed {
+            // -128 and -127 both mean -1.0
+            i32::from(v as i8).max(-127)
+        } else {
+            i32::from(v)
+        }
+    };
+    let (e0, e1) = (endpoint(block[0]), endpoint(block[1]));
+    let (min, max) = if signed { (-127, 127) } else { (0, 0xff) };
+    let mut palette = [e0, e1, 0, 0, 0, 0, min, max];
+    if e0 > e1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as i32) * e0 + i as i32 * e1 + 3).div_euclid(7);
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as i32) * e0 + i as i32 * e1 + 2).div_euclid(5);
+        }
+    }
+    let offset = if signed { 128 } else { 0 };
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let mut indices = u64::from_le_bytes(indices);
+    for pixel in out.chunks_mut(4).take(16) {
+        pixel[channel] = (palette[(indices & 7) as usize] + offset) as u8;
+        indices >>= 3;
+    }
+}
+
+fn decode_bc4_with(block: &[u8], out: &mut [u8], signed: bool) {
+    for pixel in out.chunks_mut(4) {
+        pixel.copy_from_slice(&[0, 0, 0, 0xff]);
+    }
+    decode_bc4_channel(block, out, 0, signed);
+}
+
+fn decode_bc5_with(block: &[u8], out: &mut [u8], signed: bool) {
+    for pixel in out.chunks_mut(4) {
+        pixel.copy_from_slice(&[0, 0, 0, 0xff]);
+    }
+    decode_bc4_channel(&block[..8], out, 0, signed);
+    decode_bc4_channel(&block[8..], out, 1, signed);
+}
+
+pub(super) fn decode_bc4(block: &[u8], out: &mut [u8]) {
+    decode_bc4_with(block, out, false);
+}
+
+pub(super) fn decode_bc4_signed(block: &[u8], out: &mut [u8]) {
+    decode_bc4_with(block, out, true);
+}
+
+pub(super) fn decode_bc5(block: &[u8], out: &mut [u8]) {
+    decode_bc5_with(block, out, false);
+}
+
+pub(super) fn decode_bc5_signed(block: &[u8], out: &mut [u8]) {
+    decode_bc5_with(block, out, true);
+}
+
+struct Bc7Mode {
+    num_subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index_bits_2: u32,
+}
+
+macro_rules! bc7_modes {
+    ($([$ns:expr, $pb:expr, $rb:expr, $isb:expr, $cb:expr, $ab:expr, $epb:expr, $spb:expr, $ib:expr, $ib2:expr],)*) => {
+        [$(Bc7Mode {
+            num_subsets: $ns,
+            partition_bits: $pb,
+            rotation_bits: $rb,
+            index_selection_bits: $isb,
+            color_bits: $cb,
+            alpha_bits: $ab,
+            endpoint_pbits: $epb != 0,
+            shared_pbits: $spb != 0,
+            index_bits: $ib,
+            index_bits_2: $ib2,
+        },)*]
+    };
+}
+
+const BC7_MODES: [Bc7Mode; 8] = bc7_modes![
+    [3, 4, 0, 0, 4, 0, 1, 0, 3, 0],
+    [2, 6, 0, 0, 6, 0, 0, 1, 3, 0],
+    [3, 6, 0, 0, 5, 0, 0, 0, 2, 0],
+    [2, 6, 0, 0, 7, 0, 1, 0, 2, 0],
+    [1, 0, 2, 1, 5, 6, 0, 0, 2, 3],
+    [1, 0, 2, 0, 7, 8, 0, 0, 2, 2],
+    [1, 0, 0, 0, 7, 7, 1, 0, 4, 0],
+    [2, 6, 0, 0, 5, 5, 1, 0, 2, 0],
+];
+
+fn unquantize_bc7(v: u32, bits: u32) -> u32 {
+    let v = v << (8 - bits);
+    v | (v >> bits)
+}
+
+pub(super) fn decode_bc7(block: &[u8], out: &mut [u8]) {
+    let mode_idx = block[0].trailing_zeros() as usize;
+    let mode = match BC7_MODES.get(mode_idx) {
+        Some(mode) => mode,
+        None => {
+            // reserved mode
+            for v in out.iter_mut().take(64) {
+                *v = 0;
+            }
+            return;
+        }
+    };
+
+    let mut bits = BitReader::new(block);
+    bits.read(mode_idx as u32 + 1);
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let num_subsets = mode.num_subsets;
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for channel in 0..3 {
+        for subset in endpoints.iter_mut().take(num_subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = bits.read(mode.color_bits);
+            }
+        }
+    }
+    for subset in endpoints.iter_mut().take(num_subsets) {
+        for endpoint in subset.iter_mut() {
+            endpoint[3] = bits.read(mode.alpha_bits);
+        }
+    }
+
+    let (color_bits, alpha_bits) = if mode.endpoint_pbits || mode.shared_pbits {
+        (mode.color_bits + 1, mode.alpha_bits + 1)
+    } else {
+        (mode.color_bits, mode.alpha_bits)
+    };
+    if mode.endpoint_pbits || mode.shared_pbits {
+        for subset in endpoints.iter_mut().take(num_subsets) {
+            let mut pbit = 0;
+            for (idx, endpoint) in subset.iter_mut().enumerate() {
+                if mode.endpoint_pbits || idx == 0 {
+                    pbit = bits.read(1);
+                }
+                for v in endpoint.iter_mut() {
+                    *v = (*v << 1) | pbit;
+                }
+            }
+        }
+    }
+    for subset in endpoints.iter_mut().take(num_subsets) {
+        for endpoint in subset.iter_mut() {
+            for v in endpoint[..3].iter_mut() {
+                *v = unquantize_bc7(*v, color_bits);
+            }
+            endpoint[3] = if mode.alpha_bits == 0 {
+                0xff
+            } else {
+                unquantize_bc7(endpoint[3], alpha_bits)
+            };
+        }
+    }
+
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(num_subsets, partition, pixel);
+        *index = bits.read(mode.index_bits - anchor as u32);
+    }
+    let mut indices_2 = [0u32; 16];
+    if mode.index_bits_2 != 0 {
+        for (pixel, index) in indices_2.iter_mut().enumerate() {
+            *index = bits.read(mode.index_bits_2 - (pixel == 0) as u32);
+        }
+    }
+
+    for (pixel, out) in out.chunks_mut(4).take(16).enumerate() {
+        let subset = &endpoints[subset_of(num_subsets, partition, pixel)];
+        let (color_weight, alpha_weight) = if mode.index_bits_2 == 0 {
+            let weight = weights(mode.index_bits)[indices[pixel] as usize];
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                weights(mode.index_bits)[indices[pixel] as usize],
+                weights(mode.index_bits_2)[indices_2[pixel] as usize],
+            )
+        } else {
+            (
+                weights(mode.index_bits_2)[indices_2[pixel] as usize],
+                weights(mode.index_bits)[indices[pixel] as usize],
+            )
+        };
+        for c in 0..3 {
+            out[c] = interpolate(subset[0][c], subset[1][c], color_weight) as u8;
+        }
+        out[3] = interpolate(subset[0][3], subset[1][3], alpha_weight) as u8;
+        match rotation {
+            1 => out.swap(0, 3),
+            2 => out.swap(1, 3),
+            3 => out.swap(2, 3),
+            _ => {}
+        }
+    }
+}
+
+/// Endpoint and channel of a BC6H field name, with endpoints named `w`, `x`, `y` and `z`.
+macro_rules! bc6h_field {
+    (rw) => {
+        (0, 0)
+    };
+    (gw) => {
+        (0, 1)
+    };
+    (bw) => {
+        (0, 2)
+    };
+    (rx) => {
+        (1, 0)
+    };
+    (gx) => {
+        (1, 1)
+    };
+    (bx) => {
+        (1, 2)
+    };
+    (ry) => {
+        (2, 0)
+    };
+    (gy) => {
+        (2, 1)
+    };
+    (by) => {
+        (2, 2)
+    };
+    (rz) => {
+        (3, 0)
+    };
+    (gz) => {
+        (3, 1)
+    };
+    (bz) => {
+        (3, 2)
+    };
+}
+
+/// Bit layout written as in the specification, e.g. `rw[0..10]` for bits 0 to 9 of `rw`.
+macro_rules! bc6h_layout {
+    ($($field:ident[$lsb:literal $(.. $end:literal)?]),* $(,)?) => {
+        &[$({
+            let (endpoint, channel) = bc6h_field!($field);
+            let count = 1 $(+ $end - $lsb - 1)?;
+            (endpoint, channel, $lsb, count)
+        }),*]
+    };
+}
+
+struct Bc6hMode {
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Fields after the mode, as `(endpoint, channel, lowest bit, bit count)`.
+    layout: &'static [(u8, u8, u8, u8)],
+}
+
+/// Two-region modes, keyed by their mode bits.
+const BC6H_MODES: [(u32, Bc6hMode); 10] = [
+    (
+        0x00,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 10,
+            delta_bits: [5, 5, 5],
+            layout: bc6h_layout! {
+                gy[4], by[4], bz[4], rw[0..10], gw[0..10], bw[0..10], rx[0..5], gz[4], gy[0..4],
+                gx[0..5], bz[0], gz[0..4], bx[0..5], bz[1], by[0..4], ry[0..5], bz[2], rz[0..5],
+                bz[3]
+            },
+        },
+    ),
+    (
+        0x01,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 7,
+            delta_bits: [6, 6, 6],
+            layout: bc6h_layout! {
+                gy[5], gz[4], gz[5], rw[0..7], bz[0], bz[1], by[4], gw[0..7], by[5], bz[2], gy[4],
+                bw[0..7], bz[3], bz[5], bz[4], rx[0..6], gy[0..4], gx[0..6], gz[0..4], bx[0..6],
+                by[0..4], ry[0..6], rz[0..6]
+            },
+        },
+    ),
+    (
+        0x02,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 11,
+            delta_bits: [5, 4, 4],
+            layout: bc6h_layout! {
+                rw[0..10], gw[0..10], bw[0..10], rx[0..5], rw[10], gy[0..4], gx[0..4], gw[10],
+                bz[0], gz[0..4], bx[0..4], bw[10], bz[1], by[0..4], ry[0..5], bz[2], rz[0..5],
+                bz[3]
+            },
+        },
+    ),
+    (
+        0x06,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 11,
+            delta_bits: [4, 5, 4],
+            layout: bc6h_layout! {
+                rw[0..10], gw[0..10], bw[0..10], rx[0..4], rw[10], gz[4], gy[0..4], gx[0..5],
+                gw[10], gz[0..4], bx[0..4], bw[10], bz[1], by[0..4], ry[0..4], bz[0], bz[2],
+                rz[0..4], gy[4], bz[3]
+            },
+        },
+    ),
+    (
+        0x0a,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 11,
+            delta_bits: [4, 4, 5],
+            layout: bc6h_layout! {
+                rw[0..10], gw[0..10], bw[0..10], rx[0..4], rw[10], by[4], gy[0..4], gx[0..4],
+                gw[10], bz[0], gz[0..4], bx[0..5], bw[10], by[0..4], ry[0..4], bz[1], bz[2],
+                rz[0..4], bz[4], bz[3]
+            },
+        },
+    ),
+    (
+        0x0e,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 9,
+            delta_bits: [5, 5, 5],
+            layout: bc6h_layout! {
+                rw[0..9], by[4], gw[0..9], gy[4], bw[0..9], bz[4], rx[0..5], gz[4], gy[0..4],
+                gx[0..5], bz[0], gz[0..4], bx[0..5], bz[1], by[0..4], ry[0..5], bz[2], rz[0..5],
+                bz[3]
+            },
+        },
+    ),
+    (
+        0x12,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 8,
+            delta_bits: [6, 5, 5],
+            layout: bc6h_layout! {
+                rw[0..8], gz[4], by[4], gw[0..8], bz[2], gy[4], bw[0..8], bz[3], bz[4], rx[0..6],
+                gy[0..4], gx[0..5], bz[0], gz[0..4], bx[0..5], bz[1], by[0..4], ry[0..6], rz[0..6]
+            },
+        },
+    ),
+    (
+        0x16,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 8,
+            delta_bits: [5, 6, 5],
+            layout: bc6h_layout! {
+                rw[0..8], bz[0], by[4], gw[0..8], gy[5], gy[4], bw[0..8], gz[5], bz[4], rx[0..5],
+                gz[4], gy[0..4], gx[0..6], gz[0..4], bx[0..5], bz[1], by[0..4], ry[0..5], bz[2],
+                rz[0..5], bz[3]
+            },
+        },
+    ),
+    (
+        0x1a,
+        Bc6hMode {
+            transformed: true,
+            endpoint_bits: 8,
+            delta_bits: [5, 5, 6],
+            layout: bc6h_layout! {
+                rw[0..8], bz[1], by[4], gw[0..8], by[5], gy[4], bw[0..8], bz[5], bz[4], rx[0..5],
+                gz[4], gy[0..4], gx[0..5], bz[0], gz[0..4], bx[0..6], by[0..4], ry[0..5], bz[2],
+                rz[0..5], bz[3]
+            },
+        },
+    ),
+    (
+        0x1e,
+        Bc6hMode {
+            transformed: false,
+            endpoint_bits: 6,
+            delta_bits: [6, 6, 6],
+            layout: bc6h_layout! {
+                rw[0..6], gz[4], bz[0], bz[1], by[4], gw[0..6], gy[5], by[5], bz[2], gy[4],
+                bw[0..6], gz[5], bz[3], bz[5], bz[4], rx[0..6], gy[0..4], gx[0..6], gz[0..4],
+                bx[0..6], by[0..4], ry[0..6], rz[0..6]
+            },
+        },
+    ),
+];
+
+fn sign_extend(v: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((v << shift) as i32) >> shift
+}
+
+/// Unquantizes an endpoint to 16 bits, or to 15 bits and a sign if `signed`.
+fn unquantize_bc6h(v: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || v == 0 {
+            v
+        } else if v == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((v << 16) + 0x8000) >> bits
+        }
+    } else if bits >= 16 || v == 0 {
+        v
+    } else {
+        let magnitude = v.abs();
+        let magnitude = if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        magnitude * v.signum()
+    }
+}
+
+/// Scales an interpolated value to the bits of a half float.
+fn finish_bc6h(v: i32, signed: bool) -> u16 {
+    if !signed {
+        ((v * 31) >> 6) as u16
+    } else if v < 0 {
+        0x8000 | ((-v * 31) >> 5) as u16
+    } else {
+        ((v * 31) >> 5) as u16
+    }
+}
+
+/// Decodes a BC6H block. Unity only uses the signed variant for `GraphicsFormat` textures.
+pub(super) fn decode_bc6h(block: &[u8], out: &mut [f32], signed: bool) {
+    let mut bits = BitReader::new(block);
+    let mode_bits = match bits.read(2) {
+        mode @ 0..=1 => mode,
+        mode => mode | (bits.read(3) << 2),
+    };
+
+    let mut endpoints = [[0u32; 3]; 4];
+    let num_regions;
+    let endpoint_bits;
+    let delta_bits;
+    let transformed;
+    let partition;
+    if mode_bits & 3 == 3 {
+        // one-region modes: 0x03, 0x07, 0x0b, 0x0f
+        let (bits_w, bits_x) = match mode_bits {
+            0x03 => (10, 10),
+            0x07 => (11, 9),
+            0x0b => (12, 8),
+            0x0f => (16, 4),
+            _ => {
+                // reserved mode
+                for v in out.iter_mut().take(64) {
+                    *v = 0.0;
+                }
+                return;
+            }
+        };
+        for v in endpoints[0].iter_mut() {
+            *v = bits.read(10);
+        }
+        let (base, rest) = endpoints.split_at_mut(1);
+        for (base, v) in base[0].iter_mut().zip(rest[0].iter_mut()) {
+            *v = bits.read(bits_x);
+            // the higher bits of the base endpoint are stored reversed
+            for bit in (10..bits_w).rev() {
+                *base |= bits.read(1) << bit;
+            }
+        }
+        num_regions = 1;
+        endpoint_bits = bits_w;
+        delta_bits = [bits_x; 3];
+        transformed = mode_bits != 0x03;
+        partition = 0;
+    } else {
+        let mode = match BC6H_MODES.iter().find(|(bits, _)| *bits == mode_bits) {
+            Some((_, mode)) => mode,
+            None => {
+                // reserved mode
+                for v in out.iter_mut().take(64) {
+                    *v = 0.0;
+                }
+                return;
+            }
+        };
+        for &(endpoint, channel, lsb, count) in mode.layout {
+            endpoints[endpoint as usize][channel as usize] |= bits.read(count.into()) << lsb;
+        }
+        num_regions = 2;
+        endpoint_bits = mode.endpoint_bits;
+        delta_bits = mode.delta_bits;
+        transformed = mode.transformed;
+        partition = bits.read(5) as usize;
+    }
+
+    // Endpoints after the first are deltas in transformed modes. Signed endpoints are sign
+    // extended, deltas always are.
+    let base = endpoints[0];
+    let mut values = [[0i32; 3]; 4];
+    for (channel, v) in values[0].iter_mut().enumerate() {
+        *v = if signed {
+            sign_extend(base[channel], endpoint_bits)
+        } else {
+            base[channel] as i32
+        };
+    }
+    let mask = (1 << endpoint_bits) - 1;
+    for (endpoint, value) in endpoints[1..2 * num_regions]
+        .iter()
+        .zip(values[1..].iter_mut())
+    {
+        for channel in 0..3 {
+            let v = endpoint[channel];
+            value[channel] = if transformed {
+                let delta = sign_extend(v, delta_bits[channel]);
+                let v = (base[channel] as i32).wrapping_add(delta) as u32 & mask;
+                if signed {
+                    sign_extend(v, endpoint_bits)
+                } else {
+                    v as i32
+                }
+            } else if signed {
+                sign_extend(v, endpoint_bits)
+            } else {
+                v as i32
+            };
+        }
+    }
+    for value in values.iter_mut() {
+        for v in value.iter_mut() {
+            *v = unquantize_bc6h(*v, endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if num_regions == 1 { 4 } else { 3 };
+    for (pixel, out) in out.chunks_mut(4).take(16).enumerate() {
+        let anchor = is_anchor(num_regions, partition, pixel);
+        let weight = weights(index_bits)[bits.read(index_bits - anchor as u32) as usize] as i32;
+        let region = subset_of(num_regions, partition, pixel);
+        let (e0, e1) = (&values[2 * region], &values[2 * region + 1]);
+        for (c, out) in out[..3].iter_mut().enumerate() {
+            let v = ((64 - weight) * e0[c] + weight * e1[c] + 32) >> 6;
+            *out = half_to_f32(finish_bc6h(v, signed));
+        }
+        out[3] = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classes::TextureFormat;
+    use crate::texture::Pixels;
+
+    #[test]
+    fn bc1_blocks() {
+        // red to blue with four colors, indices 0, 1, 2, 3 in every row
+        let mut out = [0; 64];
+        decode_bc1(&[0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4], &mut out);
+        let colors = [
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+            [170, 0, 85, 255],
+            [85, 0, 170, 255],
+        ];
+        for (pixel, expected) in out.chunks(4).zip(colors.iter().cycle()) {
+            assert_eq!(pixel, expected);
+        }
+
+        // same endpoints swapped, so three colors and transparent black
+        decode_bc1(&[0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4], &mut out);
+        let colors = [
+            [0, 0, 255, 255],
+            [255, 0, 0, 255],
+            [128, 0, 128, 255],
+            [0, 0, 0, 0],
+        ];
+        for (pixel, expected) in out.chunks(4).zip(colors.iter().cycle()) {
+            assert_eq!(pixel, expected);
+        }
+    }
+
+    #[test]
+    fn bc2_bc3_blocks() {
+        // swapped endpoints still use four colors, the white endpoint with index 1
+        let color = [0x00, 0x00, 0xff, 0xff, 0x55, 0x55, 0x55, 0x55];
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&[0x10, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe]);
+        block[8..].copy_from_slice(&color);
+        let mut out = [0; 64];
+        decode_bc2(&block, &mut out);
+        for (i, pixel) in out.chunks(4).enumerate() {
+            assert_eq!(pixel, [255, 255, 255, i as u8 * 17]);
+        }
+
+        block[..8].copy_from_slice(&BC4_BLOCKS[0].0);
+        decode_bc3(&block, &mut out);
+        for (pixel, &alpha) in out.chunks(4).zip(&BC4_BLOCKS[0].1) {
+            assert_eq!(pixel, [255, 255, 255, alpha]);
+        }
+    }
+
+    /// Blocks encoded by hand, with pixels worked out from the format specifications.
+    const BC4_BLOCKS: [([u8; 8], [u8; 16]); 2] = [
+        // eight-value palette
+        (
+            [0xff, 0x00, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa],
+            [
+                255, 0, 219, 182, 146, 109, 73, 36, 255, 0, 219, 182, 146, 109, 73, 36,
+            ],
+        ),
+        // six-value palette with explicit 0 and 255
+        (
+            [0x0a, 0x3c, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa],
+            [
+                10, 60, 20, 30, 40, 50, 0, 255, 10, 60, 20, 30, 40, 50, 0, 255,
+            ],
+        ),
+    ];
+
+    #[test]
+    fn bc4_blocks() {
+        for (block, expected) in &BC4_BLOCKS {
+            let mut out = [0; 64];
+            decode_bc4(block, &mut out);
+            for (pixel, &red) in out.chunks(4).zip(expected) {
+                assert_eq!(pixel, [red, 0, 0, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn bc5_block() {
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&BC4_BLOCKS[0].0);
+        block[8..].copy_from_slice(&[0x20, 0xe0, 0x77, 0x39, 0x05, 0x77, 0x39, 0x05]);
+        let green = [
+            255, 0, 186, 147, 109, 70, 224, 32, 255, 0, 186, 147, 109, 70, 224, 32,
+        ];
+        let mut out = [0; 64];
+        decode_bc5(&block, &mut out);
+        for ((pixel, &red), &green) in out.chunks(4).zip(&BC4_BLOCKS[0].1).zip(&green) {
+            assert_eq!(pixel, [red, green, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn bc4_signed_blocks() {
+        // eight-value palette from 127 down to -128, which is clamped to -127
+        let block = [0x7f, 0x80, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa];
+        let red = [255, 1, 219, 182, 146, 110, 74, 37];
+        let mut out = [0; 64];
+        decode_bc4_signed(&block, &mut out);
+        for (pixel, &red) in out.chunks(4).zip(red.iter().cycle()) {
+            assert_eq!(pixel, [red, 0, 0, 255]);
+        }
+        // six-value palette with explicit -1.0 and 1.0
+        let block = [0xf6, 0x0a, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa];
+        let red = [118, 138, 122, 126, 130, 134, 1, 255];
+        let mut both = [0; 16];
+        both[..8].copy_from_slice(&block);
+        both[8..].copy_from_slice(&block);
+        decode_bc5_signed(&both, &mut out);
+        for (pixel, &red) in out.chunks(4).zip(red.iter().cycle()) {
+            assert_eq!(pixel, [red, red, 0, 255]);
+        }
+    }
+
+    type Bc6hBlock = ([u8; 16], [[u16; 3]; 16]);
+
+    /// BC6H blocks of the one-region modes 0x03 and 0x07, with pixels as half floats.
+    const BC6H_UNSIGNED_BLOCKS: [Bc6hBlock; 2] = [
+        // untransformed
+        (
+            [
+                0x03, 0x80, 0xf7, 0x00, 0x78, 0x0f, 0x80, 0xff, 0xf1, 0x78, 0xe1, 0xc3, 0xf0, 0x78,
+                0xe1, 0xc3,
+            ],
+            [
+                [0x0000, 0x3c00, 0x0000],
+                [0x3c00, 0x0000, 0x7bff],
+                [0x1fe0, 0x1c20, 0x41df],
+                [0x1c20, 0x1fe0, 0x3a20],
+                [0x03c0, 0x3840, 0x07c0],
+                [0x3840, 0x03c0, 0x743f],
+                [0x0c30, 0x2fd0, 0x1930],
+                [0x2fd0, 0x0c30, 0x62cf],
+                [0x0000, 0x3c00, 0x0000],
+                [0x3c00, 0x0000, 0x7bff],
+                [0x1fe0, 0x1c20, 0x41df],
+                [0x1c20, 0x1fe0, 0x3a20],
+                [0x03c0, 0x3840, 0x07c0],
+                [0x3840, 0x03c0, 0x743f],
+                [0x0c30, 0x2fd0, 0x1930],
+                [0x2fd0, 0x0c30, 0x62cf],
+            ],
+        ),
+        // transformed
+        (
+            [
+                0xc7, 0x7b, 0xee, 0x28, 0xe0, 0xec, 0x5f, 0x00, 0xf0, 0x78, 0xe1, 0xc3, 0xf0, 0x78,
+                0xe1, 0xc3,
+            ],
+            [
+                [0x3bf8, 0x5ad9, 0x013d],
+                [0x35ea, 0x6a4a, 0x013d],
+                [0x38c1, 0x630d, 0x013d],
+                [0x3922, 0x6216, 0x013d],
+                [0x3b97, 0x5bd0, 0x013d],
+                [0x364b, 0x6953, 0x013d],
+                [0x3abd, 0x5dfc, 0x013d],
+                [0x3725, 0x6727, 0x013d],
+                [0x3bf8, 0x5ad9, 0x013d],
+                [0x35ea, 0x6a4a, 0x013d],
+                [0x38c1, 0x630d, 0x013d],
+                [0x3922, 0x6216, 0x013d],
+                [0x3b97, 0x5bd0, 0x013d],
+                [0x364b, 0x6953, 0x013d],
+                [0x3abd, 0x5dfc, 0x013d],
+                [0x3725, 0x6727, 0x013d],
+            ],
+        ),
+    ];
+
+    const BC6H_SIGNED_BLOCKS: [Bc6hBlock; 2] = [
+        // untransformed
+        (
+            [
+                0x23, 0xe1, 0xff, 0x00, 0xb8, 0x27, 0x40, 0x32, 0xf0, 0x78, 0xe1, 0xc3, 0xf0, 0x78,
+                0xe1, 0xc3,
+            ],
+            [
+                [0xbbf1, 0x7bff, 0x0000],
+                [0x3bf1, 0xfbff, 0x1857],
+                [0x03bf, 0x87c0, 0x0cee],
+                [0x83bf, 0x07c0, 0x0b68],
+                [0xb472, 0x6c7f, 0x0185],
+                [0x3472, 0xec7f, 0x16d1],
+                [0xa397, 0x499f, 0x04f2],
+                [0x2397, 0xc99f, 0x1365],
+                [0xbbf1, 0x7bff, 0x0000],
+                [0x3bf1, 0xfbff, 0x1857],
+                [0x03bf, 0x87c0, 0x0cee],
+                [0x83bf, 0x07c0, 0x0b68],
+                [0xb472, 0x6c7f, 0x0185],
+                [0x3472, 0xec7f, 0x16d1],
+                [0xa397, 0x499f, 0x04f2],
+                [0x2397, 0xc99f, 0x1365],
+            ],
+        ),
+        // transformed
+        (
+            [
+                0x87, 0x5a, 0xc8, 0x0a, 0x00, 0x18, 0xa7, 0x7f, 0xf0, 0x78, 0xe1, 0xc3, 0xf0, 0x78,
+                0xe1, 0xc3,
+            ],
+            [
+                [0xa463, 0x307f, 0x00aa],
+                [0xc363, 0x1847, 0x1f8b],
+                [0xb4db, 0x23a1, 0x1112],
+                [0xb2eb, 0x2525, 0x0f23],
+                [0xa653, 0x2efc, 0x0298],
+                [0xc173, 0x19cb, 0x1d9d],
+                [0xaaaf, 0x2b94, 0x06f0],
+                [0xbd17, 0x1d32, 0x1946],
+                [0xa463, 0x307f, 0x00aa],
+                [0xc363, 0x1847, 0x1f8b],
+                [0xb4db, 0x23a1, 0x1112],
+                [0xb2eb, 0x2525, 0x0f23],
+                [0xa653, 0x2efc, 0x0298],
+                [0xc173, 0x19cb, 0x1d9d],
+                [0xaaaf, 0x2b94, 0x06f0],
+                [0xbd17, 0x1d32, 0x1946],
+            ],
+        ),
+    ];
+
+    fn check_bc6h(blocks: &[Bc6hBlock], signed: bool) {
+        for (block, expected) in blocks {
+            let mut out = [0.0; 64];
+            decode_bc6h(block, &mut out, signed);
+            for (pixel, expected) in out.chunks(4).zip(expected) {
+                let expected = [
+                    half_to_f32(expected[0]),
+                    half_to_f32(expected[1]),
+                    half_to_f32(expected[2]),
+                    1.0,
+                ];
+                assert_eq!(pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn bc6h_unsigned_blocks() {
+        check_bc6h(&BC6H_UNSIGNED_BLOCKS, false);
+    }
+
+    #[test]
+    fn bc6h_signed_blocks() {
+        check_bc6h(&BC6H_SIGNED_BLOCKS, true);
+    }
+
+    #[test]
+    fn signed_graphics_formats() {
+        let decode = |id, block: &[u8]| {
+            let format = TextureFormat::from_graphics_format(id).unwrap();
+            crate::texture::decode(format, 4, 4, block).unwrap().pixels
+        };
+        let flip =
+            |pixels: &[u8]| -> Vec<u8> { pixels.chunks(16).rev().flatten().copied().collect() };
+
+        let mut out = [0; 64];
+        decode_bc4_signed(&BC4_BLOCKS[0].0, &mut out);
+        assert_eq!(decode(103, &BC4_BLOCKS[0].0), Pixels::Rgba8(flip(&out)));
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&BC4_BLOCKS[0].0);
+        block[8..].copy_from_slice(&BC4_BLOCKS[1].0);
+        decode_bc5_signed(&block, &mut out);
+        assert_eq!(decode(105, &block), Pixels::Rgba8(flip(&out)));
+
+        let (block, _) = &BC6H_SIGNED_BLOCKS[0];
+        let mut out = [0.0; 64];
+        decode_bc6h(block, &mut out, true);
+        let flipped: Vec<f32> = out.chunks(16).rev().flatten().copied().collect();
+        assert_eq!(decode(107, block), Pixels::Rgba32F(flipped));
+        assert_ne!(decode(106, block), decode(107, block));
+    }
+
+    const BC7_BLOCKS: [([u8; 16], [[u8; 4]; 16]); 5] = [
+        // mode 6
+        (
+            [
+                0x40, 0x08, 0xfc, 0x0f, 0x00, 0x02, 0xff, 0xbf, 0x10, 0x32, 0x54, 0x76, 0x98, 0xba,
+                0xdc, 0xfe,
+            ],
+            [
+                [33, 255, 129, 255],
+                [45, 239, 129, 247],
+                [60, 219, 129, 237],
+                [72, 203, 129, 229],
+                [84, 187, 129, 221],
+                [96, 171, 129, 213],
+                [111, 151, 129, 203],
+                [123, 135, 129, 195],
+                [134, 120, 128, 186],
+                [146, 104, 128, 178],
+                [161, 84, 128, 168],
+                [173, 68, 128, 160],
+                [185, 52, 128, 152],
+                [197, 36, 128, 144],
+                [212, 16, 128, 134],
+                [224, 0, 128, 126],
+            ],
+        ),
+        // mode 5, alpha rotated into red
+        (
+            [
+                0x60, 0x7f, 0x00, 0xe0, 0x0f, 0x04, 0x01, 0xfc, 0xcb, 0xc9, 0xc9, 0xc9, 0x19, 0x1b,
+                0x1b, 0x1b,
+            ],
+            [
+                [0, 0, 129, 255],
+                [171, 84, 108, 171],
+                [84, 171, 85, 84],
+                [0, 255, 64, 0],
+                [255, 0, 129, 255],
+                [171, 84, 108, 171],
+                [84, 171, 85, 84],
+                [0, 255, 64, 0],
+                [255, 0, 129, 255],
+                [171, 84, 108, 171],
+                [84, 171, 85, 84],
+                [0, 255, 64, 0],
+                [255, 0, 129, 255],
+                [171, 84, 108, 171],
+                [84, 171, 85, 84],
+                [0, 255, 64, 0],
+            ],
+        ),
+        // mode 4, swapped index sets
+        (
+            [
+                0x90, 0x1f, 0x40, 0x08, 0xfe, 0x0f, 0xca, 0xc9, 0xc9, 0xc9, 0x89, 0xc6, 0xfa, 0x88,
+                0xc6, 0xfa,
+            ],
+            [
+                [255, 132, 0, 255],
+                [219, 132, 36, 214],
+                [183, 132, 72, 171],
+                [147, 132, 108, 130],
+                [108, 132, 147, 255],
+                [72, 132, 183, 214],
+                [36, 132, 219, 171],
+                [0, 132, 255, 130],
+                [255, 132, 0, 255],
+                [219, 132, 36, 214],
+                [183, 132, 72, 171],
+                [147, 132, 108, 130],
+                [108, 132, 147, 255],
+                [72, 132, 183, 214],
+                [36, 132, 219, 171],
+                [0, 132, 255, 130],
+            ],
+        ),
+        // mode 1, two subsets
+        (
+            [
+                0x36, 0x3f, 0x00, 0x80, 0xc0, 0x0f, 0x41, 0x08, 0xf2, 0x03, 0x31, 0x87, 0x57, 0x31,
+                0x87, 0xd7,
+            ],
+            [
+                [255, 2, 34, 255],
+                [148, 109, 34, 255],
+                [38, 219, 34, 255],
+                [219, 38, 34, 255],
+                [109, 148, 34, 255],
+                [2, 255, 34, 255],
+                [184, 73, 34, 255],
+                [73, 184, 34, 255],
+                [0, 64, 253, 255],
+                [54, 64, 146, 255],
+                [111, 64, 36, 255],
+                [18, 64, 217, 255],
+                [75, 64, 107, 255],
+                [129, 64, 0, 255],
+                [36, 64, 182, 255],
+                [54, 64, 146, 255],
+            ],
+        ),
+        // mode 0, three subsets
+        (
+            [
+                0xe1, 0x01, 0x1e, 0x11, 0xe0, 0x7f, 0xf8, 0x1f, 0x20, 0x24, 0xa7, 0x9a, 0xf1, 0x50,
+                0x9d, 0xf1,
+            ],
+            [
+                [255, 8, 255, 255],
+                [72, 2, 249, 255],
+                [72, 249, 2, 255],
+                [108, 250, 3, 255],
+                [108, 3, 250, 255],
+                [219, 7, 254, 255],
+                [219, 254, 7, 255],
+                [108, 250, 3, 255],
+                [255, 8, 255, 255],
+                [140, 164, 36, 255],
+                [140, 99, 29, 255],
+                [255, 255, 8, 255],
+                [140, 143, 34, 255],
+                [140, 78, 26, 255],
+                [140, 185, 39, 255],
+                [140, 120, 31, 255],
+            ],
+        ),
+    ];
+
+    #[test]
+    fn bc7_blocks() {
+        for (block, expected) in &BC7_BLOCKS {
+            let mut out = [0; 64];
+            decode_bc7(block, &mut out);
+            for (pixel, expected) in out.chunks(4).zip(expected) {
+                assert_eq!(pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn reserved_modes() {
+        let mut out = [1; 64];
+        decode_bc7(&[0; 16], &mut out);
+        assert_eq!(out, [0; 64]);
+        let mut out = [1.0; 64];
+        decode_bc6h(&[0x13; 16], &mut out, false);
+        assert_eq!(out, [0.0; 64]);
+    }
+}
@@ -0,0 +1,80 @@
+use super::{bc, decode_blocks, etc, DecodeError, Pixels};
+use crate::classes::TextureFormat;
+use crunchdec::{Format, Texture, Variant};
+
+/// Swaps the channels of DXT5 variants which store red in alpha.
+fn unswizzle(pixels: &mut [u8], format: Format) {
+    for pixel in pixels.chunks_mut(4) {
+        let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        let unswizzled = match format {
+            Format::Dxt5XGxR => [a, g, 0, 0xff],
+            Format::Dxt5XGbr => [a, g, b, 0xff],
+            Format::Dxt5Agbr => [a, g, b, r],
+            _ => return,
+        };
+        pixel.copy_from_slice(&unswizzled);
+    }
+}
+
+pub(super) fn decode(
+    format: TextureFormat,
+    width: usize,
+    height: usize,
+    data: &[u8],
+    variant: Variant,
+) -> Result<Pixels, DecodeError> {
+    let texture = Texture::parse(data, variant).map_err(DecodeError::Crunch)?;
+    let blocks = texture.unpack_level(0).map_err(DecodeError::Crunch)?;
+    let (w, h) = (width, height);
+    let mut pixels = match texture.format() {
+        Format::Dxt1 => decode_blocks(w, h, 8, &blocks, bc::decode_bc1)?,
+        Format::Dxt5 | Format::Dxt5XGxR | Format::Dxt5XGbr | Format::Dxt5Agbr => {
+            decode_blocks(w, h, 16, &blocks, bc::decode_bc3)?
+        }
+        Format::Dxt5A => decode_blocks(w, h, 8, &blocks, bc::decode_bc4)?,
+        Format::DxnXy => decode_blocks(w, h, 16, &blocks, bc::decode_bc5)?,
+        Format::DxnYx => decode_blocks(w, h, 16, &blocks, |block, out| {
+            bc::decode_bc5(block, out);
+            for pixel in out.chunks_mut(4) {
+                pixel.swap(0, 1);
+            }
+        })?,
+        Format::Etc1 | Format::Etc1S => etc::decode(w, h, &blocks, etcdec::DecodeFormat::EtcRgb4)?,
+        Format::Etc2 => etc::decode(w, h, &blocks, etcdec::DecodeFormat::Etc2Rgb)?,
+        Format::Etc2A | Format::Etc2AS => {
+            etc::decode(w, h, &blocks, etcdec::DecodeFormat::Etc2Rgba8)?
+        }
+        // luma and chroma would need the encoder's color transform, which crunch doesn't record
+        Format::Dxt5CcxY => return Err(DecodeError::UnsupportedFormat(format)),
+    };
+    unswizzle(&mut pixels, texture.format());
+    Ok(Pixels::Rgba8(pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swizzled_dxt5() {
+        let pixel = [10, 20, 30, 40];
+        for &(format, expected) in &[
+            (Format::Dxt5, [10, 20, 30, 40]),
+            (Format::Dxt5XGxR, [40, 20, 0, 255]),
+            (Format::Dxt5XGbr, [40, 20, 30, 255]),
+            (Format::Dxt5Agbr, [40, 20, 30, 10]),
+        ] {
+            let mut pixels = pixel;
+            unswizzle(&mut pixels, format);
+            assert_eq!(pixels, expected);
+        }
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert_eq!(
+            crate::texture::decode(TextureFormat::Dxt5Crunched, 4, 4, &[0; 16]),
+            Err(DecodeError::Crunch(crunchdec::Error::InvalidHeader))
+        );
+    }
+}
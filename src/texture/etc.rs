@@ -0,0 +1,59 @@
+use super::DecodeError;
+use crate::classes::TextureFormat;
+use etcdec::DecodeFormat;
+
+pub(super) fn decode_format(format: TextureFormat) -> Option<DecodeFormat> {
+    Some(match format {
+        TextureFormat::EtcRgb4 => DecodeFormat::EtcRgb4,
+        TextureFormat::Etc2Rgb => DecodeFormat::Etc2Rgb,
+        TextureFormat::Etc2Rgba1 => DecodeFormat::Etc2Rgba1,
+        TextureFormat::Etc2Rgba8 => DecodeFormat::Etc2Rgba8,
+        TextureFormat::EacR => DecodeFormat::EacR,
+        TextureFormat::EacRSigned => DecodeFormat::EacRSigned,
+        TextureFormat::EacRg => DecodeFormat::EacRg,
+        TextureFormat::EacRgSigned => DecodeFormat::EacRgSigned,
+        _ => return None,
+    })
+}
+
+pub(super) fn decode(
+    width: usize,
+    height: usize,
+    data: &[u8],
+    format: DecodeFormat,
+) -> Result<Vec<u8>, DecodeError> {
+    let expected = width.div_ceil(4) * height.div_ceil(4) * format.block_size();
+    let not_enough_data = DecodeError::NotEnoughData {
+        expected,
+        found: data.len(),
+    };
+    if data.len() < expected {
+        return Err(not_enough_data);
+    }
+    // etcdec only fails when it runs out of input
+    etcdec::decode_image_flipped(data, width, height, format).map_err(|_| not_enough_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eac_rows() {
+        // multiplier 0 with all indices 0, so every pixel is the base value minus 3/8
+        let mut data = vec![0x40, 0, 0, 0, 0, 0, 0, 0];
+        data.extend(&[0xc0, 0, 0, 0, 0, 0, 0, 0]);
+        let image = crate::texture::decode(TextureFormat::EacR, 4, 8, &data).unwrap();
+        let pixels = image.into_rgba8();
+        // the first block is the bottom one
+        assert_eq!(&pixels[..4], &[191, 0, 0, 255]);
+        assert_eq!(&pixels[pixels.len() - 4..], &[64, 0, 0, 255]);
+        assert_eq!(
+            crate::texture::decode(TextureFormat::Etc2Rgba8, 4, 4, &data[..8]),
+            Err(DecodeError::NotEnoughData {
+                expected: 16,
+                found: 8
+            })
+        );
+    }
+}
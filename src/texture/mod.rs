@@ -0,0 +1,296 @@
+mod astc;
+mod bc;
+mod crunch;
+mod etc;
+mod pvrtc;
+mod uncompressed;
+
+use crate::classes::TextureFormat;
+
+pub use crunchdec::Variant as CrunchVariant;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DecodeError {
+    UnsupportedFormat(TextureFormat),
+    NotEnoughData { expected: usize, found: usize },
+    InvalidSize { width: usize, height: usize },
+    Crunch(crunchdec::Error),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnsupportedFormat(format) => {
+                write!(f, "decoding {:?} is not supported", format)
+            }
+            DecodeError::NotEnoughData { expected, found } => write!(
+                f,
+                "not enough image data (expected {} bytes, found {})",
+                expected, found
+            ),
+            DecodeError::InvalidSize { width, height } => {
+                write!(f, "invalid image size {}x{}", width, height)
+            }
+            DecodeError::Crunch(e) => write!(f, "failed to decompress crunched data: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub(crate) fn half_to_f32(h: u16) -> f32 {
+    let sign = u32::from(h & 0x8000) << 16;
+    let exp = u32::from((h >> 10) & 0x1f);
+    let mantissa = u32::from(h & 0x3ff);
+    let bits = match exp {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // subnormal, normalize the mantissa
+            let mut exp = 127 - 15 + 1;
+            let mut mantissa = mantissa;
+            while mantissa & 0x400 == 0 {
+                mantissa <<= 1;
+                exp -= 1;
+            }
+            sign | (exp << 23) | ((mantissa & 0x3ff) << 13)
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pixels {
+    Rgba8(Vec<u8>),
+    Rgba32F(Vec<f32>),
+}
+
+/// Decoded image with rows ordered from top to bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Pixels,
+}
+
+impl Image {
+    /// Returns RGBA8 pixels, clamping float pixels to `[0, 1]`.
+    pub fn into_rgba8(self) -> Vec<u8> {
+        match self.pixels {
+            Pixels::Rgba8(v) => v,
+            Pixels::Rgba32F(v) => v
+                .into_iter()
+                .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect(),
+        }
+    }
+}
+
+/// Decodes 4x4 blocks of `block_size` bytes with `decode_block`, reversing the row order.
+///
+/// `decode_block` writes 16 pixels of four channels. Pixels past the edges are dropped.
+fn decode_blocks<T: Copy + Default>(
+    width: usize,
+    height: usize,
+    block_size: usize,
+    data: &[u8],
+    decode_block: impl Fn(&[u8], &mut [T]),
+) -> Result<Vec<T>, DecodeError> {
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    let expected = blocks_x * blocks_y * block_size;
+    if data.len() < expected {
+        return Err(DecodeError::NotEnoughData {
+            expected,
+            found: data.len(),
+        });
+    }
+    let stride = width * 4;
+    let mut pixels = vec![T::default(); stride * height];
+    let mut block_pixels = [T::default(); 64];
+    for (idx, block) in data[..expected].chunks(block_size).enumerate() {
+        decode_block(block, &mut block_pixels);
+        let x = idx % blocks_x * 4;
+        let y = idx / blocks_x * 4;
+        let block_width = std::cmp::min(4, width - x);
+        for (row, src) in block_pixels.chunks(16).take(height - y).enumerate() {
+            let start = (height - 1 - (y + row)) * stride + x * 4;
+            pixels[start..start + block_width * 4].copy_from_slice(&src[..block_width * 4]);
+        }
+    }
+    Ok(pixels)
+}
+
+/// Whether [`decode`] can decode `format`.
+pub fn is_supported(format: TextureFormat) -> bool {
+    match format {
+        TextureFormat::Dxt1 | TextureFormat::Dxt3 | TextureFormat::Dxt5 => true,
+        TextureFormat::Bc4 | TextureFormat::Bc5 | TextureFormat::Bc6h | TextureFormat::Bc7 => true,
+        TextureFormat::Bc4Signed | TextureFormat::Bc5Signed | TextureFormat::Bc6hSigned => true,
+        TextureFormat::Dxt1Crunched
+        | TextureFormat::Dxt5Crunched
+        | TextureFormat::EtcRgb4Crunched
+        | TextureFormat::Etc2Rgba8Crunched => true,
+        TextureFormat::PvrtcRgb2
+        | TextureFormat::PvrtcRgba2
+        | TextureFormat::PvrtcRgb4
+        | TextureFormat::PvrtcRgba4 => true,
+        format => {
+            astc::block_size(format).is_some()
+                || etc::decode_format(format).is_some()
+                || uncompressed::is_supported(format)
+        }
+    }
+}
+
+/// Size of a `width`x`height` image in bytes, or `None` if it isn't known for `format`.
+pub fn image_size(format: TextureFormat, width: u32, height: u32) -> Option<usize> {
+    let (w, h) = (width as usize, height as usize);
+    let blocks = |block_width: usize, block_height: usize, block_size: usize| {
+        w.div_ceil(block_width) * h.div_ceil(block_height) * block_size
+    };
+    Some(match format {
+        TextureFormat::Dxt1
+        | TextureFormat::Bc4
+        | TextureFormat::Bc4Signed
+        | TextureFormat::EtcRgb4
+        | TextureFormat::EtcRgb4_3ds
+        | TextureFormat::AtcRgb4
+        | TextureFormat::Etc2Rgb
+        | TextureFormat::Etc2Rgba1
+        | TextureFormat::EacR
+        | TextureFormat::EacRSigned => blocks(4, 4, 8),
+        TextureFormat::Dxt3
+        | TextureFormat::Dxt5
+        | TextureFormat::Bc5
+        | TextureFormat::Bc5Signed
+        | TextureFormat::Bc6h
+        | TextureFormat::Bc6hSigned
+        | TextureFormat::Bc7
+        | TextureFormat::EtcRgba8_3ds
+        | TextureFormat::AtcRgba8
+        | TextureFormat::Etc2Rgba8
+        | TextureFormat::EacRg
+        | TextureFormat::EacRgSigned => blocks(4, 4, 16),
+        TextureFormat::PvrtcRgb2 | TextureFormat::PvrtcRgba2 => {
+            std::cmp::max(w, 16) * std::cmp::max(h, 8) / 4
+        }
+        TextureFormat::PvrtcRgb4 | TextureFormat::PvrtcRgba4 => {
+            std::cmp::max(w, 8) * std::cmp::max(h, 8) / 2
+        }
+        TextureFormat::AstcRgb4x4 | TextureFormat::AstcRgba4x4 | TextureFormat::AstcHdr4x4 => {
+            blocks(4, 4, 16)
+        }
+        TextureFormat::AstcRgb5x5 | TextureFormat::AstcRgba5x5 | TextureFormat::AstcHdr5x5 => {
+            blocks(5, 5, 16)
+        }
+        TextureFormat::AstcRgb6x6 | TextureFormat::AstcRgba6x6 | TextureFormat::AstcHdr6x6 => {
+            blocks(6, 6, 16)
+        }
+        TextureFormat::AstcRgb8x8 | TextureFormat::AstcRgba8x8 | TextureFormat::AstcHdr8x8 => {
+            blocks(8, 8, 16)
+        }
+        TextureFormat::AstcRgb10x10
+        | TextureFormat::AstcRgba10x10
+        | TextureFormat::AstcHdr10x10 => blocks(10, 10, 16),
+        TextureFormat::AstcRgb12x12
+        | TextureFormat::AstcRgba12x12
+        | TextureFormat::AstcHdr12x12 => blocks(12, 12, 16),
+        format => uncompressed::row_size(format, w)? * h,
+    })
+}
+
+/// Decodes the first image of `data`, which is stored bottom-up as Unity does.
+///
+/// Integer formats decode to RGBA8 and float formats to RGBA32F. Missing channels are filled
+/// with zero, except alpha which is opaque. Crunched data is expected in the format of Unity
+/// 2017.3 and later, see [`decode_with_crunch`] for older textures.
+pub fn decode(
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Result<Image, DecodeError> {
+    decode_with_crunch(format, width, height, data, CrunchVariant::Unity)
+}
+
+/// Like [`decode`], but decompresses crunched data of the given `crunch` variant.
+pub fn decode_with_crunch(
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    crunch: CrunchVariant,
+) -> Result<Image, DecodeError> {
+    let (w, h) = (width as usize, height as usize);
+    let pixels = match format {
+        TextureFormat::Dxt1 => Pixels::Rgba8(decode_blocks(w, h, 8, data, bc::decode_bc1)?),
+        TextureFormat::Dxt3 => Pixels::Rgba8(decode_blocks(w, h, 16, data, bc::decode_bc2)?),
+        TextureFormat::Dxt5 => Pixels::Rgba8(decode_blocks(w, h, 16, data, bc::decode_bc3)?),
+        TextureFormat::Bc4 => Pixels::Rgba8(decode_blocks(w, h, 8, data, bc::decode_bc4)?),
+        TextureFormat::Bc5 => Pixels::Rgba8(decode_blocks(w, h, 16, data, bc::decode_bc5)?),
+        TextureFormat::Bc4Signed => {
+            Pixels::Rgba8(decode_blocks(w, h, 8, data, bc::decode_bc4_signed)?)
+        }
+        TextureFormat::Bc5Signed => {
+            Pixels::Rgba8(decode_blocks(w, h, 16, data, bc::decode_bc5_signed)?)
+        }
+        TextureFormat::Bc6h | TextureFormat::Bc6hSigned => {
+            let signed = format == TextureFormat::Bc6hSigned;
+            Pixels::Rgba32F(decode_blocks(w, h, 16, data, |block, out| {
+                bc::decode_bc6h(block, out, signed)
+            })?)
+        }
+        TextureFormat::Bc7 => Pixels::Rgba8(decode_blocks(w, h, 16, data, bc::decode_bc7)?),
+        TextureFormat::PvrtcRgb2 | TextureFormat::PvrtcRgba2 => {
+            Pixels::Rgba8(pvrtc::decode(w, h, data, true)?)
+        }
+        TextureFormat::PvrtcRgb4 | TextureFormat::PvrtcRgba4 => {
+            Pixels::Rgba8(pvrtc::decode(w, h, data, false)?)
+        }
+        TextureFormat::Dxt1Crunched
+        | TextureFormat::Dxt5Crunched
+        | TextureFormat::EtcRgb4Crunched
+        | TextureFormat::Etc2Rgba8Crunched => crunch::decode(format, w, h, data, crunch)?,
+        format => {
+            if let Some((block_size, hdr)) = astc::block_size(format) {
+                astc::decode(w, h, data, block_size, hdr)?
+            } else if let Some(format) = etc::decode_format(format) {
+                Pixels::Rgba8(etc::decode(w, h, data, format)?)
+            } else {
+                match uncompressed::decode(format, w, h, data) {
+                    Some(pixels) => pixels?,
+                    None => return Err(DecodeError::UnsupportedFormat(format)),
+                }
+            }
+        }
+    };
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Decodes `count` images of `data` which start `stride` bytes apart, like the faces of a cubemap
+/// or the slices of a texture array.
+pub fn decode_slices(
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    count: usize,
+    stride: usize,
+) -> Result<Vec<Image>, DecodeError> {
+    (0..count)
+        .map(|idx| {
+            let start = idx * stride;
+            let slice = data.get(start..).ok_or(DecodeError::NotEnoughData {
+                expected: start + stride,
+                found: data.len(),
+            })?;
+            decode(format, width, height, slice)
+        })
+        .collect()
+}
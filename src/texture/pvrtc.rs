@@ -0,0 +1,334 @@
+use super::DecodeError;
+
+const BLOCK_HEIGHT: usize = 4;
+const STANDARD_WEIGHTS: [u32; 4] = [0, 3, 5, 8];
+const PUNCH_THROUGH_WEIGHTS: [u32; 4] = [0, 4, 4, 8];
+
+#[derive(Copy, Clone)]
+enum Weight {
+    Stored(u32),
+    /// Average of the texels above and below, used by 2bpp blocks.
+    Vertical,
+    /// Average of the texels on the left and right, used by 2bpp blocks.
+    Horizontal,
+    /// Average of all four neighbors, used by 2bpp blocks.
+    Average,
+}
+
+struct Block {
+    /// Colors A and B with 5-bit RGB and 4-bit alpha.
+    colors: [[u32; 4]; 2],
+    weights: [Weight; 32],
+    punch_through: u32,
+}
+
+fn color_a(v: u32) -> [u32; 4] {
+    if v & 0x8000 != 0 {
+        let b = v & 0x1e;
+        [(v >> 10) & 0x1f, (v >> 5) & 0x1f, b | (b >> 4), 0xf]
+    } else {
+        let r = (v >> 8) & 0xf;
+        let g = (v >> 4) & 0xf;
+        let b = (v >> 1) & 0x7;
+        [
+            (r << 1) | (r >> 3),
+            (g << 1) | (g >> 3),
+            (b << 2) | (b >> 1),
+            (v >> 11) & 0xe,
+        ]
+    }
+}
+
+fn color_b(v: u32) -> [u32; 4] {
+    if v & 0x8000 != 0 {
+        [(v >> 10) & 0x1f, (v >> 5) & 0x1f, v & 0x1f, 0xf]
+    } else {
+        let r = (v >> 8) & 0xf;
+        let g = (v >> 4) & 0xf;
+        let b = v & 0xf;
+        [
+            (r << 1) | (r >> 3),
+            (g << 1) | (g >> 3),
+            (b << 1) | (b >> 3),
+            (v >> 11) & 0xe,
+        ]
+    }
+}
+
+fn parse_block(data: &[u8], two_bpp: bool) -> Block {
+    let mut modulation = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let color = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let mut block = Block {
+        colors: [color_a(color & 0xffff), color_b(color >> 16)],
+        weights: [Weight::Stored(0); 32],
+        punch_through: 0,
+    };
+    let alternate_mode = color & 1 != 0;
+    if !two_bpp {
+        let table = if alternate_mode {
+            &PUNCH_THROUGH_WEIGHTS
+        } else {
+            &STANDARD_WEIGHTS
+        };
+        for (idx, weight) in block.weights.iter_mut().take(16).enumerate() {
+            let v = (modulation & 3) as usize;
+            *weight = Weight::Stored(table[v]);
+            if alternate_mode && v == 2 {
+                block.punch_through |= 1 << idx;
+            }
+            modulation >>= 2;
+        }
+    } else if !alternate_mode {
+        for weight in block.weights.iter_mut() {
+            *weight = Weight::Stored(if modulation & 1 != 0 { 8 } else { 0 });
+            modulation >>= 1;
+        }
+    } else {
+        // 2-bit weights are stored in a checkerboard pattern, the rest is interpolated
+        let fill = if modulation & 1 == 0 {
+            Weight::Average
+        } else if modulation & (1 << 20) != 0 {
+            Weight::Vertical
+        } else {
+            Weight::Horizontal
+        };
+        let mut stored = modulation;
+        for (idx, weight) in block.weights.iter_mut().enumerate() {
+            let (x, y) = (idx % 8, idx / 8);
+            if (x + y) % 2 == 0 {
+                *weight = Weight::Stored(STANDARD_WEIGHTS[(stored & 3) as usize]);
+                stored >>= 2;
+            } else {
+                *weight = fill;
+            }
+        }
+        // the lowest bits of these texels are used as the mode flags above
+        let flagged: &[usize] = if modulation & 1 != 0 { &[0, 20] } else { &[0] };
+        for &idx in flagged {
+            if let Weight::Stored(w) = &mut block.weights[idx] {
+                *w = if *w >= 5 { 8 } else { 0 };
+            }
+        }
+    }
+    block
+}
+
+/// Offset of the block at `(x, y)`, stored in Morton order.
+fn morton_index(x: usize, y: usize, min_dim: usize) -> usize {
+    let mut offset = 0;
+    let mut shift = 0;
+    let mut mask = 1;
+    while mask < min_dim {
+        offset |= ((y & mask) | ((x & mask) << 1)) << shift;
+        mask <<= 1;
+        shift += 1;
+    }
+    offset | (((x | y) >> shift) << (shift * 2))
+}
+
+/// Decodes PVRTC 2bpp or 4bpp data as RGBA8, reversing the row order.
+///
+/// Colors of a block are interpolated with its neighbors, wrapping around the edges. Data is
+/// padded to at least 2x2 blocks, small images are cropped out of the padded texture.
+pub(super) fn decode(
+    width: usize,
+    height: usize,
+    data: &[u8],
+    two_bpp: bool,
+) -> Result<Vec<u8>, DecodeError> {
+    if width == 0 || height == 0 {
+        return Ok(Vec::new());
+    }
+    let block_width = if two_bpp { 8 } else { 4 };
+    let blocks_x = std::cmp::max(width, block_width * 2).div_ceil(block_width);
+    let blocks_y = std::cmp::max(height, BLOCK_HEIGHT * 2).div_ceil(BLOCK_HEIGHT);
+    if !blocks_x.is_power_of_two() || !blocks_y.is_power_of_two() {
+        return Err(DecodeError::InvalidSize { width, height });
+    }
+    let expected = blocks_x * blocks_y * 8;
+    if data.len() < expected {
+        return Err(DecodeError::NotEnoughData {
+            expected,
+            found: data.len(),
+        });
+    }
+
+    let min_dim = std::cmp::min(blocks_x, blocks_y);
+    let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+    for y in 0..blocks_y {
+        for x in 0..blocks_x {
+            let offset = morton_index(x, y, min_dim) * 8;
+            blocks.push(parse_block(&data[offset..offset + 8], two_bpp));
+        }
+    }
+
+    // colors are scaled by `block_width * BLOCK_HEIGHT` after interpolation
+    let shift = (block_width * BLOCK_HEIGHT).trailing_zeros();
+    let stride = width * 4;
+    let mut pixels = vec![0u8; stride * height];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let neighbor = |dx: isize, dy: isize| {
+                let x = (block_x as isize + dx).rem_euclid(blocks_x as isize) as usize;
+                let y = (block_y as isize + dy).rem_euclid(blocks_y as isize) as usize;
+                &blocks[y * blocks_x + x]
+            };
+            let stored_weight = |x: isize, y: isize| {
+                let (bw, bh) = (block_width as isize, BLOCK_HEIGHT as isize);
+                let block = neighbor(x.div_euclid(bw), y.div_euclid(bh));
+                let idx = (y.rem_euclid(bh) * bw + x.rem_euclid(bw)) as usize;
+                match block.weights[idx] {
+                    Weight::Stored(w) => w,
+                    _ => 0,
+                }
+            };
+            let block = neighbor(0, 0);
+
+            for y in 0..BLOCK_HEIGHT {
+                let py = block_y * BLOCK_HEIGHT + y;
+                if py >= height {
+                    break;
+                }
+                let half = BLOCK_HEIGHT / 2;
+                let wy = [
+                    half.saturating_sub(y),
+                    BLOCK_HEIGHT - half.saturating_sub(y) - y.saturating_sub(half),
+                    y.saturating_sub(half),
+                ];
+                for x in 0..block_width {
+                    let px = block_x * block_width + x;
+                    if px >= width {
+                        break;
+                    }
+                    let half = block_width / 2;
+                    let wx = [
+                        half.saturating_sub(x),
+                        block_width - half.saturating_sub(x) - x.saturating_sub(half),
+                        x.saturating_sub(half),
+                    ];
+
+                    let mut colors = [[0u32; 4]; 2];
+                    for (dy, &wy) in wy.iter().enumerate() {
+                        for (dx, &wx) in wx.iter().enumerate() {
+                            let w = (wx * wy) as u32;
+                            if w == 0 {
+                                continue;
+                            }
+                            let n = neighbor(dx as isize - 1, dy as isize - 1);
+                            for (color, n) in colors.iter_mut().zip(n.colors.iter()) {
+                                for (c, n) in color.iter_mut().zip(n.iter()) {
+                                    *c += n * w;
+                                }
+                            }
+                        }
+                    }
+                    for color in colors.iter_mut() {
+                        for c in color[..3].iter_mut() {
+                            *c = (*c >> (shift - 3)) + (*c >> (shift + 2));
+                        }
+                        color[3] = (color[3] >> (shift - 4)) + (color[3] >> shift);
+                    }
+
+                    let idx = y * block_width + x;
+                    let (sx, sy) = (x as isize, y as isize);
+                    let weight = match block.weights[idx] {
+                        Weight::Stored(w) => w,
+                        Weight::Vertical => {
+                            (stored_weight(sx, sy - 1) + stored_weight(sx, sy + 1)).div_ceil(2)
+                        }
+                        Weight::Horizontal => {
+                            (stored_weight(sx - 1, sy) + stored_weight(sx + 1, sy)).div_ceil(2)
+                        }
+                        Weight::Average => {
+                            (stored_weight(sx, sy - 1)
+                                + stored_weight(sx, sy + 1)
+                                + stored_weight(sx - 1, sy)
+                                + stored_weight(sx + 1, sy)
+                                + 2)
+                                / 4
+                        }
+                    };
+                    let start = (height - 1 - py) * stride + px * 4;
+                    let target = &mut pixels[start..start + 4];
+                    for (c, target) in target.iter_mut().enumerate() {
+                        *target = ((colors[0][c] * (8 - weight) + colors[1][c] * weight) / 8) as u8;
+                    }
+                    if block.punch_through & (1 << idx) != 0 {
+                        target[3] = 0;
+                    }
+                }
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opaque colors A and B in RGB555, with standard modulation.
+    fn block(modulation: u32, a: u32, b: u32) -> [u8; 8] {
+        let mut block = [0; 8];
+        block[..4].copy_from_slice(&modulation.to_le_bytes());
+        block[4..].copy_from_slice(&(0x8000_8000 | (b << 16) | (a & 0xfffe)).to_le_bytes());
+        block
+    }
+
+    const RED: u32 = 0x1f << 10;
+    const BLUE: u32 = 0x1f;
+
+    #[test]
+    fn single_color() {
+        // 4bpp weights 0 and 3 select color A and B, 2bpp bits select A or B
+        for &(two_bpp, width, modulation, expected) in &[
+            (false, 8, 0, [255, 0, 0, 255]),
+            (false, 8, 0xffff_ffff, [0, 0, 255, 255]),
+            (true, 16, 0, [255, 0, 0, 255]),
+            (true, 16, 0xffff_ffff, [0, 0, 255, 255]),
+        ] {
+            let data = block(modulation, RED, BLUE).repeat(4);
+            let pixels = decode(width, 8, &data, two_bpp).unwrap();
+            assert_eq!(pixels.len(), width * 8 * 4);
+            for pixel in pixels.chunks(4) {
+                assert_eq!(pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn small_levels() {
+        // blocks of different colors, so the neighbors used for interpolation matter
+        let colors = [RED, BLUE, 0x1f << 5, 0x7ffe];
+        let data = colors
+            .iter()
+            .flat_map(|&color| block(0, color, color))
+            .collect::<Vec<_>>();
+        for &(two_bpp, padded, width, height) in
+            &[(false, 8, 4, 4), (true, 16, 8, 8), (true, 16, 4, 2)]
+        {
+            let full = decode(padded, 8, &data, two_bpp).unwrap();
+            let small = decode(width, height, &data, two_bpp).unwrap();
+            assert_eq!(small.len(), width * height * 4);
+            // rows are bottom-up, so the level is the bottom left of the padded image
+            for y in 0..height {
+                let row = &small[y * width * 4..(y + 1) * width * 4];
+                let start = ((8 - height + y) * padded) * 4;
+                assert_eq!(row, &full[start..start + width * 4]);
+            }
+        }
+        let pixels = decode(4, 4, &data, false).unwrap();
+        assert!(pixels.chunks(4).any(|pixel| pixel != pixels[..4].as_ref()));
+    }
+
+    #[test]
+    fn not_enough_data() {
+        assert_eq!(
+            decode(4, 4, &[0; 8], false),
+            Err(DecodeError::NotEnoughData {
+                expected: 32,
+                found: 8
+            })
+        );
+    }
+}
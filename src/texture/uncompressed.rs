@@ -0,0 +1,291 @@
+use super::{half_to_f32, DecodeError, Pixels};
+use crate::classes::TextureFormat;
+
+enum Layout {
+    Unorm(usize, fn(&[u8]) -> [u8; 4]),
+    Float(usize, fn(&[u8]) -> [f32; 4]),
+    Yuy2,
+}
+
+fn u16_at(b: &[u8], idx: usize) -> u16 {
+    u16::from_le_bytes([b[2 * idx], b[2 * idx + 1]])
+}
+
+fn u32_at(b: &[u8], idx: usize) -> u32 {
+    u32::from_le_bytes([b[4 * idx], b[4 * idx + 1], b[4 * idx + 2], b[4 * idx + 3]])
+}
+
+fn f32_at(b: &[u8], idx: usize) -> f32 {
+    f32::from_bits(u32_at(b, idx))
+}
+
+fn half_at(b: &[u8], idx: usize) -> f32 {
+    half_to_f32(u16_at(b, idx))
+}
+
+fn unorm4(v: u16) -> u8 {
+    (v & 0xf) as u8 * 0x11
+}
+
+fn unorm16(v: u16) -> u8 {
+    ((u32::from(v) * 255 + 32767) / 65535) as u8
+}
+
+fn snorm8(v: u8) -> u8 {
+    (i16::from(v as i8) + 128) as u8
+}
+
+fn snorm16(v: u16) -> u8 {
+    ((i32::from(v as i16) + 32768) >> 8) as u8
+}
+
+fn rgb9e5(v: u32) -> [f32; 4] {
+    let scale = 2f32.powi((v >> 27) as i32 - 15 - 9);
+    [
+        (v & 0x1ff) as f32 * scale,
+        ((v >> 9) & 0x1ff) as f32 * scale,
+        ((v >> 18) & 0x1ff) as f32 * scale,
+        1.0,
+    ]
+}
+
+fn layout(format: TextureFormat) -> Option<Layout> {
+    use TextureFormat::*;
+    Some(match format {
+        Alpha8 => Layout::Unorm(1, |b| [0xff, 0xff, 0xff, b[0]]),
+        R8 => Layout::Unorm(1, |b| [b[0], 0, 0, 0xff]),
+        Rg16 => Layout::Unorm(2, |b| [b[0], b[1], 0, 0xff]),
+        Rgb24 => Layout::Unorm(3, |b| [b[0], b[1], b[2], 0xff]),
+        Bgr24 => Layout::Unorm(3, |b| [b[2], b[1], b[0], 0xff]),
+        Rgba32 => Layout::Unorm(4, |b| [b[0], b[1], b[2], b[3]]),
+        Argb32 => Layout::Unorm(4, |b| [b[1], b[2], b[3], b[0]]),
+        Bgra32 => Layout::Unorm(4, |b| [b[2], b[1], b[0], b[3]]),
+        Argb4444 => Layout::Unorm(2, |b| {
+            let v = u16_at(b, 0);
+            [unorm4(v >> 8), unorm4(v >> 4), unorm4(v), unorm4(v >> 12)]
+        }),
+        Rgba4444 => Layout::Unorm(2, |b| {
+            let v = u16_at(b, 0);
+            [unorm4(v >> 12), unorm4(v >> 8), unorm4(v >> 4), unorm4(v)]
+        }),
+        Rgb565 => Layout::Unorm(2, |b| {
+            let v = u16_at(b, 0);
+            let r = (v >> 11) as u8;
+            let g = ((v >> 5) & 0x3f) as u8;
+            let b = (v & 0x1f) as u8;
+            [
+                (r << 3) | (r >> 2),
+                (g << 2) | (g >> 4),
+                (b << 3) | (b >> 2),
+                0xff,
+            ]
+        }),
+        R16 => Layout::Unorm(2, |b| [unorm16(u16_at(b, 0)), 0, 0, 0xff]),
+        Rg32 => Layout::Unorm(4, |b| {
+            [unorm16(u16_at(b, 0)), unorm16(u16_at(b, 1)), 0, 0xff]
+        }),
+        Rgb48 => Layout::Unorm(6, |b| {
+            [
+                unorm16(u16_at(b, 0)),
+                unorm16(u16_at(b, 1)),
+                unorm16(u16_at(b, 2)),
+                0xff,
+            ]
+        }),
+        Rgba64 => Layout::Unorm(8, |b| {
+            [
+                unorm16(u16_at(b, 0)),
+                unorm16(u16_at(b, 1)),
+                unorm16(u16_at(b, 2)),
+                unorm16(u16_at(b, 3)),
+            ]
+        }),
+        R8Signed => Layout::Unorm(1, |b| [snorm8(b[0]), 0, 0, 0xff]),
+        Rg16Signed => Layout::Unorm(2, |b| [snorm8(b[0]), snorm8(b[1]), 0, 0xff]),
+        Rgb24Signed => Layout::Unorm(3, |b| [snorm8(b[0]), snorm8(b[1]), snorm8(b[2]), 0xff]),
+        Rgba32Signed => Layout::Unorm(4, |b| {
+            [snorm8(b[0]), snorm8(b[1]), snorm8(b[2]), snorm8(b[3])]
+        }),
+        R16Signed => Layout::Unorm(2, |b| [snorm16(u16_at(b, 0)), 0, 0, 0xff]),
+        Rg32Signed => Layout::Unorm(4, |b| {
+            [snorm16(u16_at(b, 0)), snorm16(u16_at(b, 1)), 0, 0xff]
+        }),
+        Rgb48Signed => Layout::Unorm(6, |b| {
+            [
+                snorm16(u16_at(b, 0)),
+                snorm16(u16_at(b, 1)),
+                snorm16(u16_at(b, 2)),
+                0xff,
+            ]
+        }),
+        Rgba64Signed => Layout::Unorm(8, |b| {
+            [
+                snorm16(u16_at(b, 0)),
+                snorm16(u16_at(b, 1)),
+                snorm16(u16_at(b, 2)),
+                snorm16(u16_at(b, 3)),
+            ]
+        }),
+        RHalf => Layout::Float(2, |b| [half_at(b, 0), 0.0, 0.0, 1.0]),
+        RgHalf => Layout::Float(4, |b| [half_at(b, 0), half_at(b, 1), 0.0, 1.0]),
+        RgbaHalf => Layout::Float(8, |b| {
+            [half_at(b, 0), half_at(b, 1), half_at(b, 2), half_at(b, 3)]
+        }),
+        RFloat => Layout::Float(4, |b| [f32_at(b, 0), 0.0, 0.0, 1.0]),
+        RgFloat => Layout::Float(8, |b| [f32_at(b, 0), f32_at(b, 1), 0.0, 1.0]),
+        RgbFloat => Layout::Float(12, |b| [f32_at(b, 0), f32_at(b, 1), f32_at(b, 2), 1.0]),
+        RgbaFloat => Layout::Float(16, |b| {
+            [f32_at(b, 0), f32_at(b, 1), f32_at(b, 2), f32_at(b, 3)]
+        }),
+        ArgbFloat => Layout::Float(16, |b| {
+            [f32_at(b, 1), f32_at(b, 2), f32_at(b, 3), f32_at(b, 0)]
+        }),
+        Rgb9e5Float => Layout::Float(4, |b| rgb9e5(u32_at(b, 0))),
+        Yuy2 => Layout::Yuy2,
+        _ => return None,
+    })
+}
+
+fn yuv_to_rgba(y: u8, u: u8, v: u8) -> [u8; 4] {
+    let c = 298 * (i32::from(y) - 16);
+    let d = i32::from(u) - 128;
+    let e = i32::from(v) - 128;
+    let clamp = |x: i32| ((x + 128) >> 8).clamp(0, 0xff) as u8;
+    [
+        clamp(c + 409 * e),
+        clamp(c - 100 * d - 208 * e),
+        clamp(c + 516 * d),
+        0xff,
+    ]
+}
+
+/// Decodes rows of `stride` bytes into `T` pixels, reversing the row order.
+fn decode_rows<T: Copy + Default>(
+    width: usize,
+    height: usize,
+    stride: usize,
+    data: &[u8],
+    decode_row: impl Fn(&[u8], &mut [T]),
+) -> Result<Vec<T>, DecodeError> {
+    let expected = stride * height;
+    if data.len() < expected {
+        return Err(DecodeError::NotEnoughData {
+            expected,
+            found: data.len(),
+        });
+    }
+    let mut pixels = vec![T::default(); width * height * 4];
+    if width > 0 {
+        for (src, dst) in data[..expected]
+            .chunks(stride)
+            .rev()
+            .zip(pixels.chunks_mut(width * 4))
+        {
+            decode_row(src, dst);
+        }
+    }
+    Ok(pixels)
+}
+
+pub(super) fn is_supported(format: TextureFormat) -> bool {
+    layout(format).is_some()
+}
+
+/// Size of a row of `width` pixels in bytes. Returns `None` if `format` is compressed.
+pub(super) fn row_size(format: TextureFormat, width: usize) -> Option<usize> {
+    Some(match layout(format)? {
+        Layout::Unorm(bpp, _) | Layout::Float(bpp, _) => width * bpp,
+        Layout::Yuy2 => width.div_ceil(2) * 4,
+    })
+}
+
+/// Decodes uncompressed formats. Returns `None` if `format` is compressed.
+pub(super) fn decode(
+    format: TextureFormat,
+    width: usize,
+    height: usize,
+    data: &[u8],
+) -> Option<Result<Pixels, DecodeError>> {
+    let pixels = match layout(format)? {
+        Layout::Unorm(bpp, f) => decode_rows(width, height, width * bpp, data, |src, dst| {
+            for (src, dst) in src.chunks(bpp).zip(dst.chunks_mut(4)) {
+                dst.copy_from_slice(&f(src));
+            }
+        })
+        .map(Pixels::Rgba8),
+        Layout::Float(bpp, f) => decode_rows(width, height, width * bpp, data, |src, dst| {
+            for (src, dst) in src.chunks(bpp).zip(dst.chunks_mut(4)) {
+                dst.copy_from_slice(&f(src));
+            }
+        })
+        .map(Pixels::Rgba32F),
+        Layout::Yuy2 => {
+            let stride = width.div_ceil(2) * 4;
+            decode_rows(width, height, stride, data, |src, dst| {
+                for (src, dst) in src.chunks(4).zip(dst.chunks_mut(8)) {
+                    dst[..4].copy_from_slice(&yuv_to_rgba(src[0], src[1], src[3]));
+                    if dst.len() == 8 {
+                        dst[4..].copy_from_slice(&yuv_to_rgba(src[2], src[1], src[3]));
+                    }
+                }
+            })
+            .map(Pixels::Rgba8)
+        }
+    };
+    Some(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba8(format: TextureFormat, width: usize, height: usize, data: &[u8]) -> Vec<u8> {
+        match decode(format, width, height, data).unwrap().unwrap() {
+            Pixels::Rgba8(pixels) => pixels,
+            Pixels::Rgba32F(_) => panic!("{:?} decoded to floats", format),
+        }
+    }
+
+    fn rgba32f(format: TextureFormat, data: &[u8]) -> Vec<f32> {
+        match decode(format, 1, 1, data).unwrap().unwrap() {
+            Pixels::Rgba32F(pixels) => pixels,
+            Pixels::Rgba8(_) => panic!("{:?} decoded to bytes", format),
+        }
+    }
+
+    #[test]
+    fn rgb565() {
+        // the low bits of each channel are filled from its high bits
+        let pixels = rgba8(TextureFormat::Rgb565, 2, 1, &[0x10, 0x84, 0x1f, 0xf8]);
+        assert_eq!(pixels, [132, 130, 132, 255, 255, 0, 255, 255]);
+    }
+
+    #[test]
+    fn rgba4444() {
+        // the two rows come out swapped, top first
+        let pixels = rgba8(TextureFormat::Rgba4444, 1, 2, &[0x34, 0x12, 0x0f, 0xf0]);
+        assert_eq!(pixels, [0xff, 0, 0, 0xff, 0x11, 0x22, 0x33, 0x44]);
+    }
+
+    #[test]
+    fn argb32() {
+        let pixels = rgba8(TextureFormat::Argb32, 1, 1, &[4, 1, 2, 3]);
+        assert_eq!(pixels, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn float_formats() {
+        assert_eq!(
+            rgba32f(TextureFormat::RHalf, &[0x00, 0xc0]),
+            [-2.0, 0.0, 0.0, 1.0]
+        );
+        let data: Vec<u8> = [0.25f32, -1.5, 8.0, 0.5]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        assert_eq!(
+            rgba32f(TextureFormat::RgbaFloat, &data),
+            [0.25, -1.5, 8.0, 0.5]
+        );
+    }
+}
@@ -1,5 +1,63 @@
+use nom::number::Endianness;
+
 pub fn align<'a>(offset: usize, base: &'a [u8], target: &'a [u8]) -> &'a [u8] {
     let dist = (target.as_ptr() as usize) - (base.as_ptr() as usize);
     let new = ((offset + dist + 3) & 0xfffffffc) - offset;
-    return &base[new..];
+    &base[new.min(base.len())..]
+}
+
+pub fn write_u16(out: &mut Vec<u8>, v: u16, endianness: Endianness) {
+    match endianness {
+        Endianness::Big => out.extend_from_slice(&v.to_be_bytes()),
+        Endianness::Little => out.extend_from_slice(&v.to_le_bytes()),
+    }
+}
+
+pub fn write_u32(out: &mut Vec<u8>, v: u32, endianness: Endianness) {
+    match endianness {
+        Endianness::Big => out.extend_from_slice(&v.to_be_bytes()),
+        Endianness::Little => out.extend_from_slice(&v.to_le_bytes()),
+    }
+}
+
+pub fn write_u64(out: &mut Vec<u8>, v: u64, endianness: Endianness) {
+    match endianness {
+        Endianness::Big => out.extend_from_slice(&v.to_be_bytes()),
+        Endianness::Little => out.extend_from_slice(&v.to_le_bytes()),
+    }
+}
+
+pub fn write_align(out: &mut Vec<u8>, alignment: usize) {
+    let len = out.len().div_ceil(alignment) * alignment;
+    out.resize(len, 0);
+}
+
+pub fn read_u16(data: &[u8], endianness: Endianness) -> u16 {
+    let bytes = [data[0], data[1]];
+    match endianness {
+        Endianness::Big => u16::from_be_bytes(bytes),
+        Endianness::Little => u16::from_le_bytes(bytes),
+    }
+}
+
+pub fn read_u32(data: &[u8], endianness: Endianness) -> u32 {
+    let bytes = [data[0], data[1], data[2], data[3]];
+    match endianness {
+        Endianness::Big => u32::from_be_bytes(bytes),
+        Endianness::Little => u32::from_le_bytes(bytes),
+    }
+}
+
+/// Escapes `s` to be written inside a JSON string.
+pub fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
 }
@@ -0,0 +1,265 @@
+use crate::compression::{compress_lz4, CompressionType};
+use crate::metadata::{ArchiveBlockInfo, Metadata, NodeInfo};
+use crate::UnityFs;
+use std::borrow::Cow;
+
+const DEFAULT_BLOCK_SIZE: u32 = 0x20000;
+const FLAG_BLOCKS_AND_DIRECTORY_COMBINED: u32 = 0x40;
+
+/// Node status of serialized files (`Asset`s) inside a bundle.
+pub const NODE_SERIALIZED_FILE: u32 = 0x4;
+
+/// Compression used for blocks written by `BundleWriter`.
+///
+/// LZMA and LZHAM bundles can be read but not written, so they have no variant here.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlockCompression {
+    Lz4,
+    Lz4Hc,
+}
+
+impl BlockCompression {
+    fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            BlockCompression::Lz4 => compress_lz4(data, 1),
+            BlockCompression::Lz4Hc => compress_lz4(data, 256),
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        match self {
+            BlockCompression::Lz4 => CompressionType::Lz4.to_u32(),
+            BlockCompression::Lz4Hc => CompressionType::Lz4Hc.to_u32(),
+        }
+    }
+}
+
+struct Node<'a> {
+    name: String,
+    status: u32,
+    data: Cow<'a, [u8]>,
+}
+
+/// Builds UnityFS bundles, either from scratch or from a parsed `UnityFs`.
+pub struct BundleWriter<'a> {
+    format_version: u32,
+    unity_version: String,
+    generator_version: String,
+    guid: [u8; 16],
+    compression: Option<BlockCompression>,
+    block_size: u32,
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> BundleWriter<'a> {
+    pub fn new(unity_version: &str, generator_version: &str) -> Self {
+        Self {
+            format_version: 6,
+            unity_version: unity_version.to_owned(),
+            generator_version: generator_version.to_owned(),
+            guid: [0; 16],
+            compression: Some(BlockCompression::Lz4Hc),
+            block_size: DEFAULT_BLOCK_SIZE,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Starts from an existing bundle, keeping its header, GUID and nodes in order.
+    pub fn from_unityfs(fs: &UnityFs<'a>) -> Self {
+        let nodes = fs
+            .nodes
+            .iter()
+            .map(|node| Node {
+                name: node.name.clone(),
+                status: node.status,
+                data: Cow::Borrowed(fs.resources[&node.name]),
+            })
+            .collect();
+        Self {
+            format_version: fs.format_version,
+            unity_version: fs.unity_version.to_owned(),
+            generator_version: fs.generator_version.to_owned(),
+            guid: fs.guid,
+            compression: Some(BlockCompression::Lz4Hc),
+            block_size: DEFAULT_BLOCK_SIZE,
+            nodes,
+        }
+    }
+
+    pub fn set_guid(&mut self, guid: [u8; 16]) -> &mut Self {
+        self.guid = guid;
+        self
+    }
+
+    /// Sets the compression of data blocks and the block table.
+    pub fn set_compression(&mut self, compression: Option<BlockCompression>) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn set_block_size(&mut self, block_size: u32) -> &mut Self {
+        assert!(block_size > 0, "block size must not be zero");
+        self.block_size = block_size;
+        self
+    }
+
+    /// Adds a node, or replaces the data and status of the node with the same name.
+    ///
+    /// The first node is read as the main asset, so serialized files should come first.
+    pub fn insert_node(
+        &mut self,
+        name: &str,
+        status: u32,
+        data: impl Into<Cow<'a, [u8]>>,
+    ) -> &mut Self {
+        let data = data.into();
+        match self.nodes.iter_mut().find(|node| node.name == name) {
+            Some(node) => {
+                node.status = status;
+                node.data = data;
+            }
+            None => self.nodes.push(Node {
+                name: name.to_owned(),
+                status,
+                data,
+            }),
+        }
+        self
+    }
+
+    /// Replaces the data of an existing node. Returns `false` if there's no such node.
+    pub fn replace_node(&mut self, name: &str, data: impl Into<Cow<'a, [u8]>>) -> bool {
+        match self.nodes.iter_mut().find(|node| node.name == name) {
+            Some(node) => {
+                node.data = data.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_node(&mut self, name: &str) -> bool {
+        let len = self.nodes.len();
+        self.nodes.retain(|node| node.name != name);
+        self.nodes.len() != len
+    }
+
+    fn compress(&self, data: &[u8]) -> (Vec<u8>, u32) {
+        match self.compression {
+            Some(compression) => {
+                let compressed = compression.compress(data);
+                if compressed.len() < data.len() {
+                    (compressed, compression.to_u32())
+                } else {
+                    (data.to_vec(), 0)
+                }
+            }
+            None => (data.to_vec(), 0),
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        let mut stream = Vec::new();
+        for node in &self.nodes {
+            nodes.push(NodeInfo {
+                offset: stream.len() as u64,
+                size: node.data.len() as u64,
+                status: node.status,
+                name: node.name.clone(),
+            });
+            stream.extend_from_slice(&node.data);
+        }
+
+        let mut blocks = Vec::new();
+        let mut block_data = Vec::new();
+        for chunk in stream.chunks(self.block_size as usize) {
+            let (compressed, flags) = self.compress(chunk);
+            blocks.push(ArchiveBlockInfo {
+                u_size: chunk.len() as u32,
+                c_size: compressed.len() as u32,
+                flags: flags as u16,
+            });
+            block_data.extend_from_slice(&compressed);
+        }
+
+        let metadata = Metadata {
+            guid: self.guid,
+            blocks,
+            nodes,
+        }
+        .write();
+        let (raw_metadata, compression) = self.compress(&metadata);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(b"UnityFS\0");
+        header.extend_from_slice(&self.format_version.to_be_bytes());
+        header.extend_from_slice(self.unity_version.as_bytes());
+        header.push(0);
+        header.extend_from_slice(self.generator_version.as_bytes());
+        header.push(0);
+        let file_size = header.len() + 8 + 4 * 3 + raw_metadata.len() + block_data.len();
+        header.extend_from_slice(&(file_size as u64).to_be_bytes());
+        header.extend_from_slice(&(raw_metadata.len() as u32).to_be_bytes());
+        header.extend_from_slice(&(metadata.len() as u32).to_be_bytes());
+        header.extend_from_slice(&(compression | FLAG_BLOCKS_AND_DIRECTORY_COMBINED).to_be_bytes());
+
+        let mut out = header;
+        out.extend_from_slice(&raw_metadata);
+        out.extend_from_slice(&block_data);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UnityFsMeta;
+
+    fn check_round_trip(compression: Option<BlockCompression>) {
+        let asset = (0..0x30000u32).map(|i| (i / 7) as u8).collect::<Vec<_>>();
+        let resource = b"resource bytes".to_vec();
+        let mut writer = BundleWriter::new("5.x.x", "2019.4.0f1");
+        writer
+            .set_guid([7; 16])
+            .set_compression(compression)
+            .set_block_size(0x10000)
+            .insert_node("CAB-test", NODE_SERIALIZED_FILE, &asset[..])
+            .insert_node("CAB-test.resS", 0, &resource[..]);
+        let bundle = writer.write();
+
+        let (rest, meta) = UnityFsMeta::parse(&bundle).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(meta.unity_version, "5.x.x");
+        assert_eq!(meta.generator_version, "2019.4.0f1");
+        assert_eq!(meta.metadata.guid, [7; 16]);
+        assert_eq!(meta.metadata.blocks.len(), 4);
+        let nodes = &meta.metadata.nodes;
+        assert_eq!(nodes.len(), 2);
+        for (node, (name, status, data)) in nodes.iter().zip(&[
+            ("CAB-test", NODE_SERIALIZED_FILE, &asset),
+            ("CAB-test.resS", 0, &resource),
+        ]) {
+            assert_eq!(node.name, *name);
+            assert_eq!(node.status, *status);
+            assert_eq!(node.size, data.len() as u64);
+            let range = node.offset..node.offset + node.size;
+            assert_eq!(meta.storage.read_range(range), &data[..]);
+        }
+    }
+
+    #[test]
+    fn round_trip_uncompressed() {
+        check_round_trip(None);
+    }
+
+    #[test]
+    fn round_trip_lz4() {
+        check_round_trip(Some(BlockCompression::Lz4));
+    }
+
+    #[test]
+    fn round_trip_lz4hc() {
+        check_round_trip(Some(BlockCompression::Lz4Hc));
+    }
+}